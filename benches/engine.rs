@@ -0,0 +1,129 @@
+use std::collections::{ HashMap, HashSet };
+use std::time::Duration;
+use criterion::{ criterion_group, criterion_main, BenchmarkId, Criterion };
+use sdl2::pixels::{ Color, PixelFormatEnum };
+use sdl2::rect::Rect;
+use sdl2::surface::Surface;
+use pokemon::compress;
+use pokemon::dict::*;
+use pokemon::engine::*;
+use pokemon::intern::Key;
+use pokemon::palette::Palette;
+use pokemon::renderer::SdlRenderer;
+use pokemon::pokemon::{ Line, Zone };
+use pokemon::textures::TextureCache;
+
+const SIZES: [usize; 3] = [10, 100, 1_000];
+
+fn on_tick(_: &mut Scene, _: &mut EngineContext, _: Duration) -> SceneFnOutcome {
+    SceneFnOutcome::Continue
+}
+
+fn on_child_quit(_: &mut Scene, _: &mut EngineContext, _: Dict) -> SceneFnOutcome {
+    SceneFnOutcome::Continue
+}
+
+fn on_zone(_: &Zone, _: &mut Scene) -> SceneFnOutcome {
+    SceneFnOutcome::Continue
+}
+
+/// Renders scenes with N sprites (half textures, half rects) to a software canvas
+fn scene_render(c: &mut Criterion) {
+    let mut canvas = Surface::new(320, 240, PixelFormatEnum::RGBA8888)
+        .unwrap()
+        .into_canvas()
+        .unwrap();
+    let texture_creator = canvas.texture_creator();
+    let sheet = Surface::new(64, 64, PixelFormatEnum::RGBA8888).unwrap();
+    let mut index = HashMap::new();
+    index.insert(Key::new("tile"), Rect::new(0, 0, 16, 16));
+    let mut spritesheet = SpriteSheet::new(texture_creator.create_texture_from_surface(&sheet).unwrap(), index);
+    let backgrounds = TextureCache::new(0);
+    let mut missing_sprites = HashSet::new();
+    let mut group = c.benchmark_group("scene_render");
+    for n in SIZES.iter() {
+        let sprites = (0..*n)
+            .map(|i| {
+                let rect = Rect::new((i % 20 * 16) as i32, (i / 20 % 15 * 16) as i32, 16, 16);
+                if i % 2 == 0 {
+                    Sprite::Texture { rect, sprite: Key::new("tile") }
+                } else {
+                    Sprite::Rect { rect, color: Color::RED }
+                }
+            })
+            .collect();
+        let scene = Scene::new(
+            ClearMode::Color(Color::BLACK),
+            HashMap::new(),
+            sprites,
+            HashMap::new(),
+            on_tick,
+            on_child_quit,
+        );
+        group.bench_with_input(BenchmarkId::from_parameter(n), &scene, |b, scene| {
+            let mut renderer = SdlRenderer::new(&mut canvas, &mut spritesheet, &backgrounds);
+            b.iter(|| scene.render(&mut renderer, Palette::Default, &mut missing_sprites, 1.0))
+        });
+    }
+    group.finish();
+}
+
+/// Checks one step of movement against N zones, mixed rects and lines
+fn zone_triggers(c: &mut Criterion) {
+    let mut group = c.benchmark_group("zone_triggers");
+    for n in SIZES.iter() {
+        let zones: Vec<Zone> = (0..*n)
+            .map(|i| {
+                let (x, y) = ((i % 50 * 16) as u32, (i / 50 * 16) as u32);
+                if i % 2 == 0 {
+                    Zone::Rect(Rect::new(x as i32, y as i32, 16, 16), on_zone)
+                } else {
+                    Zone::Line(Line((x, y), (x + 16, y)), on_zone)
+                }
+            })
+            .collect();
+        let (start, end) = (Rect::new(100, 100, 16, 16), Rect::new(100, 116, 16, 16));
+        group.bench_with_input(BenchmarkId::from_parameter(n), &zones, |b, zones| {
+            b.iter(|| zones.iter().filter(|zone| zone.sprite_triggered(start, end)).count())
+        });
+    }
+    group.finish();
+}
+
+/// Dict lookups with a key that is already interned vs. interning the key on every access
+fn dict_access(c: &mut Criterion) {
+    let mut dict: Dict = HashMap::new();
+    for i in 0..100 {
+        dict.insert(Key::new(&format!("key_{}", i)), DictValue::U32(i));
+    }
+    let key = Key::new("key_50");
+    c.bench_function("dict_get_interned", |b| b.iter(|| dict.contains_key(&key)));
+    c.bench_function("dict_get_by_name", |b| b.iter(|| dict.contains_key(&Key::new("key_50"))));
+    c.bench_function("dict_insert", |b| {
+        b.iter(|| dict.insert(key, DictValue::U32(0)))
+    });
+}
+
+/// Decoding an N by N tile map layer from plain vs. zstd compressed JSON. Prints the sizes, since
+/// the smaller files are most of the win on slow disks.
+fn map_load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("map_load");
+    for n in [64, 256].iter() {
+        let tiles: Vec<&str> = (0..n * n).map(|i| if i % 7 == 0 { "tree" } else { "grass" }).collect();
+        let plain = serde_json::to_vec(&serde_json::json!({ "width": n, "height": n, "layers": [tiles] })).unwrap();
+        let compressed = compress::compress(&plain).unwrap();
+        println!("map_load/{}: {} bytes plain, {} compressed", n, plain.len(), compressed.len());
+        for (name, data) in [("plain", plain), ("zstd", compressed)].iter() {
+            group.bench_with_input(BenchmarkId::new(*name, n), data, |b, data| {
+                b.iter(|| {
+                    let bytes = compress::decompress(data.clone()).unwrap();
+                    serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, scene_render, zone_triggers, dict_access, map_load);
+criterion_main!(benches);
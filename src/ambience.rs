@@ -0,0 +1,132 @@
+#[cfg(feature = "mixer")]
+use sdl2::mixer::Channel;
+#[cfg(not(feature = "mixer"))]
+use crate::silent::Channel;
+use sdl2::rect::{ Point, Rect };
+use crate::audio::{ Audio, MUSIC_CROSSFADE };
+use crate::mapfile::{ MapEntity, MapFile, MapZone };
+
+/// Kind of the map entities that are sound emitters
+pub const EMITTER_ENTITY: &str = "sound";
+/// Start of the names of zones that change the music, followed by the track's path, e.g.
+/// `music:music/lab.ogg`
+pub const MUSIC_ZONE_PREFIX: &str = "music:";
+
+/// A looping sound placed on a map, e.g. a waterfall or a generator's hum. It's heard within
+/// `radius` pixels of it, louder the closer the player gets, and from the side it's on.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SoundEmitter {
+    pub sound: String, // name of a loaded sound effect
+    pub pos: Point, // in pixels
+    pub radius: u32, // in pixels
+}
+
+impl SoundEmitter {
+    /// Reads an emitter from a map entity of kind `sound`, with a `sound` property and a
+    /// `radius` in tiles (4 if there's none)
+    pub fn from_entity(entity: &MapEntity, tile_size: u32) -> Option<Self> {
+        if entity.kind != EMITTER_ENTITY {
+            return None;
+        }
+        let property = |name: &str| entity.properties.iter().find(|(field, _)| field == name).map(|(_, value)| value);
+        let radius = property("radius").and_then(|radius| radius.parse::<u32>().ok()).unwrap_or(4);
+        Some(Self {
+            sound: property("sound")?.clone(),
+            pos: Point::new(entity.pos.0, entity.pos.1),
+            radius: radius * tile_size,
+        })
+    }
+}
+
+/// A part of a map with its own music, e.g. the lab in a town
+#[derive(Clone, PartialEq, Debug)]
+pub struct MusicArea {
+    pub rect: Rect, // in pixels
+    pub track: String, // path of the music
+}
+
+impl MusicArea {
+    /// Reads a music area from a zone named `music:<path of the track>`
+    pub fn from_zone(zone: &MapZone) -> Option<Self> {
+        let track = zone.name.strip_prefix(MUSIC_ZONE_PREFIX)?;
+        Some(Self { rect: zone.rect, track: track.to_string() })
+    }
+}
+
+/// The sound of a map around the player: its music, switching with a crossfade when the player
+/// walks into or out of a music area, and its sound emitters. The scene calls `update` with the
+/// player's position every tick and `stop` when it leaves the map.
+pub struct Ambience {
+    track: Option<String>, // the map's own music, played outside every music area
+    areas: Vec<MusicArea>,
+    emitters: Vec<(SoundEmitter, Option<Channel>)>, // with the channel each is playing on
+}
+
+impl Ambience {
+    pub fn new(track: Option<&str>, areas: Vec<MusicArea>, emitters: Vec<SoundEmitter>) -> Self {
+        Self {
+            track: track.map(String::from),
+            areas,
+            emitters: emitters.into_iter().map(|emitter| (emitter, None)).collect(),
+        }
+    }
+
+    /// The music areas and emitters of a compiled map, see `MusicArea::from_zone` and
+    /// `SoundEmitter::from_entity`
+    pub fn from_map(map: &MapFile, track: Option<&str>) -> Self {
+        let areas = map.zones().iter().filter_map(MusicArea::from_zone).collect();
+        let emitters = map
+            .entities()
+            .iter()
+            .filter_map(|entity| SoundEmitter::from_entity(entity, map.tile_size()))
+            .collect();
+        Self::new(track, areas, emitters)
+    }
+
+    /// The track that plays with the player at `player`: that of the first music area they're in,
+    /// or the map's own
+    pub fn track_at(&self, player: Point) -> Option<&str> {
+        self.areas
+            .iter()
+            .find(|area| area.rect.contains_point(player))
+            .map(|area| area.track.as_str())
+            .or(self.track.as_deref())
+    }
+
+    /// Switches the music if the player crossed into another area, and starts, stops and moves
+    /// the emitters by how far the player is from them
+    pub fn update(&mut self, audio: &mut Audio, player: Point) {
+        match self.track_at(player) {
+            Some(track) => audio.play_music(track, MUSIC_CROSSFADE),
+            None => audio.stop_music(MUSIC_CROSSFADE / 2),
+        }
+        for (emitter, channel) in &mut self.emitters {
+            let (dx, dy) = ((emitter.pos.x() - player.x()) as f32, (emitter.pos.y() - player.y()) as f32);
+            let distance = (dx * dx + dy * dy).sqrt();
+            if distance >= emitter.radius as f32 {
+                if let Some(channel) = channel.take() {
+                    channel.halt();
+                }
+                continue;
+            }
+            if channel.is_none_or(|channel| !channel.is_playing()) {
+                *channel = audio.play_looping(&emitter.sound);
+            }
+            if let Some(channel) = channel {
+                // 0 is straight ahead (up on the screen), going clockwise
+                let angle = dx.atan2(-dy).to_degrees().rem_euclid(360.0) as i16;
+                let _ = channel.set_position(angle, (distance / emitter.radius as f32 * 255.0) as u8);
+            }
+        }
+    }
+
+    /// Stops every emitter, e.g. when the player leaves the map. The music is left to the next
+    /// map's `Ambience`, so the same track carries on if both play it.
+    pub fn stop(&mut self) {
+        for (_, channel) in &mut self.emitters {
+            if let Some(channel) = channel.take() {
+                channel.halt();
+            }
+        }
+    }
+}
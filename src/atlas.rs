@@ -0,0 +1,248 @@
+use std::collections::{ BTreeMap, HashMap };
+use std::time::Duration;
+use sdl2::event::{ Event, EventType };
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use crate::dict::*;
+use crate::engine::*;
+use crate::input::Action;
+use crate::intern::Key;
+use crate::text::TextSettings;
+
+/// Time (ms) each frame is shown while an animation plays in the atlas preview
+pub const ATLAS_PREVIEW_FRAME_TIME: u32 = 150;
+/// How many times bigger sprites are drawn in the atlas preview, if they fit
+const PREVIEW_SCALE: u32 = 4;
+const PREVIEW_AREA: (i32, i32, u32, u32) = (160, 8, 152, 152);
+const BACKGROUND: Color = Color::RGB(48, 48, 64);
+
+/// An entry of the atlas preview's list: a single sprite, or an animation made of the sprites
+/// named `<name>_<frame number>`
+#[derive(Clone, PartialEq, Debug)]
+pub struct AtlasEntry {
+    pub name: String,
+    pub frames: Vec<(String, Rect)>, // (sprite name, src_rect), in frame order
+}
+
+/// Groups a spritesheet index (see `SpriteSheet::to_dict`) into entries sorted by name. Sprites
+/// ending in `_<number>` are frames of the same animation, ordered by the number.
+pub fn atlas_entries(atlas: &Dict) -> Vec<AtlasEntry> {
+    let mut entries: BTreeMap<String, Vec<(u32, String, Rect)>> = BTreeMap::new();
+    for (sprite, rect) in atlas {
+        let rect = match read_rect(rect) {
+            Some(rect) => rect,
+            None => continue,
+        };
+        let name = sprite.name();
+        let frame = name.rsplit_once('_').and_then(|(base, frame)| Some((base, frame.parse::<u32>().ok()?)));
+        let (base, number) = frame.unwrap_or((name, 0));
+        entries.entry(base.to_string()).or_default().push((number, name.to_string(), rect));
+    }
+    entries
+        .into_iter()
+        .map(|(name, mut frames)| {
+            frames.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+            AtlasEntry { name, frames: frames.into_iter().map(|(_, sprite, rect)| (sprite, rect)).collect() }
+        })
+        .collect()
+}
+
+fn read_rect(value: &DictValue) -> Option<Rect> {
+    let values = match value {
+        DictValue::Array(values) if values.len() == 4 => values,
+        _ => return None,
+    };
+    let number = |value: &DictValue| match value {
+        DictValue::I32(n) => Some(*n as i64),
+        DictValue::U32(n) => Some(*n as i64),
+        DictValue::I64(n) => Some(*n),
+        DictValue::U64(n) => Some(*n as i64),
+        _ => None,
+    };
+    Some(Rect::new(
+        number(&values[0])? as i32,
+        number(&values[1])? as i32,
+        number(&values[2])? as u32,
+        number(&values[3])? as u32,
+    ))
+}
+
+/// Creates the atlas preview scene, a tool for artists to check the spritesheet's index without
+/// putting sprites into the game. The opening scene sets `_ATLAS` in the props so the engine
+/// passes the index along, and optionally `text`, `TextSettings` as a dict.
+///
+/// Every sprite and animation is listed on the left, and the selected one is drawn enlarged with
+/// its name, src_rect and frame number. Up and Down select an entry, A plays or pauses its
+/// animation, Left and Right step through it a frame at a time, Select toggles onion skinning
+/// (the previous frame faded under the current one), and B quits.
+pub fn create_atlas_preview(props: Dict) -> Scene {
+    let mut state = HashMap::new();
+    state.insert(Key::new("atlas"), props.get(&Key::new("atlas")).cloned().unwrap_or(DictValue::Dict(HashMap::new())));
+    if let Some(text) = props.get(&Key::new("text")) {
+        state.insert(Key::new("text"), text.clone());
+    }
+    state.insert(Key::new("selected"), DictValue::U32(0));
+    state.insert(Key::new("frame"), DictValue::U32(0));
+    state.insert(Key::new("elapsed"), DictValue::U32(0));
+    state.insert(Key::new("playing"), DictValue::Bool(false));
+    state.insert(Key::new("onion"), DictValue::Bool(false));
+    let mut event_callbacks: HashMap<EventType, EventCallbackFn> = HashMap::new();
+    event_callbacks.insert(EventType::KeyDown, atlas_preview_key_down);
+    let mut scene = Scene::new(
+        ClearMode::Color(BACKGROUND),
+        state,
+        Vec::new(),
+        event_callbacks,
+        atlas_preview_tick,
+        |_, _, _| SceneFnOutcome::Continue,
+    );
+    layout_atlas_preview(&mut scene);
+    scene
+}
+
+fn u32_state(state: &Dict, key: &str) -> u32 {
+    dict_u32(state, key).unwrap_or(0)
+}
+
+fn bool_state(state: &Dict, key: &str) -> bool {
+    matches!(state.get(&Key::new(key)), Some(DictValue::Bool(true)))
+}
+
+/// The entries of the atlas and the selected one
+fn selected_entry(state: &Dict) -> (Vec<AtlasEntry>, usize) {
+    let entries = match state.get(&Key::new("atlas")) {
+        Some(DictValue::Dict(atlas)) => atlas_entries(atlas),
+        _ => Vec::new(),
+    };
+    let selected = (u32_state(state, "selected") as usize).min(entries.len().saturating_sub(1));
+    (entries, selected)
+}
+
+fn atlas_preview_tick(scene: &mut Scene, _ctx: &mut EngineContext, interval: Duration) -> SceneFnOutcome {
+    if !bool_state(scene.state(), "playing") {
+        return SceneFnOutcome::Continue;
+    }
+    let elapsed = u32_state(scene.state(), "elapsed") + interval.as_millis() as u32;
+    let steps = elapsed / ATLAS_PREVIEW_FRAME_TIME;
+    scene.state().insert(Key::new("elapsed"), DictValue::U32(elapsed % ATLAS_PREVIEW_FRAME_TIME));
+    if steps > 0 {
+        step_frame(scene.state(), steps as i64);
+        layout_atlas_preview(scene);
+    }
+    SceneFnOutcome::Continue
+}
+
+/// Moves the selected animation `by` frames, wrapping around at either end
+fn step_frame(state: &mut Dict, by: i64) {
+    let (entries, selected) = selected_entry(state);
+    let frames = entries.get(selected).map_or(1, |entry| entry.frames.len()).max(1) as i64;
+    let frame = (u32_state(state, "frame") as i64 + by).rem_euclid(frames);
+    state.insert(Key::new("frame"), DictValue::U32(frame as u32));
+}
+
+fn atlas_preview_key_down(scene: &mut Scene, ctx: &mut EngineContext, event: &Event) -> SceneFnOutcome {
+    let action = match *event {
+        Event::KeyDown { keycode: Some(key), .. } => ctx.input.map.get(key),
+        _ => None,
+    };
+    let state = scene.state();
+    let selected = u32_state(state, "selected");
+    match action {
+        Some(Action::B) => return SceneFnOutcome::Quit(HashMap::new()),
+        Some(Action::Up) | Some(Action::Down) => {
+            let count = selected_entry(state).0.len() as u32;
+            let selected = match action {
+                Some(Action::Up) => selected.saturating_sub(1),
+                _ => (selected + 1).min(count.saturating_sub(1)),
+            };
+            state.insert(Key::new("selected"), DictValue::U32(selected));
+            state.insert(Key::new("frame"), DictValue::U32(0));
+            state.insert(Key::new("elapsed"), DictValue::U32(0));
+        },
+        Some(Action::Left) | Some(Action::Right) => {
+            state.insert(Key::new("playing"), DictValue::Bool(false));
+            step_frame(state, if action == Some(Action::Left) { -1 } else { 1 });
+        },
+        Some(Action::A) => {
+            let playing = !bool_state(state, "playing");
+            state.insert(Key::new("playing"), DictValue::Bool(playing));
+        },
+        Some(Action::Select) => {
+            let onion = !bool_state(state, "onion");
+            state.insert(Key::new("onion"), DictValue::Bool(onion));
+        },
+        _ => return SceneFnOutcome::Continue,
+    }
+    layout_atlas_preview(scene);
+    SceneFnOutcome::Continue
+}
+
+/// Where a sprite of the given size is drawn in the preview area: scaled up as far as
+/// `PREVIEW_SCALE` while it fits, and centered
+fn preview_rect(width: u32, height: u32) -> Rect {
+    let (x, y, area_width, area_height) = PREVIEW_AREA;
+    let fit = (area_width / width.max(1)).min(area_height / height.max(1));
+    let scale = fit.clamp(1, PREVIEW_SCALE);
+    let (width, height) = (width * scale, height * scale);
+    Rect::new(x + (area_width as i32 - width as i32) / 2, y + (area_height as i32 - height as i32) / 2, width, height)
+}
+
+fn layout_atlas_preview(scene: &mut Scene) {
+    let state = scene.state();
+    let text = match state.get(&Key::new("text")) {
+        Some(DictValue::Dict(text)) => TextSettings::from_dict(text),
+        _ => TextSettings::default(),
+    };
+    let line = text.line_height() as i32;
+    let (entries, selected) = selected_entry(state);
+    let mut sprites = Vec::new();
+    if entries.is_empty() {
+        sprites.extend(text.sprites("The atlas is empty", (8, 8)));
+        *scene.sprites() = sprites;
+        return;
+    }
+
+    // the list, scrolled to keep the selection in view
+    let rows = (232 / line).max(1) as usize;
+    let scroll = (selected + 1).saturating_sub(rows);
+    for (i, entry) in entries.iter().enumerate().skip(scroll).take(rows) {
+        let y = 4 + line * (i - scroll) as i32;
+        if i == selected {
+            sprites.push(Sprite::Rect { rect: Rect::new(4, y, 148, line as u32), color: Color::RGB(80, 80, 128) });
+        }
+        let label = match entry.frames.len() {
+            1 => entry.name.clone(),
+            frames => format!("{} ({})", entry.name, frames),
+        };
+        sprites.extend(text.sprites(&label, (6, y)));
+    }
+
+    // the selected sprite, enlarged, with the previous frame faded under it for onion skinning
+    let entry = &entries[selected];
+    let frame = (u32_state(state, "frame") as usize).min(entry.frames.len() - 1);
+    let (name, src) = &entry.frames[frame];
+    let (x, y, width, height) = PREVIEW_AREA;
+    sprites.push(Sprite::Rect { rect: Rect::new(x, y, width, height), color: Color::RGB(96, 96, 112) });
+    if bool_state(state, "onion") && entry.frames.len() > 1 {
+        let (previous, previous_src) = &entry.frames[(frame + entry.frames.len() - 1) % entry.frames.len()];
+        let rect = preview_rect(previous_src.width(), previous_src.height());
+        sprites.push(Sprite::Texture { rect, sprite: Key::new(previous) });
+        sprites.push(Sprite::Rect { rect, color: Color::RGBA(96, 96, 112, 160) });
+    }
+    sprites.push(Sprite::Texture { rect: preview_rect(src.width(), src.height()), sprite: Key::new(name) });
+
+    let info = [
+        name.clone(),
+        format!("src {},{} {}x{}", src.x(), src.y(), src.width(), src.height()),
+        format!("frame {}/{}", frame + 1, entry.frames.len()),
+        format!(
+            "{}{}",
+            if bool_state(state, "playing") { "playing" } else { "paused" },
+            if bool_state(state, "onion") { ", onion skin" } else { "" },
+        ),
+    ];
+    for (i, info) in info.iter().enumerate() {
+        sprites.extend(text.sprites(info, (x, y + height as i32 + 4 + line * i as i32)));
+    }
+    *scene.sprites() = sprites;
+}
@@ -0,0 +1,303 @@
+use std::cell::{ Ref, RefCell };
+use std::collections::HashMap;
+#[cfg(feature = "mixer")]
+use sdl2::mixer::{
+    self,
+    Channel,
+    Chunk,
+    LoaderRWops,
+    Music,
+    AUDIO_S16LSB,
+    DEFAULT_CHANNELS,
+    MAX_VOLUME,
+};
+#[cfg(not(feature = "mixer"))]
+use crate::silent::{
+    self as mixer,
+    Channel,
+    Chunk,
+    LoaderRWops,
+    Music,
+    AUDIO_S16LSB,
+    DEFAULT_CHANNELS,
+    MAX_VOLUME,
+};
+use sdl2::rect::Rect;
+use sdl2::rwops::RWops;
+use crate::captions::Captions;
+use crate::input::Action;
+
+/// Sounds that UI widgets (menus, shops, text boxes) play in response to the player. Widgets only
+/// say which of these happened; what file actually gets played is decided by the `UiSoundMap`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum UiSound {
+    Cursor,
+    Confirm,
+    Cancel,
+    Buzzer, // error, e.g. not enough money or an invalid choice
+    Purchase,
+}
+
+impl UiSound {
+    /// The sound a menu plays when the player presses an action: directions move the cursor, A
+    /// confirms and B cancels. See `Scene::set_menu_sounds`.
+    pub fn for_action(action: Action) -> Option<Self> {
+        match action {
+            Action::Up | Action::Down | Action::Left | Action::Right => Some(Self::Cursor),
+            Action::A => Some(Self::Confirm),
+            Action::B => Some(Self::Cancel),
+            Action::Start | Action::Select => None,
+        }
+    }
+}
+
+/// Decodes a sound effect with SDL_mixer's chunk loader. Despite its name `load_wav` reads every
+/// format the mixer was built with, not only WAV, the same as loading from a file does.
+fn load_chunk(rwops: RWops<'_>) -> Result<Chunk, String> {
+    rwops.load_wav().or_else(|err| Err(format!("not a sound SDL_mixer can decode: {}", err)))
+}
+
+/// Maps each `UiSound` to the name of a loaded sound effect
+pub type UiSoundMap = HashMap<UiSound, String>;
+
+/// Music volume while a ducking sound plays, as a percentage of the normal music volume
+pub const DUCK_VOLUME: i32 = 40;
+/// How many sound effects can play at once before the limiter starts turning them down
+pub const LIMITER_CHANNELS: i32 = 3;
+/// Default ms `play_music` takes to switch tracks: half fading the old one out, half fading the
+/// new one in
+pub const MUSIC_CROSSFADE: u32 = 1000;
+
+/// Holds all loaded sound effects and plays them on free mixer channels. Without the `mixer`
+/// feature (SDL_mixer) it keeps the same API but stays silent, as does an `Audio` made with
+/// `silent` for machines without a sound card.
+pub struct Audio {
+    device: bool, // whether the audio device is open, see `silent`
+    sounds: HashMap<String, Chunk>,
+    ui_sounds: UiSoundMap,
+    music_volume: i32, // 0 to `MAX_VOLUME`, before ducking
+    ducking: Vec<Channel>, // channels playing sounds that duck the music
+    captions: RefCell<Captions>, // playing a sound only borrows the audio, but can show a caption
+    music: Option<(String, Music<'static>)>, // path and track playing, or fading out
+    next_music: Option<(String, u32)>, // path and fade in ms of the track to start once it's faded out
+}
+
+impl Audio {
+    /// Opens the audio device and returns a new `Audio` with no sounds loaded
+    pub fn new(ui_sounds: UiSoundMap) -> Result<Self, String> {
+        mixer::open_audio(44_100, AUDIO_S16LSB, DEFAULT_CHANNELS, 1_024)?;
+        Ok(Self {
+            device: true,
+            sounds: HashMap::new(),
+            ui_sounds,
+            music_volume: Music::get_volume(),
+            ducking: Vec::new(),
+            captions: RefCell::new(Captions::new()),
+            music: None,
+            next_music: None,
+        })
+    }
+
+    /// An `Audio` that never opens the audio device, for when it can't be opened. Sounds and music
+    /// aren't loaded, so playing them does nothing, like playing one that doesn't exist.
+    pub fn silent(ui_sounds: UiSoundMap) -> Self {
+        Self {
+            device: false,
+            sounds: HashMap::new(),
+            ui_sounds,
+            music_volume: MAX_VOLUME,
+            ducking: Vec::new(),
+            captions: RefCell::new(Captions::new()),
+            music: None,
+            next_music: None,
+        }
+    }
+
+    /// Loads a sound effect from a file and stores it under `name`. Sound effects can be in any
+    /// format SDL_mixer's chunk loader reads (WAV, OGG, FLAC, ...), see `load_chunk`.
+    pub fn load(&mut self, name: &str, path: &str) -> Result<(), String> {
+        if !self.device {
+            return Ok(());
+        }
+        let chunk = load_chunk(RWops::from_file(path, "rb")?).or_else(|err| Err(format!("{}: {}", path, err)))?;
+        self.sounds.insert(name.to_string(), chunk);
+        Ok(())
+    }
+
+    /// Loads a sound effect from the contents of a file, e.g. one read ahead of time on another
+    /// thread. Takes the same formats as `load`.
+    pub fn load_bytes(&mut self, name: &str, bytes: &[u8]) -> Result<(), String> {
+        if !self.device {
+            return Ok(());
+        }
+        self.sounds.insert(name.to_string(), load_chunk(RWops::from_bytes(bytes)?)?);
+        Ok(())
+    }
+
+    /// Whether a sound effect is loaded under `name`. A silent `Audio` counts every sound as
+    /// loaded, since it skips loading them.
+    pub fn is_loaded(&self, name: &str) -> bool {
+        !self.device || self.sounds.contains_key(name)
+    }
+
+    /// Plays the sound effect with the given name. Unknown names are ignored.
+    pub fn play(&self, name: &str) {
+        self.play_channel(name);
+    }
+
+    /// Plays a sound effect that the music should make room for, e.g. a dialog blip or a cry. The
+    /// music is turned down to `DUCK_VOLUME` until every ducking sound has finished.
+    pub fn play_ducking(&mut self, name: &str) {
+        if let Some(channel) = self.play_channel(name) {
+            if self.ducking.is_empty() {
+                Music::set_volume(self.music_volume * DUCK_VOLUME / 100);
+            }
+            self.ducking.push(channel);
+        }
+    }
+
+    /// Plays a sound effect made by something at `x` in the world, panned left or right by where
+    /// `x` is relative to the camera: fully to one side at the edge of the view.
+    pub fn play_at(&self, name: &str, x: i32, camera: Rect) {
+        if let Some(channel) = self.play_channel(name) {
+            let half_width = (camera.width() / 2).max(1) as i32;
+            let pan = (x - camera.center().x()).clamp(-half_width, half_width) * 255 / half_width; // -255 to 255
+            let left = (255 - pan).min(255) as u8;
+            let right = (255 + pan).min(255) as u8;
+            let _ = channel.set_panning(left, right);
+        }
+    }
+
+    /// Switches the music to the track at `path`, looping, fading the current track out and the
+    /// new one in over `fade` ms in all. SDL_mixer plays one track at a time, so the two fades
+    /// follow each other instead of overlapping. Asking for the track that's already playing (or
+    /// about to) does nothing.
+    pub fn play_music(&mut self, path: &str, fade: u32) {
+        if self.current_music() == Some(path) {
+            return;
+        }
+        if self.music.is_some() && Music::is_playing() {
+            let _ = Music::fade_out((fade / 2) as i32);
+            self.next_music = Some((path.to_string(), fade / 2));
+        } else {
+            self.next_music = None;
+            self.start_music(path, fade / 2);
+        }
+    }
+
+    /// Fades the music out over `fade` ms
+    pub fn stop_music(&mut self, fade: u32) {
+        self.next_music = None;
+        if self.music.take().is_some() {
+            let _ = Music::fade_out(fade as i32);
+        }
+    }
+
+    /// Path of the track playing, or the one that will once the current one has faded out
+    pub fn current_music(&self) -> Option<&str> {
+        match &self.next_music {
+            Some((next, _)) => Some(next),
+            None => self.music.as_ref().map(|(current, _)| current.as_str()),
+        }
+    }
+
+    fn start_music(&mut self, path: &str, fade: u32) {
+        if !self.device {
+            return;
+        }
+        match Music::from_file(path) {
+            Ok(music) => {
+                let _ = music.fade_in(-1, fade as i32);
+                self.music = Some((path.to_string(), music));
+            },
+            Err(_) => self.music = None, // a missing track just means silence, like a missing sound
+        }
+    }
+
+    /// Music volume from 0 to `MAX_VOLUME`, the volume it goes back to after ducking
+    pub fn set_music_volume(&mut self, volume: i32) {
+        self.music_volume = volume.clamp(0, MAX_VOLUME);
+        if self.ducking.is_empty() {
+            Music::set_volume(self.music_volume);
+        } else {
+            Music::set_volume(self.music_volume * DUCK_VOLUME / 100);
+        }
+    }
+
+    /// Called by the engine once per frame. Brings the music back up once the ducking sounds have
+    /// finished, and keeps the limiter's volume in line with how many sounds are still playing.
+    pub fn update(&mut self) {
+        if self.next_music.is_some() && !Music::is_playing() {
+            if let Some((path, fade)) = self.next_music.take() {
+                self.start_music(&path, fade);
+            }
+        }
+        if !self.ducking.is_empty() {
+            self.ducking.retain(|channel| channel.is_playing());
+            if self.ducking.is_empty() {
+                Music::set_volume(self.music_volume);
+            }
+        }
+        self.limit();
+    }
+
+    /// Tags a sound with the caption shown when it plays while captions are on
+    pub fn set_caption(&mut self, name: &str, caption: &str) {
+        self.captions.get_mut().tag(name, caption);
+    }
+
+    pub fn set_captions_enabled(&mut self, enabled: bool) {
+        self.captions.get_mut().enabled = enabled;
+    }
+
+    pub fn captions(&self) -> Ref<'_, Captions> {
+        self.captions.borrow()
+    }
+
+    /// Counts down the captions on screen
+    pub fn tick_captions(&mut self, interval: u32) {
+        self.captions.get_mut().tick(interval);
+    }
+
+    /// Plays a sound effect on a loop until its channel is halted, e.g. for the hum of a machine.
+    /// Returns the channel, for positioning and stopping it.
+    pub fn play_looping(&self, name: &str) -> Option<Channel> {
+        self.play_loops(name, -1)
+    }
+
+    fn play_channel(&self, name: &str) -> Option<Channel> {
+        self.play_loops(name, 0)
+    }
+
+    fn play_loops(&self, name: &str, loops: i32) -> Option<Channel> {
+        let chunk = self.sounds.get(name)?;
+        let channel = Channel::all().play(chunk, loops).ok()?; // no free channel just means the sound is skipped
+        let _ = channel.unset_panning(); // the channel may have been panned for an earlier sound
+        self.limit();
+        self.captions.borrow_mut().sound_played(name);
+        Some(channel)
+    }
+
+    /// Turns all sound effects down when more than `LIMITER_CHANNELS` play at once, so stacked
+    /// effects don't add up past full scale and clip
+    fn limit(&self) {
+        let playing = mixer::get_playing_channels_number();
+        let volume = if playing <= LIMITER_CHANNELS {
+            MAX_VOLUME
+        } else {
+            (MAX_VOLUME as f32 * (LIMITER_CHANNELS as f32 / playing as f32).sqrt()) as i32
+        };
+        Channel::all().set_volume(volume);
+    }
+
+    /// Plays whatever sound effect is configured for a UI action
+    pub fn play_ui(&self, sound: UiSound) {
+        if let Some(name) = self.ui_sounds.get(&sound) {
+            self.play(name);
+        }
+    }
+
+    pub fn set_ui_sound(&mut self, sound: UiSound, name: &str) {
+        self.ui_sounds.insert(sound, name.to_string());
+    }
+}
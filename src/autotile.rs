@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use crate::dict::*;
+use crate::intern::{ Key, SpriteId };
+use crate::tilemap::TileLayer;
+
+// Bits of a tile's neighbour mask, set where the neighbour is the same terrain
+pub const NORTH: u8 = 1;
+pub const NORTH_EAST: u8 = 2;
+pub const EAST: u8 = 4;
+pub const SOUTH_EAST: u8 = 8;
+pub const SOUTH: u8 = 16;
+pub const SOUTH_WEST: u8 = 32;
+pub const WEST: u8 = 64;
+pub const NORTH_WEST: u8 = 128;
+/// The bits of the four sides, for tilesets that only draw edges and no inner corners
+pub const EDGES: u8 = NORTH | EAST | SOUTH | WEST;
+
+/// How one terrain is drawn: which tile variant goes with which neighbours
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Terrain {
+    pub tiles: HashMap<u8, SpriteId>, // neighbour mask -> tile
+    pub fallback: Option<SpriteId>, // for masks without a tile
+    pub joins: Vec<Key>, // other terrains that count as this one, e.g. deep water for water
+}
+
+/// Terrain rules of a tileset, for resolving a layer of logical terrain ids (grass, path,
+/// water) into the tile variants with the right edges and corners, instead of placing every edge
+/// tile by hand. Read from the tileset's data:
+///
+/// `{ "water": { "tiles": { "255": "water_center", "31": "water_edge_w", ... },
+///   "fallback": "water_center", "joins": ["deep_water"] } }`
+///
+/// The keys of `tiles` are neighbour masks (see `NORTH` and the rest). A corner only counts when
+/// both sides next to it are the same terrain too, leaving the 47 combinations a full tileset
+/// draws. A mask without a tile tries its sides alone (see `EDGES`), then the fallback, and then
+/// the sprite `<terrain>_<mask>`.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TerrainRules {
+    terrains: HashMap<Key, Terrain>,
+}
+
+impl TerrainRules {
+    pub fn from_dict(dict: &Dict) -> Result<Self, String> {
+        let mut terrains = HashMap::new();
+        for (name, terrain) in dict {
+            let terrain = match terrain {
+                DictValue::Dict(terrain) => terrain,
+                _ => return Err(format!("terrain \"{}\" is not an object", name)),
+            };
+            let mut tiles = HashMap::new();
+            if let Some(DictValue::Dict(masks)) = terrain.get(&Key::new("tiles")) {
+                for (mask, tile) in masks {
+                    let mask = mask.name().parse::<u8>().or_else(|_| Err(format!("\"{}\" is not a mask", mask)))?;
+                    if let DictValue::String(tile) = tile {
+                        tiles.insert(mask, Key::new(tile));
+                    }
+                }
+            }
+            let fallback = match terrain.get(&Key::new("fallback")) {
+                Some(DictValue::String(fallback)) => Some(Key::new(fallback)),
+                _ => None,
+            };
+            let joins = match terrain.get(&Key::new("joins")) {
+                Some(DictValue::Array(joins)) => joins
+                    .iter()
+                    .filter_map(|join| match join {
+                        DictValue::String(join) => Some(Key::new(join)),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            terrains.insert(*name, Terrain { tiles, fallback, joins });
+        }
+        Ok(Self { terrains })
+    }
+
+    pub fn terrain(&self, name: Key) -> Option<&Terrain> {
+        self.terrains.get(&name)
+    }
+
+    /// Resolves a layer of terrain ids, row by row, into tiles. Neighbours outside the map count
+    /// as the same terrain, so terrain running off the edge doesn't get a border there.
+    pub fn resolve(&self, terrain: &[Option<Key>], width: u32, height: u32) -> TileLayer {
+        let (width, height) = (width as i64, height as i64);
+        let at = |x: i64, y: i64| terrain.get((y * width + x) as usize).copied().flatten();
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let name = at(x, y)?;
+                let rules = self.terrains.get(&name);
+                let same = |dx: i64, dy: i64| {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                        return true;
+                    }
+                    match at(nx, ny) {
+                        Some(other) => other == name || rules.is_some_and(|rules| rules.joins.contains(&other)),
+                        None => false,
+                    }
+                };
+                Some(self.tile(name, neighbour_mask(same)))
+            })
+            .collect()
+    }
+
+    /// The tile for a terrain with the given neighbours
+    pub fn tile(&self, name: Key, mask: u8) -> SpriteId {
+        let terrain = self.terrains.get(&name);
+        terrain
+            .and_then(|terrain| terrain.tiles.get(&mask).or_else(|| terrain.tiles.get(&(mask & EDGES))))
+            .copied()
+            .or_else(|| terrain.and_then(|terrain| terrain.fallback))
+            .unwrap_or_else(|| Key::new(&format!("{}_{}", name, mask)))
+    }
+}
+
+/// Builds a tile's neighbour mask from whether each neighbour is the same terrain. Corners only
+/// count when both sides next to them do.
+fn neighbour_mask<F: Fn(i64, i64) -> bool>(same: F) -> u8 {
+    let mut mask = 0;
+    for (bit, (dx, dy)) in [(NORTH, (0, -1)), (EAST, (1, 0)), (SOUTH, (0, 1)), (WEST, (-1, 0))].iter() {
+        if same(*dx, *dy) {
+            mask |= bit;
+        }
+    }
+    let corners = [
+        (NORTH_EAST, NORTH | EAST, (1, -1)),
+        (SOUTH_EAST, SOUTH | EAST, (1, 1)),
+        (SOUTH_WEST, SOUTH | WEST, (-1, 1)),
+        (NORTH_WEST, NORTH | WEST, (-1, -1)),
+    ];
+    for (bit, sides, (dx, dy)) in corners.iter() {
+        if mask & sides == *sides && same(*dx, *dy) {
+            mask |= bit;
+        }
+    }
+    mask
+}
@@ -0,0 +1,484 @@
+use std::collections::HashMap;
+use sdl2::event::{ Event, EventType };
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use crate::dict::*;
+use crate::engine::*;
+use crate::input::Action;
+use crate::intern::Key;
+use crate::text::TextSettings;
+
+/// Name of the global that holds the bag, a dict of item id to how many the player has
+pub const BAG_GLOBAL: &str = "bag";
+/// Items listed at once in a pocket
+const VISIBLE_ITEMS: usize = 8;
+
+/// The pockets of the bag, in the order the player flips through them
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Pocket {
+    Items,
+    Medicine,
+    Balls,
+    Berries,
+    BattleItems,
+    Tms,
+    KeyItems,
+}
+
+impl Pocket {
+    pub const ALL: [Pocket; 7] = [
+        Pocket::Items,
+        Pocket::Medicine,
+        Pocket::Balls,
+        Pocket::Berries,
+        Pocket::BattleItems,
+        Pocket::Tms,
+        Pocket::KeyItems,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Items => "items",
+            Self::Medicine => "medicine",
+            Self::Balls => "balls",
+            Self::Berries => "berries",
+            Self::BattleItems => "battle_items",
+            Self::Tms => "tms",
+            Self::KeyItems => "key_items",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|pocket| pocket.name() == name)
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::Items => "Items",
+            Self::Medicine => "Medicine",
+            Self::Balls => "Poke Balls",
+            Self::Berries => "Berries",
+            Self::BattleItems => "Battle Items",
+            Self::Tms => "TMs",
+            Self::KeyItems => "Key Items",
+        }
+    }
+}
+
+/// What using an item does. The bag only says which item was chosen and on whom; the battle (see
+/// `Battler::use_item`) or the overworld applies it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ItemEffect {
+    Heal(u32), // HP
+    HealFull,
+    Cure(Option<Key>), // one status, or every status
+    Revive(u32), // percent of max HP the pokemon comes back with
+    Ball,
+    StatStage(Key, i8), // e.g. X Attack raising "attack" by 2
+    Escape, // ends a wild battle, e.g. a Poke Doll
+    Repel(u32), // steps it keeps weaker wild pokemon away for, see `repel`
+    None, // can't be used, e.g. key items that do their thing elsewhere
+}
+
+impl ItemEffect {
+    /// Whether the player picks a party member to use the item on
+    pub fn needs_target(&self) -> bool {
+        matches!(self, Self::Heal(_) | Self::HealFull | Self::Cure(_) | Self::Revive(_))
+    }
+
+    /// Whether the item would do anything to a pokemon with `hp` of `max_hp` and `status`, so
+    /// it isn't wasted. Always true for effects without a target.
+    pub fn would_affect(&self, hp: u32, max_hp: u32, status: Option<Key>) -> bool {
+        match self {
+            Self::Heal(_) | Self::HealFull => hp > 0 && hp < max_hp,
+            Self::Cure(None) => hp > 0 && status.is_some(),
+            Self::Cure(Some(cured)) => hp > 0 && status == Some(*cured),
+            Self::Revive(_) => hp == 0,
+            Self::None => false,
+            _ => true,
+        }
+    }
+
+    fn from_dict(dict: &Dict) -> Self {
+        let number = |key: &str| match dict.get(&Key::new(key)) {
+            Some(DictValue::I64(n)) => Some(*n),
+            Some(DictValue::U64(n)) => Some(*n as i64),
+            _ => None,
+        };
+        let key = |key: &str| match dict.get(&Key::new(key)) {
+            Some(DictValue::String(value)) => Some(Key::new(value)),
+            _ => None,
+        };
+        match key("effect").as_ref().map(Key::name) {
+            Some("heal") => Self::Heal(number("amount").unwrap_or(20).max(0) as u32),
+            Some("heal_full") => Self::HealFull,
+            Some("cure") => Self::Cure(key("status")),
+            Some("revive") => Self::Revive(number("percent").unwrap_or(50).clamp(1, 100) as u32),
+            Some("ball") => Self::Ball,
+            Some("stat_stage") => match key("stat") {
+                Some(stat) => Self::StatStage(stat, number("stages").unwrap_or(1).clamp(-6, 6) as i8),
+                None => Self::None,
+            },
+            Some("escape") => Self::Escape,
+            Some("repel") => Self::Repel(number("steps").unwrap_or(100).max(1) as u32),
+            _ => Self::None,
+        }
+    }
+}
+
+/// An item as defined in data, e.g. `{ "name": "Potion", "pocket": "medicine", "effect": "heal",
+/// "amount": 20, "battle": true, "field": true }`
+#[derive(Clone, PartialEq, Debug)]
+pub struct ItemData {
+    pub id: Key,
+    pub name: String,
+    pub pocket: Pocket,
+    pub effect: ItemEffect,
+    pub battle: bool, // can be used in battle
+    pub field: bool, // can be used from the bag outside of battle
+}
+
+impl ItemData {
+    pub fn from_dict(id: Key, dict: &Dict) -> Result<Self, String> {
+        let name = match dict.get(&Key::new("name")) {
+            Some(DictValue::String(name)) => name.clone(),
+            _ => id.name().to_string(),
+        };
+        let pocket = match dict.get(&Key::new("pocket")) {
+            Some(DictValue::String(pocket)) => {
+                Pocket::from_name(pocket).ok_or_else(|| format!("item {}: unknown pocket {}", id, pocket))?
+            },
+            _ => return Err(format!("item {}: \"pocket\" is missing", id)),
+        };
+        let flag = |key: &str| matches!(dict.get(&Key::new(key)), Some(DictValue::Bool(true)));
+        Ok(Self { id, name, pocket, effect: ItemEffect::from_dict(dict), battle: flag("battle"), field: flag("field") })
+    }
+}
+
+/// Every item of the game, read from a dict of item id to item (see `ItemData::from_dict`)
+pub fn items_from_dict(dict: &Dict) -> Result<HashMap<Key, ItemData>, String> {
+    let mut items = HashMap::new();
+    for (id, item) in dict {
+        match item {
+            DictValue::Dict(item) => items.insert(*id, ItemData::from_dict(*id, item)?),
+            _ => return Err(format!("item {}: not a dict", id)),
+        };
+    }
+    Ok(items)
+}
+
+/// Where the bag was opened from, which decides what it shows and what choosing an item does
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BagContext {
+    Overworld,
+    Battle,
+}
+
+impl BagContext {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Overworld => "overworld",
+            Self::Battle => "battle",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "overworld" => Some(Self::Overworld),
+            "battle" => Some(Self::Battle),
+            _ => None,
+        }
+    }
+
+    /// The pockets the bag shows: only those with items that can be used in battle, or all of
+    /// them
+    pub fn pockets(&self) -> &'static [Pocket] {
+        match self {
+            Self::Overworld => &Pocket::ALL,
+            Self::Battle => &[Pocket::Medicine, Pocket::Balls, Pocket::Berries, Pocket::BattleItems],
+        }
+    }
+
+    /// Whether an item is listed. In battle only items that work in battle are.
+    pub fn shows(&self, item: &ItemData) -> bool {
+        self.pockets().contains(&item.pocket) && (*self == Self::Overworld || item.battle)
+    }
+
+    pub fn can_use(&self, item: &ItemData) -> bool {
+        item.effect != ItemEffect::None && if *self == Self::Battle { item.battle } else { item.field }
+    }
+}
+
+/// The item the player chose from the bag, read from the props the bag quits with. In battle,
+/// using an item takes the player's turn, and the battle applies it (see `Battler::use_item`)
+/// and takes it out of the bag.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ItemUse {
+    pub item: Key,
+    pub target: Option<usize>, // party index, for items that need one
+}
+
+impl ItemUse {
+    /// `None` if the player closed the bag without using anything, which doesn't take a turn
+    pub fn from_props(props: &Dict) -> Option<Self> {
+        let item = match props.get(&Key::new("item")) {
+            Some(DictValue::String(item)) => Key::new(item),
+            _ => return None,
+        };
+        let target = dict_u32(props, "target").map(|target| target as usize);
+        Some(Self { item, target })
+    }
+
+    fn to_props(self) -> Dict {
+        let mut props = HashMap::new();
+        props.insert(Key::new("item"), DictValue::String(self.item.name().to_string()));
+        if let Some(target) = self.target {
+            props.insert(Key::new("target"), DictValue::U32(target as u32));
+        }
+        props
+    }
+}
+
+/// How many of each item the player has, from the bag global
+pub fn counts(bag: &Dict) -> HashMap<Key, u32> {
+    bag.iter().filter_map(|(item, count)| Some((*item, count.as_u32()?))).collect()
+}
+
+/// Takes one of an item out of the bag global. Returns false if there was none.
+pub fn take(globals: &mut Dict, item: Key) -> bool {
+    let bag = match globals.get_mut(&Key::new(BAG_GLOBAL)) {
+        Some(DictValue::Dict(bag)) => bag,
+        _ => return false,
+    };
+    match bag.get(&item).and_then(DictValue::as_u32) {
+        Some(count) if count > 1 => {
+            bag.insert(item, DictValue::U32(count - 1));
+            true
+        },
+        Some(1) => {
+            bag.remove(&item);
+            true
+        },
+        _ => false,
+    }
+}
+
+/// A party member as the bag sees it when picking a target
+struct Member {
+    name: String,
+    hp: u32,
+    max_hp: u32,
+    status: Option<Key>,
+}
+
+impl Member {
+    fn from_dict(dict: &Dict) -> Self {
+        let name = match dict.get(&Key::new("name")) {
+            Some(DictValue::String(name)) => name.clone(),
+            _ => String::new(),
+        };
+        let status = match dict.get(&Key::new("status")) {
+            Some(DictValue::String(status)) => Some(Key::new(status)),
+            _ => None,
+        };
+        let max_hp = dict_u32(dict, "max_hp").unwrap_or(1);
+        Self { name, hp: dict_u32(dict, "hp").unwrap_or(max_hp), max_hp, status }
+    }
+}
+
+/// Creates the bag scene. Expects `bag`, the bag global; `items`, the item data (see
+/// `items_from_dict`); and optionally `context` ("overworld" or "battle", the default being the
+/// overworld), `party`, an array of `{ "name", "hp", "max_hp", "status" }` dicts to pick targets
+/// from, and `text`, the player's `TextSettings` as a dict.
+///
+/// Left and right flip through the pockets and up and down pick an item. A on an item that needs
+/// a target asks which party member to use it on, refusing those it would have no effect on. The
+/// bag then quits with the choice (see `ItemUse::from_props`) without using anything itself, so
+/// the battle can apply it as the player's turn. B backs out of picking a target, or quits with
+/// nothing chosen.
+pub fn create_bag(props: Dict) -> Scene {
+    let mut state = HashMap::new();
+    for key in ["bag", "items", "party", "text"] {
+        if let Some(value) = props.get(&Key::new(key)) {
+            state.insert(Key::new(key), value.clone());
+        }
+    }
+    let context = match props.get(&Key::new("context")) {
+        Some(DictValue::String(context)) => BagContext::from_name(context).unwrap_or(BagContext::Overworld),
+        _ => BagContext::Overworld,
+    };
+    state.insert(Key::new("context"), DictValue::String(context.name().to_string()));
+    state.insert(Key::new("pocket"), DictValue::U32(0));
+    state.insert(Key::new("cursor"), DictValue::U32(0));
+    let mut event_callbacks: HashMap<EventType, EventCallbackFn> = HashMap::new();
+    event_callbacks.insert(EventType::KeyDown, bag_key_down);
+    let mut scene = Scene::new(
+        ClearMode::Color(Color::RGB(232, 224, 200)),
+        state,
+        Vec::new(),
+        event_callbacks,
+        |_, _, _| SceneFnOutcome::Continue,
+        |_, _, _| SceneFnOutcome::Continue,
+    );
+    scene.set_menu_sounds(true);
+    layout_bag(&mut scene);
+    scene
+}
+
+/// What the bag scene shows: its context, the pocket open, the items listed in it with how many
+/// of each, and the party
+struct BagView {
+    context: BagContext,
+    pocket: usize,
+    listed: Vec<(ItemData, u32)>,
+    party: Vec<Member>,
+}
+
+fn view(state: &Dict) -> BagView {
+    let context = match state.get(&Key::new("context")) {
+        Some(DictValue::String(context)) => BagContext::from_name(context).unwrap_or(BagContext::Overworld),
+        _ => BagContext::Overworld,
+    };
+    let pocket = dict_u32(state, "pocket").unwrap_or(0) as usize % context.pockets().len();
+    let items = match state.get(&Key::new("items")) {
+        Some(DictValue::Dict(items)) => items_from_dict(items).unwrap_or_default(),
+        _ => HashMap::new(),
+    };
+    let mut listed: Vec<(ItemData, u32)> = match state.get(&Key::new("bag")) {
+        Some(DictValue::Dict(bag)) => counts(bag)
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .filter_map(|(item, count)| Some((items.get(&item)?.clone(), count)))
+            .filter(|(item, _)| item.pocket == context.pockets()[pocket] && context.shows(item))
+            .collect(),
+        _ => Vec::new(),
+    };
+    listed.sort_by(|a, b| a.0.name.cmp(&b.0.name));
+    let party = match state.get(&Key::new("party")) {
+        Some(DictValue::Array(party)) => party
+            .iter()
+            .map(|member| match member {
+                DictValue::Dict(member) => Member::from_dict(member),
+                _ => Member { name: String::new(), hp: 0, max_hp: 1, status: None },
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    BagView { context, pocket, listed, party }
+}
+
+fn bag_key_down(scene: &mut Scene, ctx: &mut EngineContext, event: &Event) -> SceneFnOutcome {
+    let action = match *event {
+        Event::KeyDown { keycode: Some(key), .. } => ctx.input.map.get(key),
+        _ => None,
+    };
+    let action = match action {
+        Some(action) => action,
+        None => return SceneFnOutcome::Continue,
+    };
+    let bag = view(scene.state());
+    let state = scene.state();
+    state.remove(&Key::new("message"));
+    let cursor = dict_u32(state, "cursor").unwrap_or(0) as usize;
+    let target = dict_u32(state, "target").map(|target| target as usize);
+    let pockets = bag.context.pockets().len();
+    match (action, target) {
+        (Action::Up, Some(target)) if !bag.party.is_empty() => {
+            let target = (target + bag.party.len() - 1) % bag.party.len();
+            state.insert(Key::new("target"), DictValue::U32(target as u32));
+        },
+        (Action::Down, Some(target)) if !bag.party.is_empty() => {
+            state.insert(Key::new("target"), DictValue::U32(((target + 1) % bag.party.len()) as u32));
+        },
+        (Action::B, Some(_)) => {
+            state.remove(&Key::new("target"));
+        },
+        (Action::A, Some(target)) => {
+            let (item, member) = match (bag.listed.get(cursor), bag.party.get(target)) {
+                (Some((item, _)), Some(member)) => (item, member),
+                _ => return SceneFnOutcome::Continue,
+            };
+            if !item.effect.would_affect(member.hp, member.max_hp, member.status) {
+                state.insert(Key::new("message"), DictValue::String("It won't have any effect.".to_string()));
+            } else {
+                return SceneFnOutcome::Quit(ItemUse { item: item.id, target: Some(target) }.to_props());
+            }
+        },
+        (Action::Left, None) | (Action::Right, None) => {
+            let step = if action == Action::Left { pockets - 1 } else { 1 };
+            state.insert(Key::new("pocket"), DictValue::U32(((bag.pocket + step) % pockets) as u32));
+            state.insert(Key::new("cursor"), DictValue::U32(0));
+        },
+        (Action::Up, None) => {
+            state.insert(Key::new("cursor"), DictValue::U32(cursor.saturating_sub(1) as u32));
+        },
+        (Action::Down, None) => {
+            let last = bag.listed.len().saturating_sub(1);
+            state.insert(Key::new("cursor"), DictValue::U32((cursor + 1).min(last) as u32));
+        },
+        (Action::A, None) => {
+            let item = match bag.listed.get(cursor) {
+                Some((item, _)) => item,
+                None => return SceneFnOutcome::Continue,
+            };
+            if !bag.context.can_use(item) {
+                state.insert(Key::new("message"), DictValue::String("Can't use that here.".to_string()));
+            } else if item.effect.needs_target() {
+                state.insert(Key::new("target"), DictValue::U32(0));
+            } else {
+                return SceneFnOutcome::Quit(ItemUse { item: item.id, target: None }.to_props());
+            }
+        },
+        (Action::B, None) => return SceneFnOutcome::Quit(HashMap::new()),
+        _ => {},
+    }
+    layout_bag(scene);
+    SceneFnOutcome::Continue
+}
+
+fn layout_bag(scene: &mut Scene) {
+    let bag = view(scene.state());
+    let state = scene.state();
+    let text = match state.get(&Key::new("text")) {
+        Some(DictValue::Dict(text)) => TextSettings::from_dict(text),
+        _ => TextSettings::default(),
+    };
+    let line = text.line_height() as i32;
+    let cursor = dict_u32(state, "cursor").unwrap_or(0) as usize;
+    let target = dict_u32(state, "target").map(|target| target as usize);
+    let highlight = Color::RGB(248, 200, 96);
+
+    let mut sprites = vec![Sprite::Rect { rect: Rect::new(8, 8, 304, line as u32 + 8), color: Color::RGB(40, 56, 96) }];
+    let pocket = bag.context.pockets()[bag.pocket];
+    sprites.extend(text.sprites(&format!("< {} >", pocket.title()), (16, 12)));
+    let first = cursor.saturating_sub(VISIBLE_ITEMS - 1);
+    for (i, (item, count)) in bag.listed.iter().enumerate().skip(first).take(VISIBLE_ITEMS) {
+        let y = 8 + line + 16 + (i - first) as i32 * (line + 4);
+        if i == cursor {
+            sprites.push(Sprite::Rect { rect: Rect::new(12, y - 2, 200, line as u32 + 4), color: highlight });
+        }
+        sprites.extend(text.sprites(&item.name, (16, y)));
+        if pocket != Pocket::KeyItems {
+            sprites.extend(text.sprites(&format!("x{}", count), (168, y)));
+        }
+    }
+    if bag.listed.is_empty() {
+        sprites.extend(text.sprites("Nothing here.", (16, 8 + line + 16)));
+    }
+    if let Some(target) = target {
+        sprites.push(Sprite::Rect { rect: Rect::new(216, 24, 96, 160), color: Color::RGB(40, 56, 96) });
+        for (i, member) in bag.party.iter().enumerate() {
+            let y = 28 + i as i32 * (line * 2 + 4);
+            if i == target {
+                sprites.push(Sprite::Rect { rect: Rect::new(218, y - 2, 92, line as u32 * 2 + 4), color: highlight });
+            }
+            sprites.extend(text.sprites(&member.name, (222, y)));
+            sprites.extend(text.sprites(&format!("{}/{}", member.hp, member.max_hp), (222, y + line)));
+        }
+    }
+    if let Some(DictValue::String(message)) = state.get(&Key::new("message")) {
+        sprites.push(Sprite::Rect { rect: Rect::new(8, 200, 304, 32), color: Color::RGB(40, 56, 96) });
+        sprites.extend(text.sprites(message, (16, 208)));
+    }
+    *scene.sprites() = sprites;
+}
@@ -0,0 +1,860 @@
+use std::collections::HashMap;
+use sdl2::pixels::Color;
+use sdl2::rect::{ Point, Rect };
+use crate::bag::ItemEffect;
+use crate::dict::*;
+use crate::engine::Sprite;
+use crate::input::{ Action, HeldActions };
+use crate::intern::{ Key, SpriteId };
+use crate::mapfile::MapFile;
+use crate::stats::{ Stat, Stats };
+use crate::team::PokemonInstance;
+use crate::text::TextSettings;
+use crate::weather::{ Weather, WeatherOverlay };
+
+/// Turns between the resume snapshots of an important battle, see `BattleSnapshot`
+pub const SNAPSHOT_TURNS: u32 = 3;
+
+/// Start of the names of zones that set the battle terrain inside them, followed by the terrain,
+/// e.g. `terrain:cave` for a cave entrance on a route
+pub const TERRAIN_ZONE_PREFIX: &str = "terrain:";
+
+/// Seconds left at which the turn timer turns red
+pub const TURN_TIMER_WARNING: u32 = 10;
+/// How much faster battle animations play while B is held
+pub const FAST_FORWARD_SPEED: u32 = 4;
+
+/// Counts down the time a side has to choose what to do this turn, for net play and the Battle
+/// Tower (see `RuleSet::turn_timer`). The battle starts it at the start of every turn and, once
+/// it has expired, picks for the player with whatever the UI has selected.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TurnTimer {
+    pub limit: u32, // ms
+    left: u32, // ms
+    running: bool,
+}
+
+impl TurnTimer {
+    pub fn new(limit: u32) -> Self {
+        Self { limit, left: limit, running: false }
+    }
+
+    /// Restarts the timer for a new turn
+    pub fn start(&mut self) {
+        self.left = self.limit;
+        self.running = true;
+    }
+
+    /// Stops the timer once the player has chosen
+    pub fn stop(&mut self) {
+        self.running = false;
+    }
+
+    /// Counts down. Returns true on the tick the timer runs out, when the battle should pick the
+    /// selected option for the player.
+    pub fn tick(&mut self, interval: u32) -> bool {
+        if !self.running {
+            return false;
+        }
+        self.left = self.left.saturating_sub(interval);
+        if self.left == 0 {
+            self.running = false;
+            return true;
+        }
+        false
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Whole seconds left, rounded up so the display reads 0 only once time is up
+    pub fn seconds_left(&self) -> u32 {
+        self.left.div_ceil(1000)
+    }
+
+    /// The timer as shown in the battle UI: a bar that empties as time runs out, with the seconds
+    /// left next to it. Red for the last `TURN_TIMER_WARNING` seconds.
+    pub fn sprites(&self, rect: Rect, text: &TextSettings) -> Vec<Sprite> {
+        let color = if self.seconds_left() <= TURN_TIMER_WARNING {
+            Color::RGB(224, 56, 56)
+        } else {
+            Color::RGB(56, 168, 224)
+        };
+        let width = (rect.width() as u64 * self.left as u64 / self.limit.max(1) as u64) as u32;
+        let mut sprites = vec![Sprite::Rect { rect, color: Color::RGB(48, 48, 48) }];
+        if width > 0 {
+            sprites.push(Sprite::Rect { rect: Rect::new(rect.x(), rect.y(), width, rect.height()), color });
+        }
+        sprites.extend(text.sprites(&self.seconds_left().to_string(), (rect.right() + 4, rect.y())));
+        sprites
+    }
+
+    /// Kept in the battle scene's state under `turn_timer`
+    pub fn to_dict(&self) -> Dict {
+        let mut dict = HashMap::new();
+        dict.insert(Key::new("limit"), DictValue::U32(self.limit));
+        dict.insert(Key::new("left"), DictValue::U32(self.left));
+        dict.insert(Key::new("running"), DictValue::Bool(self.running));
+        dict
+    }
+
+    pub fn from_dict(dict: &Dict) -> Self {
+        let number = |key: &str| dict_u32(dict, key);
+        let limit = number("limit").unwrap_or(0);
+        Self {
+            limit,
+            left: number("left").unwrap_or(limit).min(limit),
+            running: matches!(dict.get(&Key::new("running")), Some(DictValue::Bool(true))),
+        }
+    }
+}
+
+/// How fast battle animations should play this frame: `FAST_FORWARD_SPEED` times as fast while
+/// the player holds B, normal speed otherwise. Animations multiply their tick interval by this.
+/// Animations that can be skipped outright (e.g. stat change sparkles) should check
+/// `skip_animations` instead.
+pub fn animation_speed(held: &HeldActions) -> u32 {
+    if held.is_held(Action::B) {
+        FAST_FORWARD_SPEED
+    } else {
+        1
+    }
+}
+
+/// Whether skippable battle animations should be skipped this frame, see `animation_speed`
+pub fn skip_animations(held: &HeldActions) -> bool {
+    held.is_held(Action::B)
+}
+
+/// What moves like Transform and Mimic change about a pokemon for the rest of a battle. The
+/// pokemon itself is left alone, so everything here is gone once the battle ends (see
+/// `Battler::end_battle`) or the pokemon switches out.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct BattleOverrides {
+    pub species: Option<Key>, // what it looks like and counts as, e.g. after Transform
+    pub form: Option<Key>,
+    pub stats: Option<Stats>, // battle stats, the HP in them unused since HP is never copied
+    pub ability: Option<Key>,
+    pub moves: Option<Vec<Key>>, // the whole move set while it's changed
+}
+
+impl BattleOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn to_dict(&self) -> Dict {
+        let string = |key: Key| DictValue::String(key.name().to_string());
+        let mut dict = HashMap::new();
+        for (name, value) in [("species", self.species), ("form", self.form), ("ability", self.ability)] {
+            if let Some(value) = value {
+                dict.insert(Key::new(name), string(value));
+            }
+        }
+        if let Some(stats) = &self.stats {
+            let stats = Stat::ALL.iter().map(|stat| DictValue::U32(stats.get(*stat))).collect();
+            dict.insert(Key::new("stats"), DictValue::Array(stats));
+        }
+        if let Some(moves) = &self.moves {
+            dict.insert(Key::new("moves"), DictValue::Array(moves.iter().map(|m| string(*m)).collect()));
+        }
+        dict
+    }
+
+    fn from_dict(dict: &Dict) -> Self {
+        let key = |name: &str| match dict.get(&Key::new(name)) {
+            Some(DictValue::String(value)) => Some(Key::new(value)),
+            _ => None,
+        };
+        let stats = match dict.get(&Key::new("stats")) {
+            Some(DictValue::Array(values)) if values.len() == Stat::ALL.len() => {
+                let mut stats = Stats::default();
+                for (stat, value) in Stat::ALL.iter().zip(values) {
+                    stats.set(*stat, value.as_u32().unwrap_or(0));
+                }
+                Some(stats)
+            },
+            _ => None,
+        };
+        let moves = match dict.get(&Key::new("moves")) {
+            Some(DictValue::Array(moves)) => Some(
+                moves
+                    .iter()
+                    .filter_map(|m| if let DictValue::String(m) = m { Some(Key::new(m)) } else { None })
+                    .collect(),
+            ),
+            _ => None,
+        };
+        Self { species: key("species"), form: key("form"), stats, ability: key("ability"), moves }
+    }
+}
+
+/// A pokemon in a battle: who it is and how the battle has left it. Also what a
+/// `BattleSnapshot` keeps of each pokemon.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Battler {
+    pub pokemon: PokemonInstance,
+    pub hp: u32,
+    pub status: Option<Key>, // e.g. "poison" or "sleep"
+    pub overrides: BattleOverrides,
+}
+
+impl Battler {
+    pub fn new(pokemon: PokemonInstance, hp: u32) -> Self {
+        Self { pokemon, hp, status: None, overrides: BattleOverrides::default() }
+    }
+
+    /// The species it counts as in battle, e.g. for sprites and type matchups
+    pub fn species(&self) -> Key {
+        self.overrides.species.unwrap_or(self.pokemon.species)
+    }
+
+    pub fn form(&self) -> Key {
+        self.overrides.form.unwrap_or(self.pokemon.form)
+    }
+
+    pub fn ability(&self) -> Key {
+        self.overrides.ability.unwrap_or(self.pokemon.ability)
+    }
+
+    pub fn moves(&self) -> &[Key] {
+        self.overrides.moves.as_deref().unwrap_or(&self.pokemon.moves)
+    }
+
+    /// Its battle stats, `own` being what they are from its species, level and so on. HP is
+    /// always its own.
+    pub fn stats(&self, own: Stats) -> Stats {
+        match self.overrides.stats {
+            Some(stats) => Stats { hp: own.hp, ..stats },
+            None => own,
+        }
+    }
+
+    /// Transform: takes on the target's species, form, ability, moves and stats (`stats`, the
+    /// target's battle stats) for the rest of the battle. HP and status stay its own.
+    pub fn transform_into(&mut self, target: &Battler, stats: Stats) {
+        self.overrides = BattleOverrides {
+            species: Some(target.species()),
+            form: Some(target.form()),
+            stats: Some(target.stats(stats)),
+            ability: Some(target.ability()),
+            moves: Some(target.moves().to_vec()),
+        };
+    }
+
+    /// Mimic: replaces the move in `slot` with `copied` for the rest of the battle. Returns false
+    /// if there's no such slot or it already knows the move.
+    pub fn mimic(&mut self, slot: usize, copied: Key) -> bool {
+        if slot >= self.moves().len() || self.moves().contains(&copied) {
+            return false;
+        }
+        let mut moves = self.moves().to_vec();
+        moves[slot] = copied;
+        self.overrides.moves = Some(moves);
+        true
+    }
+
+    /// Sketch: replaces the move in `slot` with `copied` for good, so unlike `mimic` it changes
+    /// the pokemon itself. While transformed, it's only copied into the transformed move set.
+    pub fn sketch(&mut self, slot: usize, copied: Key) -> bool {
+        if self.overrides.moves.is_some() {
+            return self.mimic(slot, copied);
+        }
+        if slot >= self.pokemon.moves.len() || self.pokemon.moves.contains(&copied) {
+            return false;
+        }
+        self.pokemon.moves[slot] = copied;
+        true
+    }
+
+    /// Undoes everything `BattleOverrides` changed, e.g. when the pokemon switches out
+    pub fn clear_overrides(&mut self) {
+        self.overrides = BattleOverrides::default();
+    }
+
+    /// Applies a targeted item used on it from the bag in battle (see `bag::ItemUse`), with
+    /// `max_hp` its max HP. Returns false if the item had no effect, in which case it isn't used
+    /// up. Balls, stat boosts and escape items act on the battle rather than a pokemon, so the
+    /// battle handles those itself.
+    pub fn use_item(&mut self, effect: &ItemEffect, max_hp: u32) -> bool {
+        if !effect.would_affect(self.hp, max_hp, self.status) {
+            return false;
+        }
+        match *effect {
+            ItemEffect::Heal(amount) => self.hp = (self.hp + amount).min(max_hp),
+            ItemEffect::HealFull => self.hp = max_hp,
+            ItemEffect::Cure(_) => self.status = None,
+            ItemEffect::Revive(percent) => {
+                self.hp = (max_hp * percent / 100).max(1);
+                self.status = None;
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    /// The pokemon as it goes back to the party after the battle, without any battle overrides
+    pub fn end_battle(self) -> PokemonInstance {
+        self.pokemon
+    }
+
+    fn to_dict(&self) -> Dict {
+        let mut dict = HashMap::new();
+        dict.insert(Key::new("pokemon"), DictValue::Dict(self.pokemon.to_dict()));
+        dict.insert(Key::new("hp"), DictValue::U32(self.hp));
+        if let Some(status) = self.status {
+            dict.insert(Key::new("status"), DictValue::String(status.name().to_string()));
+        }
+        if !self.overrides.is_empty() {
+            dict.insert(Key::new("overrides"), DictValue::Dict(self.overrides.to_dict()));
+        }
+        dict
+    }
+
+    fn from_dict(dict: &Dict) -> Result<Self, String> {
+        let pokemon = match dict.get(&Key::new("pokemon")) {
+            Some(DictValue::Dict(pokemon)) => PokemonInstance::from_dict(pokemon)?,
+            _ => return Err(String::from("a battler has no pokemon")),
+        };
+        let status = match dict.get(&Key::new("status")) {
+            Some(DictValue::String(status)) => Some(Key::new(status)),
+            _ => None,
+        };
+        let overrides = match dict.get(&Key::new("overrides")) {
+            Some(DictValue::Dict(overrides)) => BattleOverrides::from_dict(overrides),
+            _ => BattleOverrides::default(),
+        };
+        Ok(Self { pokemon, hp: snapshot_number(dict, "hp")? as u32, status, overrides })
+    }
+}
+
+/// Just enough of a battle to pick it up again after a crash or the power going out, instead of
+/// fighting through the whole gym again: both teams with their HP, the turn, and where the
+/// battle's random numbers were. Important battles save one every `SNAPSHOT_TURNS` turns with
+/// `Profiles::save_battle`; when the game starts, a slot with a snapshot offers to resume it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BattleSnapshot {
+    pub battle: Key, // which battle this is, e.g. "gym_3", for the game to set it up again
+    pub turn: u32,
+    pub rng: u64, // state of the battle's random number generator
+    pub player: Vec<Battler>,
+    pub opponent: Vec<Battler>,
+    pub active: (usize, usize), // index of the pokemon each side has out
+}
+
+impl BattleSnapshot {
+    /// Whether an important battle should save a snapshot at the start of `turn`
+    pub fn is_due(turn: u32) -> bool {
+        turn > 0 && turn.is_multiple_of(SNAPSHOT_TURNS)
+    }
+
+    pub fn to_dict(&self) -> Dict {
+        let battlers = |side: &[Battler]| DictValue::Array(side.iter().map(|b| DictValue::Dict(b.to_dict())).collect());
+        let mut dict = HashMap::new();
+        dict.insert(Key::new("battle"), DictValue::String(self.battle.name().to_string()));
+        dict.insert(Key::new("turn"), DictValue::U32(self.turn));
+        // as a string, since numbers in JSON lose precision past 2^53
+        dict.insert(Key::new("rng"), DictValue::String(self.rng.to_string()));
+        dict.insert(Key::new("player"), battlers(&self.player));
+        dict.insert(Key::new("opponent"), battlers(&self.opponent));
+        dict.insert(
+            Key::new("active"),
+            DictValue::Array(vec![DictValue::U32(self.active.0 as u32), DictValue::U32(self.active.1 as u32)]),
+        );
+        dict
+    }
+
+    pub fn from_dict(dict: &Dict) -> Result<Self, String> {
+        let battle = match dict.get(&Key::new("battle")) {
+            Some(DictValue::String(battle)) => Key::new(battle),
+            _ => return Err(String::from("the snapshot doesn't say which battle it is")),
+        };
+        let rng = match dict.get(&Key::new("rng")) {
+            Some(DictValue::String(rng)) => rng.parse::<u64>().or_else(|_| Err("\"rng\" is not a number"))?,
+            _ => return Err(String::from("\"rng\" is missing")),
+        };
+        let battlers = |side: &str| -> Result<Vec<Battler>, String> {
+            match dict.get(&Key::new(side)) {
+                Some(DictValue::Array(battlers)) => battlers
+                    .iter()
+                    .map(|battler| match battler {
+                        DictValue::Dict(battler) => Battler::from_dict(battler),
+                        _ => Err(format!("a battler of \"{}\" is not a dict", side)),
+                    })
+                    .collect(),
+                _ => Err(format!("\"{}\" is missing", side)),
+            }
+        };
+        let active = match dict.get(&Key::new("active")) {
+            Some(DictValue::Array(active)) if active.len() == 2 => {
+                let index = |value: &DictValue| value.as_u32().unwrap_or(0) as usize;
+                (index(&active[0]), index(&active[1]))
+            },
+            _ => (0, 0),
+        };
+        let snapshot = Self {
+            battle,
+            turn: snapshot_number(dict, "turn")? as u32,
+            rng,
+            player: battlers("player")?,
+            opponent: battlers("opponent")?,
+            active,
+        };
+        if snapshot.active.0 >= snapshot.player.len() || snapshot.active.1 >= snapshot.opponent.len() {
+            return Err(String::from("the active pokemon aren't in the teams"));
+        }
+        Ok(snapshot)
+    }
+}
+
+fn snapshot_number(dict: &Dict, key: &str) -> Result<u64, String> {
+    dict.get(&Key::new(key)).and_then(DictValue::as_u64).ok_or_else(|| format!("\"{}\" is missing", key))
+}
+
+/// Where a battle takes place, which decides how it looks, see `BattleSetting`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum BattleTerrain {
+    #[default]
+    Grass,
+    Cave,
+    Water,
+    Indoor,
+}
+
+impl BattleTerrain {
+    /// Name in map data and sprite names, e.g. `cave`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Grass => "grass",
+            Self::Cave => "cave",
+            Self::Water => "water",
+            Self::Indoor => "indoor",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "grass" => Some(Self::Grass),
+            "cave" => Some(Self::Cave),
+            "water" => Some(Self::Water),
+            "indoor" => Some(Self::Indoor),
+            _ => None,
+        }
+    }
+
+    /// A map's own terrain, from `terrain` in its data, e.g. `{ "terrain": "cave" }`. Maps
+    /// without one are grass.
+    pub fn from_dict(dict: &Dict) -> Self {
+        match dict.get(&Key::new("terrain")) {
+            Some(DictValue::String(name)) => Self::from_name(name).unwrap_or_default(),
+            _ => Self::default(),
+        }
+    }
+
+    /// The terrain of a battle started at `pos` (in pixels) on a map whose own terrain is
+    /// `default`: water while the player is surfing or fishing, otherwise that of the first
+    /// `terrain:` zone the position is in, or the map's
+    pub fn at(map: &MapFile, pos: Point, default: Self, on_water: bool) -> Self {
+        if on_water {
+            return Self::Water;
+        }
+        map.zones()
+            .iter()
+            .filter(|zone| zone.rect.contains_point(pos))
+            .find_map(|zone| Self::from_name(zone.name.strip_prefix(TERRAIN_ZONE_PREFIX)?))
+            .unwrap_or(default)
+    }
+}
+
+/// The transition from the overworld into a battle
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BattleIntro {
+    Swirl,
+    Shatter,
+    Ripple,
+    Fade,
+}
+
+impl BattleIntro {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Swirl => "swirl",
+            Self::Shatter => "shatter",
+            Self::Ripple => "ripple",
+            Self::Fade => "fade",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "swirl" => Some(Self::Swirl),
+            "shatter" => Some(Self::Shatter),
+            "ripple" => Some(Self::Ripple),
+            "fade" => Some(Self::Fade),
+            _ => None,
+        }
+    }
+}
+
+/// How a battle looks: its background, the platforms the pokemon stand on and the intro
+/// transition, picked from the terrain where it started. The overworld passes it to the battle
+/// scene in its props with `to_props`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BattleSetting {
+    pub terrain: BattleTerrain,
+    pub background: String, // name of a loaded background
+    pub platforms: (SpriteId, SpriteId), // under the player's pokemon and the opponent's
+    pub intro: BattleIntro,
+}
+
+impl BattleSetting {
+    /// The usual look of a terrain: the background `battle_<terrain>`, the platform sprites
+    /// `platform_<terrain>_player` and `platform_<terrain>_opponent`, and a swirl into battles in
+    /// grass, a shatter in caves, a ripple on water and a fade indoors
+    pub fn for_terrain(terrain: BattleTerrain) -> Self {
+        let platform = |side: &str| Key::new(&format!("platform_{}_{}", terrain.name(), side));
+        let intro = match terrain {
+            BattleTerrain::Grass => BattleIntro::Swirl,
+            BattleTerrain::Cave => BattleIntro::Shatter,
+            BattleTerrain::Water => BattleIntro::Ripple,
+            BattleTerrain::Indoor => BattleIntro::Fade,
+        };
+        Self {
+            terrain,
+            background: format!("battle_{}", terrain.name()),
+            platforms: (platform("player"), platform("opponent")),
+            intro,
+        }
+    }
+
+    /// Adds the setting to a battle scene's props, under `terrain`, `background`, `platforms`
+    /// and `intro`
+    pub fn to_props(&self, props: &mut Dict) {
+        let name = |name: &str| DictValue::String(name.to_string());
+        props.insert(Key::new("terrain"), name(self.terrain.name()));
+        props.insert(Key::new("background"), name(&self.background));
+        props.insert(
+            Key::new("platforms"),
+            DictValue::Array(vec![name(self.platforms.0.name()), name(self.platforms.1.name())]),
+        );
+        props.insert(Key::new("intro"), name(self.intro.name()));
+    }
+
+    /// Reads the setting from a battle scene's props. Whatever is missing comes from the
+    /// terrain's usual look, so props with just a `background` (or nothing) still work.
+    pub fn from_props(props: &Dict) -> Self {
+        let string = |key: &str| match props.get(&Key::new(key)) {
+            Some(DictValue::String(value)) => Some(value.as_str()),
+            _ => None,
+        };
+        let terrain = string("terrain").and_then(BattleTerrain::from_name).unwrap_or_default();
+        let mut setting = Self::for_terrain(terrain);
+        if let Some(background) = string("background") {
+            setting.background = background.to_string();
+        }
+        if let Some(DictValue::Array(platforms)) = props.get(&Key::new("platforms")) {
+            if let [DictValue::String(player), DictValue::String(opponent)] = platforms.as_slice() {
+                setting.platforms = (Key::new(player), Key::new(opponent));
+            }
+        }
+        if let Some(intro) = string("intro").and_then(BattleIntro::from_name) {
+            setting.intro = intro;
+        }
+        setting
+    }
+}
+
+/// ms the sparkles of a shiny pokemon's entry last
+pub const SPARKLE_TIME: u32 = 1200;
+/// ms the aura of a boss pokemon's entry takes to pulse in before the battle goes on. It keeps
+/// pulsing after that, more faintly.
+pub const AURA_TIME: u32 = 1500;
+/// Sparkles around a shiny pokemon
+const SPARKLES: u32 = 8;
+/// ms between the frames of a sparkle
+const SPARKLE_FRAME_TIME: u32 = 100;
+
+/// The extra effects a pokemon enters the battle with when it's sent out or appears, on top of
+/// the usual entry: sparkles and a sound for a shiny pokemon, a pulsing aura for a boss (see
+/// `PokemonInstance::boss`). The battle scene makes one with `for_pokemon` on every send, plays
+/// `sounds` once, ticks it and draws its sprites around the pokemon until it's done.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EntryAnimation {
+    pub sparkle: bool,
+    pub aura: bool,
+    time: u32, // ms
+}
+
+impl EntryAnimation {
+    pub fn for_pokemon(pokemon: &PokemonInstance) -> Self {
+        Self { sparkle: pokemon.shiny, aura: pokemon.boss, time: 0 }
+    }
+
+    /// Whether the entry has no extra effects, so the battle doesn't need to wait on it
+    pub fn is_plain(&self) -> bool {
+        !self.sparkle && !self.aura
+    }
+
+    /// Names of the sound effects to play as the pokemon enters
+    pub fn sounds(&self) -> Vec<&'static str> {
+        let mut sounds = Vec::new();
+        if self.sparkle {
+            sounds.push("shiny_sparkle");
+        }
+        if self.aura {
+            sounds.push("boss_aura");
+        }
+        sounds
+    }
+
+    pub fn tick(&mut self, interval: u32) {
+        self.time = self.time.saturating_add(interval);
+    }
+
+    /// Whether the battle can go on, i.e. the sparkles are over and the aura has pulsed in
+    pub fn is_done(&self) -> bool {
+        (!self.sparkle || self.time >= SPARKLE_TIME) && (!self.aura || self.time >= AURA_TIME)
+    }
+
+    /// Sprites of the effects around the pokemon at `rect`: the aura behind it as a rect, which
+    /// the scene puts under the pokemon's sprite, and sparkles `sparkle_<frame>` (0 to 3)
+    /// spreading out from it, which go on top. Returns (under, over).
+    pub fn sprites(&self, rect: Rect) -> (Vec<Sprite>, Vec<Sprite>) {
+        let mut under = Vec::new();
+        if self.aura {
+            // pulses twice as it comes in, then keeps pulsing at half strength
+            let phase = (self.time % 750) as i32;
+            let pulse = if phase < 375 { phase } else { 750 - phase };
+            let strength = if self.time < AURA_TIME { 160 } else { 80 };
+            let grow = (pulse / 40) as u32;
+            under.push(Sprite::Rect {
+                rect: Rect::from_center(rect.center(), rect.width() + grow * 2, rect.height() + grow * 2),
+                color: Color::RGBA(200, 40, 60, (strength * pulse / 375) as u8),
+            });
+        }
+        let mut over = Vec::new();
+        if self.sparkle && self.time < SPARKLE_TIME {
+            let frame = self.time / SPARKLE_FRAME_TIME % 4;
+            let distance = (rect.width().max(rect.height()) / 2) as f32 * self.time as f32 / SPARKLE_TIME as f32;
+            let size = (rect.width() / 4).max(4);
+            for i in 0..SPARKLES {
+                let angle = std::f32::consts::PI * 2.0 * i as f32 / SPARKLES as f32;
+                let center = rect.center().offset((angle.cos() * distance) as i32, (angle.sin() * distance) as i32);
+                over.push(Sprite::Texture {
+                    rect: Rect::from_center(center, size, size),
+                    sprite: Key::new(&format!("sparkle_{}", frame)),
+                });
+            }
+        }
+        (under, over)
+    }
+}
+
+/// Turns weather set by a move or ability lasts
+pub const WEATHER_TURNS: u32 = 5;
+/// Turns it lasts when its setter holds the rock that extends it, e.g. Damp Rock for rain
+pub const EXTENDED_WEATHER_TURNS: u32 = 8;
+
+/// The weather of a battle, set by moves like Rain Dance and abilities like Drizzle. It changes
+/// move damage and accuracy, chips at the battlers at the end of each turn, and is drawn over the
+/// battle scene with a `WeatherOverlay`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct BattleWeather {
+    pub weather: Weather,
+    pub turns_left: u32, // 0 while clear
+}
+
+impl BattleWeather {
+    /// The weather a move sets, if any
+    pub fn from_move(move_name: Key) -> Option<Weather> {
+        match move_name.name() {
+            "rain_dance" => Some(Weather::Rain),
+            "sunny_day" => Some(Weather::Sun),
+            "sandstorm" => Some(Weather::Sandstorm),
+            "hail" => Some(Weather::Hail),
+            _ => None,
+        }
+    }
+
+    /// The weather an ability sets when its pokemon comes in, if any
+    pub fn from_ability(ability: Key) -> Option<Weather> {
+        match ability.name() {
+            "drizzle" => Some(Weather::Rain),
+            "drought" => Some(Weather::Sun),
+            "sand_stream" => Some(Weather::Sandstorm),
+            "snow_warning" => Some(Weather::Hail),
+            _ => None,
+        }
+    }
+
+    /// The held item that makes `weather` last `EXTENDED_WEATHER_TURNS`
+    pub fn extending_item(weather: Weather) -> Option<&'static str> {
+        match weather {
+            Weather::Rain => Some("damp_rock"),
+            Weather::Sun => Some("heat_rock"),
+            Weather::Sandstorm => Some("smooth_rock"),
+            Weather::Hail => Some("icy_rock"),
+            Weather::Clear => None,
+        }
+    }
+
+    /// Sets the weather for `WEATHER_TURNS`, or longer if `item`, the setter's held item, extends
+    /// it. Returns false, changing nothing, if that weather is already up.
+    pub fn set(&mut self, weather: Weather, item: Option<Key>) -> bool {
+        if weather == self.weather {
+            return false;
+        }
+        let extended = item.is_some() && item.map(|item| item.name()) == Self::extending_item(weather);
+        self.weather = weather;
+        self.turns_left = match weather {
+            Weather::Clear => 0,
+            _ if extended => EXTENDED_WEATHER_TURNS,
+            _ => WEATHER_TURNS,
+        };
+        true
+    }
+
+    /// Counts down a turn, at the end of the turn after the chip damage. Returns true if the
+    /// weather ended, for the battle to say so.
+    pub fn end_turn(&mut self) -> bool {
+        if self.weather == Weather::Clear {
+            return false;
+        }
+        self.turns_left = self.turns_left.saturating_sub(1);
+        if self.turns_left == 0 {
+            self.weather = Weather::Clear;
+            return true;
+        }
+        false
+    }
+
+    /// Percent of its damage a move of type `move_type` does: rain boosts Water moves and weakens
+    /// Fire moves, harsh sunlight the other way around
+    pub fn damage_percent(&self, move_type: Key) -> u32 {
+        match (self.weather, move_type.name()) {
+            (Weather::Rain, "water") | (Weather::Sun, "fire") => 150,
+            (Weather::Rain, "fire") | (Weather::Sun, "water") => 50,
+            _ => 100,
+        }
+    }
+
+    /// The accuracy of `move_name` in this weather, `None` if it can't miss. Thunder and Hurricane
+    /// can't miss in rain and drop to 50 in harsh sunlight, and Blizzard can't miss in hail.
+    pub fn accuracy(&self, move_name: Key, accuracy: Option<u32>) -> Option<u32> {
+        match (self.weather, move_name.name()) {
+            (Weather::Rain, "thunder") | (Weather::Rain, "hurricane") | (Weather::Hail, "blizzard") => None,
+            (Weather::Sun, "thunder") | (Weather::Sun, "hurricane") => Some(50),
+            _ => accuracy,
+        }
+    }
+
+    /// Damage a battler takes at the end of a turn: a sixteenth of its `max_hp` in a sandstorm
+    /// unless it's Rock, Ground or Steel, and in hail unless it's Ice. Abilities like Sand Veil and
+    /// Overcoat and the Safety Goggles keep it out.
+    pub fn chip_damage(&self, types: &[Key], ability: Key, item: Option<Key>, max_hp: u32) -> u32 {
+        let (immune_types, immune_abilities): (&[&str], &[&str]) = match self.weather {
+            Weather::Sandstorm => (&["rock", "ground", "steel"], &["sand_veil", "sand_rush", "sand_force"]),
+            Weather::Hail => (&["ice"], &["ice_body", "snow_cloak"]),
+            _ => return 0,
+        };
+        let immune = types.iter().any(|t| immune_types.contains(&t.name()))
+            || immune_abilities.contains(&ability.name())
+            || matches!(ability.name(), "overcoat" | "magic_guard")
+            || item.map(|item| item.name()) == Some("safety_goggles");
+        if immune {
+            0
+        } else {
+            (max_hp / 16).max(1)
+        }
+    }
+
+    /// The overlay that draws this weather over the battle scene
+    pub fn overlay(&self) -> WeatherOverlay {
+        WeatherOverlay::new(self.weather)
+    }
+
+    pub fn to_dict(&self) -> Dict {
+        let mut dict = Dict::new();
+        dict.insert(Key::new("weather"), DictValue::String(self.weather.name().to_string()));
+        dict.insert(Key::new("turns_left"), DictValue::U32(self.turns_left));
+        dict
+    }
+
+    pub fn from_dict(dict: &Dict) -> Self {
+        let weather = match dict.get(&Key::new("weather")) {
+            Some(DictValue::String(weather)) => Weather::from_name(weather).unwrap_or_default(),
+            _ => Weather::Clear,
+        };
+        let turns_left = dict_u32(dict, "turns_left").unwrap_or(0);
+        Self { weather, turns_left }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use crate::stats::Nature;
+    use super::*;
+
+    fn battler(hp: u32, status: Option<&str>) -> Battler {
+        let pokemon = PokemonInstance {
+            species: Key::new("pidgey"),
+            form: Key::new("default"),
+            level: 5,
+            nature: Nature::new(0),
+            ability: Key::new("keen_eye"),
+            item: None,
+            moves: vec![Key::new("tackle")],
+            ivs: Stats::default(),
+            evs: Stats::default(),
+            shiny: false,
+            boss: false,
+        };
+        let mut battler = Battler::new(pokemon, hp);
+        battler.status = status.map(Key::new);
+        battler
+    }
+
+    fn item_effects() -> impl Strategy<Value = ItemEffect> {
+        prop_oneof![
+            (0u32..=999).prop_map(ItemEffect::Heal),
+            Just(ItemEffect::HealFull),
+            Just(ItemEffect::Cure(None)),
+            Just(ItemEffect::Cure(Some(Key::new("poison")))),
+            (1u32..=100).prop_map(ItemEffect::Revive),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn chip_damage_never_exceeds_max_hp(
+            weather in prop::sample::select(Weather::ALL.to_vec()),
+            types in prop::sample::subsequence(vec!["normal", "rock", "ground", "steel", "ice"], 0..=2),
+            ability in prop::sample::select(vec!["keen_eye", "sand_veil", "ice_body", "overcoat"]),
+            goggles in any::<bool>(),
+            max_hp in 1u32..=999,
+        ) {
+            let types: Vec<Key> = types.into_iter().map(Key::new).collect();
+            let item = if goggles { Some(Key::new("safety_goggles")) } else { None };
+            let damage = BattleWeather { weather, turns_left: 5 }.chip_damage(&types, Key::new(ability), item, max_hp);
+            prop_assert!(damage <= max_hp, "{} damage with {} max HP", damage, max_hp);
+        }
+
+        #[test]
+        fn items_never_heal_past_max_hp(
+            effect in item_effects(),
+            max_hp in 1u32..=999,
+            hp_percent in 0u32..=100,
+            status in prop::option::of(prop::sample::select(vec!["poison", "sleep"])),
+        ) {
+            let mut battler = battler(max_hp * hp_percent / 100, status);
+            let before = battler.clone();
+            let used = battler.use_item(&effect, max_hp);
+            prop_assert!(battler.hp <= max_hp, "{} HP with {} max HP", battler.hp, max_hp);
+            if !used {
+                prop_assert_eq!(battler, before); // an item that wasn't used up did nothing
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use crate::renderer::Renderer;
+use crate::text::TextSettings;
+
+/// How long (ms) a caption stays on screen
+pub const CAPTION_DURATION: u32 = 3_000;
+/// Most captions shown at once; older ones make room for new ones
+pub const MAX_CAPTIONS: usize = 3;
+
+/// Accessibility option that shows text descriptions of significant sounds, e.g. "[Door opens]"
+/// or "[Trainer music]", in the bottom right corner of the screen. Sounds are tagged with their
+/// caption through `Audio::set_caption`, and playing a tagged sound shows its caption.
+pub struct Captions {
+    pub enabled: bool,
+    tags: HashMap<String, String>, // caption by sound name
+    shown: Vec<(String, u32)>, // (caption, ms left), oldest first
+}
+
+impl Captions {
+    pub fn new() -> Self {
+        Self { enabled: false, tags: HashMap::new(), shown: Vec::new() }
+    }
+
+    /// Tags a sound with the caption shown when it plays
+    pub fn tag(&mut self, sound: &str, caption: &str) {
+        self.tags.insert(sound.to_string(), caption.to_string());
+    }
+
+    /// Shows the caption of a sound that just started playing, if it has one and captions are on.
+    /// A caption that is already showing is restarted instead of shown twice.
+    pub fn sound_played(&mut self, sound: &str) {
+        if !self.enabled {
+            return;
+        }
+        let caption = match self.tags.get(sound) {
+            Some(caption) => caption.clone(),
+            None => return,
+        };
+        self.shown.retain(|(shown, _)| *shown != caption);
+        if self.shown.len() >= MAX_CAPTIONS {
+            self.shown.remove(0);
+        }
+        self.shown.push((caption, CAPTION_DURATION));
+    }
+
+    pub fn tick(&mut self, interval: u32) {
+        for (_, left) in self.shown.iter_mut() {
+            *left = left.saturating_sub(interval);
+        }
+        self.shown.retain(|(_, left)| *left > 0);
+    }
+
+    /// Captions showing right now, oldest first
+    pub fn shown(&self) -> impl Iterator<Item = &str> {
+        self.shown.iter().map(|(caption, _)| caption.as_str())
+    }
+
+    /// Draws the captions in the bottom right corner of the screen, newest at the bottom, over a
+    /// dark box so they can be read on any background
+    pub fn render(&self, renderer: &mut dyn Renderer, text: &TextSettings) {
+        if self.shown.is_empty() {
+            return;
+        }
+        let viewport = renderer.viewport();
+        let camera = renderer.camera();
+        renderer.set_camera((0, 0)); // captions are fixed to the screen
+        let line = text.line_height() as i32;
+        let mut y = viewport.height() as i32 - 4 - line * self.shown.len() as i32;
+        for (caption, _) in &self.shown {
+            let width = text.char_width() * caption.chars().count() as u32;
+            let x = viewport.width() as i32 - 4 - width as i32;
+            renderer.draw_rect(Rect::new(x - 2, y, width + 4, line as u32), Color::RGB(0, 0, 0));
+            renderer.draw_text(caption, (x, y), text);
+            y += line;
+        }
+        renderer.set_camera(camera);
+    }
+}
+
+impl Default for Captions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
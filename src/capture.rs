@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use crate::dict::*;
+use crate::intern::Key;
+use crate::rng::Rng;
+
+/// Pokedex caught counts at which critical captures get more likely, with the multiplier each
+/// gives, highest first
+pub const CRITICAL_CAPTURE_THRESHOLDS: [(u32, f64); 5] = [(600, 2.5), (450, 2.0), (300, 1.5), (150, 1.0), (30, 0.5)];
+/// Catch rate bonus of a Repeat Ball against a species the player has caught before
+pub const REPEAT_BALL_BONUS: f64 = 3.5;
+
+/// Everything a `CaptureModel` knows about a ball being thrown
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Throw {
+    pub ball: Key,
+    pub species: Key,
+    pub catch_rate: u32, // the species' own, from 3 (legendaries) to 255
+    pub level: u32,
+    pub hp: u32,
+    pub max_hp: u32,
+    pub status: Option<Key>, // e.g. "sleep" or "poison"
+    pub turn: u32, // turns into the battle, from 1
+    pub caught_before: bool, // the player has caught this species before
+    pub dex_caught: u32, // species the player has caught
+}
+
+/// How a throw went
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Capture {
+    pub caught: bool,
+    pub shakes: u8, // times the ball shook, up to 3, or 1 for a critical capture
+    pub critical: bool,
+}
+
+/// Decides how likely a ball is to catch a pokemon, so games can change the rules (new balls,
+/// dex-based critical captures, event bonuses) without touching `Captures`. Every method defaults
+/// to the usual rules, with no ball bonuses and no critical captures.
+pub trait CaptureModel {
+    /// How much the ball multiplies the catch rate, e.g. 1.5 for a Great Ball
+    fn ball_bonus(&mut self, _throw: &Throw) -> f64 {
+        1.0
+    }
+
+    /// How much the pokemon's status multiplies the catch rate
+    fn status_bonus(&mut self, throw: &Throw) -> f64 {
+        match throw.status.as_ref().map(Key::name) {
+            Some("sleep") | Some("freeze") => 2.5,
+            Some(_) => 1.5,
+            None => 1.0,
+        }
+    }
+
+    /// Chance from 0 to 1 of a critical capture, a throw that shakes once and only has to pass one
+    /// shake check. `modified_rate` is the catch rate with every bonus applied, up to 255.
+    fn critical_chance(&mut self, _throw: &Throw, _modified_rate: f64) -> f64 {
+        0.0
+    }
+
+    /// Is told about every throw, for models that keep track of e.g. the balls used
+    fn thrown(&mut self, _throw: &Throw, _capture: &Capture) {}
+}
+
+/// The usual rules, with the ball bonuses read from data, e.g. `{ "great_ball": 1.5,
+/// "ultra_ball": 2.0, "master_ball": 255 }`. Balls that aren't listed have no bonus.
+#[derive(Clone, Default, Debug)]
+pub struct BallBonuses {
+    bonuses: HashMap<Key, f64>,
+}
+
+impl BallBonuses {
+    pub fn from_dict(dict: &Dict) -> Result<Self, String> {
+        let mut bonuses = HashMap::new();
+        for (ball, bonus) in dict {
+            let bonus = match bonus {
+                DictValue::F64(bonus) => *bonus,
+                DictValue::I64(bonus) => *bonus as f64,
+                DictValue::U64(bonus) => *bonus as f64,
+                DictValue::U32(bonus) => *bonus as f64,
+                _ => return Err(format!("ball bonus {}: not a number", ball)),
+            };
+            bonuses.insert(*ball, bonus);
+        }
+        Ok(Self { bonuses })
+    }
+
+    pub fn bonus(&self, ball: Key) -> f64 {
+        self.bonuses.get(&ball).copied().unwrap_or(1.0)
+    }
+}
+
+impl CaptureModel for BallBonuses {
+    fn ball_bonus(&mut self, throw: &Throw) -> f64 {
+        self.bonus(throw.ball)
+    }
+}
+
+/// Gives the Repeat Ball its bonus against species the player has caught before, on top of
+/// another model
+pub struct RepeatBall<M: CaptureModel> {
+    pub inner: M,
+    pub ball: Key,
+    pub bonus: f64,
+}
+
+impl<M: CaptureModel> RepeatBall<M> {
+    pub fn new(inner: M) -> Self {
+        Self { inner, ball: Key::new("repeat_ball"), bonus: REPEAT_BALL_BONUS }
+    }
+}
+
+impl<M: CaptureModel> CaptureModel for RepeatBall<M> {
+    fn ball_bonus(&mut self, throw: &Throw) -> f64 {
+        if throw.ball == self.ball && throw.caught_before {
+            self.bonus
+        } else {
+            self.inner.ball_bonus(throw)
+        }
+    }
+
+    fn status_bonus(&mut self, throw: &Throw) -> f64 {
+        self.inner.status_bonus(throw)
+    }
+
+    fn critical_chance(&mut self, throw: &Throw, modified_rate: f64) -> f64 {
+        self.inner.critical_chance(throw, modified_rate)
+    }
+
+    fn thrown(&mut self, throw: &Throw, capture: &Capture) {
+        self.inner.thrown(throw, capture)
+    }
+}
+
+/// Critical captures that get more likely the more species the player has caught, by
+/// `thresholds` of (caught count, multiplier), highest first. `new` uses
+/// `CRITICAL_CAPTURE_THRESHOLDS`; a Catching Charm style item can be had by raising `multiplier`.
+pub struct CriticalCapture<M: CaptureModel> {
+    pub inner: M,
+    pub thresholds: Vec<(u32, f64)>,
+    pub multiplier: f64,
+}
+
+impl<M: CaptureModel> CriticalCapture<M> {
+    pub fn new(inner: M) -> Self {
+        Self { inner, thresholds: CRITICAL_CAPTURE_THRESHOLDS.to_vec(), multiplier: 1.0 }
+    }
+}
+
+impl<M: CaptureModel> CaptureModel for CriticalCapture<M> {
+    fn ball_bonus(&mut self, throw: &Throw) -> f64 {
+        self.inner.ball_bonus(throw)
+    }
+
+    fn status_bonus(&mut self, throw: &Throw) -> f64 {
+        self.inner.status_bonus(throw)
+    }
+
+    fn critical_chance(&mut self, throw: &Throw, modified_rate: f64) -> f64 {
+        let dex = self
+            .thresholds
+            .iter()
+            .find(|(caught, _)| throw.dex_caught > *caught)
+            .map_or(0.0, |(_, multiplier)| *multiplier);
+        let own = (modified_rate * dex * self.multiplier / 6.0).min(255.0) / 256.0;
+        own.max(self.inner.critical_chance(throw, modified_rate))
+    }
+
+    fn thrown(&mut self, throw: &Throw, capture: &Capture) {
+        self.inner.thrown(throw, capture)
+    }
+}
+
+/// Rolls ball throws following a `CaptureModel`. Uses its own seeded random numbers, so a given
+/// seed always gives the same captures, e.g. for replays and tests.
+pub struct Captures<M: CaptureModel> {
+    pub model: M,
+    rng: Rng,
+}
+
+impl<M: CaptureModel> Captures<M> {
+    pub fn new(model: M, seed: u64) -> Self {
+        Self { model, rng: Rng::new(seed) }
+    }
+
+    /// Where the generator is, to carry on from in a resumed battle
+    pub fn rng_state(&self) -> u64 {
+        self.rng.state()
+    }
+
+    /// The catch rate with the pokemon's HP and every bonus applied, up to 255, at which the
+    /// throw can't fail
+    pub fn modified_rate(&mut self, throw: &Throw) -> f64 {
+        let max_hp = throw.max_hp.max(1) as f64;
+        let hp = throw.hp.min(throw.max_hp) as f64;
+        let rate = (3.0 * max_hp - 2.0 * hp) * throw.catch_rate as f64 * self.model.ball_bonus(throw) / (3.0 * max_hp);
+        (rate * self.model.status_bonus(throw)).clamp(1.0, 255.0)
+    }
+
+    /// Throws a ball. A modified rate of 255 always catches; otherwise the ball shakes up to three
+    /// times and catches if it passes a fourth check, or only has to pass one check on a
+    /// critical capture.
+    pub fn throw(&mut self, throw: &Throw) -> Capture {
+        let rate = self.modified_rate(throw);
+        let critical = self.rng.next_f64() < self.model.critical_chance(throw, rate);
+        let capture = if rate >= 255.0 {
+            Capture { caught: true, shakes: if critical { 1 } else { 3 }, critical }
+        } else {
+            let check = 65536.0 / (255.0 / rate).powf(0.1875);
+            let checks = if critical { 1 } else { 4 };
+            let passed = (0..checks).take_while(|_| ((self.rng.next_u64() % 65536) as f64) < check).count();
+            Capture { caught: passed == checks, shakes: passed.min(3) as u8, critical }
+        };
+        self.model.thrown(throw, &capture);
+        capture
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use super::*;
+
+    /// A model with a fixed ball bonus, for throwing every kind of ball from a Poke Ball to a
+    /// Master Ball
+    struct Bonus(f64);
+
+    impl CaptureModel for Bonus {
+        fn ball_bonus(&mut self, _throw: &Throw) -> f64 {
+            self.0
+        }
+    }
+
+    fn throws() -> impl Strategy<Value = Throw> {
+        let status = prop::option::of(prop::sample::select(vec!["sleep", "freeze", "poison", "burn", "paralysis"]));
+        (3u32..=255, 0u32..=999, 0u32..=999, status, 0u32..=1000)
+            .prop_map(|(catch_rate, hp, max_hp, status, dex_caught)| Throw {
+                ball: Key::new("poke_ball"),
+                species: Key::new("pidgey"),
+                catch_rate,
+                level: 5,
+                hp,
+                max_hp,
+                status: status.map(Key::new),
+                turn: 1,
+                caught_before: false,
+                dex_caught,
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn modified_rate_stays_in_range(throw in throws(), bonus in 0.0f64..=255.0) {
+            let rate = Captures::new(Bonus(bonus), 0).modified_rate(&throw);
+            prop_assert!((1.0..=255.0).contains(&rate), "rate {}", rate);
+        }
+
+        #[test]
+        fn critical_chance_is_a_probability(
+            throw in throws(),
+            bonus in 0.0f64..=255.0,
+            multiplier in 0.0f64..=10.0,
+        ) {
+            let mut model = CriticalCapture::new(Bonus(bonus));
+            model.multiplier = multiplier;
+            let rate = Captures::new(Bonus(bonus), 0).modified_rate(&throw);
+            let chance = model.critical_chance(&throw, rate);
+            prop_assert!((0.0..=1.0).contains(&chance), "chance {}", chance);
+        }
+
+        #[test]
+        fn throws_follow_the_rules(throw in throws(), bonus in 0.0f64..=255.0, seed in any::<u64>()) {
+            let mut captures = Captures::new(CriticalCapture::new(Bonus(bonus)), seed);
+            let rate = captures.modified_rate(&throw);
+            let capture = captures.throw(&throw);
+            let most_shakes = if capture.critical { 1 } else { 3 };
+            prop_assert!(capture.shakes <= most_shakes, "{} shakes", capture.shakes);
+            prop_assert!(rate < 255.0 || capture.caught);
+            // the same seed always throws the same way
+            prop_assert_eq!(Captures::new(CriticalCapture::new(Bonus(bonus)), seed).throw(&throw), capture);
+        }
+    }
+}
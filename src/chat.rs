@@ -0,0 +1,178 @@
+use std::collections::VecDeque;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use crate::engine::Sprite;
+use crate::intern::{ Key, SpriteId };
+use crate::text::TextSettings;
+
+/// Something a player can say during a link battle or trade. There's no free text: players pick
+/// from the game's preset phrases and emotes, so messages are just indices and can be sent over
+/// any link as a single byte each.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChatMessage {
+    Phrase(u8), // index into `ChatPresets::phrases`
+    Emote(u8), // index into `ChatPresets::emotes`
+}
+
+impl ChatMessage {
+    /// Packs the message into a byte, phrases in the low half and emotes in the high half
+    pub fn to_byte(&self) -> u8 {
+        match self {
+            Self::Phrase(index) => index & 0x7f,
+            Self::Emote(index) => 0x80 | (index & 0x7f),
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Self {
+        if byte & 0x80 == 0 {
+            Self::Phrase(byte)
+        } else {
+            Self::Emote(byte & 0x7f)
+        }
+    }
+}
+
+/// What players can pick from. Both sides of a link need the same presets, since only indices
+/// are sent.
+pub struct ChatPresets {
+    pub phrases: Vec<String>,
+    pub emotes: Vec<SpriteId>,
+}
+
+impl Default for ChatPresets {
+    fn default() -> Self {
+        Self {
+            phrases: ["Hi!", "Good luck!", "Nice move!", "Well played!", "Thanks!", "Oops!", "Bye!"]
+                .iter()
+                .map(|phrase| phrase.to_string())
+                .collect(),
+            emotes: ["emote_happy", "emote_sad", "emote_angry", "emote_surprised", "emote_heart"]
+                .iter()
+                .map(|emote| Key::new(emote))
+                .collect(),
+        }
+    }
+}
+
+/// Which side of the link said something
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Speaker {
+    Local,
+    Remote,
+}
+
+/// Most messages a player can send within `CHAT_RATE_WINDOW`
+pub const CHAT_RATE_LIMIT: usize = 3;
+/// Time (ms) the rate limit counts messages over
+pub const CHAT_RATE_WINDOW: u32 = 5_000;
+/// Time (ms) a speech bubble stays up
+pub const CHAT_BUBBLE_DURATION: u32 = 3_000;
+
+/// Speech bubbles shown above the trainers during a link session, with rate limiting so neither
+/// player can flood the other, and muting of the other player
+pub struct Chat {
+    presets: ChatPresets,
+    muted: bool, // hides the remote player's messages
+    sent: VecDeque<u32>, // ms since each recent local message, oldest first
+    received: VecDeque<u32>, // same for remote messages
+    bubbles: Vec<(Speaker, ChatMessage, u32)>, // (speaker, message, ms left)
+}
+
+impl Chat {
+    pub fn new(presets: ChatPresets) -> Self {
+        Self { presets, muted: false, sent: VecDeque::new(), received: VecDeque::new(), bubbles: Vec::new() }
+    }
+
+    pub fn presets(&self) -> &ChatPresets {
+        &self.presets
+    }
+
+    /// Shows a message from the local player and returns it to be sent over the link, or `None`
+    /// if it isn't a preset or the player is sending too fast
+    pub fn send(&mut self, message: ChatMessage) -> Option<ChatMessage> {
+        if !self.is_preset(message) || self.sent.len() >= CHAT_RATE_LIMIT {
+            return None;
+        }
+        self.sent.push_back(0);
+        self.show(Speaker::Local, message);
+        Some(message)
+    }
+
+    /// Shows a message that came in over the link. Unknown messages (e.g. from a game with
+    /// different presets) and messages while muted are dropped, and so is anything past the rate
+    /// limit, in case the other side doesn't enforce it.
+    pub fn receive(&mut self, message: ChatMessage) {
+        if self.muted || !self.is_preset(message) || self.received.len() >= CHAT_RATE_LIMIT {
+            return;
+        }
+        self.received.push_back(0);
+        self.show(Speaker::Remote, message);
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        if muted {
+            self.bubbles.retain(|(speaker, ..)| *speaker == Speaker::Local);
+        }
+    }
+
+    pub fn muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Ages the bubbles and the rate limit by `interval` ms
+    pub fn tick(&mut self, interval: u32) {
+        for recent in [&mut self.sent, &mut self.received] {
+            for age in recent.iter_mut() {
+                *age += interval;
+            }
+            recent.retain(|age| *age < CHAT_RATE_WINDOW);
+        }
+        for bubble in self.bubbles.iter_mut() {
+            bubble.2 = bubble.2.saturating_sub(interval);
+        }
+        self.bubbles.retain(|(.., left)| *left > 0);
+    }
+
+    /// Builds the sprites of the newest bubble of each speaker, above the trainer sprite at
+    /// `local` or `remote`
+    pub fn sprites(&self, local: Rect, remote: Rect, text: &TextSettings) -> Vec<Sprite> {
+        let mut sprites = Vec::new();
+        for (speaker, trainer) in [(Speaker::Local, local), (Speaker::Remote, remote)] {
+            let message = match self.bubbles.iter().rev().find(|(s, ..)| *s == speaker) {
+                Some((_, message, _)) => *message,
+                None => continue,
+            };
+            match message {
+                ChatMessage::Phrase(index) => {
+                    let phrase = &self.presets.phrases[index as usize];
+                    let width = phrase.chars().count() as u32 * text.char_width() + 8;
+                    let height = text.line_height() + 8;
+                    let x = trainer.center().x() - width as i32 / 2;
+                    let y = trainer.top() - height as i32 - 4;
+                    sprites.push(Sprite::Rect { rect: Rect::new(x, y, width, height), color: Color::WHITE });
+                    sprites.extend(text.sprites(phrase, (x + 4, y + 4)));
+                },
+                ChatMessage::Emote(index) => {
+                    let size = text.line_height() * 2;
+                    sprites.push(Sprite::Texture {
+                        rect: Rect::new(trainer.center().x() - size as i32 / 2, trainer.top() - size as i32 - 4, size, size),
+                        sprite: self.presets.emotes[index as usize],
+                    });
+                },
+            }
+        }
+        sprites
+    }
+
+    fn is_preset(&self, message: ChatMessage) -> bool {
+        match message {
+            ChatMessage::Phrase(index) => (index as usize) < self.presets.phrases.len(),
+            ChatMessage::Emote(index) => (index as usize) < self.presets.emotes.len(),
+        }
+    }
+
+    fn show(&mut self, speaker: Speaker, message: ChatMessage) {
+        self.bubbles.push((speaker, message, CHAT_BUBBLE_DURATION));
+    }
+}
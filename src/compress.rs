@@ -0,0 +1,41 @@
+use std::fs;
+use std::path::Path;
+
+/// Start of every zstd frame. Files starting with it are decompressed when read; any other file
+/// is read as it is, so data written before compression was turned on keeps loading.
+pub const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+/// zstd level files are compressed with. Low levels decompress just as fast and the files are
+/// compressed once, at build or save time.
+pub const COMPRESSION_LEVEL: i32 = 9;
+
+pub fn is_compressed(data: &[u8]) -> bool {
+    data.starts_with(&ZSTD_MAGIC)
+}
+
+pub fn compress(data: &[u8]) -> Result<Vec<u8>, String> {
+    zstd::encode_all(data, COMPRESSION_LEVEL).or_else(|err| Err(format!("{}", err)))
+}
+
+/// Decompresses data if it's compressed, and otherwise returns it unchanged
+pub fn decompress(data: Vec<u8>) -> Result<Vec<u8>, String> {
+    if is_compressed(&data) {
+        zstd::decode_all(&data[..]).or_else(|err| Err(format!("{}", err)))
+    } else {
+        Ok(data)
+    }
+}
+
+/// Reads a file that may or may not be compressed
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, String> {
+    decompress(fs::read(path).or_else(|err| Err(format!("{}", err)))?)
+}
+
+/// Compresses a file for shipping, e.g. a map or data file as a build step. Returns the sizes
+/// before and after. Files that are already compressed are copied as they are.
+pub fn compress_file<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> Result<(usize, usize), String> {
+    let (src, dst) = (src.as_ref(), dst.as_ref());
+    let data = fs::read(src).or_else(|err| Err(format!("{}: {}", src.display(), err)))?;
+    let compressed = if is_compressed(&data) { data.clone() } else { compress(&data)? };
+    fs::write(dst, &compressed).or_else(|err| Err(format!("{}: {}", dst.display(), err)))?;
+    Ok((data.len(), compressed.len()))
+}
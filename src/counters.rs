@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use crate::dict::*;
+use crate::intern::Key;
+
+/// Name of the global every counter is kept in, as a dict of counter name to
+/// `{ "value", "since", "expires" }`
+pub const COUNTERS_GLOBAL: &str = "counters";
+
+/// Called when a counter's value changes, with its name and the old and new values. Gets the
+/// globals so it can react, e.g. by unlocking an evolution once friendship is high enough.
+pub type CounterHookFn = fn(globals: &mut Dict, name: Key, old: i64, new: i64);
+
+fn number(value: Option<&DictValue>) -> Option<i64> {
+    match value? {
+        DictValue::I64(n) => Some(*n),
+        DictValue::U64(n) => Some(*n as i64),
+        DictValue::I32(n) => Some(*n as i64),
+        DictValue::U32(n) => Some(*n as i64),
+        _ => None,
+    }
+}
+
+/// How a counter behaves
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CounterRule {
+    pub min: i64,
+    pub max: i64,
+    pub default: i64, // value of a counter that was never set, and what decay and expiry go back to
+    pub decay: Option<(i64, u64)>, // moves this much back towards the default every this many seconds
+}
+
+impl Default for CounterRule {
+    fn default() -> Self {
+        Self { min: i64::MIN, max: i64::MAX, default: 0, decay: None }
+    }
+}
+
+impl CounterRule {
+    /// Reads a rule like `{ "min": 0, "max": 255, "default": 70, "decay": [1, 3600] }`. Missing
+    /// fields keep their defaults.
+    pub fn from_dict(dict: &Dict) -> Self {
+        let field = |name: &str| number(dict.get(&Key::new(name)));
+        let mut rule = Self::default();
+        rule.min = field("min").unwrap_or(rule.min);
+        rule.max = field("max").unwrap_or(rule.max).max(rule.min);
+        rule.default = rule.clamp(field("default").unwrap_or(rule.default));
+        if let Some(DictValue::Array(decay)) = dict.get(&Key::new("decay")) {
+            if let (Some(amount), Some(every)) = (number(decay.first()), number(decay.get(1))) {
+                rule.decay = Some((amount.abs(), every.max(1) as u64));
+            }
+        }
+        rule
+    }
+
+    fn clamp(&self, value: i64) -> i64 {
+        value.max(self.min).min(self.max)
+    }
+
+    /// `value` after `steps` decay steps towards the default
+    fn decayed(&self, value: i64, steps: u64) -> i64 {
+        let amount = match self.decay {
+            Some((amount, _)) => amount.saturating_mul(steps.min(i64::MAX as u64) as i64),
+            None => return value,
+        };
+        if value > self.default {
+            value.saturating_sub(amount).max(self.default)
+        } else {
+            value.saturating_add(amount).min(self.default)
+        }
+    }
+}
+
+/// Named counters kept in the globals, for mechanics that are a number going up and down:
+/// friendship, reputation with a gym, or a timed buff like an O-Power. Each counter can have a
+/// rule that clamps it, decays it back to its default over time, and hooks that run when it
+/// changes; counters without a rule are plain unclamped numbers starting at 0.
+///
+/// Times are in seconds, from whatever clock the game uses for the counter (play time for
+/// friendship, the real time for a buff that should run out while the game is closed). Like
+/// `StateMachine`, the rules and hooks are built the same way wherever they're used and only the
+/// values are kept, in `COUNTERS_GLOBAL`, so they're saved with the rest of the globals.
+#[derive(Default)]
+pub struct Counters {
+    rules: HashMap<Key, CounterRule>,
+    hooks: Vec<(Option<Key>, CounterHookFn)>, // None runs for every counter
+}
+
+impl Counters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the rules of many counters at once, from a dict of counter name to rule (see
+    /// `CounterRule::from_dict`)
+    pub fn from_dict(dict: &Dict) -> Self {
+        let mut counters = Self::new();
+        for (name, rule) in dict {
+            if let DictValue::Dict(rule) = rule {
+                counters.rules.insert(*name, CounterRule::from_dict(rule));
+            }
+        }
+        counters
+    }
+
+    pub fn rule(mut self, name: &str, rule: CounterRule) -> Self {
+        self.rules.insert(Key::new(name), rule);
+        self
+    }
+
+    /// Adds a hook for changes to the counter `name`, or to any counter for `None`
+    pub fn on_change(mut self, name: Option<&str>, hook: CounterHookFn) -> Self {
+        self.hooks.push((name.map(Key::new), hook));
+        self
+    }
+
+    fn rule_of(&self, name: Key) -> CounterRule {
+        self.rules.get(&name).copied().unwrap_or_default()
+    }
+
+    fn entry(globals: &Dict, name: Key) -> Option<&Dict> {
+        match globals.get(&Key::new(COUNTERS_GLOBAL))? {
+            DictValue::Dict(counters) => match counters.get(&name)? {
+                DictValue::Dict(entry) => Some(entry),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The counter's value, or its default if it was never set. Doesn't apply decay or expiry
+    /// that's due, see `update`.
+    pub fn get(&self, globals: &Dict, name: &str) -> i64 {
+        let name = Key::new(name);
+        Self::entry(globals, name)
+            .and_then(|entry| number(entry.get(&Key::new("value"))))
+            .unwrap_or_else(|| self.rule_of(name).default)
+    }
+
+    /// Sets a counter, clamped to its rule, and returns the value it was set to. Decay starts
+    /// counting from `now`.
+    pub fn set(&self, globals: &mut Dict, name: &str, value: i64, now: u64) -> i64 {
+        self.write(globals, Key::new(name), value, now, None)
+    }
+
+    /// Adds `delta` (which may be negative) to a counter, see `set`
+    pub fn add(&self, globals: &mut Dict, name: &str, delta: i64, now: u64) -> i64 {
+        let value = self.get(globals, name).saturating_add(delta);
+        self.set(globals, name, value, now)
+    }
+
+    /// Sets a counter for `duration` seconds, after which `update` puts it back to its default,
+    /// e.g. a buff that lasts three minutes
+    pub fn set_for(&self, globals: &mut Dict, name: &str, value: i64, duration: u64, now: u64) -> i64 {
+        self.write(globals, Key::new(name), value, now, Some(now.saturating_add(duration)))
+    }
+
+    /// Seconds left until a counter set with `set_for` expires, `None` if it doesn't
+    pub fn remaining(&self, globals: &Dict, name: &str, now: u64) -> Option<u64> {
+        let expires = number(Self::entry(globals, Key::new(name))?.get(&Key::new("expires")))?;
+        Some((expires as u64).saturating_sub(now))
+    }
+
+    /// Applies the decay and expiry that are due by `now` to every counter, running the hooks of
+    /// the ones that changed. Call it when a save loads and then every so often, e.g. once a
+    /// second.
+    pub fn update(&self, globals: &mut Dict, now: u64) {
+        let names: Vec<Key> = match globals.get(&Key::new(COUNTERS_GLOBAL)) {
+            Some(DictValue::Dict(counters)) => counters.keys().copied().collect(),
+            _ => return,
+        };
+        for name in names {
+            let entry = match Self::entry(globals, name) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let field = |field: &str| number(entry.get(&Key::new(field)));
+            let rule = self.rule_of(name);
+            let value = field("value").unwrap_or(rule.default);
+            let since = field("since").unwrap_or(0) as u64;
+            if let Some(expires) = field("expires") {
+                if now >= expires as u64 {
+                    self.write(globals, name, rule.default, now, None);
+                }
+                continue; // timed counters don't decay
+            }
+            if let Some((_, every)) = rule.decay {
+                let steps = now.saturating_sub(since) / every;
+                if steps > 0 {
+                    let decayed = rule.decayed(value, steps);
+                    // keep the time of the last step, so partial steps aren't lost
+                    self.write(globals, name, decayed, since + steps * every, None);
+                }
+            }
+        }
+    }
+
+    fn write(&self, globals: &mut Dict, name: Key, value: i64, since: u64, expires: Option<u64>) -> i64 {
+        let old = self.get(globals, name.name());
+        let value = self.rule_of(name).clamp(value);
+        let mut entry = Dict::new();
+        entry.insert(Key::new("value"), DictValue::I64(value));
+        entry.insert(Key::new("since"), DictValue::U64(since));
+        if let Some(expires) = expires {
+            entry.insert(Key::new("expires"), DictValue::U64(expires));
+        }
+        let counters = globals.entry(Key::new(COUNTERS_GLOBAL)).or_insert_with(|| DictValue::Dict(Dict::new()));
+        if !matches!(counters, DictValue::Dict(_)) {
+            *counters = DictValue::Dict(Dict::new());
+        }
+        if let DictValue::Dict(counters) = counters {
+            counters.insert(name, DictValue::Dict(entry));
+        }
+        if value != old {
+            for (hook_name, hook) in &self.hooks {
+                if hook_name.is_none_or(|hook_name| hook_name == name) {
+                    hook(globals, name, old, value);
+                }
+            }
+        }
+        value
+    }
+}
@@ -0,0 +1,222 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{ SystemTime, UNIX_EPOCH };
+use sdl2::event::{ Event, EventType };
+use sdl2::pixels::Color;
+use serde_json::{ Map, Value };
+use crate::data::dict_value_to_json;
+use crate::dict::*;
+use crate::engine::*;
+use crate::input::Action;
+use crate::intern::Key;
+use crate::text::TextSettings;
+
+/// Directory crash reports are written to
+pub const CRASH_DIR: &str = "crashes";
+/// Most log lines the engine keeps for crash reports
+pub const CRASH_LOG_LINES: usize = 200;
+/// Global naming the map the player is on, if the game keeps one
+pub const MAP_GLOBAL: &str = "map";
+/// Global holding the game's random seed, if the game keeps one
+pub const SEED_GLOBAL: &str = "seed";
+
+/// Globals whose names contain any of these are left out of crash reports
+const SENSITIVE_KEYS: [&str; 4] = ["password", "token", "secret", "email"];
+/// Longer arrays (e.g. signature bitmaps) are only counted in crash reports
+const DUMP_ARRAY_LIMIT: usize = 32;
+/// Longer strings are cut off in crash reports
+const DUMP_STRING_LIMIT: usize = 256;
+
+/// What the engine knew when the game crashed, written to a file the player can send along with
+/// a bug report. See `Engine::report_crash`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CrashReport {
+    pub reason: String,
+    pub time: u64, // seconds since the Unix epoch
+    pub scenes: Vec<String>, // the scenes' tags, bottom of the stack first
+    pub map: Option<String>,
+    pub seed: Option<String>,
+    pub globals: Value, // see `sanitize`
+    pub log: Vec<String>, // oldest first
+}
+
+impl CrashReport {
+    pub fn new(reason: &str, scenes: Vec<String>, globals: &Dict, log: Vec<String>) -> Self {
+        let global = |key: &str| globals.get(&Key::new(key)).map(|value| match value {
+            DictValue::String(value) => value.clone(),
+            value => value.summary(),
+        });
+        Self {
+            reason: reason.to_string(),
+            time: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs()),
+            scenes,
+            map: global(MAP_GLOBAL),
+            seed: global(SEED_GLOBAL),
+            globals: sanitize(globals),
+            log,
+        }
+    }
+
+    /// The report as plain text
+    pub fn text(&self) -> String {
+        let unknown = String::from("unknown");
+        let mut lines = vec![
+            format!("reason: {}", self.reason),
+            format!("time: {}", self.time),
+            format!("map: {}", self.map.as_ref().unwrap_or(&unknown)),
+            format!("seed: {}", self.seed.as_ref().unwrap_or(&unknown)),
+            String::from(""),
+            String::from("scenes (bottom first):"),
+        ];
+        lines.extend(self.scenes.iter().enumerate().map(|(depth, tag)| format!("  #{} {}", depth, tag)));
+        lines.push(String::from(""));
+        lines.push(String::from("globals:"));
+        lines.push(serde_json::to_string_pretty(&self.globals).unwrap_or_default());
+        lines.push(String::from(""));
+        lines.push(format!("log (last {} lines):", self.log.len()));
+        lines.extend(self.log.iter().cloned());
+        lines.join("\n")
+    }
+
+    /// Writes the report to `dir` as `crash-<time>.txt` and returns its path
+    pub fn write(&self, dir: &str) -> Result<String, String> {
+        let path = Path::new(dir).join(format!("crash-{}.txt", self.time));
+        fs::create_dir_all(dir)
+            .and_then(|_| fs::write(&path, self.text()))
+            .or_else(|err| Err(format!("{}: {}", path.display(), err)))?;
+        Ok(path.to_string_lossy().to_string())
+    }
+}
+
+/// Converts globals to JSON for a crash report, leaving out anything that looks private (see
+/// `SENSITIVE_KEYS`) and shortening long arrays and strings, so reports stay small and safe to
+/// share
+pub fn sanitize(dict: &Dict) -> Value {
+    let mut map = Map::new();
+    for (key, value) in dict {
+        let name = key.name().to_lowercase();
+        let json = if SENSITIVE_KEYS.iter().any(|sensitive| name.contains(sensitive)) {
+            Some(Value::String(String::from("<redacted>")))
+        } else {
+            sanitize_value(value)
+        };
+        if let Some(json) = json {
+            map.insert(key.name().to_string(), json);
+        }
+    }
+    Value::Object(map)
+}
+
+fn sanitize_value(value: &DictValue) -> Option<Value> {
+    match value {
+        DictValue::String(s) if s.chars().count() > DUMP_STRING_LIMIT => {
+            Some(Value::String(format!("{}...", s.chars().take(DUMP_STRING_LIMIT).collect::<String>())))
+        },
+        DictValue::Array(values) if values.len() > DUMP_ARRAY_LIMIT => Some(Value::String(value.summary())),
+        DictValue::Array(values) => Some(Value::Array(values.iter().filter_map(sanitize_value).collect())),
+        DictValue::Dict(dict) => Some(sanitize(dict)),
+        value => dict_value_to_json(value),
+    }
+}
+
+/// The message a panic was started with, e.g. by `panic!` or a failed `unwrap`
+pub fn panic_reason(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        format!("panic: {}", message)
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        format!("panic: {}", message)
+    } else {
+        String::from("panic")
+    }
+}
+
+/// Opens the directory a file is in with the system's file manager
+pub fn open_location(path: &str) -> Result<(), String> {
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+    let opener = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    Command::new(opener).arg(dir).spawn().map(|_| ()).or_else(|err| Err(format!("{}: {}", opener, err)))
+}
+
+/// Creates the scene shown in place of the game after a crash. Expects the props `reason`, and
+/// `report`, the path the crash report was written to, if it could be written. A opens the
+/// report's location and B quits.
+pub fn create_crash_scene(props: Dict) -> Scene {
+    let mut state = HashMap::new();
+    for key in ["reason", "report"] {
+        if let Some(value) = props.get(&Key::new(key)) {
+            state.insert(Key::new(key), value.clone());
+        }
+    }
+    let mut event_callbacks: HashMap<EventType, EventCallbackFn> = HashMap::new();
+    event_callbacks.insert(EventType::KeyDown, crash_scene_key_down);
+    let mut scene = Scene::new(
+        ClearMode::Color(Color::RGB(24, 24, 48)),
+        state,
+        Vec::new(),
+        event_callbacks,
+        |_, _, _| SceneFnOutcome::Continue,
+        |_, _, _| SceneFnOutcome::Continue,
+    );
+    scene.set_tag("crash");
+    layout_crash_scene(&mut scene, &TextSettings::default());
+    scene
+}
+
+fn crash_scene_key_down(scene: &mut Scene, ctx: &mut EngineContext, event: &Event) -> SceneFnOutcome {
+    let action = match *event {
+        Event::KeyDown { keycode: Some(key), .. } => ctx.input.map.get(key),
+        _ => None,
+    };
+    match action {
+        Some(Action::A) => {
+            if let Some(DictValue::String(report)) = scene.state().get(&Key::new("report")).cloned() {
+                if let Err(err) = open_location(&report) {
+                    scene.state().insert(Key::new("error"), DictValue::String(err));
+                    layout_crash_scene(scene, &ctx.text);
+                }
+            }
+            SceneFnOutcome::Continue
+        },
+        Some(Action::B) => SceneFnOutcome::Quit(HashMap::new()),
+        _ => SceneFnOutcome::Continue,
+    }
+}
+
+fn layout_crash_scene(scene: &mut Scene, text: &TextSettings) {
+    let state = scene.state();
+    let string = |key: &str| match state.get(&Key::new(key)) {
+        Some(DictValue::String(value)) => Some(value.clone()),
+        _ => None,
+    };
+    let mut message = String::from("Sorry, something went wrong and the game had to stop.\n\n");
+    match string("report") {
+        Some(report) => message.push_str(&format!(
+            "A report was saved to {}. Please send it along when you report the problem.\n\nA: open folder  B: quit",
+            report,
+        )),
+        None => message.push_str("The crash report couldn't be saved.\n\nB: quit"),
+    }
+    if let Some(reason) = string("reason") {
+        message.push_str(&format!("\n\n({})", reason));
+    }
+    if let Some(err) = string("error") {
+        message.push_str(&format!("\n\nCouldn't open the folder: {}", err));
+    }
+    let line = text.line_height() as i32;
+    let sprites = text
+        .wrap(&message, 304)
+        .iter()
+        .enumerate()
+        .flat_map(|(i, line_text)| text.sprites(line_text, (8, 8 + line * i as i32)))
+        .collect();
+    *scene.sprites() = sprites;
+}
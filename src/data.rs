@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use rayon::prelude::*;
+use serde_json::{ Map, Number, Value };
+use crate::compress;
+use crate::dict::*;
+use crate::intern::Key;
+
+/// Paths to the game's JSON data files
+pub struct DataPaths<'a> {
+    pub species: &'a str,
+    pub moves: &'a str,
+    pub encounters: &'a str,
+    pub dialog: &'a str,
+}
+
+/// All of the game's static data, loaded at startup. Each file is a JSON object that is converted
+/// into a `Dict`.
+pub struct DataBundle {
+    pub species: Dict,
+    pub moves: Dict,
+    pub encounters: Dict,
+    pub dialog: Dict,
+}
+
+impl DataBundle {
+    /// Reads and parses every data file in parallel. If any fail, returns the errors of all the
+    /// files that failed, always in the order the files are listed in `DataPaths`, so the same
+    /// broken data gives the same errors on every run.
+    pub fn load_all(paths: &DataPaths) -> Result<Self, Vec<String>> {
+        let files = [paths.species, paths.moves, paths.encounters, paths.dialog];
+        // `Dict` can't be sent between threads, so only reading and parsing happen in parallel
+        let results: Vec<Result<Value, String>> = files
+            .par_iter()
+            .map(|path| read_json(path).or_else(|err| Err(format!("{}: {}", path, err))))
+            .collect();
+        let mut dicts = Vec::with_capacity(results.len());
+        let mut errors = Vec::new();
+        for (path, result) in files.iter().zip(results) {
+            match result.and_then(|json| json_to_dict(json).or_else(|err| Err(format!("{}: {}", path, err)))) {
+                Ok(dict) => dicts.push(dict),
+                Err(err) => errors.push(err),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let mut dicts = dicts.into_iter();
+        Ok(Self {
+            species: dicts.next().unwrap(),
+            moves: dicts.next().unwrap(),
+            encounters: dicts.next().unwrap(),
+            dialog: dicts.next().unwrap(),
+        })
+    }
+}
+
+/// Reads a JSON file whose top level is an object into a `Dict`
+pub fn load_file(path: &str) -> Result<Dict, String> {
+    json_to_dict(read_json(path)?)
+}
+
+/// Reads a JSON file, which may be compressed (see `compress`), e.g. a large map
+pub(crate) fn read_json(path: &str) -> Result<Value, String> {
+    let bytes = compress::read(path)?;
+    serde_json::from_slice(&bytes).or_else(|err| Err(format!("{}", err)))
+}
+
+pub(crate) fn json_to_dict(json: Value) -> Result<Dict, String> {
+    match json_to_dict_value(json) {
+        DictValue::Dict(dict) => Ok(dict),
+        _ => Err("top level is not an object".to_string()),
+    }
+}
+
+/// Converts parsed JSON into a `DictValue`. Integers become `I64`/`U64` and other numbers `F64`.
+pub fn json_to_dict_value(json: Value) -> DictValue {
+    match json {
+        Value::Null => DictValue::Null,
+        Value::Bool(b) => DictValue::Bool(b),
+        Value::Number(number) => {
+            if let Some(n) = number.as_i64() {
+                DictValue::I64(n)
+            } else if let Some(n) = number.as_u64() {
+                DictValue::U64(n)
+            } else {
+                DictValue::F64(number.as_f64().unwrap_or(0.0))
+            }
+        },
+        Value::String(s) => DictValue::String(s),
+        Value::Array(values) => DictValue::Array(values.into_iter().map(json_to_dict_value).collect()),
+        Value::Object(map) => {
+            let mut dict = HashMap::new();
+            for (key, value) in map {
+                dict.insert(Key::new(&key), json_to_dict_value(value));
+            }
+            DictValue::Dict(dict)
+        },
+    }
+}
+
+/// Converts a `DictValue` back into JSON, e.g. for writing saves. Returns `None` for values that
+/// can't be stored (functions and objects), which are left out of arrays and dicts. Chars become
+/// strings, and 128-bit integers that don't fit in 64 bits become strings of their digits.
+pub fn dict_value_to_json(value: &DictValue) -> Option<Value> {
+    Some(match value {
+        DictValue::Null => Value::Null,
+        DictValue::Bool(b) => Value::Bool(*b),
+        DictValue::String(s) => Value::String(s.clone()),
+        DictValue::Char(c) => Value::String(c.to_string()),
+        DictValue::U8(n) => Value::from(*n),
+        DictValue::I8(n) => Value::from(*n),
+        DictValue::U16(n) => Value::from(*n),
+        DictValue::I16(n) => Value::from(*n),
+        DictValue::U32(n) => Value::from(*n),
+        DictValue::I32(n) => Value::from(*n),
+        DictValue::U64(n) => Value::from(*n),
+        DictValue::I64(n) => Value::from(*n),
+        DictValue::U128(n) => match u64::try_from(*n) {
+            Ok(n) => Value::from(n),
+            Err(_) => Value::String(n.to_string()),
+        },
+        DictValue::I128(n) => match i64::try_from(*n) {
+            Ok(n) => Value::from(n),
+            Err(_) => Value::String(n.to_string()),
+        },
+        DictValue::F32(n) => Number::from_f64(*n as f64).map_or(Value::Null, Value::Number),
+        DictValue::F64(n) => Number::from_f64(*n).map_or(Value::Null, Value::Number),
+        DictValue::Array(values) => Value::Array(values.iter().filter_map(dict_value_to_json).collect()),
+        DictValue::Dict(dict) => dict_to_json(dict),
+        DictValue::Func(_) | DictValue::FuncMut(_) | DictValue::Object(_) => return None,
+    })
+}
+
+/// Converts a `Dict` into a JSON object, see `dict_value_to_json`
+pub fn dict_to_json(dict: &Dict) -> Value {
+    let mut map = Map::new();
+    for (key, value) in dict {
+        if let Some(json) = dict_value_to_json(value) {
+            map.insert(key.name().to_string(), json);
+        }
+    }
+    Value::Object(map)
+}
@@ -0,0 +1,103 @@
+use std::collections::{ HashMap, VecDeque };
+use sdl2::VideoSubsystem;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use crate::diagnostics::SceneDiagnostics;
+use crate::dict::*;
+use crate::intern::{ Key, SpriteId };
+use crate::renderer::{ Renderer, SdlRenderer };
+use crate::text::TextSettings;
+use crate::textures::TextureCache;
+use crate::windows::WindowVideo;
+
+/// Size of the debug window, in pixels
+pub const DEBUG_WINDOW_SIZE: (u32, u32) = (640, 720);
+/// Most lines the log console keeps
+pub const DEBUG_LOG_LINES: usize = 200;
+
+/// A second window for debug tools, so they don't cover the game: the diagnostics of the scenes
+/// on the stack, a viewer for the top scene's state and the globals, and a log console. Only in
+/// debug builds, see `Engine::open_debug_window`.
+pub struct DebugWindow {
+    video: WindowVideo<'static>,
+    backgrounds: TextureCache<'static>, // always empty, the debug window has no backgrounds
+    text: TextSettings,
+    log: VecDeque<String>,
+}
+
+impl DebugWindow {
+    pub(crate) fn open(
+        video_subsystem: &VideoSubsystem,
+        spritesheet: &str,
+        index: HashMap<SpriteId, Rect>,
+    ) -> Result<Self, String> {
+        let mut window = video_subsystem.window("Debug", DEBUG_WINDOW_SIZE.0, DEBUG_WINDOW_SIZE.1);
+        window.resizable();
+        Ok(Self {
+            video: WindowVideo::open(window, false, spritesheet, index)?,
+            backgrounds: TextureCache::new(0),
+            text: TextSettings::default(),
+            log: VecDeque::new(),
+        })
+    }
+
+    /// SDL's id of the window, to tell its events apart from the game window's
+    pub fn id(&self) -> u32 {
+        self.video.id()
+    }
+
+    /// Adds a line to the log console, dropping the oldest past `DEBUG_LOG_LINES`
+    pub fn log(&mut self, line: &str) {
+        if self.log.len() >= DEBUG_LOG_LINES {
+            self.log.pop_front();
+        }
+        self.log.push_back(line.to_string());
+    }
+
+    pub(crate) fn render(&mut self, scenes: &[SceneDiagnostics], state: Option<&Dict>, globals: &Dict) {
+        let (width, height) = self.video.canvas.output_size().unwrap_or(DEBUG_WINDOW_SIZE);
+        let line = self.text.line_height().max(1) as i32;
+        let rows = (height as i32 / line) as usize;
+        let mut lines = vec![String::from("== scenes (top first)")];
+        lines.extend(scenes.iter().rev().map(SceneDiagnostics::line));
+        lines.push(String::from("== top scene state"));
+        if let Some(state) = state {
+            dict_lines(state, 1, &mut lines);
+        }
+        lines.push(String::from("== globals"));
+        dict_lines(globals, 1, &mut lines);
+        // the log takes the bottom third, the rest is cut off at the window's edge
+        let log_rows = rows / 3;
+        lines.truncate(rows.saturating_sub(log_rows + 1));
+        lines.push(String::from("== log"));
+        let skip = self.log.len().saturating_sub(log_rows);
+        lines.extend(self.log.iter().skip(skip).cloned());
+
+        let video = &mut self.video;
+        let mut renderer = SdlRenderer::new(&mut video.canvas, &mut video.spritesheet, &self.backgrounds);
+        renderer.clear(Color::RGB(16, 16, 24));
+        for (i, text) in lines.iter().enumerate() {
+            if text.starts_with("==") {
+                renderer.draw_rect(Rect::new(0, line * i as i32, width, line as u32), Color::RGB(48, 48, 80));
+            }
+            renderer.draw_text(text, (4, line * i as i32), &self.text);
+        }
+        renderer.present();
+    }
+}
+
+/// Lists a dict's entries sorted by key, one per line, with nested dicts indented under their key
+fn dict_lines(dict: &Dict, depth: usize, lines: &mut Vec<String>) {
+    let mut keys: Vec<&Key> = dict.keys().collect();
+    keys.sort_by_key(|key| key.name());
+    for key in keys {
+        let indent = "  ".repeat(depth);
+        match &dict[key] {
+            DictValue::Dict(nested) => {
+                lines.push(format!("{}{}:", indent, key));
+                dict_lines(nested, depth + 1, lines);
+            },
+            value => lines.push(format!("{}{}: {}", indent, key, value.summary())),
+        }
+    }
+}
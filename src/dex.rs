@@ -0,0 +1,135 @@
+use crate::dict::*;
+use crate::engine::EngineContext;
+use crate::intern::Key;
+
+/// Name of the global that lists the ids of the dex rewards already given, so each is only given
+/// once
+pub const DEX_REWARDS_GLOBAL: &str = "dex_rewards";
+
+/// Which pokedex count a reward goes by
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DexCount {
+    Seen,
+    Caught,
+}
+
+impl DexCount {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "seen" => Some(Self::Seen),
+            "caught" => Some(Self::Caught),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Seen => "seen",
+            Self::Caught => "caught",
+        }
+    }
+}
+
+/// A reward for filling the pokedex, e.g. an aide's gift at 10 caught or the diploma at 150: once
+/// the count reaches `at`, the game event `event` is sent with `props` (see
+/// `EngineContext::emit`), for the scene that handles it to hand out an item or play a cutscene
+#[derive(Clone)]
+pub struct DexReward {
+    pub id: Key,
+    pub count: DexCount,
+    pub at: u32,
+    pub event: Key,
+    pub props: Dict,
+}
+
+impl DexReward {
+    /// Reads a reward like `{ "count": "caught", "at": 10, "event": "aide_gift", "props": {
+    /// "item": "exp_share" } }`. `props` may be left out.
+    pub fn from_dict(id: Key, dict: &Dict) -> Result<Self, String> {
+        let error = |message: &str| format!("dex reward {}: {}", id, message);
+        let count = match dict.get(&Key::new("count")) {
+            Some(DictValue::String(count)) => DexCount::from_name(count).ok_or_else(|| error("unknown count"))?,
+            _ => return Err(error("\"count\" is missing")),
+        };
+        let at = dict_u32(dict, "at").ok_or_else(|| error("\"at\" is missing"))?;
+        let event = match dict.get(&Key::new("event")) {
+            Some(DictValue::String(event)) => Key::new(event),
+            _ => return Err(error("\"event\" is missing")),
+        };
+        let props = match dict.get(&Key::new("props")) {
+            Some(DictValue::Dict(props)) => props.clone(),
+            _ => Dict::new(),
+        };
+        Ok(Self { id, count, at, event, props })
+    }
+
+    pub fn is_reached(&self, seen: u32, caught: u32) -> bool {
+        match self.count {
+            DexCount::Seen => seen >= self.at,
+            DexCount::Caught => caught >= self.at,
+        }
+    }
+}
+
+/// Every dex reward of the game, defined in data so content can add or move them without code
+/// changes. The game calls `update` whenever the seen or caught count goes up.
+#[derive(Clone, Default)]
+pub struct DexRewards {
+    rewards: Vec<DexReward>, // lowest threshold first
+}
+
+impl DexRewards {
+    /// Reads the rewards from a dict of reward id to reward (see `DexReward::from_dict`)
+    pub fn from_dict(dict: &Dict) -> Result<Self, String> {
+        let mut rewards = Vec::new();
+        for (id, reward) in dict {
+            match reward {
+                DictValue::Dict(reward) => rewards.push(DexReward::from_dict(*id, reward)?),
+                _ => return Err(format!("dex reward {}: not a dict", id)),
+            }
+        }
+        rewards.sort_by(|a, b| a.at.cmp(&b.at).then_with(|| a.id.name().cmp(b.id.name())));
+        Ok(Self { rewards })
+    }
+
+    pub fn rewards(&self) -> &[DexReward] {
+        &self.rewards
+    }
+
+    /// Whether a reward was already given
+    pub fn is_given(globals: &Dict, id: Key) -> bool {
+        match globals.get(&Key::new(DEX_REWARDS_GLOBAL)) {
+            Some(DictValue::Array(given)) => given.iter().any(|given| match given {
+                DictValue::String(given) => Key::new(given) == id,
+                _ => false,
+            }),
+            _ => false,
+        }
+    }
+
+    /// Sends the events of the rewards the counts have reached that weren't given yet and marks
+    /// them as given. Each event's props get the reward's `reward` id and the `count` that reached
+    /// it on top of its own. Returns how many were sent.
+    pub fn update(&self, globals: &mut Dict, ctx: &mut EngineContext, seen: u32, caught: u32) -> usize {
+        let due: Vec<&DexReward> = self
+            .rewards
+            .iter()
+            .filter(|reward| reward.is_reached(seen, caught) && !Self::is_given(globals, reward.id))
+            .collect();
+        for reward in &due {
+            let mut props = reward.props.clone();
+            props.insert(Key::new("reward"), DictValue::String(reward.id.name().to_string()));
+            let count = if reward.count == DexCount::Seen { seen } else { caught };
+            props.insert(Key::new("count"), DictValue::U32(count));
+            ctx.emit(reward.event.name(), props);
+            let given = globals.entry(Key::new(DEX_REWARDS_GLOBAL)).or_insert_with(|| DictValue::Array(Vec::new()));
+            if !matches!(given, DictValue::Array(_)) {
+                *given = DictValue::Array(Vec::new());
+            }
+            if let DictValue::Array(given) = given {
+                given.push(DictValue::String(reward.id.name().to_string()));
+            }
+        }
+        due.len()
+    }
+}
@@ -0,0 +1,124 @@
+use std::mem::size_of;
+use std::time::Duration;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use crate::dict::*;
+use crate::intern::Key;
+use crate::renderer::Renderer;
+use crate::text::TextSettings;
+
+/// How much of each new measurement goes into a scene's average tick and render cost, so one slow
+/// frame doesn't make the overlay jump around
+const COST_SMOOTHING: f64 = 0.1;
+
+/// Running averages of how long a scene takes per frame, kept by the engine
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct SceneCosts {
+    tick: f64, // µs
+    render: f64, // µs
+}
+
+impl SceneCosts {
+    pub(crate) fn add_tick(&mut self, cost: Duration) {
+        self.tick = smooth(self.tick, cost);
+    }
+
+    pub(crate) fn add_render(&mut self, cost: Duration) {
+        self.render = smooth(self.render, cost);
+    }
+
+    pub fn tick(&self) -> Duration {
+        Duration::from_micros(self.tick as u64)
+    }
+
+    pub fn render(&self) -> Duration {
+        Duration::from_micros(self.render as u64)
+    }
+}
+
+fn smooth(average: f64, cost: Duration) -> f64 {
+    let cost = cost.as_micros() as f64;
+    if average <= 0.0 {
+        cost
+    } else {
+        average + (cost - average) * COST_SMOOTHING
+    }
+}
+
+/// What one scene on the stack is holding on to and costing, for catching scenes that leak state
+/// or are never popped. See `Engine::scene_diagnostics`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SceneDiagnostics {
+    pub depth: usize, // 0 is the bottom of the stack
+    pub sprites: usize,
+    pub state_entries: usize, // counting the entries of nested dicts and arrays
+    pub state_bytes: usize, // approximate
+    pub background: Option<String>, // the background texture the scene clears with, if any
+    pub age: Duration, // since the scene was created
+    pub costs: SceneCosts,
+}
+
+impl SceneDiagnostics {
+    /// One line for the debug overlay, e.g.
+    /// `#1  12s  40 sprites  18 keys ~2KB  bg:town  tick 40µs  render 310µs`
+    pub fn line(&self) -> String {
+        let mut line = format!(
+            "#{}  {}s  {} sprites  {} keys ~{}KB",
+            self.depth,
+            self.age.as_secs(),
+            self.sprites,
+            self.state_entries,
+            self.state_bytes.div_ceil(1024),
+        );
+        if let Some(background) = &self.background {
+            line.push_str(&format!("  bg:{}", background));
+        }
+        line.push_str(&format!(
+            "  tick {}µs  render {}µs",
+            self.costs.tick().as_micros(),
+            self.costs.render().as_micros(),
+        ));
+        line
+    }
+}
+
+/// Counts the entries in a dict, including those of nested dicts and arrays, and estimates how
+/// many bytes it takes up. Shared objects are only counted as a pointer.
+pub fn dict_footprint(dict: &Dict) -> (usize, usize) {
+    let mut entries = 0;
+    let mut bytes = 0;
+    for value in dict.values() {
+        let (value_entries, value_bytes) = value_footprint(value);
+        entries += 1 + value_entries;
+        bytes += size_of::<(Key, DictValue)>() + value_bytes;
+    }
+    (entries, bytes)
+}
+
+fn value_footprint(value: &DictValue) -> (usize, usize) {
+    match value {
+        DictValue::String(string) => (0, string.capacity()),
+        DictValue::Array(values) => values.iter().fold((values.len(), 0), |(entries, bytes), value| {
+            let (value_entries, value_bytes) = value_footprint(value);
+            (entries + value_entries, bytes + size_of::<DictValue>() + value_bytes)
+        }),
+        DictValue::Dict(dict) => dict_footprint(dict),
+        _ => (0, 0),
+    }
+}
+
+/// Draws the diagnostics of every scene in the top left corner, top of the stack first, over a
+/// dark box so they can be read on any background
+pub fn render_overlay(renderer: &mut dyn Renderer, scenes: &[SceneDiagnostics], text: &TextSettings) {
+    let camera = renderer.camera();
+    renderer.set_camera((0, 0)); // the overlay is fixed to the screen
+    let line = text.line_height() as i32;
+    for (i, scene) in scenes.iter().rev().enumerate() {
+        let y = 2 + line * i as i32;
+        let label = scene.line();
+        let width = text.char_width() * label.chars().count() as u32;
+        renderer.draw_rect(Rect::new(0, y, width + 4, line as u32), Color::RGB(0, 0, 0));
+        renderer.draw_text(&label, (2, y), text);
+    }
+    renderer.set_camera(camera);
+}
@@ -0,0 +1,567 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use sdl2::event::{ Event, EventType };
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use crate::data::load_file;
+use crate::dict::*;
+use crate::engine::*;
+use crate::glyphs;
+use crate::input::Action;
+use crate::intern::{ Key, SpriteId };
+use crate::markup::{ self, RichLine, RichText };
+use crate::text::TextSettings;
+
+const DIALOG_BOX_PADDING: u32 = 6;
+
+/// One line of a conversation. `text` and the options' texts are localization keys.
+#[derive(Clone, PartialEq, Debug)]
+pub struct DialogNode {
+    pub text: String,
+    pub next: Option<String>, // node after this one when there are no options
+    pub options: Vec<(String, Option<String>)>, // (text, next node)
+}
+
+/// A branching conversation, from a JSON file like
+/// `{ "start": "hi", "nodes": { "hi": { "text": "npc.hi", "options": [{ "text": "npc.bye" }] } } }`,
+/// where a node or option without a `next` ends the conversation
+#[derive(Clone, PartialEq, Debug)]
+pub struct DialogTree {
+    pub start: String,
+    pub nodes: HashMap<String, DialogNode>,
+}
+
+impl DialogTree {
+    pub fn from_dict(dict: &Dict) -> Result<Self, String> {
+        let start = match dict.get(&Key::new("start")) {
+            Some(DictValue::String(start)) => start.clone(),
+            _ => return Err(String::from("missing \"start\"")),
+        };
+        let mut nodes = HashMap::new();
+        if let Some(DictValue::Dict(dict)) = dict.get(&Key::new("nodes")) {
+            for (id, node) in dict {
+                let node = match node {
+                    DictValue::Dict(node) => node,
+                    _ => return Err(format!("node \"{}\" is not an object", id)),
+                };
+                let text = match node.get(&Key::new("text")) {
+                    Some(DictValue::String(text)) => text.clone(),
+                    _ => return Err(format!("node \"{}\" has no \"text\"", id)),
+                };
+                let options = match node.get(&Key::new("options")) {
+                    Some(DictValue::Array(options)) => options
+                        .iter()
+                        .filter_map(|option| match option {
+                            DictValue::Dict(option) => Some((string(option, "text")?, string(option, "next"))),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                nodes.insert(id.to_string(), DialogNode { text, next: string(node, "next"), options });
+            }
+        }
+        if !nodes.contains_key(&start) {
+            return Err(format!("start node \"{}\" doesn't exist", start));
+        }
+        Ok(Self { start, nodes })
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        Self::from_dict(&load_file(path)?).or_else(|err| Err(format!("{}: {}", path, err)))
+    }
+
+    /// Problems a writer should fix, sorted: localization keys missing from `strings`, texts whose
+    /// markup doesn't parse (see `RichText`) and links to nodes that don't exist
+    pub fn problems(&self, strings: &Dict) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (id, node) in &self.nodes {
+            let texts = Some(&node.text).into_iter().chain(node.options.iter().map(|(text, _)| text));
+            for text in texts.filter(|text| localize(strings, text).is_none()) {
+                problems.push(format!("missing string \"{}\" in node \"{}\"", text, id));
+            }
+            if let Some(Err(err)) = localize(strings, &node.text).map(|text| RichText::parse(text, &HashMap::new())) {
+                problems.push(format!("bad markup in node \"{}\": {}", id, err));
+            }
+            let links = node.next.iter().chain(node.options.iter().filter_map(|(_, next)| next.as_ref()));
+            for next in links.filter(|next| !self.nodes.contains_key(*next)) {
+                problems.push(format!("node \"{}\" goes to missing node \"{}\"", id, next));
+            }
+        }
+        problems.sort();
+        problems.dedup();
+        problems
+    }
+}
+
+fn string(dict: &Dict, key: &str) -> Option<String> {
+    match dict.get(&Key::new(key)) {
+        Some(DictValue::String(value)) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Looks up a localization key in a strings table, a flat dict of key to text
+pub fn localize<'s>(strings: &'s Dict, key: &str) -> Option<&'s str> {
+    match strings.get(&Key::new(key)) {
+        Some(DictValue::String(text)) => Some(text),
+        _ => None,
+    }
+}
+
+/// The box conversations are shown in: a page of text, then the options with a cursor next to
+/// the selected one. Lays itself out as sprites like any other scene content.
+pub struct DialogBox {
+    pub rect: Rect,
+}
+
+impl DialogBox {
+    pub fn new(rect: Rect) -> Self {
+        Self { rect }
+    }
+
+    /// Splits text into the pages that fit in the box
+    pub fn pages(&self, text: &str, settings: &TextSettings) -> Vec<Vec<String>> {
+        settings.paginate(text, self.inner())
+    }
+
+    /// Splits rich text into the pages that fit in the box
+    pub fn rich_pages(&self, text: &RichText, settings: &TextSettings) -> Vec<Vec<RichLine>> {
+        text.paginate(settings, self.inner())
+    }
+
+    /// Builds the box showing `page`, with `options` listed above it when there are any
+    pub fn sprites(
+        &self,
+        page: &[String],
+        options: &[String],
+        selected: usize,
+        settings: &TextSettings,
+    ) -> Vec<Sprite> {
+        let line = settings.line_height() as i32;
+        let inner = self.inner();
+        let mut sprites = self.frame();
+        for (i, text) in page.iter().enumerate() {
+            sprites.extend(settings.sprites(text, (inner.x(), inner.y() + line * i as i32)));
+        }
+        sprites.extend(self.options(options, selected, settings));
+        sprites
+    }
+
+    /// Builds the box showing a page of rich text like `sprites`, up to its first `shown` glyphs
+    /// so it can appear bit by bit (see `markup::revealed`). `buttons` has the glyph sprites of
+    /// the player's buttons by action, for `{button:..}` codes.
+    pub fn rich_sprites(
+        &self,
+        page: &[RichLine],
+        shown: usize,
+        options: &[String],
+        selected: usize,
+        settings: &TextSettings,
+        buttons: &HashMap<Action, SpriteId>,
+    ) -> Vec<Sprite> {
+        let line = settings.line_height() as i32;
+        let inner = self.inner();
+        let mut sprites = self.frame();
+        let mut left = shown;
+        for (i, text) in page.iter().enumerate() {
+            let pos = (inner.x(), inner.y() + line * i as i32);
+            sprites.extend(markup::line_sprites(text, left, pos, settings, buttons));
+            left = left.saturating_sub(text.len());
+        }
+        sprites.extend(self.options(options, selected, settings));
+        sprites
+    }
+
+    /// The box itself, a white panel with a dark border
+    fn frame(&self) -> Vec<Sprite> {
+        vec![
+            Sprite::Rect { rect: self.rect, color: Color::RGB(40, 40, 56) },
+            Sprite::Rect {
+                rect: Rect::new(self.rect.x() + 2, self.rect.y() + 2, self.rect.width() - 4, self.rect.height() - 4),
+                color: Color::WHITE,
+            },
+        ]
+    }
+
+    /// The options in their own box above the right end of the box, if there are any
+    fn options(&self, options: &[String], selected: usize, settings: &TextSettings) -> Vec<Sprite> {
+        let line = settings.line_height() as i32;
+        let mut sprites = Vec::new();
+        if !options.is_empty() {
+            let height = line as u32 * options.len() as u32 + DIALOG_BOX_PADDING * 2;
+            let width = options.iter().map(|option| option.chars().count() as u32 + 2).max().unwrap_or(0)
+                * settings.char_width()
+                + DIALOG_BOX_PADDING * 2;
+            let rect = Rect::new(self.rect.right() - width as i32, self.rect.y() - height as i32, width, height);
+            sprites.push(Sprite::Rect { rect, color: Color::RGB(40, 40, 56) });
+            sprites.push(Sprite::Rect {
+                rect: Rect::new(rect.x() + 2, rect.y() + 2, width - 4, height - 4),
+                color: Color::WHITE,
+            });
+            for (i, option) in options.iter().enumerate() {
+                let cursor = if i == selected { ">" } else { " " };
+                let pos = (rect.x() + DIALOG_BOX_PADDING as i32, rect.y() + DIALOG_BOX_PADDING as i32 + line * i as i32);
+                sprites.extend(settings.sprites(&format!("{} {}", cursor, option), pos));
+            }
+        }
+        sprites
+    }
+
+    /// The button to press to go on, drawn in the bottom right corner of the box. `glyph` is the
+    /// sprite of the button on the player's device, see `glyphs::glyph`; without one the prompt
+    /// is an arrow.
+    pub fn prompt(&self, glyph: Option<SpriteId>, settings: &TextSettings) -> Vec<Sprite> {
+        let size = settings.line_height();
+        let pos = (self.rect.right() - size as i32 - 4, self.rect.bottom() - size as i32 - 4);
+        match glyph {
+            Some(sprite) => vec![Sprite::Texture { rect: Rect::new(pos.0, pos.1, size, size), sprite }],
+            None => settings.sprites("v", pos),
+        }
+    }
+
+    /// The box without its padding, where the text goes
+    fn inner(&self) -> Rect {
+        Rect::new(
+            self.rect.x() + DIALOG_BOX_PADDING as i32,
+            self.rect.y() + DIALOG_BOX_PADDING as i32,
+            self.rect.width() - DIALOG_BOX_PADDING * 2,
+            self.rect.height() - DIALOG_BOX_PADDING * 2,
+        )
+    }
+}
+
+impl Default for DialogBox {
+    /// Along the bottom of the screen, where the game shows conversations
+    fn default() -> Self {
+        Self::new(Rect::new(8, 168, 304, 64))
+    }
+}
+
+/// Creates the dialog preview scene, a tool for writers to step through a conversation without
+/// playing up to it. Expects the props `tree`, the path of a dialog tree file, and optionally
+/// `strings`, the path of a strings table, `text`, `TextSettings` as a dict, and `vars`, the
+/// values of `{PLAYER}` and the like (see `RichText`).
+///
+/// The conversation is shown in the game's `DialogBox`, with its markup, appearing character by
+/// character. A shows the rest of the page at once, turns the page or picks the selected option,
+/// Up and Down move between options, B goes back to the previous node, Select reloads both files
+/// after editing them, and Start quits. Missing strings are shown as their key in brackets, and
+/// every problem `DialogTree::problems` finds is listed at the top of the screen. The prompt to
+/// turn the page and `{button:..}` codes show the buttons of whatever the player last pressed on.
+pub fn create_dialog_preview(props: Dict) -> Scene {
+    let mut state = HashMap::new();
+    for key in ["tree", "strings", "text", "vars"] {
+        if let Some(value) = props.get(&Key::new(key)) {
+            state.insert(Key::new(key), value.clone());
+        }
+    }
+    let mut event_callbacks: HashMap<EventType, EventCallbackFn> = HashMap::new();
+    event_callbacks.insert(EventType::KeyDown, dialog_preview_key_down);
+    let mut scene = Scene::new(
+        ClearMode::Color(Color::RGB(96, 128, 96)),
+        state,
+        Vec::new(),
+        event_callbacks,
+        dialog_preview_tick,
+        |_, _, _| SceneFnOutcome::Continue,
+    );
+    restart_dialog_preview(&mut scene);
+    scene
+}
+
+/// Files the preview was opened with
+fn preview_files(state: &Dict) -> (Result<DialogTree, String>, Result<Dict, String>) {
+    let tree = match state.get(&Key::new("tree")) {
+        Some(DictValue::String(path)) => DialogTree::load(path),
+        _ => Err(String::from("no \"tree\" file given")),
+    };
+    let strings = match state.get(&Key::new("strings")) {
+        Some(DictValue::String(path)) => load_file(path).or_else(|err| Err(format!("{}: {}", path, err))),
+        _ => Ok(HashMap::new()),
+    };
+    (tree, strings)
+}
+
+fn restart_dialog_preview(scene: &mut Scene) {
+    let start = match preview_files(scene.state()).0 {
+        Ok(tree) => DictValue::String(tree.start),
+        Err(_) => DictValue::Null,
+    };
+    let state = scene.state();
+    state.insert(Key::new("node"), start);
+    state.insert(Key::new("page"), DictValue::U32(0));
+    state.insert(Key::new("selected"), DictValue::U32(0));
+    state.insert(Key::new("elapsed"), DictValue::U32(0));
+    state.insert(Key::new("history"), DictValue::Array(Vec::new()));
+    layout_dialog_preview(scene);
+}
+
+/// The pages of a node's text in the box, its markup parsed with the preview's `vars`. Text
+/// whose markup doesn't parse is shown as it is.
+fn node_pages(state: &Dict, strings: &Dict, node: &DialogNode, settings: &TextSettings) -> Vec<Vec<RichLine>> {
+    let text = localize(strings, &node.text).map_or_else(|| format!("[{}]", node.text), String::from);
+    let vars = match state.get(&Key::new("vars")) {
+        Some(DictValue::Dict(vars)) => vars.clone(),
+        _ => HashMap::new(),
+    };
+    let text = RichText::parse(&text, &vars).unwrap_or_else(|_| RichText::literal(&text));
+    DialogBox::default().rich_pages(&text, settings)
+}
+
+fn u32_state(state: &Dict, key: &str) -> u32 {
+    dict_u32(state, key).unwrap_or(0)
+}
+
+fn dialog_preview_key_down(scene: &mut Scene, ctx: &mut EngineContext, event: &Event) -> SceneFnOutcome {
+    let action = match *event {
+        Event::KeyDown { keycode: Some(key), .. } => ctx.input.map.get(key),
+        _ => None,
+    };
+    let (tree, strings) = match preview_files(scene.state()) {
+        (Ok(tree), Ok(strings)) => (tree, strings),
+        _ if action == Some(Action::Start) => return SceneFnOutcome::Quit(HashMap::new()),
+        _ => {
+            if action == Some(Action::Select) {
+                restart_dialog_preview(scene);
+            }
+            return SceneFnOutcome::Continue;
+        },
+    };
+    let state = scene.state();
+    let node = match state.get(&Key::new("node")) {
+        Some(DictValue::String(id)) => tree.nodes.get(id).cloned(),
+        _ => None,
+    };
+    let page = u32_state(state, "page");
+    let selected = u32_state(state, "selected");
+    let settings = match state.get(&Key::new("text")) {
+        Some(DictValue::Dict(text)) => TextSettings::from_dict(text),
+        _ => TextSettings::default(),
+    };
+    match action {
+        Some(Action::Start) => return SceneFnOutcome::Quit(HashMap::new()),
+        Some(Action::Select) => {
+            restart_dialog_preview(scene);
+            return SceneFnOutcome::Continue;
+        },
+        Some(Action::Up) => {
+            state.insert(Key::new("selected"), DictValue::U32(selected.saturating_sub(1)));
+        },
+        Some(Action::Down) => {
+            let options = node.as_ref().map_or(0, |node| node.options.len() as u32);
+            state.insert(Key::new("selected"), DictValue::U32((selected + 1).min(options.saturating_sub(1))));
+        },
+        Some(Action::B) => {
+            let previous = match state.get_mut(&Key::new("history")) {
+                Some(DictValue::Array(history)) => history.pop(),
+                _ => None,
+            };
+            if let Some(previous) = previous {
+                state.insert(Key::new("node"), previous);
+                state.insert(Key::new("page"), DictValue::U32(0));
+                state.insert(Key::new("elapsed"), DictValue::U32(0));
+                state.insert(Key::new("selected"), DictValue::U32(0));
+            }
+        },
+        Some(Action::A) => match node {
+            None => {
+                restart_dialog_preview(scene); // the conversation ended, start it over
+                return SceneFnOutcome::Continue;
+            },
+            Some(node) => {
+                let pages = node_pages(state, &strings, &node, &settings);
+                let reveal_time = pages.get(page as usize).map_or(0, |page| markup::reveal_time(page));
+                let pages = pages.len() as u32;
+                if u32_state(state, "elapsed") < reveal_time {
+                    state.insert(Key::new("elapsed"), DictValue::U32(reveal_time)); // show the rest of the page
+                } else if page + 1 < pages {
+                    state.insert(Key::new("page"), DictValue::U32(page + 1));
+                    state.insert(Key::new("elapsed"), DictValue::U32(0));
+                } else {
+                    let next = match node.options.get(selected as usize) {
+                        Some((_, next)) => next.clone(),
+                        None => node.next.clone(),
+                    };
+                    if let (Some(current), Some(DictValue::Array(history))) =
+                        (state.get(&Key::new("node")).cloned(), state.get_mut(&Key::new("history")))
+                    {
+                        history.push(current);
+                    }
+                    state.insert(Key::new("node"), next.map_or(DictValue::Null, DictValue::String));
+                    state.insert(Key::new("page"), DictValue::U32(0));
+                    state.insert(Key::new("elapsed"), DictValue::U32(0));
+                    state.insert(Key::new("selected"), DictValue::U32(0));
+                }
+            },
+        },
+        _ => return SceneFnOutcome::Continue,
+    }
+    layout_dialog_preview(scene);
+    SceneFnOutcome::Continue
+}
+
+/// Switches the button glyphs to the device the player last used, and shows more of the page
+/// while it's appearing
+fn dialog_preview_tick(scene: &mut Scene, ctx: &mut EngineContext, interval: Duration) -> SceneFnOutcome {
+    let buttons: Dict = Action::ALL
+        .iter()
+        .filter_map(|action| {
+            let glyph = glyphs::glyph(&ctx.input.map, ctx.input.device, *action)?;
+            Some((Key::new(action.name()), DictValue::String(glyph.name().to_string())))
+        })
+        .collect();
+    let state = scene.state();
+    let mut changed = match state.get(&Key::new("buttons")) {
+        Some(DictValue::Dict(shown)) => !same_buttons(shown, &buttons),
+        _ => true,
+    };
+    state.insert(Key::new("buttons"), DictValue::Dict(buttons));
+    let (elapsed, reveal_time) = (u32_state(state, "elapsed"), u32_state(state, "reveal_time"));
+    if elapsed < reveal_time {
+        state.insert(Key::new("elapsed"), DictValue::U32((elapsed + interval.as_millis() as u32).min(reveal_time)));
+        changed = true;
+    }
+    if changed {
+        layout_dialog_preview(scene);
+    }
+    SceneFnOutcome::Continue
+}
+
+fn same_buttons(a: &Dict, b: &Dict) -> bool {
+    a.len() == b.len()
+        && a.iter().all(|(action, glyph)| match (glyph, b.get(action)) {
+            (DictValue::String(a), Some(DictValue::String(b))) => a == b,
+            _ => false,
+        })
+}
+
+/// The glyph sprites of the player's buttons, as the tick last saw them
+fn preview_buttons(state: &Dict) -> HashMap<Action, SpriteId> {
+    match state.get(&Key::new("buttons")) {
+        Some(DictValue::Dict(buttons)) => buttons
+            .iter()
+            .filter_map(|(action, glyph)| match glyph {
+                DictValue::String(glyph) => Some((Action::from_name(action.name())?, Key::new(glyph))),
+                _ => None,
+            })
+            .collect(),
+        _ => HashMap::new(),
+    }
+}
+
+fn layout_dialog_preview(scene: &mut Scene) {
+    let state = scene.state();
+    let settings = match state.get(&Key::new("text")) {
+        Some(DictValue::Dict(text)) => TextSettings::from_dict(text),
+        _ => TextSettings::default(),
+    };
+    let line = settings.line_height() as i32;
+    let dialog_box = DialogBox::default();
+    let mut sprites = Vec::new();
+    let (tree, strings) = match preview_files(state) {
+        (Ok(tree), Ok(strings)) => (tree, strings),
+        (tree, strings) => {
+            let errors = tree.err().into_iter().chain(strings.err());
+            for (i, err) in errors.enumerate() {
+                sprites.extend(settings.sprites(&err, (8, 8 + line * i as i32)));
+            }
+            *scene.sprites() = sprites;
+            return;
+        },
+    };
+    let problems = tree.problems(&strings);
+    if !problems.is_empty() {
+        let header = format!("{} problem(s)", problems.len());
+        let lines = Some(header).into_iter().chain(problems);
+        for (i, problem) in lines.enumerate().take(8) {
+            let y = 4 + line * i as i32;
+            let width = settings.char_width() * problem.chars().count() as u32 + 4;
+            sprites.push(Sprite::Rect { rect: Rect::new(4, y, width, line as u32), color: Color::RGB(160, 32, 32) });
+            sprites.extend(settings.sprites(&problem, (6, y)));
+        }
+    }
+    let node = match state.get(&Key::new("node")) {
+        Some(DictValue::String(id)) => tree.nodes.get(id),
+        _ => None,
+    };
+    let text = |key: &str| localize(&strings, key).map_or_else(|| format!("[{}]", key), String::from);
+    match node {
+        Some(node) => {
+            let pages = node_pages(state, &strings, node, &settings);
+            let page = (u32_state(state, "page") as usize).min(pages.len().saturating_sub(1));
+            let last_page = page + 1 >= pages.len();
+            let page = pages.get(page).map_or(&[][..], |page| &page[..]);
+            let reveal_time = markup::reveal_time(page);
+            let elapsed = u32_state(state, "elapsed");
+            let done = elapsed >= reveal_time;
+            let options: Vec<String> = if last_page && done {
+                node.options.iter().map(|(option, _)| text(option)).collect()
+            } else {
+                Vec::new()
+            };
+            let buttons = preview_buttons(state);
+            let (shown, selected) = (markup::revealed(page, elapsed), u32_state(state, "selected") as usize);
+            sprites.extend(dialog_box.rich_sprites(page, shown, &options, selected, &settings, &buttons));
+            if done && (!last_page || node.options.is_empty()) {
+                sprites.extend(dialog_box.prompt(buttons.get(&Action::A).copied(), &settings));
+            }
+            state.insert(Key::new("reveal_time"), DictValue::U32(reveal_time));
+        },
+        None => sprites.extend(dialog_box.sprites(&[String::from("(end)")], &[], 0, &settings)),
+    }
+    *scene.sprites() = sprites;
+}
+
+#[cfg(all(test, feature = "image"))]
+mod tests {
+    use sdl2::pixels::PixelFormatEnum;
+    use sdl2::surface::Surface;
+    use crate::golden::{ self, Image };
+    use crate::renderer::{ Renderer, SdlRenderer };
+    use crate::textures::TextureCache;
+    use super::*;
+
+    /// Draws a box with wrapped text and options to a software canvas and compares it with
+    /// `golden/dialog_box.png`. Every glyph is the same block, so this checks where the text goes
+    /// rather than the font.
+    #[test]
+    fn dialog_box_layout() {
+        let settings = TextSettings::default();
+        let dialog_box = DialogBox::new(Rect::new(4, 44, 152, 48));
+        let pages = dialog_box.pages("Wild PIDGEY appeared! Do you want to catch it?", &settings);
+        assert_eq!(pages.len(), 1);
+        let options = [String::from("YES"), String::from("NO")];
+        let sprites = dialog_box.sprites(&pages[0], &options, 1, &settings);
+
+        let mut canvas = Surface::new(160, 96, PixelFormatEnum::RGBA32).unwrap().into_canvas().unwrap();
+        let texture_creator = canvas.texture_creator();
+        let mut glyph = Surface::new(6, 12, PixelFormatEnum::RGBA32).unwrap();
+        glyph.fill_rect(Rect::new(1, 2, 4, 8), Color::RGB(40, 40, 56)).unwrap();
+        let index = sprites
+            .iter()
+            .filter_map(|sprite| match *sprite {
+                Sprite::Texture { sprite, .. } => Some((sprite, Rect::new(0, 0, 6, 12))),
+                _ => None,
+            })
+            .collect();
+        let mut spritesheet = SpriteSheet::new(texture_creator.create_texture_from_surface(&glyph).unwrap(), index);
+        let backgrounds = TextureCache::new(0);
+        let mut renderer = SdlRenderer::new(&mut canvas, &mut spritesheet, &backgrounds);
+        renderer.clear(Color::BLACK);
+        for sprite in sprites {
+            match sprite {
+                Sprite::Texture { rect, sprite } => assert!(renderer.draw_sprite(sprite, rect)),
+                Sprite::Rect { rect, color } => renderer.draw_rect(rect, color),
+                Sprite::Tinted { .. } => panic!("plain text has no tinted glyphs"),
+            }
+        }
+
+        let pixels = canvas.read_pixels(None, PixelFormatEnum::RGBA32).unwrap();
+        let image = Image { width: 160, height: 96, pixels };
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/golden/dialog_box.png");
+        if let Err(err) = golden::compare_golden(&image, path, 0) {
+            panic!("{}", err);
+        }
+    }
+}
@@ -1,5 +1,13 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::rc::Rc;
+use crate::intern::Key;
+
+#[derive(Clone)]
 pub enum DictValue {
     Null,
+    Bool(bool),
     String(String),
     Char(char),
     U8(u8),
@@ -18,10 +26,67 @@ pub enum DictValue {
     Dict(Dict),
     Func(fn(dict: &Dict) -> DictValue),
     FuncMut(fn(dict: &mut Dict) -> DictValue),
-    Object(Box<dyn IsDictValue>),
+    Object(Rc<RefCell<dyn IsDictValue>>), // shared, so cloning a Dict doesn't copy objects
 }
 
-pub type Dict = HashMap<String, DictValue>;
+impl DictValue {
+    /// The value in a form short enough to fit on a line of a debug tool: primitives as they are,
+    /// strings quoted, and arrays and dicts by their length
+    pub fn summary(&self) -> String {
+        match self {
+            Self::Null => String::from("null"),
+            Self::Bool(b) => b.to_string(),
+            Self::String(s) => format!("{:?}", s),
+            Self::Char(c) => format!("{:?}", c),
+            Self::U8(n) => n.to_string(),
+            Self::I8(n) => n.to_string(),
+            Self::U16(n) => n.to_string(),
+            Self::I16(n) => n.to_string(),
+            Self::U32(n) => n.to_string(),
+            Self::I32(n) => n.to_string(),
+            Self::U64(n) => n.to_string(),
+            Self::I64(n) => n.to_string(),
+            Self::U128(n) => n.to_string(),
+            Self::I128(n) => n.to_string(),
+            Self::F32(n) => n.to_string(),
+            Self::F64(n) => n.to_string(),
+            Self::Array(values) => format!("[{} items]", values.len()),
+            Self::Dict(dict) => format!("{{{} keys}}", dict.len()),
+            Self::Func(_) | Self::FuncMut(_) => String::from("<fn>"),
+            Self::Object(_) => String::from("<object>"),
+        }
+    }
+
+    /// The value as a u64 if it's a whole number that isn't negative, whichever integer type it
+    /// was stored as. Numbers read from JSON are `I64`, the ones the game writes usually `U32`.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Self::U8(n) => Some(n as u64),
+            Self::U16(n) => Some(n as u64),
+            Self::U32(n) => Some(n as u64),
+            Self::U64(n) => Some(n),
+            Self::U128(n) => u64::try_from(n).ok(),
+            Self::I8(n) => u64::try_from(n).ok(),
+            Self::I16(n) => u64::try_from(n).ok(),
+            Self::I32(n) => u64::try_from(n).ok(),
+            Self::I64(n) => u64::try_from(n).ok(),
+            Self::I128(n) => u64::try_from(n).ok(),
+            _ => None,
+        }
+    }
+
+    /// The value as a u32 if it's a whole number that fits, see `as_u64`
+    pub fn as_u32(&self) -> Option<u32> {
+        u32::try_from(self.as_u64()?).ok()
+    }
+}
+
+pub type Dict = HashMap<Key, DictValue>;
+
+/// The number at `key` as a u32, `None` if it's missing or isn't one, see `DictValue::as_u32`
+pub fn dict_u32(dict: &Dict, key: &str) -> Option<u32> {
+    dict.get(&Key::new(key))?.as_u32()
+}
 
 pub trait IsDictValue {}
 
@@ -0,0 +1,42 @@
+use crate::mapfile::MapFile;
+
+/// Elevation of stairs, ladders and slopes: they can be entered from any level, and whoever is on
+/// them takes the level of the next tile they step on
+pub const ELEVATION_TRANSITION: u8 = 0;
+/// Elevation of bridges: they can be entered from any level and don't change it, so the same tile
+/// is walked over on the bridge's level and under it (e.g. surfing) on the level below
+pub const ELEVATION_BRIDGE: u8 = 15;
+
+/// The level someone on `level` is on after stepping onto a tile of `elevation`, or `None` if
+/// the tile is on another level and can't be entered from this one. Levels go from 1 to 14,
+/// besides `ELEVATION_TRANSITION` and `ELEVATION_BRIDGE`:
+///
+/// - A tile of the same level can be walked onto, and so can any tile from a transition
+/// - Stepping onto a transition or another level takes that level, which is how stairs move the
+///   player up or down: the stairs are a transition between tiles of two levels
+/// - A bridge keeps the level, so from the bridge's own level the player can only walk along it,
+///   and from the level below only under it
+pub fn step(level: u8, elevation: u8) -> Option<u8> {
+    match elevation {
+        ELEVATION_BRIDGE => Some(level),
+        _ if elevation == level || level == ELEVATION_TRANSITION || elevation == ELEVATION_TRANSITION => {
+            Some(elevation)
+        },
+        _ => None,
+    }
+}
+
+/// Whether a tile is drawn over someone on `level` standing on it, i.e. they're under a bridge.
+/// `bridge_level` is the level of the bridge's deck, usually that of the tiles at its ends.
+pub fn is_under(level: u8, elevation: u8, bridge_level: u8) -> bool {
+    elevation == ELEVATION_BRIDGE && level != ELEVATION_TRANSITION && level < bridge_level
+}
+
+/// Moves someone on `level` onto a tile of a map, checking both the map's collision and its
+/// elevation. Returns their new level, or `None` if they can't walk there.
+pub fn walk(map: &MapFile, level: u8, to: (u32, u32)) -> Option<u8> {
+    if map.collision(to.0, to.1) != 0 {
+        return None;
+    }
+    step(level, map.elevation(to.0, to.1))
+}
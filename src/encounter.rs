@@ -0,0 +1,247 @@
+use crate::dict::*;
+use crate::intern::Key;
+use crate::rng::Rng;
+
+/// One wild pokemon that can appear in an area, with how likely it is relative to the others
+#[derive(Clone, PartialEq, Debug)]
+pub struct EncounterSlot {
+    pub species: Key,
+    pub weight: u32,
+    pub levels: (u32, u32), // inclusive
+}
+
+impl EncounterSlot {
+    /// Reads an area's slots from the encounters data, an array of dicts with `species`,
+    /// `weight`, `min_level` and `max_level`. Entries with a missing or mistyped field are skipped.
+    pub fn from_dict_value(value: &DictValue) -> Vec<Self> {
+        let entries = match value {
+            DictValue::Array(entries) => entries,
+            _ => return Vec::new(),
+        };
+        entries
+            .iter()
+            .filter_map(|entry| {
+                let entry = match entry {
+                    DictValue::Dict(entry) => entry,
+                    _ => return None,
+                };
+                let species = match entry.get(&Key::new("species")) {
+                    Some(DictValue::String(species)) => Key::new(species),
+                    _ => return None,
+                };
+                let (min, max) = (dict_u32(entry, "min_level")?, dict_u32(entry, "max_level")?);
+                Some(Self { species, weight: dict_u32(entry, "weight")?, levels: (min.min(max), max.max(min)) })
+            })
+            .collect()
+    }
+}
+
+/// A wild pokemon the player ran into
+#[derive(Clone, PartialEq, Debug)]
+pub struct Encounter {
+    pub species: Key,
+    pub level: u32,
+    pub shiny: bool,
+}
+
+/// What an `EncounterModel` knows about the step being rolled for
+pub struct Step<'a> {
+    pub area: Key,
+    pub slots: &'a [EncounterSlot],
+    pub steps: u32, // steps taken since the last encounter, including this one
+}
+
+/// Default chance of a step in grass starting an encounter
+pub const DEFAULT_ENCOUNTER_CHANCE: f64 = 0.1;
+/// Default shiny odds, as 1 in this many
+pub const DEFAULT_SHINY_ODDS: u32 = 4096;
+
+/// Decides how encounters happen, so games can change the rules (chaining, lures, abilities
+/// like Arena Trap) without touching `Encounters`. Only `chance` is required, the rest default to
+/// the usual rules.
+pub trait EncounterModel {
+    /// Chance from 0 to 1 that this step starts an encounter
+    fn chance(&mut self, step: &Step) -> f64;
+
+    /// Weight used when picking a slot for an encounter, e.g. raised for the species a lure
+    /// attracts
+    fn weight(&mut self, _step: &Step, slot: &EncounterSlot) -> u32 {
+        slot.weight
+    }
+
+    /// Shiny odds for a species about to be encountered, as 1 in the returned number
+    fn shiny_odds(&mut self, _species: Key) -> u32 {
+        DEFAULT_SHINY_ODDS
+    }
+
+    /// Whether a rolled encounter happens, e.g. false for pokemon a repel keeps away. Encounters
+    /// that don't happen aren't passed to `encountered`.
+    fn allows(&mut self, _encounter: &Encounter) -> bool {
+        true
+    }
+
+    /// Is told about every encounter, for models that keep track of e.g. a chain
+    fn encountered(&mut self, _encounter: &Encounter) {}
+}
+
+/// The same chance on every step
+pub struct FlatRate(pub f64);
+
+impl EncounterModel for FlatRate {
+    fn chance(&mut self, _step: &Step) -> f64 {
+        self.0
+    }
+}
+
+impl Default for FlatRate {
+    fn default() -> Self {
+        Self(DEFAULT_ENCOUNTER_CHANCE)
+    }
+}
+
+/// Multiplies another model's encounter chance, e.g. 2.0 for Arena Trap style abilities or 0.0
+/// while a repel is active
+pub struct RateMultiplier<M: EncounterModel> {
+    pub inner: M,
+    pub multiplier: f64,
+}
+
+impl<M: EncounterModel> EncounterModel for RateMultiplier<M> {
+    fn chance(&mut self, step: &Step) -> f64 {
+        self.inner.chance(step) * self.multiplier
+    }
+
+    fn weight(&mut self, step: &Step, slot: &EncounterSlot) -> u32 {
+        self.inner.weight(step, slot)
+    }
+
+    fn shiny_odds(&mut self, species: Key) -> u32 {
+        self.inner.shiny_odds(species)
+    }
+
+    fn allows(&mut self, encounter: &Encounter) -> bool {
+        self.inner.allows(encounter)
+    }
+
+    fn encountered(&mut self, encounter: &Encounter) {
+        self.inner.encountered(encounter)
+    }
+}
+
+/// Encountering the same species again and again builds a chain that raises the shiny odds of
+/// that species, up to `max_bonus` times the normal odds. Any other species breaks the chain.
+pub struct Chaining<M: EncounterModel> {
+    pub inner: M,
+    pub max_bonus: u32,
+    chain: Option<(Key, u32)>, // (species, length)
+}
+
+impl<M: EncounterModel> Chaining<M> {
+    pub fn new(inner: M, max_bonus: u32) -> Self {
+        Self { inner, max_bonus, chain: None }
+    }
+
+    pub fn chain(&self) -> Option<(Key, u32)> {
+        self.chain
+    }
+}
+
+impl<M: EncounterModel> EncounterModel for Chaining<M> {
+    fn chance(&mut self, step: &Step) -> f64 {
+        self.inner.chance(step)
+    }
+
+    fn weight(&mut self, step: &Step, slot: &EncounterSlot) -> u32 {
+        self.inner.weight(step, slot)
+    }
+
+    fn shiny_odds(&mut self, species: Key) -> u32 {
+        let odds = self.inner.shiny_odds(species);
+        match self.chain {
+            Some((chained, length)) if chained == species => (odds / (1 + length).min(self.max_bonus.max(1))).max(1),
+            _ => odds,
+        }
+    }
+
+    fn allows(&mut self, encounter: &Encounter) -> bool {
+        self.inner.allows(encounter)
+    }
+
+    fn encountered(&mut self, encounter: &Encounter) {
+        self.chain = match self.chain {
+            Some((species, length)) if species == encounter.species => Some((species, length + 1)),
+            _ => Some((encounter.species, 1)),
+        };
+        self.inner.encountered(encounter);
+    }
+}
+
+/// Rolls for wild encounters as the player walks through an area, following an
+/// `EncounterModel`. Uses its own seeded random numbers, so a given seed always gives the same
+/// encounters, e.g. for replays and tests.
+pub struct Encounters<M: EncounterModel> {
+    pub model: M,
+    rng: Rng,
+    steps: u32,
+}
+
+impl<M: EncounterModel> Encounters<M> {
+    pub fn new(model: M, seed: u64) -> Self {
+        Self { model, rng: Rng::new(seed), steps: 0 }
+    }
+
+    /// Call for every step the player takes in an area with wild pokemon. Returns the encounter
+    /// if the step started one.
+    pub fn step(&mut self, area: Key, slots: &[EncounterSlot]) -> Option<Encounter> {
+        self.steps += 1;
+        let step = Step { area, slots, steps: self.steps };
+        if slots.is_empty() || self.rng.next_f64() >= self.model.chance(&step) {
+            return None;
+        }
+        self.roll(&step)
+    }
+
+    /// Picks a wild pokemon from an area's slots without rolling the step's chance, e.g. for one
+    /// that shows up walking around in the grass (see `roaming::Roamers`)
+    pub fn pick(&mut self, area: Key, slots: &[EncounterSlot]) -> Option<Encounter> {
+        if slots.is_empty() {
+            return None;
+        }
+        let step = Step { area, slots, steps: self.steps + 1 };
+        self.roll(&step)
+    }
+
+    fn roll(&mut self, step: &Step) -> Option<Encounter> {
+        let slots = step.slots;
+        let weights: Vec<u32> = slots.iter().map(|slot| self.model.weight(step, slot)).collect();
+        let total: u64 = weights.iter().map(|weight| *weight as u64).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = self.rng.next_u64() % total;
+        let index = weights
+            .iter()
+            .position(|weight| {
+                if pick < *weight as u64 {
+                    return true;
+                }
+                pick -= *weight as u64;
+                false
+            })
+            .unwrap_or(slots.len() - 1);
+        let slot = &slots[index];
+        let level = slot.levels.0 + (self.rng.next_u64() % (slot.levels.1 - slot.levels.0 + 1) as u64) as u32;
+        let odds = self.model.shiny_odds(slot.species).max(1);
+        let encounter = Encounter {
+            species: slot.species,
+            level,
+            shiny: self.rng.next_u64().is_multiple_of(odds as u64),
+        };
+        if !self.model.allows(&encounter) {
+            return None;
+        }
+        self.model.encountered(&encounter);
+        self.steps = 0;
+        Some(encounter)
+    }
+}
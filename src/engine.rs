@@ -1,27 +1,53 @@
-use std::collections::HashMap;
-use std::time::Duration;
+use std::collections::{ HashMap, HashSet, VecDeque };
+use std::fmt;
+use std::panic::{ self, AssertUnwindSafe };
+use std::time::{ Duration, Instant };
 use sdl2::{
+    EventPump,
     Sdl,
     VideoSubsystem,
+    AudioSubsystem,
+    GameControllerSubsystem,
 };
+use sdl2::controller::{ Button, GameController };
 use sdl2::event::{
     Event,
     EventType,
+    WindowEvent,
 };
 use sdl2::render::{
     WindowCanvas,
-    TextureCreator,
     Texture,
 };
-use sdl2::video::{
-    WindowContext,
-    Window,
-};
+use sdl2::keyboard::{ Keycode, Mod };
 use sdl2::rect::Rect;
-use sdl2::pixels::Color;
-use sdl2::image::LoadTexture;
+use sdl2::pixels::{ Color, PixelFormatEnum };
+use sdl2::hint;
 use crate::stack::Stack;
 use crate::dict::*;
+use crate::audio::{ Audio, UiSound, UiSoundMap };
+use crate::palette::Palette;
+use crate::text::TextSettings;
+use crate::input::{ Action, Binding, Input, InputMap };
+use crate::textures::{ TextureCache, TextureStats };
+use crate::tilemap::{ Tilemap, TilemapRenderer };
+use crate::intern::{ Key, SpriteId };
+use crate::golden::Image;
+use crate::renderer::{ NullRenderer, Renderer, SdlRenderer };
+use crate::preload::{ Kind, Loaded, Preloader, SceneAssets };
+use crate::data::json_to_dict_value;
+use crate::macros::{ InputMacro, Macros };
+use crate::leaks::{ KeyLeaks, KeyOrigin };
+use crate::crash::{ self, CrashReport, CRASH_DIR, CRASH_LOG_LINES };
+use crate::watchdog::FrameWatchdog;
+use crate::toasts::Toasts;
+#[cfg(debug_assertions)]
+use crate::debugwindow::DebugWindow;
+use crate::diagnostics::{ self, dict_footprint, SceneCosts, SceneDiagnostics };
+use crate::inspector::Inspector;
+use crate::images;
+use crate::glyphs::InputDevice;
+use crate::windows::{ GameWindow, WindowId, WindowVideo };
 
 /// Holds basic info for a `Engine`, such as title, time between frames, size, scale, etc
 pub struct EngineInfo<'a> {
@@ -29,11 +55,345 @@ pub struct EngineInfo<'a> {
     delay: u32,
     size: (u32, u32),
     scale: (f32, f32),
+    filter: ScaleFilter,
+    integer_scale: bool,
+    texture_budget: usize, // bytes of textures the engine keeps loaded, see `TextureCache`
+    max_delta: u32, // longest a single frame can be (ms), see `with_max_delta`
+    timestep: u32, // length of a tick (ms), see `with_timestep`
+    vsync: bool, // present in step with the display, see `with_vsync`
+    max_fps: Option<u32>, // see `with_max_fps`
+    clear_color: Color, // see `with_clear_color`
+    pause_unfocused: bool, // see `with_pause_when_unfocused`
+    max_stack_depth: usize, // see `with_stack_guards`
+    replace_loop_frames: u32,
 }
 
 impl<'a> EngineInfo<'a> {
     pub fn new(title: &'a str, delay: u32, size: (u32, u32), scale: (f32, f32)) -> Self {
-        Self { title, delay, size, scale }
+        Self {
+            title,
+            delay,
+            size,
+            scale,
+            filter: ScaleFilter::Nearest,
+            integer_scale: false,
+            texture_budget: DEFAULT_TEXTURE_BUDGET,
+            max_delta: DEFAULT_MAX_DELTA,
+            timestep: delay,
+            vsync: false,
+            max_fps: None,
+            clear_color: Color::BLACK,
+            pause_unfocused: false,
+            max_stack_depth: DEFAULT_MAX_STACK_DEPTH,
+            replace_loop_frames: DEFAULT_REPLACE_LOOP_FRAMES,
+        }
+    }
+
+    pub fn with_texture_budget(mut self, texture_budget: usize) -> Self {
+        self.texture_budget = texture_budget;
+        self
+    }
+
+    /// Caps the time a single frame can advance the game by. Frames that took longer (e.g. while
+    /// the window was being dragged) are ticked as if only `max_delta` ms passed, so animations
+    /// and timers don't jump ahead all at once.
+    pub fn with_max_delta(mut self, max_delta: u32) -> Self {
+        self.max_delta = max_delta;
+        self
+    }
+
+    /// Sets the length of a game tick, `delay` by default. Time that passes is collected and the
+    /// scenes are ticked in steps of exactly `timestep` ms, as many as fit, whatever the frame
+    /// rate, so movement and cutscenes play out the same on a slow machine as on a fast one.
+    /// Scenes that `set_interpolate` are drawn part of the way between their last two ticks by
+    /// whatever time is left over.
+    pub fn with_timestep(mut self, timestep: u32) -> Self {
+        self.timestep = timestep.max(1);
+        self
+    }
+
+    /// Guards against runaway scene code: children that would make the stack deeper than
+    /// `max_stack_depth` aren't pushed, and a warning is printed when scenes have replaced
+    /// themselves for `replace_loop_frames` frames in a row, which is almost always a scene that
+    /// returns `Replace` from every tick by mistake
+    pub fn with_stack_guards(mut self, max_stack_depth: usize, replace_loop_frames: u32) -> Self {
+        self.max_stack_depth = max_stack_depth;
+        self.replace_loop_frames = replace_loop_frames;
+        self
+    }
+
+    /// Waits for the display's refresh before showing each frame, which stops tearing and paces
+    /// the loop to the refresh rate. Without a frame rate cap (see `with_max_fps`) the loop then
+    /// doesn't sleep on its own.
+    pub fn with_vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
+    /// Caps how many frames are drawn a second, sleeping out the rest of each frame, e.g. to save
+    /// battery when vsync is off or the display is very fast. `None` leaves pacing to vsync, or
+    /// to `delay` between frames when vsync is off too.
+    pub fn with_max_fps(mut self, max_fps: Option<u32>) -> Self {
+        self.max_fps = max_fps.filter(|fps| *fps > 0);
+        self
+    }
+
+    /// Sets the color the canvas is cleared to at the start of every frame, black by default.
+    /// It shows wherever the top scene doesn't draw, e.g. around scenes with `ClearMode::None`.
+    pub fn with_clear_color(mut self, clear_color: Color) -> Self {
+        self.clear_color = clear_color;
+        self
+    }
+
+    /// Stops ticking the scenes while the window doesn't have focus or is minimized, so cutscene
+    /// timers and battle animations don't run on while the player is away. Events are still
+    /// handled and frames still drawn. Scenes are told either way, see `Scene::set_on_focus_changed`.
+    pub fn with_pause_when_unfocused(mut self, pause_unfocused: bool) -> Self {
+        self.pause_unfocused = pause_unfocused;
+        self
+    }
+
+    /// Shortest time a frame of the loop takes, if the loop sleeps at all
+    fn frame_time(&self) -> Option<Duration> {
+        match (self.max_fps, self.vsync) {
+            (Some(fps), _) => Some(Duration::from_micros(1_000_000 / fps as u64)),
+            (None, true) => None,
+            (None, false) => Some(Duration::from_millis(self.delay as u64)),
+        }
+    }
+
+    /// Sets how textures are filtered when scaled up
+    pub fn with_filter(mut self, filter: ScaleFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Only scales the game by whole numbers, letterboxing whatever space is left over, so every
+    /// pixel of pixel art ends up the same size on screen
+    pub fn with_integer_scale(mut self, integer_scale: bool) -> Self {
+        self.integer_scale = integer_scale;
+        self
+    }
+
+    /// Size of the game before scaling, i.e. the area scenes draw to
+    fn logical_size(&self) -> (u32, u32) {
+        (
+            (self.size.0 as f32 / self.scale.0) as u32,
+            (self.size.1 as f32 / self.scale.1) as u32,
+        )
+    }
+}
+
+/// Default time (ms) between frames of an `EngineBuilder`, about 60 a second
+const DEFAULT_DELAY: u32 = 16;
+
+/// Default window size of an `EngineBuilder`
+const DEFAULT_SIZE: (u32, u32) = (960, 640);
+
+/// Default for `EngineInfo::max_delta`
+const DEFAULT_MAX_DELTA: u32 = 250;
+
+/// Default for `EngineInfo::max_stack_depth`
+const DEFAULT_MAX_STACK_DEPTH: usize = 32;
+
+/// Default for `EngineInfo::replace_loop_frames`
+const DEFAULT_REPLACE_LOOP_FRAMES: u32 = 60;
+
+/// Default for `EngineInfo::texture_budget`
+const DEFAULT_TEXTURE_BUDGET: usize = 256 * 1024 * 1024;
+
+/// How textures are sampled when they are scaled. `Nearest` keeps pixel art sharp, `Linear`
+/// smooths it out.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScaleFilter {
+    Nearest,
+    Linear,
+}
+
+impl ScaleFilter {
+    /// Value for SDL's render scale quality hint, which is used for textures created afterwards
+    fn hint(&self) -> &'static str {
+        match self {
+            Self::Nearest => "nearest",
+            Self::Linear => "linear",
+        }
+    }
+
+    /// Changes the filter of an already created texture
+    fn apply(&self, texture: &mut Texture) {
+        let mode = match self {
+            Self::Nearest => sdl2::sys::SDL_ScaleMode::SDL_ScaleModeNearest,
+            Self::Linear => sdl2::sys::SDL_ScaleMode::SDL_ScaleModeLinear,
+        };
+        unsafe {
+            sdl2::sys::SDL_SetTextureScaleMode(texture.raw(), mode);
+        }
+    }
+}
+
+/// What can go wrong setting up or running an `Engine`
+#[derive(Clone, PartialEq, Debug)]
+pub enum EngineError {
+    Config(String), // the engine was given settings it can't use, e.g. a window of size 0
+    MissingSpritesheet,
+    MissingSprite(SpriteId), // see `Engine::require_sprites`
+    EmptyStack, // the engine ran without a scene on the stack
+    Sdl(String), // SDL failed, e.g. to create the window or load a texture
+    Headless, // something that needs a window was asked of a headless engine
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(problem) => write!(f, "invalid engine settings: {}", problem),
+            Self::MissingSpritesheet => write!(f, "no spritesheet was given"),
+            Self::MissingSprite(sprite) => write!(f, "sprite \"{}\" is not on the spritesheet", sprite),
+            Self::EmptyStack => write!(f, "there is no scene to run"),
+            Self::Sdl(err) => write!(f, "{}", err),
+            Self::Headless => write!(f, "the engine is headless and has no window"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<String> for EngineError {
+    fn from(err: String) -> Self {
+        Self::Sdl(err)
+    }
+}
+
+/// Quits with the props of the last scene, for engines built without `EngineBuilder::on_quit`
+fn quit_with_props(_engine: &mut Engine, props: Dict) -> Dict {
+    props
+}
+
+/// Sets up an `Engine` step by step instead of through `Engine::new`'s long argument list, and
+/// checks the settings before SDL is started, e.g.
+/// `EngineBuilder::new().title("Pokemon").size(960, 640).scale(4.0, 4.0).spritesheet(path, index).build()`.
+/// Everything but the spritesheet has a default. The options of `EngineInfo` are set through
+/// `info`, whose title, delay, size and scale the other methods then change.
+pub struct EngineBuilder<'a> {
+    info: EngineInfo<'a>,
+    handle_quit: HandleQuitFn,
+    globals: Dict,
+    backgrounds: HashMap<String, Texture<'a>>,
+    spritesheet: Option<(String, HashMap<SpriteId, Rect>)>, // (path, index)
+    ui_sounds: UiSoundMap,
+}
+
+impl<'a> Default for EngineBuilder<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> EngineBuilder<'a> {
+    pub fn new() -> Self {
+        Self {
+            info: EngineInfo::new("", DEFAULT_DELAY, DEFAULT_SIZE, (1.0, 1.0)),
+            handle_quit: quit_with_props,
+            globals: Dict::new(),
+            backgrounds: HashMap::new(),
+            spritesheet: None,
+            ui_sounds: UiSoundMap::new(),
+        }
+    }
+
+    /// Replaces every `EngineInfo` setting, e.g. to turn on vsync with `EngineInfo::with_vsync`
+    pub fn info(mut self, info: EngineInfo<'a>) -> Self {
+        self.info = info;
+        self
+    }
+
+    pub fn title(mut self, title: &'a str) -> Self {
+        self.info.title = title;
+        self
+    }
+
+    /// Time (ms) between frames, see `EngineInfo::new`. Also the tick length, unless a
+    /// timestep was set through `info`.
+    pub fn delay(mut self, delay: u32) -> Self {
+        if self.info.timestep == self.info.delay {
+            self.info.timestep = delay.max(1);
+        }
+        self.info.delay = delay;
+        self
+    }
+
+    /// Size of the window, in screen pixels
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.info.size = (width, height);
+        self
+    }
+
+    pub fn scale(mut self, x: f32, y: f32) -> Self {
+        self.info.scale = (x, y);
+        self
+    }
+
+    /// The spritesheet image and where each sprite is on it
+    pub fn spritesheet(mut self, path: &str, index: HashMap<SpriteId, Rect>) -> Self {
+        self.spritesheet = Some((path.to_string(), index));
+        self
+    }
+
+    pub fn background(mut self, name: &str, background: Texture<'a>) -> Self {
+        self.backgrounds.insert(name.to_string(), background);
+        self
+    }
+
+    /// Sets a global the game starts with
+    pub fn global(mut self, key: &str, value: DictValue) -> Self {
+        self.globals.insert(Key::new(key), value);
+        self
+    }
+
+    /// Sets every global the game starts with, replacing those set so far
+    pub fn globals(mut self, globals: Dict) -> Self {
+        self.globals = globals;
+        self
+    }
+
+    /// Called when the last scene quits, with the props it quit with, to decide what `run`
+    /// returns. By default the props are returned as they are.
+    pub fn on_quit(mut self, handle_quit: HandleQuitFn) -> Self {
+        self.handle_quit = handle_quit;
+        self
+    }
+
+    pub fn ui_sounds(mut self, ui_sounds: UiSoundMap) -> Self {
+        self.ui_sounds = ui_sounds;
+        self
+    }
+
+    /// Checks the settings, then starts SDL and creates the engine
+    pub fn build(self) -> Result<Engine<'a>, EngineError> {
+        let info = &self.info;
+        if info.size.0 == 0 || info.size.1 == 0 {
+            return Err(EngineError::Config(format!("window size {}x{}", info.size.0, info.size.1)));
+        }
+        let is_valid_scale = |scale: f32| scale.is_finite() && scale > 0.0;
+        if !is_valid_scale(info.scale.0) || !is_valid_scale(info.scale.1) {
+            return Err(EngineError::Config(format!("scale {}x{}", info.scale.0, info.scale.1)));
+        }
+        let (width, height) = info.logical_size();
+        if width == 0 || height == 0 {
+            return Err(EngineError::Config(format!("scaled down to nothing ({}x{})", width, height)));
+        }
+        if info.delay == 0 && info.max_fps.is_none() && !info.vsync {
+            return Err(EngineError::Config("a delay of 0 without vsync or a frame rate cap".to_string()));
+        }
+        let (spritesheet, index) = self.spritesheet.ok_or(EngineError::MissingSpritesheet)?;
+        Ok(Engine::new(
+            self.info,
+            self.handle_quit,
+            self.globals,
+            self.backgrounds,
+            &spritesheet,
+            index,
+            self.ui_sounds,
+        )?)
     }
 }
 
@@ -42,152 +402,1483 @@ impl<'a> EngineInfo<'a> {
 pub struct Engine<'a> {
     info: EngineInfo<'a>,
     sdl_context: Sdl,
-    video_subsystem: VideoSubsystem,
-    window: Window,
-    canvas: WindowCanvas,
-    texture_creator: TextureCreator<WindowContext>,
+    backgrounds: TextureCache<'a>, // before `video`, so it's dropped before the creator it borrows
+    video: Option<Video<'a>>, // None for a headless engine
+    _audio_subsystem: Option<AudioSubsystem>, // never read, but keeps SDL's audio alive for the mixer
+    controller_subsystem: Option<GameControllerSubsystem>, // None where controllers aren't supported
+    controllers: HashMap<u32, (GameController, InputDevice)>, // open controllers by instance id
     handle_quit: HandleQuitFn, // when lone scene on stack quits, this fn is called
     globals: Dict,
-    backgrounds: HashMap<String, Texture<'a>>,
-    spritesheet: SpriteSheet<'a>,
+    sprite_index: HashMap<SpriteId, Rect>, // the spritesheet's, also kept by a headless engine
     stack: Stack<Scene>,
+    context: EngineContext,
+    hotkeys: HashMap<Keycode, HotkeyFn>,
+    global_event_handlers: HashMap<EventType, Vec<GlobalEventFn>>, // in the order they were added
+    macros: Macros,
+    debug_overlay: bool,
+    inspector: Option<Inspector>, // only while the inspector is open
+    leaks: Option<KeyLeaks>, // only while leak detection is on
+    watchdog: Option<FrameWatchdog>, // only while a frame budget is set
+    queued_outcomes: VecDeque<(u64, SceneFnOutcome)>, // (stack generation, outcome) from during a transition
+    stack_generation: u64, // goes up every time a callback changes the stack
+    replaced: bool, // whether a scene was replaced since the last tick
+    replace_streak: u32, // ticks in a row in which a scene was replaced
+    spritesheet_path: String, // for loading the spritesheet again in other windows
+    #[cfg(debug_assertions)]
+    debug_window: Option<DebugWindow>,
+    missing_sprites: HashSet<SpriteId>, // sprite names scenes used that aren't on the spritesheet
+    log: VecDeque<String>, // last `CRASH_LOG_LINES` lines of `debug_log`, for crash reports
+    crashed: bool, // the crash scene is up, so another panic is not caught again
+    last_frame: Option<Instant>, // when the previous frame started, for measuring delta time
+    step_keys: HashSet<Keycode>, // keys held down according to the events passed to `step`
+    step_buttons: HashSet<Button>, // controller buttons held down, the same way
+    accumulator: u32, // ms that have passed but weren't enough for another tick
+    focused: bool, // whether the window has focus and isn't minimized
+    error: Option<EngineError>, // why the loop stopped, if it wasn't the last scene quitting
+    windows: HashMap<WindowId, GameWindow>, // see `create_window`
+    pushed_events: VecDeque<Event>, // see `push_event`
+}
+
+/// The game's window and what draws to it, which a headless engine goes without
+struct Video<'a> {
+    subsystem: VideoSubsystem,
+    tilemaps: TilemapRenderer<'a>, // draws the scenes' tilemaps, see `Scene::set_tilemap`
+    window: WindowVideo<'a>, // after `tilemaps`, whose chunks are made with its texture creator
 }
 
 impl<'a> Engine<'a> {
-    /// Sets up SDL2 context and returns a new `Engine` from args
+    /// Sets up SDL2 context and returns a new `Engine` from args. `EngineBuilder` is easier to
+    /// use and checks the settings first.
     pub fn new(
         info: EngineInfo<'a>,
         handle_quit: HandleQuitFn,
         globals: Dict,
         backgrounds: HashMap<String, Texture<'a>>,
         spritesheet: &str,
-        index: HashMap<String, Rect>
+        index: HashMap<SpriteId, Rect>,
+        ui_sounds: UiSoundMap,
     ) -> Result<Self, String> {
+        hint::set("SDL_RENDER_SCALE_QUALITY", info.filter.hint());
         let sdl_context = sdl2::init()?;
         let video_subsystem = sdl_context.video()?;
-        let window = video_subsystem
-            .window(info.title, info.size.0, info.size.1)
-            .position_centered()
-            .build()
-            .or_else(|err| Err(format!("{}", err)))?;
-        let mut canvas = window
-            .into_canvas()
-            .build()
-            .or_else(|err| Err(format!("{}", err)))?;
-        canvas.set_scale(info.scale.0, info.scale.1);
-        let texture_creator = canvas.texture_creator();
+        let mut window = video_subsystem.window(info.title, info.size.0, info.size.1);
+        window.position_centered();
+        let mut window = WindowVideo::open(window, info.vsync, spritesheet, index.clone())?;
+        apply_canvas_scaling(&mut window.canvas, &info)?;
+        // besides the window's spritesheet, the engine's textures are the tilemap chunks, which
+        // `Video` declares before the window, and its backgrounds, which the engine declares
+        // before `video`
+        let video = Video {
+            subsystem: video_subsystem,
+            tilemaps: TilemapRenderer::new(window.texture_creator),
+            window,
+        };
+        let mut engine = Self::with_video(info, sdl_context, Some(video), handle_quit, globals, index, ui_sounds)?;
+        for (name, background) in backgrounds {
+            engine.backgrounds.insert(&name, background, false);
+        }
+        engine.spritesheet_path = spritesheet.to_string();
+        Ok(engine)
+    }
+
+    /// Sets up an engine without a window, for running scenes in CI or tools where there is no
+    /// display: SDL's video is never started, nothing is drawn (see `NullRenderer`) and audio
+    /// goes to SDL's dummy driver. Everything else works as with a window, including sprites
+    /// missing from `index` being reported, and events can be fed in with `push_event` or
+    /// `step`. Snapshots, preloaded backgrounds, `capture` and other windows aren't available.
+    ///
+    /// The dummy audio driver is picked with the `SDL_AUDIODRIVER` hint, which is global to the
+    /// process. It's only set while audio starts and put back afterwards, so engines created
+    /// later with `new` still get the real driver. An `SDL_AUDIODRIVER` environment variable
+    /// wins over the hint.
+    pub fn new_headless(
+        info: EngineInfo<'a>,
+        handle_quit: HandleQuitFn,
+        globals: Dict,
+        index: HashMap<SpriteId, Rect>,
+        ui_sounds: UiSoundMap,
+    ) -> Result<Self, String> {
+        let sdl_context = sdl2::init()?;
+        with_hint("SDL_AUDIODRIVER", "dummy", || {
+            Self::with_video(info, sdl_context, None, handle_quit, globals, index, ui_sounds)
+        })
+    }
+
+    /// What `new` and `new_headless` share
+    fn with_video(
+        info: EngineInfo<'a>,
+        sdl_context: Sdl,
+        video: Option<Video<'a>>,
+        handle_quit: HandleQuitFn,
+        globals: Dict,
+        sprite_index: HashMap<SpriteId, Rect>,
+        ui_sounds: UiSoundMap,
+    ) -> Result<Self, String> {
+        let (audio_subsystem, audio) = open_audio(&sdl_context, ui_sounds);
+        let controller_subsystem = sdl_context.game_controller().ok();
+        let backgrounds = TextureCache::new(info.texture_budget);
         Ok(Self {
             info,
             sdl_context,
-            video_subsystem,
-            window,
-            texture_creator,
-            canvas,
+            video,
+            _audio_subsystem: audio_subsystem,
+            controller_subsystem,
+            controllers: HashMap::new(),
             handle_quit,
             globals,
             backgrounds,
-            spritesheet: SpriteSheet::new(
-                texture_creator.load_texture(spritesheet)?,
-                index,
-            ),
+            sprite_index,
             stack: Stack::new(),
+            hotkeys: HashMap::new(),
+            global_event_handlers: HashMap::new(),
+            macros: Macros::new(),
+            debug_overlay: false,
+            inspector: None,
+            leaks: None,
+            watchdog: None,
+            queued_outcomes: VecDeque::new(),
+            stack_generation: 0,
+            replaced: false,
+            replace_streak: 0,
+            spritesheet_path: String::new(),
+            #[cfg(debug_assertions)]
+            debug_window: None,
+            missing_sprites: HashSet::new(),
+            log: VecDeque::new(),
+            crashed: false,
+            last_frame: None,
+            step_keys: HashSet::new(),
+            step_buttons: HashSet::new(),
+            accumulator: 0,
+            focused: true,
+            error: None,
+            windows: HashMap::new(),
+            pushed_events: VecDeque::new(),
+            context: EngineContext {
+                audio,
+                palette: Palette::Default,
+                text: TextSettings::default(),
+                input: Input::new(InputMap::default(), INPUT_BUFFER_WINDOW),
+                toasts: Toasts::new(),
+                frame_time: Duration::ZERO,
+                snapshot: None,
+                preloader: Preloader::new(),
+                transition_lock: false,
+                events: VecDeque::new(),
+            },
         })
     }
 
-    /// Runs the engine and then consumes itself, returning a game-specified `Dict` or an error
-    pub fn run(mut self) -> Dict {
-        let event_pump = self.sdl_context.event_pump().unwrap(); // THIS IS NOT SAFE
-        'running: loop {
-            if let Some(scene) = self.stack.peek() {
-                scene.render(&mut self.canvas, &self.backgrounds, &self.spritesheet);
+    /// Changes the scale and filtering while the game is running, e.g. from an options menu.
+    /// Applies to the canvas and every texture the engine holds.
+    pub fn set_scaling(
+        &mut self,
+        scale: (f32, f32),
+        filter: ScaleFilter,
+        integer_scale: bool,
+    ) -> Result<(), String> {
+        self.info.scale = scale;
+        self.info.filter = filter;
+        self.info.integer_scale = integer_scale;
+        hint::set("SDL_RENDER_SCALE_QUALITY", filter.hint());
+        if let Some(video) = &mut self.video {
+            apply_canvas_scaling(&mut video.window.canvas, &self.info)?;
+            filter.apply(&mut video.window.spritesheet.texture);
+            video.tilemaps.invalidate(); // chunks are filtered as they were when drawn
+        }
+        for background in self.backgrounds.iter_mut() {
+            filter.apply(background);
+        }
+        Ok(())
+    }
+
+    /// Loads a sound effect that scenes can later play by name through the `EngineContext`
+    pub fn load_sound(&mut self, name: &str, path: &str) -> Result<(), String> {
+        self.context.audio.load(name, path)
+    }
+
+    /// Runs the engine and then consumes itself, returning a game-specified `Dict`, or an error
+    /// if the game couldn't go on, e.g. because there was no scene to run
+    #[cfg(not(target_os = "emscripten"))]
+    pub fn run(mut self) -> Result<Dict, EngineError> {
+        let mut event_pump = self.sdl_context.event_pump()?;
+        let frame_time = self.info.frame_time();
+        let exit_props = loop {
+            let start = Instant::now();
+            if let Some(exit_props) = self.guarded(|engine| engine.frame(&mut event_pump)) {
+                break exit_props;
+            }
+            // only wait for what's left of the frame, the time spent in it already counts
+            if let Some(frame_time) = frame_time {
+                ::std::thread::sleep(frame_time.saturating_sub(start.elapsed()));
+            }
+        };
+        if let Some(report) = self.leak_report().filter(|report| !report.is_empty()) {
+            eprintln!("warning: possibly mistyped keys:\n{}", report);
+        }
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => Ok(exit_props),
+        }
+    }
+
+    /// Checks that sprites are on the spritesheet, e.g. the ones a game can't do without, so it
+    /// can fail at startup instead of drawing placeholders (see `Scene::draw`)
+    pub fn require_sprites(&self, sprites: &[SpriteId]) -> Result<(), EngineError> {
+        match sprites.iter().find(|sprite| !self.sprite_index.contains_key(sprite)) {
+            Some(sprite) => Err(EngineError::MissingSprite(*sprite)),
+            None => Ok(()),
+        }
+    }
+
+    /// Stops the loop with an error, returned by `run` or `step` in place of quit props
+    fn fail(&mut self, err: EngineError) -> Option<Dict> {
+        self.debug_log(&format!("error: {}", err));
+        self.error = Some(err);
+        Some(HashMap::new())
+    }
+
+    /// Runs a frame, turning a panic in it into the crash scene like `run` does. A panic while
+    /// the crash scene is up is passed on.
+    fn guarded<F: FnOnce(&mut Self) -> Option<Dict>>(&mut self, frame: F) -> Option<Dict> {
+        match panic::catch_unwind(AssertUnwindSafe(|| frame(self))) {
+            Ok(exit_props) => exit_props,
+            Err(payload) if self.crashed => panic::resume_unwind(payload), // the crash scene itself failed
+            Err(payload) => {
+                self.report_crash(&crash::panic_reason(&*payload));
+                None
+            },
+        }
+    }
+
+    /// Runs one frame of the game: handles events, ticks, and renders. Is called in a loop by
+    /// `run`, or by the browser once per animation frame on the web, where the loop can't block.
+    pub fn frame(&mut self, event_pump: &mut EventPump) -> Option<Dict> { // None=continue, Some(props)=exit with props
+        let events: Vec<Event> = event_pump.poll_iter().collect();
+        let pressed: Vec<Keycode> = event_pump
+            .keyboard_state()
+            .pressed_scancodes()
+            .filter_map(Keycode::from_scancode)
+            .collect();
+        let buttons = self.pressed_buttons();
+        let delta = self.delta();
+        self.watched(|engine| engine.run_frame(events, &pressed, &buttons, delta))
+    }
+
+    /// The buttons bound to an action that are held down on any open controller
+    fn pressed_buttons(&self) -> Vec<Button> {
+        let map = &self.context.input.map;
+        Action::ALL
+            .iter()
+            .flat_map(|action| map.slots(*action))
+            .filter_map(|binding| match binding {
+                Some(Binding::Button(button)) => Some(button),
+                _ => None,
+            })
+            .filter(|button| self.controllers.values().any(|(controller, _)| controller.button(*button)))
+            .collect()
+    }
+
+    /// Runs one frame with events from the host instead of from SDL, for applications that drive
+    /// the loop themselves (an editor, a test runner, a wasm frame callback) and do their own
+    /// work between frames. Time passes by the real time since the last step; see `step_by` for
+    /// a fixed amount. Keys and controller buttons count as held from their down event to their
+    /// up event.
+    ///
+    /// Panics in scenes bring up the crash scene, as in `run`.
+    pub fn step<I: IntoIterator<Item = Event>>(&mut self, events: I) -> StepResult {
+        let delta = self.delta(); // sets the frame time, fraction of a ms included
+        self.step_frame(events, delta)
+    }
+
+    /// Runs one frame like `step`, advancing time by exactly `interval` ms. Like every frame, the
+    /// time goes into the fixed-timestep accumulator, so it's ticked in steps of
+    /// `EngineInfo::with_timestep` and a short interval may not tick at all.
+    pub fn step_by<I: IntoIterator<Item = Event>>(&mut self, events: I, interval: u32) -> StepResult {
+        self.context.frame_time = Duration::from_millis(interval as u64);
+        self.step_frame(events, interval)
+    }
+
+    fn step_frame<I: IntoIterator<Item = Event>>(&mut self, events: I, interval: u32) -> StepResult {
+        let events: Vec<Event> = events.into_iter().collect();
+        for event in &events {
+            match *event {
+                Event::KeyDown { keycode: Some(key), .. } => {
+                    self.step_keys.insert(key);
+                },
+                Event::KeyUp { keycode: Some(key), .. } => {
+                    self.step_keys.remove(&key);
+                },
+                Event::ControllerButtonDown { button, .. } => {
+                    self.step_buttons.insert(button);
+                },
+                Event::ControllerButtonUp { button, .. } => {
+                    self.step_buttons.remove(&button);
+                },
+                _ => {},
+            }
+        }
+        let pressed: Vec<Keycode> = self.step_keys.iter().copied().collect();
+        let buttons: Vec<Button> = self.step_buttons.iter().copied().collect();
+        let frame = |engine: &mut Self| engine.watched(|engine| engine.run_frame(events, &pressed, &buttons, interval));
+        match self.guarded(frame) {
+            Some(exit_props) => match self.error.take() {
+                Some(err) => StepResult::Failed(err),
+                None => StepResult::Quit(exit_props),
+            },
+            None => StepResult::Running,
+        }
+    }
+
+    /// Runs a frame, timing it for the frame watchdog while it's on
+    fn watched<F: FnOnce(&mut Self) -> Option<Dict>>(&mut self, frame: F) -> Option<Dict> {
+        let start = Instant::now();
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.begin(self.backgrounds.stats().evictions);
+        }
+        let exit_props = frame(self);
+        let evictions = self.backgrounds.stats().evictions;
+        if let Some(spike) = self.watchdog.as_mut().and_then(|watchdog| watchdog.finish(start.elapsed(), evictions)) {
+            self.debug_log(&format!("warning: {}", spike));
+        }
+        exit_props
+    }
+
+    fn run_frame(
+        &mut self,
+        events: Vec<Event>,
+        pressed: &[Keycode],
+        buttons: &[Button],
+        delta: u32,
+    ) -> Option<Dict> { // None=continue, Some(props)=exit with props
+        if self.stack.is_empty() {
+            return self.fail(EngineError::EmptyStack);
+        }
+        let start = Instant::now();
+        let count = self.pushed_events.len() + events.len();
+        if let Some(exit_props) = self.handle_pushed_events() {
+            return Some(exit_props);
+        }
+        for event in events {
+            if let Some(exit_props) = self.handle_event(&event) {
+                return Some(exit_props);
+            }
+        }
+        self.watch(|| format!("{} event(s)", count), start);
+        let map = &self.context.input.map;
+        self.context.input.held.update(
+            pressed
+                .iter()
+                .filter_map(|key| map.get(*key))
+                .chain(buttons.iter().filter_map(|button| map.get_button(*button)))
+                .chain(self.macros.held().iter().copied()),
+        );
+        self.finish_preloads();
+        let start = Instant::now();
+        self.context.audio.update();
+        self.watch(|| String::from("audio"), start);
+        if self.is_paused() {
+            self.accumulator = 0; // so the time away isn't ticked all at once on the way back
+            self.render();
+            return None;
+        }
+        // fixed timestep: tick as many whole steps as the time passed allows, keeping the rest
+        let timestep = self.info.timestep.max(1);
+        self.accumulator = (self.accumulator + delta).min(self.info.max_delta.max(timestep));
+        while self.accumulator >= timestep {
+            self.accumulator -= timestep;
+            if let Some(exit_props) = self.tick(timestep) {
+                return Some(exit_props);
+            }
+        }
+        let start = Instant::now();
+        self.render();
+        self.watch(|| String::from("render"), start);
+        None
+    }
+
+    /// Whether ticking is paused because the window lost focus, see
+    /// `EngineInfo::with_pause_when_unfocused`
+    pub fn is_paused(&self) -> bool {
+        self.info.pause_unfocused && !self.focused
+    }
+
+    /// Tells every scene on the stack that the window gained or lost focus, top scene last. Only
+    /// the top scene's outcome is acted on, like for game events.
+    fn set_focused(&mut self, focused: bool) -> Option<Dict> { // None=continue, Some(props)=exit with props
+        if focused == self.focused {
+            return None;
+        }
+        self.focused = focused;
+        self.debug_log(if focused { "window focused" } else { "window unfocused" });
+        let top = self.stack.len().saturating_sub(1);
+        let mut top_outcome = None;
+        for (depth, scene) in self.stack.iter_mut().enumerate() {
+            if let Some(callback) = scene.on_focus_changed {
+                let outcome = callback(scene, &mut self.context, focused);
+                if depth == top {
+                    top_outcome = Some(outcome);
+                }
+            }
+        }
+        self.handle_scene_fn_outcome(top_outcome?)
+    }
+
+    /// How far between the last tick and the next one the game is, from 0 to 1, for drawing
+    /// interpolated scenes
+    fn alpha(&self) -> f32 {
+        self.accumulator as f32 / self.info.timestep.max(1) as f32
+    }
+
+    /// Records how long a part of the frame took since `start`, while the frame watchdog is on.
+    /// The label is only built if it is.
+    fn watch<F: FnOnce() -> String>(&mut self, label: F, start: Instant) {
+        if let Some(watchdog) = &mut self.watchdog {
+            watchdog.record(label(), start.elapsed());
+        }
+    }
+
+    /// Starts reading the files scenes asked to preload that aren't loaded, and adds the ones that
+    /// finished reading to the backgrounds, globals and sounds
+    fn finish_preloads(&mut self) {
+        let headless = self.video.is_none();
+        let (backgrounds, globals, audio) = (&self.backgrounds, &self.globals, &self.context.audio);
+        self.context.preloader.start(|kind, name| match kind {
+            Kind::Background => headless || backgrounds.contains(name), // headless engines don't keep them
+            Kind::Data => globals.contains_key(&Key::new(name)),
+            Kind::Sound => audio.is_loaded(name),
+        });
+        let loaded = self.context.preloader.finished();
+        for loaded in loaded {
+            let start = Instant::now();
+            let label = match &loaded {
+                Ok(Loaded::Background(name, _)) => format!("load background {}", name),
+                Ok(Loaded::Data(name, _)) => format!("load data {}", name),
+                Ok(Loaded::Sound(name, _)) => format!("load sound {}", name),
+                Err(_) => String::from("failed load"),
+            };
+            let result = loaded.and_then(|loaded| match loaded {
+                Loaded::Background(name, bytes) => {
+                    let texture_creator = match &self.video {
+                        Some(video) => video.window.texture_creator,
+                        None => return Ok(()), // nothing would draw it
+                    };
+                    let mut texture = images::load_texture_bytes(texture_creator, &bytes)
+                        .or_else(|err| Err(format!("background {}: {}", name, err)))?;
+                    self.info.filter.apply(&mut texture);
+                    self.backgrounds.insert(&name, texture, false);
+                    Ok(())
+                },
+                Loaded::Data(name, json) => {
+                    let key = Key::new(&name);
+                    if let Some(leaks) = &mut self.leaks {
+                        leaks.written(key, KeyOrigin::Global);
+                    }
+                    self.globals.insert(key, json_to_dict_value(json));
+                    Ok(())
+                },
+                Loaded::Sound(name, bytes) => self.context.audio
+                    .load_bytes(&name, &bytes)
+                    .or_else(|err| Err(format!("sound {}: {}", name, err))),
+            });
+            if let Err(err) = result {
+                self.debug_log(&format!("warning: couldn't preload {}", err));
+            }
+            self.watch(|| label, start);
+        }
+    }
+
+    /// Measures the time (ms) since the previous frame, clamped to `EngineInfo::max_delta`. The
+    /// first frame is assumed to take the configured delay. Only whole ms are passed on, and the
+    /// fraction left over counts towards the next frame, so no time is lost to rounding down at
+    /// high frame rates (at 144 fps that would be an eighth of it).
+    fn delta(&mut self) -> u32 {
+        let now = Instant::now();
+        let elapsed = match self.last_frame {
+            Some(last_frame) => now.duration_since(last_frame),
+            None => Duration::from_millis(self.info.delay as u64),
+        };
+        self.context.frame_time = elapsed;
+        let delta = elapsed.as_millis().min(u32::MAX as u128) as u32;
+        self.last_frame = match self.last_frame {
+            Some(last_frame) if delta < self.info.max_delta => Some(last_frame + Duration::from_millis(delta as u64)),
+            _ => Some(now), // the time over the limit is dropped, not caught up on later
+        };
+        delta.min(self.info.max_delta)
+    }
+
+    /// Queues an event to be handled at the start of the next frame, before the ones from SDL or
+    /// `step`, e.g. to drive a headless engine (see `new_headless`) with synthetic input. Keys
+    /// pressed this way aren't held for `Input::held`; pass them to `step` for that.
+    pub fn push_event(&mut self, event: Event) {
+        self.pushed_events.push_back(event);
+    }
+
+    /// Handles the events queued with `push_event` now instead of at the start of the next frame.
+    /// Returns the props to exit with if one of them ended the game.
+    pub(crate) fn handle_pushed_events(&mut self) -> Option<Dict> {
+        while let Some(event) = self.pushed_events.pop_front() {
+            if let Some(exit_props) = self.handle_event(&event) {
+                return Some(exit_props);
+            }
+        }
+        None
+    }
+
+    /// Pushes a scene on top of the stack, e.g. the first scene of the game before calling `run`
+    pub fn push_scene(&mut self, scene: Scene) {
+        self.stack.push(scene);
+    }
+
+    /// Reads back what is currently on the canvas, e.g. for comparing against golden images
+    pub fn capture(&self) -> Result<Image, String> {
+        let canvas = match &self.video {
+            Some(video) => &video.window.canvas,
+            None => return Err(format!("{}", EngineError::Headless)),
+        };
+        let (width, height) = canvas.output_size()?;
+        let pixels = canvas.read_pixels(None, PixelFormatEnum::RGBA32)?;
+        Ok(Image { width, height, pixels })
+    }
+
+    /// Renders the top scene into a texture and adds it to the backgrounds as `name`, so the next
+    /// scene can draw the previous one with `ClearMode::Texture`, e.g. to pixelate or spiral the
+    /// overworld during a battle transition instead of cutting to black. Scenes ask for this from
+    /// a callback with `EngineContext::snapshot_before_transition`. Does nothing on a headless
+    /// engine, where the scene drawing it would draw nothing anyway.
+    pub fn snapshot(&mut self, name: &str) -> Result<(), String> {
+        let video = match &mut self.video {
+            Some(video) => video,
+            None => return Ok(()),
+        };
+        let (width, height) = self.info.logical_size();
+        let mut texture = video.window.texture_creator
+            .create_texture_target(None, width, height)
+            .or_else(|err| Err(format!("{}", err)))?;
+        self.info.filter.apply(&mut texture);
+        if let Some(scene) = self.stack.peek() {
+            let (spritesheet, backgrounds) = (&mut video.window.spritesheet, &self.backgrounds);
+            let (palette, missing_sprites) = (self.context.palette, &mut self.missing_sprites);
+            video.window.canvas
+                .with_texture_canvas(&mut texture, |canvas| {
+                    let mut renderer = SdlRenderer::new(canvas, spritesheet, backgrounds);
+                    scene.draw(&mut renderer, palette, missing_sprites, 1.0);
+                })
+                .or_else(|err| Err(format!("{}", err)))?;
+        }
+        self.backgrounds.insert(name, texture, false);
+        Ok(())
+    }
+
+    pub fn stack_len(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn global(&self, key: Key) -> Option<&DictValue> {
+        if let Some(leaks) = &self.leaks {
+            leaks.read(key);
+        }
+        self.globals.get(&key)
+    }
+
+    /// Renders a frame and shows it: clears the canvas to `EngineInfo::clear_color`, draws the top
+    /// scene, draws the overlays (captions, toasts, debug overlay and inspector) on top and
+    /// presents. Every phase runs even with an empty stack, so a stale frame is never left on
+    /// screen.
+    pub(crate) fn render(&mut self) {
+        self.draw_frame();
+        if let Some(video) = &mut self.video {
+            video.window.canvas.present();
+        }
+        let alpha = self.alpha();
+        for window in self.windows.values_mut() {
+            window.render(&self.context, alpha);
+        }
+        self.render_debug_window();
+    }
+
+    /// Everything `render` does but presenting, so the frame can still be read back from the
+    /// canvas, see `capture`
+    pub(crate) fn draw_frame(&mut self) {
+        self.clear_frame();
+        self.render_scene();
+        self.render_overlays();
+    }
+
+    fn clear_frame(&mut self) {
+        if let Some(video) = &mut self.video {
+            video.window.canvas.set_draw_color(self.context.palette.apply(self.info.clear_color));
+            video.window.canvas.clear();
+        }
+    }
+
+    fn render_scene(&mut self) {
+        let background_mod = self.context.palette.background_mod();
+        for background in self.backgrounds.iter_mut() {
+            background.set_color_mod(background_mod.r, background_mod.g, background_mod.b);
+        }
+        let alpha = self.alpha();
+        if let Some(scene) = self.stack.peek_mut() {
+            let size = self.info.logical_size();
+            let mut renderer = renderer(&mut self.video, &self.backgrounds, &self.sprite_index, size);
+            let start = Instant::now();
+            scene.draw(&mut *renderer, self.context.palette, &mut self.missing_sprites, alpha);
+            scene.costs.add_render(start.elapsed());
+        }
+    }
+
+    fn render_overlays(&mut self) {
+        let diagnostics = if self.debug_overlay { self.scene_diagnostics() } else { Vec::new() };
+        let state = self.stack.peek().map(|scene| &scene.state);
+        let size = self.info.logical_size();
+        let mut renderer = renderer(&mut self.video, &self.backgrounds, &self.sprite_index, size);
+        self.context.audio.captions().render(&mut *renderer, &self.context.text);
+        self.context.toasts.render(&mut *renderer, &self.context.text);
+        if self.debug_overlay {
+            diagnostics::render_overlay(&mut *renderer, &diagnostics, &self.context.text);
+        }
+        if let Some(inspector) = &self.inspector {
+            inspector.render(&mut *renderer, &self.context.text, state, &self.globals);
+        }
+    }
+
+    fn render_debug_window(&mut self) {
+        #[cfg(debug_assertions)]
+        {
+            if self.debug_window.is_some() {
+                let diagnostics = self.scene_diagnostics();
+                let state = self.stack.peek().map(|scene| &scene.state);
+                if let Some(window) = &mut self.debug_window {
+                    window.render(&diagnostics, state, &self.globals);
+                }
+            }
+        }
+    }
+
+    /// Advances time by `interval` ms: replays outcomes queued during a finished transition, then
+    /// ticks the input buffer, covered scenes that tick in the background, and then the top
+    /// scene, whose outcome is handled like any other callback's
+    pub(crate) fn tick(&mut self, interval: u32) -> Option<Dict> { // None=continue, Some(props)=exit with props
+        if let Some(exit_props) = self.replay_queued_outcomes() {
+            return Some(exit_props);
+        }
+        self.replace_streak = if self.replaced { self.replace_streak + 1 } else { 0 };
+        self.replaced = false;
+        if self.replace_streak == self.info.replace_loop_frames {
+            self.debug_log(&format!(
+                "warning: a scene has been replaced {} frames in a row, is it returning `Replace` from every tick?",
+                self.replace_streak,
+            ));
+        }
+        for step in self.macros.tick(interval) {
+            if let Some(key) = self.context.input.map.key_for(step.action) {
+                if let Some(exit_props) = self.handle_event(&key_event(key, step.down)) {
+                    return Some(exit_props);
+                }
+            }
+        }
+        self.context.input.buffer.tick(interval);
+        self.context.audio.tick_captions(interval);
+        self.context.toasts.tick(interval);
+        if let Some(inspector) = &mut self.inspector {
+            inspector.tick(interval, self.stack.peek().map(|scene| &scene.state), &self.globals);
+        }
+        let covered = self.stack.len().saturating_sub(1);
+        for (depth, scene) in self.stack.iter_mut().enumerate().take(covered) {
+            if scene.tick_when_covered {
+                scene.remember_positions();
+                scene.bars.tick(interval);
+                if let Some(tilemap) = &mut scene.tilemap {
+                    tilemap.advance(interval);
+                }
+                let start = Instant::now();
+                (scene.on_tick)(scene, &mut self.context, Duration::from_millis(interval as u64));
+                scene.costs.add_tick(start.elapsed());
+                if let Some(watchdog) = &mut self.watchdog {
+                    watchdog.record(format!("tick #{} {}", depth, scene.tag().unwrap_or("scene")), start.elapsed());
+                }
+            }
+        }
+        let context = &mut self.context;
+        self.windows.retain(|_, window| window.tick(context, interval));
+        let depth = self.stack.len().saturating_sub(1);
+        if let Some(scene) = self.stack.peek_mut() {
+            scene.remember_positions();
+            scene.bars.tick(interval);
+            if let Some(tilemap) = &mut scene.tilemap {
+                tilemap.advance(interval);
+            }
+            let start = Instant::now();
+            let outcome = (scene.on_tick)(scene, &mut self.context, Duration::from_millis(interval as u64));
+            scene.costs.add_tick(start.elapsed());
+            if let Some(watchdog) = &mut self.watchdog {
+                watchdog.record(format!("tick #{} {}", depth, scene.tag().unwrap_or("scene")), start.elapsed());
+            }
+            if let Some(exit_props) = self.handle_scene_fn_outcome(outcome) {
+                return Some(exit_props);
+            }
+            self.dispatch_game_events()
+        } else {
+            self.fail(EngineError::EmptyStack)
+        }
+    }
+
+    /// Delivers the game events sent this tick, see `EngineContext::emit`. Events sent while
+    /// handling them wait for the next tick, so two scenes can't bounce events back and forth
+    /// forever. Like a covered scene's `on_tick`, a covered scene's callback can't change the stack.
+    fn dispatch_game_events(&mut self) -> Option<Dict> { // None=continue, Some(props)=exit with props
+        for _ in 0..self.context.events.len() {
+            let (name, props) = self.context.events.pop_front()?;
+            let top = self.stack.len().saturating_sub(1);
+            let listener = self
+                .stack
+                .iter_mut()
+                .enumerate()
+                .rev()
+                .find_map(|(depth, scene)| Some((depth, *scene.game_event_callbacks.get(&name)?, scene)));
+            let (depth, callback, scene) = match listener {
+                Some(listener) => listener,
+                None => {
+                    self.debug_log(&format!("warning: no scene handles the game event {}", name));
+                    continue;
+                },
+            };
+            let outcome = callback(scene, &mut self.context, name, &props);
+            if depth == top {
+                if let Some(exit_props) = self.handle_scene_fn_outcome(outcome) {
+                    return Some(exit_props);
+                }
+            }
+        }
+        None
+    }
+
+    /// What each scene on the stack holds and costs, bottom of the stack first
+    pub fn scene_diagnostics(&self) -> Vec<SceneDiagnostics> {
+        self.stack
+            .iter()
+            .enumerate()
+            .map(|(depth, scene)| {
+                let (state_entries, state_bytes) = dict_footprint(&scene.state);
+                SceneDiagnostics {
+                    depth,
+                    sprites: scene.sprites.len(),
+                    state_entries,
+                    state_bytes,
+                    background: match &scene.clear {
+                        ClearMode::Texture { name, .. } => Some(name.clone()),
+                        _ => None,
+                    },
+                    age: scene.created.elapsed(),
+                    costs: scene.costs,
+                }
+            })
+            .collect()
+    }
+
+    /// Debug mode that tracks globals and props keys that are written but never read, and
+    /// globals that are requested but missing, to catch mistyped key names. The globals the
+    /// engine already has count as written from when it's turned on. See `KeyLeaks`.
+    pub fn set_leak_detection(&mut self, enabled: bool) {
+        self.leaks = if enabled {
+            let mut leaks = KeyLeaks::new();
+            for key in self.globals.keys() {
+                leaks.written(*key, KeyOrigin::Global);
+            }
+            Some(leaks)
+        } else {
+            None
+        };
+    }
+
+    /// What leak detection found so far, `None` if it's off. `run` prints it when the game quits.
+    pub fn leak_report(&self) -> Option<String> {
+        self.leaks.as_ref().map(KeyLeaks::report)
+    }
+
+    /// Opens another window with its own stack of scenes, e.g. a map editor next to the game, and
+    /// returns its id for `push_window_scene`. The title, size, scale, vsync and clear color of
+    /// `info` are used; the window runs at the game's timing. Events go to the window they
+    /// happened in, so key presses go to whichever window has focus. The window closes when the
+    /// player closes it or its last scene quits, which doesn't quit the game.
+    pub fn create_window(&mut self, info: EngineInfo) -> Result<WindowId, EngineError> {
+        let subsystem = &self.video.as_ref().ok_or(EngineError::Headless)?.subsystem;
+        let window = subsystem.window(info.title, info.size.0, info.size.1);
+        let mut video = WindowVideo::open(window, info.vsync, &self.spritesheet_path, self.sprite_index.clone())?;
+        apply_canvas_scaling(&mut video.canvas, &info)?;
+        let window = GameWindow::new(video, info.clear_color);
+        let id = window.id();
+        self.windows.insert(id, window);
+        Ok(id)
+    }
+
+    /// Pushes a scene on a window's stack, like `push_scene` for the game's. Returns false if
+    /// the window is closed.
+    pub fn push_window_scene(&mut self, window: WindowId, scene: Scene) -> bool {
+        match self.windows.get_mut(&window) {
+            Some(window) => {
+                window.push_scene(scene);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn close_window(&mut self, window: WindowId) {
+        self.windows.remove(&window);
+    }
+
+    /// Ids of the windows opened with `create_window` that are still open
+    pub fn windows(&self) -> Vec<WindowId> {
+        self.windows.keys().copied().collect()
+    }
+
+    /// Opens a second window with the scene diagnostics, the top scene's state, the globals and a
+    /// log console, so debug tools don't cover the game. Debug builds only.
+    #[cfg(debug_assertions)]
+    pub fn open_debug_window(&mut self) -> Result<(), String> {
+        if self.debug_window.is_none() {
+            let video = self.video.as_ref().ok_or_else(|| format!("{}", EngineError::Headless))?;
+            let index = self.sprite_index.clone();
+            self.debug_window = Some(DebugWindow::open(&video.subsystem, &self.spritesheet_path, index)?);
+        }
+        Ok(())
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn close_debug_window(&mut self) {
+        self.debug_window = None;
+    }
+
+    /// Prints a line to stderr and, if it's open, the debug window's log console. The last
+    /// `CRASH_LOG_LINES` lines are kept for crash reports.
+    pub fn debug_log(&mut self, line: &str) {
+        eprintln!("{}", line);
+        if self.log.len() >= CRASH_LOG_LINES {
+            self.log.pop_front();
+        }
+        self.log.push_back(line.to_string());
+        #[cfg(debug_assertions)]
+        {
+            if let Some(window) = &mut self.debug_window {
+                window.log(line);
+            }
+        }
+    }
+
+    /// Writes a crash report (see `CrashReport`) to `CRASH_DIR` and replaces every scene with the
+    /// crash scene, which shows where the report went. Is called when a frame panics while the
+    /// engine runs, and can be called by the game for errors it can't recover from. Returns the
+    /// report's path, if it could be written.
+    pub fn report_crash(&mut self, reason: &str) -> Option<String> {
+        self.crashed = true;
+        let scenes = self.stack.iter().map(|scene| scene.tag().unwrap_or("<untagged>").to_string()).collect();
+        let report = CrashReport::new(reason, scenes, &self.globals, self.log.iter().cloned().collect());
+        let path = match report.write(CRASH_DIR) {
+            Ok(path) => Some(path),
+            Err(err) => {
+                self.debug_log(&format!("error: couldn't write the crash report: {}", err));
+                None
+            },
+        };
+        let mut props = HashMap::new();
+        props.insert(Key::new("reason"), DictValue::String(reason.to_string()));
+        if let Some(path) = &path {
+            props.insert(Key::new("report"), DictValue::String(path.clone()));
+        }
+        self.queued_outcomes.clear();
+        self.context.transition_lock = false;
+        self.stack = Stack::new();
+        self.stack.push(crash::create_crash_scene(props));
+        path
+    }
+
+    /// Logs a breakdown of every frame that takes longer than `budget` (see `FrameWatchdog`) to
+    /// `debug_log`. `None` turns the watchdog off.
+    pub fn set_frame_budget(&mut self, budget: Option<Duration>) {
+        self.watchdog = budget.map(FrameWatchdog::new);
+    }
+
+    /// Shows the diagnostics of every scene on the stack over the game, see `scene_diagnostics`
+    pub fn set_debug_overlay(&mut self, debug_overlay: bool) {
+        self.debug_overlay = debug_overlay;
+    }
+
+    /// Opens or closes the inspector over the game, see `Inspector`. While it's open, the
+    /// player's actions go to it instead of the scenes.
+    pub fn set_inspector(&mut self, open: bool) {
+        self.inspector = if open { Some(Inspector::new()) } else { None };
+    }
+
+    pub fn texture_stats(&self) -> TextureStats {
+        self.backgrounds.stats()
+    }
+
+    /// Report of every sprite name that has been drawn this session but isn't on the spritesheet,
+    /// sorted by name
+    pub fn missing_sprites_report(&self) -> String {
+        let mut names: Vec<&str> = self.missing_sprites.iter().map(|sprite| sprite.name()).collect();
+        names.sort();
+        let mut report = format!("{} missing sprite(s)", names.len());
+        for name in names {
+            report.push_str("\n  ");
+            report.push_str(name);
+        }
+        report
+    }
+
+    /// Starts recording the player's input as a macro, see `InputMacro`. Dev tool, meant to be
+    /// bound to hotkeys or debug commands along with `finish_macro` and `play_macro`.
+    pub fn record_macro(&mut self, name: &str) {
+        self.macros.record(name);
+    }
+
+    /// Stops recording or playing a macro. A macro being recorded is saved to `dir`.
+    pub fn finish_macro(&mut self, dir: &str) -> Result<(), String> {
+        match self.macros.stop() {
+            Some(input_macro) => input_macro.save(dir),
+            None => Ok(()),
+        }
+    }
+
+    /// Replays a macro saved to `dir`, pressing its actions as if the player did
+    pub fn play_macro(&mut self, dir: &str, name: &str) -> Result<(), String> {
+        self.macros.play(InputMacro::load(dir, name)?);
+        Ok(())
+    }
+
+    /// Registers a hotkey that is handled by the engine before the event reaches any scene, e.g.
+    /// for screenshots or toggling fullscreen. Scenes can opt out with `Scene::set_text_entry`.
+    pub fn add_hotkey(&mut self, key: Keycode, hotkey: HotkeyFn) {
+        self.hotkeys.insert(key, hotkey);
+    }
+
+    /// Registers a handler for every event of a type, whichever scene is on top, e.g. for volume
+    /// keys, a quick-save key or pausing when a controller is unplugged. Handlers run in the order
+    /// they were added, before hotkeys and scene callbacks, and one that returns true consumes
+    /// the event so nothing after it sees it, not even the input buffer. Unlike hotkeys, handlers
+    /// get keyboard events while the top scene takes text input too, so a handler that shouldn't
+    /// eat typed keys checks `is_text_entry` first.
+    pub fn add_global_event_handler(&mut self, event_type: EventType, handler: GlobalEventFn) {
+        self.global_event_handlers.entry(event_type).or_default().push(handler);
+    }
+
+    /// Removes every global event handler of a type
+    pub fn clear_global_event_handlers(&mut self, event_type: EventType) {
+        self.global_event_handlers.remove(&event_type);
+    }
+
+    /// Runs the global event handlers of an event, see `add_global_event_handler`. Returns true
+    /// if one consumed it.
+    fn run_global_event_handlers(&mut self, event: &Event) -> bool {
+        let handlers = match event_type(event).and_then(|event_type| self.global_event_handlers.get(&event_type)) {
+            Some(handlers) => handlers.clone(), // handlers can add more while they run
+            None => return false,
+        };
+        handlers.into_iter().any(|handler| handler(self, event))
+    }
+
+    /// Whether the top scene is taking text input, see `Scene::set_text_entry`
+    pub fn is_text_entry(&self) -> bool {
+        self.stack.peek().is_some_and(|scene| scene.text_entry)
+    }
+
+    /// Opens controllers as they're plugged in, and keeps `Input::device` on whatever the player
+    /// last used, so button prompts switch as soon as they pick up a controller or the keyboard
+    fn track_device(&mut self, event: &Event) {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                if let Some(controller) = self.controller_subsystem.as_ref().and_then(|sub| sub.open(which).ok()) {
+                    let device = InputDevice::from_controller_name(&controller.name());
+                    self.controllers.insert(controller.instance_id(), (controller, device));
+                }
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.controllers.remove(&which);
+            },
+            Event::ControllerButtonDown { which, .. } => {
+                self.context.input.device = self.controller_device(which);
+            },
+            // only a real push of the stick, not one resting slightly off center
+            Event::ControllerAxisMotion { which, value, .. } if value.unsigned_abs() > i16::MAX as u16 / 2 => {
+                self.context.input.device = self.controller_device(which);
+            },
+            Event::KeyDown { .. } => {
+                self.context.input.device = InputDevice::Keyboard;
+            },
+            _ => {},
+        }
+    }
+
+    /// The key a controller button event is handled as, and whether it's pressed: the key of the
+    /// action the button is bound to, the way macros press actions, so the input buffer, macros,
+    /// menu sounds and scenes reading key events all work with a controller. `None` for other
+    /// events and for buttons of actions without a key, which reach the scene as they are.
+    fn button_key(&self, event: &Event) -> Option<(Keycode, bool)> {
+        let (button, down) = match *event {
+            Event::ControllerButtonDown { button, .. } => (button, true),
+            Event::ControllerButtonUp { button, .. } => (button, false),
+            _ => return None,
+        };
+        let map = &self.context.input.map;
+        let key = map.get_button(button).and_then(|action| map.key_for(action))?;
+        Some((key, down))
+    }
+
+    fn controller_device(&self, which: u32) -> InputDevice {
+        self.controllers.get(&which).map_or(InputDevice::Xbox, |(_, device)| *device)
+    }
+
+    /// Handles a single event: runs the global event handlers, updates input state, runs hotkeys,
+    /// and then passes the event to the top scene's callback for its type. Controller buttons
+    /// bound to an action are handled as the action's key, see `button_key`.
+    pub(crate) fn handle_event(&mut self, event: &Event) -> Option<Dict> { // None=continue, Some(props)=exit with props
+        #[cfg(debug_assertions)]
+        {
+            let debug_window = self.debug_window.as_ref().map(DebugWindow::id);
+            if debug_window.is_some() && event.get_window_id() == debug_window {
+                if let Event::Window { win_event: sdl2::event::WindowEvent::Close, .. } = event {
+                    self.debug_window = None;
+                }
+                return None; // the game's scenes never see the debug window's events
+            }
+        }
+        self.track_device(event);
+        if let Some(id) = event.get_window_id().filter(|id| self.windows.contains_key(id)) {
+            let context = &mut self.context;
+            let open = self.windows.get_mut(&id).is_some_and(|window| window.handle_event(context, event));
+            if !open {
+                self.windows.remove(&id);
+            }
+            return None; // the game's scenes never see other windows' events
+        }
+        if let Event::Window { win_event, .. } = event {
+            let focused = match win_event {
+                WindowEvent::FocusLost | WindowEvent::Minimized => Some(false),
+                WindowEvent::FocusGained | WindowEvent::Restored => Some(true),
+                _ => None,
+            };
+            if let Some(exit_props) = focused.and_then(|focused| self.set_focused(focused)) {
+                return Some(exit_props);
+            }
+        }
+        if self.run_global_event_handlers(event) {
+            return None;
+        }
+        if let Event::ControllerAxisMotion { axis, value, .. } = *event {
+            self.context.input.stick.set_axis(axis, value);
+        }
+        if let Some((key, down)) = self.button_key(event) {
+            let device = self.context.input.device; // the key event would switch it to the keyboard
+            let exit_props = self.handle_event(&key_event(key, down));
+            self.context.input.device = device;
+            return exit_props;
+        }
+        if let Event::KeyUp { keycode: Some(key), .. } = *event {
+            if let Some(action) = self.context.input.map.get(key) {
+                self.macros.observe(action, false);
             }
-            if let Some(scene) = self.stack.peek_mut() {
-                for event in event_pump.poll_iter() {
-                    if let Some(callback) = scene.event_callbacks.get(&EventType::from(event.to_ll().unwrap().r#type)) {
-                        if let Some(exit_props) = self.handle_scene_fn_outcome(callback(scene, &event)) {
-                            break 'running exit_props;
+        }
+        if let Event::KeyDown { keycode: Some(key), repeat, .. } = *event {
+            if !repeat && self.inspector.is_none() {
+                if let Some(action) = self.context.input.map.get(key) {
+                    self.context.input.buffer.push(action);
+                    self.macros.observe(action, true);
+                    if self.stack.peek().is_some_and(|scene| scene.menu_sounds) {
+                        if let Some(sound) = UiSound::for_action(action) {
+                            self.context.audio.play_ui(sound);
                         }
                     }
                 }
-            } else {
-                break 'running HashMap::new(); // TODO: MAKE ACTUAL ERROR MSG
             }
-            ::std::thread::sleep(Duration::new(0, self.info.delay * 1E6 as u32)); // does this work?
-            if let Some(scene) = self.stack.peek_mut() {
-                (scene.on_tick)(scene, self.info.delay);
-            } else {
-                break 'running HashMap::new(); // TODO: MAKE ACTUAL ERROR MSG
+            let text_entry = self.stack.peek().is_some_and(|scene| scene.text_entry);
+            if let (Some(hotkey), false) = (self.hotkeys.get(&key).copied(), text_entry) {
+                if !repeat {
+                    hotkey(self, key);
+                }
+                return None;
+            }
+            if let (Some(inspector), Some(action)) = (&mut self.inspector, self.context.input.map.get(key)) {
+                let state = self.stack.peek_mut().map(|scene| &mut scene.state);
+                inspector.handle(action, state, &mut self.globals);
+                return None;
             }
         }
+        let scene = self.stack.peek_mut()?;
+        let callback = scene.event_callbacks.get(&event_type(event)?).copied()?;
+        let outcome = callback(scene, &mut self.context, event);
+        self.handle_scene_fn_outcome(outcome)
     }
 
     /// Handles the outcome of a scene callback function (e.g. event callbacks, etc). Is needed
     /// because these functions have the ability to create a child scene, replace itself on the
     /// stack, and delete itself on the stack, which all require `Engine` level privileges.
     fn handle_scene_fn_outcome(&mut self, outcome: SceneFnOutcome) -> Option<Dict> { // None=continue, Some(props)=exit with props
+        if matches!(outcome, SceneFnOutcome::Continue) {
+            return None;
+        }
+        if self.context.transition_lock {
+            self.queued_outcomes.push_back((self.stack_generation, outcome));
+            return None;
+        }
+        self.stack_generation += 1;
+        if let Some(name) = self.context.snapshot.take() {
+            if let Err(err) = self.snapshot(&name) {
+                self.debug_log(&format!("warning: couldn't snapshot the screen: {}", err));
+            }
+        }
         match outcome {
-            SceneFnOutcome::CreateChild { create_scene, props } => {
-                self.handle_props(props);
+            SceneFnOutcome::CreateChild { create_scene, mut props } => {
+                if self.stack.len() >= self.info.max_stack_depth {
+                    self.debug_log(&format!(
+                        "error: not pushing a child scene, the stack is already {} scenes deep",
+                        self.stack.len(),
+                    ));
+                    return None;
+                }
+                self.handle_props(&mut props);
                 self.stack.push(create_scene(props));
             },
-            SceneFnOutcome::Replace {create_scene, props } => {
-                self.handle_props(props);
+            SceneFnOutcome::Replace { create_scene, mut props } => {
+                self.handle_props(&mut props);
                 self.stack.replace(create_scene(props));
+                self.replaced = true;
             },
-            SceneFnOutcome::Quit(props) => {
+            SceneFnOutcome::Quit(mut props) => {
                 self.stack.pop();
-                self.handle_props(props);
+                self.track_props(&props);
+                self.handle_props(&mut props);
                 if let Some(parent) = self.stack.peek_mut() {
-                    return self.handle_scene_fn_outcome((parent.on_child_quit)(parent, props));
+                    let missing: Vec<&str> = parent
+                        .child_quit_props
+                        .iter()
+                        .filter(|key| !props.contains_key(key))
+                        .map(|key| key.name())
+                        .collect();
+                    debug_assert!(missing.is_empty(), "child scene quit without props {:?}", missing);
+                    let outcome = (parent.on_child_quit)(parent, &mut self.context, props);
+                    return self.handle_scene_fn_outcome(outcome);
                 } else {
                     return Some((self.handle_quit)(self, props));
                 }
             },
-            _ => {},
+            SceneFnOutcome::Continue => {},
+        }
+        None
+    }
+
+    /// Applies the outcomes queued during a transition once it's over, see
+    /// `EngineContext::begin_transition`. Outcomes from before the stack last changed are stale
+    /// and dropped.
+    fn replay_queued_outcomes(&mut self) -> Option<Dict> { // None=continue, Some(props)=exit with props
+        while !self.context.transition_lock {
+            let (generation, outcome) = self.queued_outcomes.pop_front()?;
+            if generation == self.stack_generation {
+                if let Some(exit_props) = self.handle_scene_fn_outcome(outcome) {
+                    return Some(exit_props);
+                }
+            }
         }
         None
     }
 
+    /// Records the keys of props a child quit with while leak detection is on. Only parents that
+    /// declare the props they read are tracked (see `Scene::set_child_quit_props`): the keys they
+    /// declared count as read, and the rest as written but never read.
+    fn track_props(&mut self, props: &Dict) {
+        let (leaks, parent) = match (&mut self.leaks, self.stack.peek()) {
+            (Some(leaks), Some(parent)) if !parent.child_quit_props.is_empty() => (leaks, parent),
+            _ => return,
+        };
+        let (requests, atlas) = (Key::new("_REQUESTS"), Key::new("_ATLAS"));
+        for key in props.keys().filter(|key| **key != requests && **key != atlas) {
+            leaks.written(*key, KeyOrigin::Props);
+        }
+        for key in &parent.child_quit_props {
+            leaks.read(*key);
+        }
+    }
+
     /// Processes props passed from a scene to another scene via a scene callback function. For
     /// global objects (stored in `engine.globals` and are useful for storing things such as a
     /// player object), the caller scene can request, via the props["_REQUESTS"] array, for some
     /// global to be passed to the callee. The globals retrieved will be stored in props["globals"].
-    fn handle_props(&self, props: &mut Dict) {
-        let globals = HashMap::new();
-        if let Some(DictValue::Array(requests)) = props.get("_REQUESTS") {
-            for raw_request in requests {
-                if let DictValue::String(request) = raw_request {
-                    if let Some(object) = self.globals.get(request) {
-                        globals.insert(request, object);
+    /// Objects are shared with the callee rather than copied. Props without requests are left
+    /// untouched, so most scene switches don't allocate here. Dev tools can also set
+    /// props["_ATLAS"] to get the spritesheet's index in props["atlas"], see `SpriteSheet::to_dict`.
+    fn handle_props(&mut self, props: &mut Dict) {
+        if props.remove(&Key::new("_ATLAS")).is_some() {
+            props.insert(Key::new("atlas"), DictValue::Dict(index_to_dict(&self.sprite_index)));
+        }
+        let requests = match props.remove(&Key::new("_REQUESTS")) {
+            Some(DictValue::Array(requests)) => requests,
+            _ => return,
+        };
+        let mut globals = HashMap::with_capacity(requests.len());
+        for request in requests {
+            if let DictValue::String(request) = request {
+                let key = Key::new(&request);
+                if let Some(object) = self.globals.get(&key) {
+                    globals.insert(key, object.clone());
+                    if let Some(leaks) = &self.leaks {
+                        leaks.read(key);
                     }
+                } else if let Some(leaks) = &mut self.leaks {
+                    leaks.requested_missing(key);
                 }
             }
-            props.delete("_REQUESTS");
         }
-        props.insert("globals", globals);
+        props.insert(Key::new("globals"), DictValue::Dict(globals));
+    }
+}
+
+/// The `EventType` scene callbacks are registered under for an event. Matches on the event
+/// directly instead of converting it to a raw SDL event. `None` for events SDL doesn't know.
+fn event_type(event: &Event) -> Option<EventType> {
+    Some(match event {
+        Event::Quit { .. } => EventType::Quit,
+        Event::AppTerminating { .. } => EventType::AppTerminating,
+        Event::AppLowMemory { .. } => EventType::AppLowMemory,
+        Event::AppWillEnterBackground { .. } => EventType::AppWillEnterBackground,
+        Event::AppDidEnterBackground { .. } => EventType::AppDidEnterBackground,
+        Event::AppWillEnterForeground { .. } => EventType::AppWillEnterForeground,
+        Event::AppDidEnterForeground { .. } => EventType::AppDidEnterForeground,
+        Event::Display { .. } => EventType::Display,
+        Event::Window { .. } => EventType::Window,
+        Event::KeyDown { .. } => EventType::KeyDown,
+        Event::KeyUp { .. } => EventType::KeyUp,
+        Event::TextEditing { .. } => EventType::TextEditing,
+        Event::TextInput { .. } => EventType::TextInput,
+        Event::MouseMotion { .. } => EventType::MouseMotion,
+        Event::MouseButtonDown { .. } => EventType::MouseButtonDown,
+        Event::MouseButtonUp { .. } => EventType::MouseButtonUp,
+        Event::MouseWheel { .. } => EventType::MouseWheel,
+        Event::JoyAxisMotion { .. } => EventType::JoyAxisMotion,
+        Event::JoyBallMotion { .. } => EventType::JoyBallMotion,
+        Event::JoyHatMotion { .. } => EventType::JoyHatMotion,
+        Event::JoyButtonDown { .. } => EventType::JoyButtonDown,
+        Event::JoyButtonUp { .. } => EventType::JoyButtonUp,
+        Event::JoyDeviceAdded { .. } => EventType::JoyDeviceAdded,
+        Event::JoyDeviceRemoved { .. } => EventType::JoyDeviceRemoved,
+        Event::ControllerAxisMotion { .. } => EventType::ControllerAxisMotion,
+        Event::ControllerButtonDown { .. } => EventType::ControllerButtonDown,
+        Event::ControllerButtonUp { .. } => EventType::ControllerButtonUp,
+        Event::ControllerDeviceAdded { .. } => EventType::ControllerDeviceAdded,
+        Event::ControllerDeviceRemoved { .. } => EventType::ControllerDeviceRemoved,
+        Event::ControllerDeviceRemapped { .. } => EventType::ControllerDeviceRemapped,
+        Event::FingerDown { .. } => EventType::FingerDown,
+        Event::FingerUp { .. } => EventType::FingerUp,
+        Event::FingerMotion { .. } => EventType::FingerMotion,
+        Event::DollarGesture { .. } => EventType::DollarGesture,
+        Event::DollarRecord { .. } => EventType::DollarRecord,
+        Event::MultiGesture { .. } => EventType::MultiGesture,
+        Event::ClipboardUpdate { .. } => EventType::ClipboardUpdate,
+        Event::DropFile { .. } => EventType::DropFile,
+        Event::DropText { .. } => EventType::DropText,
+        Event::DropBegin { .. } => EventType::DropBegin,
+        Event::DropComplete { .. } => EventType::DropComplete,
+        Event::AudioDeviceAdded { .. } => EventType::AudioDeviceAdded,
+        Event::AudioDeviceRemoved { .. } => EventType::AudioDeviceRemoved,
+        Event::RenderTargetsReset { .. } => EventType::RenderTargetsReset,
+        Event::RenderDeviceReset { .. } => EventType::RenderDeviceReset,
+        Event::User { .. } => EventType::User,
+        Event::Unknown { .. } => return None,
+    })
+}
+
+/// Runs `f` with the SDL hint `name` set to `value`, then puts back what it was before. SDL's
+/// hints are global to the process and read when a subsystem starts, so this keeps a hint meant
+/// for one engine from leaking into the next. An empty hint counts as unset to SDL.
+pub(crate) fn with_hint<T, F: FnOnce() -> T>(name: &str, value: &str, f: F) -> T {
+    let previous = hint::get(name).unwrap_or_default();
+    hint::set(name, value);
+    let result = f();
+    hint::set(name, &previous);
+    result
+}
+
+/// Starts SDL's audio and opens the audio device. If either fails, e.g. on a machine without a
+/// sound card, the game runs silently instead of not starting.
+fn open_audio(sdl_context: &Sdl, ui_sounds: UiSoundMap) -> (Option<AudioSubsystem>, Audio) {
+    let opened = sdl_context.audio().and_then(|subsystem| Ok((subsystem, Audio::new(ui_sounds.clone())?)));
+    match opened {
+        Ok((subsystem, audio)) => (Some(subsystem), audio),
+        Err(err) => {
+            eprintln!("warning: can't open the audio device, running without sound: {}", err);
+            (None, Audio::silent(ui_sounds))
+        },
+    }
+}
+
+/// Draws with the window's canvas, or with a `NullRenderer` on a headless engine
+fn renderer<'r, 'a>(
+    video: &'r mut Option<Video<'a>>,
+    backgrounds: &'r TextureCache<'a>,
+    sprite_index: &'r HashMap<SpriteId, Rect>,
+    size: (u32, u32),
+) -> Box<dyn Renderer + 'r> {
+    match video {
+        Some(video) => Box::new(
+            SdlRenderer::new(&mut video.window.canvas, &mut video.window.spritesheet, backgrounds)
+                .with_tilemaps(&mut video.tilemaps),
+        ),
+        None => Box::new(NullRenderer::new(sprite_index, size)),
+    }
+}
+
+/// Scales the canvas according to `info`. With integer scaling the canvas is given a logical size
+/// instead, which makes SDL letterbox the game inside the window.
+fn apply_canvas_scaling(canvas: &mut WindowCanvas, info: &EngineInfo) -> Result<(), String> {
+    if info.integer_scale {
+        let (width, height) = info.logical_size();
+        canvas
+            .set_logical_size(width, height)
+            .or_else(|err| Err(format!("{}", err)))?;
+        canvas.set_integer_scale(true)
+    } else {
+        canvas
+            .set_logical_size(0, 0) // turns off logical sizing
+            .or_else(|err| Err(format!("{}", err)))?;
+        canvas.set_integer_scale(false)?;
+        canvas.set_scale(info.scale.0, info.scale.1)
+    }
+}
+
+#[cfg(target_os = "emscripten")]
+impl Engine<'static> {
+    /// Hands the engine to the browser, which calls `frame` once per animation frame. Never
+    /// returns: when the game quits the main loop is cancelled and the quit props are dropped, so
+    /// web games should save from their `HandleQuitFn`. Errors are only logged, see `debug_log`.
+    pub fn run(mut self) -> Result<Dict, EngineError> {
+        let mut event_pump = self.sdl_context.event_pump()?;
+        crate::web::main_loop(move || self.frame(&mut event_pump).is_none())
     }
 }
 
+/// What came of a frame run by `Engine::step`
+#[derive(Clone)]
+pub enum StepResult {
+    Running,
+    Quit(Dict), // the last scene quit with these props, after `HandleQuitFn` had its say
+    Failed(EngineError), // the game couldn't go on, see `Engine::run`
+}
+
+/// Engine-owned services that scene callbacks can use directly without going through a
+/// `SceneFnOutcome`, such as playing UI sounds. Is passed to every scene callback.
+pub struct EngineContext {
+    pub audio: Audio,
+    pub palette: Palette, // can be changed from an options menu, applies from the next frame
+    pub text: TextSettings,
+    pub input: Input,
+    pub toasts: Toasts, // shown above every scene, e.g. `ctx.toasts.show("Autosaved")`
+    snapshot: Option<String>, // background name to snapshot the screen to before the next scene change
+    preloader: Preloader,
+    transition_lock: bool,
+    events: VecDeque<(Key, Dict)>, // game events waiting for the end of the tick, see `emit`
+    frame_time: Duration, // see `frame_time`
+}
+
+impl EngineContext {
+    /// Locks scene switching while a transition (e.g. a fade or a swirl) plays. Outcomes that
+    /// callbacks return meanwhile are queued instead of applied, and replayed once the lock is
+    /// lifted, skipping any from a scene that is no longer on top by then. Stops the player from
+    /// pushing two scenes by mashing A during a door transition.
+    pub fn begin_transition(&mut self) {
+        self.transition_lock = true;
+    }
+
+    /// Lifts the lock of `begin_transition`. The outcome the callback returns is applied right
+    /// away, before the queued ones.
+    pub fn end_transition(&mut self) {
+        self.transition_lock = false;
+    }
+
+    pub fn in_transition(&self) -> bool {
+        self.transition_lock
+    }
+
+    /// Snapshots the screen into the background `name` right before the scene change the
+    /// callback returns, see `Engine::snapshot`
+    pub fn snapshot_before_transition(&mut self, name: &str) {
+        self.snapshot = Some(name.to_string());
+    }
+
+    /// Starts loading the assets of a scene the game is likely to switch to soon (e.g. the gym
+    /// the player is standing outside of) in the background, so the switch itself doesn't have
+    /// to wait on the disk. Files are read on another thread and added to the engine over the
+    /// next frames. Assets that are still loaded are skipped.
+    pub fn preload_scene(&mut self, assets: &SceneAssets) {
+        self.preloader.request(assets);
+    }
+
+    /// The real time the current frame stands for, measured with `Instant` and not clamped, e.g.
+    /// for an FPS counter or for effects that should follow the wall clock. Scenes that move
+    /// things should advance by their tick's `interval` instead, which is game time and stops
+    /// while the game is paused.
+    pub fn frame_time(&self) -> Duration {
+        self.frame_time
+    }
+
+    /// Sends a game event, e.g. `diploma` when the pokedex is complete. At the end of the tick it
+    /// goes to the highest scene on the stack with a callback for it (see `Scene::on_game_event`),
+    /// so whichever scene is in charge of that part of the game handles it, wherever it was sent
+    /// from.
+    pub fn emit(&mut self, name: &str, props: Dict) {
+        self.events.push_back((Key::new(name), props));
+    }
+}
+
+/// How long (ms) a pressed action stays in the input buffer before it is dropped
+const INPUT_BUFFER_WINDOW: u32 = 100;
+
 /// Handles what happens if the game quits (the only item on the scene stack quits). Takes in props
 /// passed by the last item on the stack quitting, and returns the engine's return value.
 pub type HandleQuitFn = fn(engine: &mut Engine, props: Dict) -> Dict;
 
+/// Handles an engine-level hotkey, regardless of which scene is on top of the stack
+pub type HotkeyFn = fn(engine: &mut Engine, key: Keycode);
+
+/// Handles an event before any scene does, see `Engine::add_global_event_handler`. Returns true
+/// to consume it.
+pub type GlobalEventFn = fn(engine: &mut Engine, event: &Event) -> bool;
+
 /// A texture with all the sprites in the game. Has an index that holds the src_rects for all
 /// sprites.
 pub struct SpriteSheet<'a> {
     pub texture: Texture<'a>,
-    index: HashMap<String, Rect>,
+    index: HashMap<SpriteId, Rect>,
 }
 
 impl<'a> SpriteSheet<'a> {
-    pub fn new(texture: Texture<'a>, index: HashMap<String, Rect>) -> Self {
+    pub fn new(texture: Texture<'a>, index: HashMap<SpriteId, Rect>) -> Self {
         Self { texture, index }
     }
 
-    /// Retrieves src_rect for given sprite name
-    pub fn get(&self, key: &str) -> Option<&Rect> {
-        self.index.get(key)
+    /// Retrieves src_rect for given sprite
+    pub fn get(&self, sprite: SpriteId) -> Option<&Rect> {
+        self.index.get(&sprite)
     }
+
+    /// The index as a `Dict` of sprite name to its src_rect as `[x, y, width, height]`
+    pub fn to_dict(&self) -> Dict {
+        index_to_dict(&self.index)
+    }
+}
+
+/// A spritesheet's index as a `Dict`, see `SpriteSheet::to_dict`
+fn index_to_dict(index: &HashMap<SpriteId, Rect>) -> Dict {
+    index
+        .iter()
+        .map(|(sprite, rect)| {
+            let rect = vec![
+                DictValue::I32(rect.x()),
+                DictValue::I32(rect.y()),
+                DictValue::U32(rect.width()),
+                DictValue::U32(rect.height()),
+            ];
+            (*sprite, DictValue::Array(rect))
+        })
+        .collect()
 }
 
 /// In different parts of a game, there will be different sprites, backgrounds, and ways the game
@@ -196,67 +1887,358 @@ impl<'a> SpriteSheet<'a> {
 /// input events and time passing, and have completely different backgrounds and sprites, and so
 /// therefore need different scenes.
 pub struct Scene {
-    background: String,
-    bg_rect: (Option<Rect>, Option<Rect>), // (src, dst)
+    clear: ClearMode,
+    bars: CinematicBars,
+    tick_when_covered: bool, // keep calling on_tick while another scene is on top of this one
+    interpolate: bool, // draw sprites between where they were on the last two ticks
+    previous: Vec<Rect>, // where the sprites were before the last tick, while interpolating
+    text_entry: bool, // while true, hotkeys are passed to the scene like any other key
+    menu_sounds: bool, // play the UI sound of each action the player presses, see `set_menu_sounds`
     state: Dict,
+    tilemap: Option<Tilemap>, // drawn under the sprites, see `set_tilemap`
     sprites: Vec<Sprite>,
     event_callbacks: HashMap<EventType, EventCallbackFn>,
+    game_event_callbacks: HashMap<Key, GameEventFn>,
     on_tick: SceneOnTickFn,
     on_child_quit: SceneOnChildQuitFn,
+    on_focus_changed: Option<SceneOnFocusChangedFn>,
+    created: Instant,
+    costs: SceneCosts,
+    child_quit_props: Vec<Key>, // props `on_child_quit` needs, checked in debug builds
+    tag: Option<String>,
 }
 
 impl Scene {
     pub fn new(
-        background: String,
-        bg_rect: (Option<Rect>, Option<Rect>),
+        clear: ClearMode,
         state: Dict,
         sprites: Vec<Sprite>,
         event_callbacks: HashMap<EventType, EventCallbackFn>,
         on_tick: SceneOnTickFn,
         on_child_quit: SceneOnChildQuitFn,
     ) -> Self {
-        Self { background, bg_rect, state, sprites, event_callbacks, on_tick, on_child_quit }
+        Self {
+            clear,
+            bars: CinematicBars::new(CINEMATIC_BAR_HEIGHT, CINEMATIC_BAR_DURATION),
+            tick_when_covered: false,
+            interpolate: false,
+            previous: Vec::new(),
+            text_entry: false,
+            menu_sounds: false,
+            state,
+            tilemap: None,
+            sprites,
+            event_callbacks,
+            game_event_callbacks: HashMap::new(),
+            on_tick,
+            on_child_quit,
+            on_focus_changed: None,
+            created: Instant::now(),
+            costs: SceneCosts::default(),
+            child_quit_props: Vec::new(),
+            tag: None,
+        }
+    }
+
+    /// Names the scene in crash reports, e.g. "overworld" or "battle"
+    pub fn set_tag(&mut self, tag: &str) {
+        self.tag = Some(tag.to_string());
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    /// Declares the props this scene's `on_child_quit` needs from its children. In debug builds,
+    /// a child that quits without one of them is a panic naming the missing keys, instead of the
+    /// parent silently reading nothing. With leak detection on, props a child quits with that
+    /// aren't declared are reported as never read, see `KeyLeaks`.
+    pub fn set_child_quit_props(&mut self, keys: &[&str]) {
+        self.child_quit_props = keys.iter().map(|key| Key::new(key)).collect();
+    }
+
+    /// Handles the game event `name` sent with `EngineContext::emit` while this is the highest
+    /// scene on the stack with a callback for it
+    pub fn on_game_event(&mut self, name: &str, callback: GameEventFn) {
+        self.game_event_callbacks.insert(Key::new(name), callback);
+    }
+
+    /// Is called when the window gains or loses focus (or is minimized or restored), e.g. to
+    /// pause music or open the pause menu. Every scene on the stack is told, not just the top one.
+    pub fn set_on_focus_changed(&mut self, on_focus_changed: SceneOnFocusChangedFn) {
+        self.on_focus_changed = Some(on_focus_changed);
+    }
+
+    /// Lets the scene keep ticking (e.g. animating water or wandering NPCs) while a lighter scene
+    /// such as a menu is on top of it. Covered scenes never receive events, and the outcomes
+    /// their `on_tick` returns are ignored, since only the top scene may change the stack.
+    pub fn set_tick_when_covered(&mut self, tick_when_covered: bool) {
+        self.tick_when_covered = tick_when_covered;
+    }
+
+    /// Draws the scene's sprites part of the way from where they were on the previous tick to
+    /// where they are now, by how much of the next tick has passed, so movement looks smooth at
+    /// frame rates above the tick rate (see `EngineInfo::with_timestep`). A sprite is matched to
+    /// the one at the same index on the previous tick, and only moved if it's the same size and
+    /// moved less than `INTERPOLATION_MAX_DISTANCE`, so scenes that rebuild their sprites or
+    /// teleport something aren't smeared across the screen.
+    pub fn set_interpolate(&mut self, interpolate: bool) {
+        self.interpolate = interpolate;
+        self.previous.clear();
+    }
+
+    fn remember_positions(&mut self) {
+        if self.interpolate {
+            self.previous = self.sprites.iter().map(Sprite::rect).collect();
+        }
+    }
+
+    /// Passes an event to the scene's callback for it, `None` if it has none. For stacks the
+    /// engine drives outside of its own, see `GameWindow`.
+    pub(crate) fn handle_event(&mut self, ctx: &mut EngineContext, event: &Event) -> Option<SceneFnOutcome> {
+        let callback = self.event_callbacks.get(&event_type(event)?).copied()?;
+        Some(callback(self, ctx, event))
+    }
+
+    /// Ticks the scene like the engine ticks the top scene of its stack
+    pub(crate) fn tick(&mut self, ctx: &mut EngineContext, interval: u32) -> SceneFnOutcome {
+        self.remember_positions();
+        self.bars.tick(interval);
+        if let Some(tilemap) = &mut self.tilemap {
+            tilemap.advance(interval);
+        }
+        let start = Instant::now();
+        let outcome = (self.on_tick)(self, ctx, Duration::from_millis(interval as u64));
+        self.costs.add_tick(start.elapsed());
+        outcome
+    }
+
+    pub(crate) fn child_quit(&mut self, ctx: &mut EngineContext, props: Dict) -> SceneFnOutcome {
+        (self.on_child_quit)(self, ctx, props)
+    }
+
+    /// Turns engine hotkeys off while the scene is taking text input, so e.g. typing a nickname
+    /// doesn't take screenshots
+    pub fn set_text_entry(&mut self, text_entry: bool) {
+        self.text_entry = text_entry;
+    }
+
+    /// Plays the UI sound for each action the player presses while the scene is on top (see
+    /// `UiSound::for_action`), so menus get cursor, confirm and cancel sounds without their
+    /// callbacks playing them. Sounds for what an action led to, like `Buzzer` or `Purchase`, are
+    /// still up to the scene.
+    pub fn set_menu_sounds(&mut self, menu_sounds: bool) {
+        self.menu_sounds = menu_sounds;
+    }
+
+    /// The scene's own data, for callbacks to keep track of e.g. a cursor or a timer
+    pub fn state(&mut self) -> &mut Dict {
+        &mut self.state
+    }
+
+    /// What the scene draws every frame. Callbacks change these to update the screen.
+    pub fn sprites(&mut self) -> &mut Vec<Sprite> {
+        &mut self.sprites
+    }
+
+    /// Draws a map under the scene's sprites, from the chunks the engine keeps of it (see
+    /// `TilemapRenderer`), and moves its animated tiles on as the scene ticks. Tiles that aren't on
+    /// the spritesheet are reported like missing sprites.
+    pub fn set_tilemap(&mut self, tilemap: Option<Tilemap>) {
+        self.tilemap = tilemap;
+    }
+
+    /// The map the scene draws, e.g. to change tiles as the player cuts a tree
+    pub fn tilemap(&mut self) -> Option<&mut Tilemap> {
+        self.tilemap.as_mut()
+    }
+
+    pub fn set_clear(&mut self, clear: ClearMode) {
+        self.clear = clear;
+    }
+
+    /// Slides the cinematic bars in, e.g. when a cutscene starts
+    pub fn show_bars(&mut self) {
+        self.bars.showing = true;
     }
 
-    /// Renders a scene to a `WindowCanvas`. Renders background and all sprites. If any
-    /// background/sprite doesn't exist, just don't render it.
-    fn render(
+    /// Slides the cinematic bars back out
+    pub fn hide_bars(&mut self) {
+        self.bars.showing = false;
+    }
+
+    /// Renders a scene through a `Renderer` and presents it, see `draw`
+    pub fn render(
         &self,
-        canvas: &mut WindowCanvas,
-        backgrounds: &HashMap<String, Texture>,
-        spritesheet: &SpriteSheet,
+        renderer: &mut dyn Renderer,
+        palette: Palette,
+        missing_sprites: &mut HashSet<SpriteId>,
+        alpha: f32,
     ) {
-        canvas.set_draw_color(Color::BLACK);
-        canvas.clear();
-        if let Some(background) = backgrounds.get(self.background.as_str()) {
-            canvas.copy(background, self.bg_rect.0, self.bg_rect.1);
-        }
-        for sprite in self.sprites {
-            match sprite {
-                Sprite::Texture { rect: dst_rect, sprite: sprite_name } => {
-                    if let Some(src_rect) = spritesheet.get(sprite_name.as_str()) {
-                        canvas.copy(&spritesheet.texture, *src_rect, dst_rect);
-                    }
+        self.draw(renderer, palette, missing_sprites, alpha);
+        renderer.present();
+    }
+
+    /// Draws a scene through a `Renderer` without presenting it. Draws background, tilemap and all
+    /// sprites. If a background doesn't exist, just don't draw it. If a sprite doesn't exist, a
+    /// placeholder is drawn in its place and its name is added to `missing_sprites` (with a warning
+    /// the first time). Colors of rect sprites are passed through the accessibility `palette`.
+    /// `alpha` is how far into the next tick the game is, from 0 to 1, see `set_interpolate`.
+    pub fn draw(
+        &self,
+        renderer: &mut dyn Renderer,
+        palette: Palette,
+        missing_sprites: &mut HashSet<SpriteId>,
+        alpha: f32,
+    ) {
+        match &self.clear {
+            ClearMode::Color(color) => renderer.clear(*color),
+            ClearMode::Texture { name, src, dst } => {
+                renderer.clear(Color::BLACK);
+                renderer.draw_background(name, *src, *dst);
+            },
+            ClearMode::None => {},
+        }
+        if let Some(tilemap) = &self.tilemap {
+            for tile in renderer.draw_tilemap(tilemap) {
+                if missing_sprites.insert(tile) {
+                    eprintln!("warning: tile \"{}\" is not on the spritesheet", tile);
+                }
+            }
+        }
+        for (index, sprite) in self.sprites.iter().enumerate() {
+            let rect = match self.previous.get(index) {
+                Some(previous) if self.interpolate => interpolate(*previous, sprite.rect(), alpha),
+                _ => sprite.rect(),
+            };
+            let (sprite_name, drawn) = match *sprite {
+                Sprite::Texture { sprite, .. } => (sprite, renderer.draw_sprite(sprite, rect)),
+                Sprite::Tinted { sprite, color, .. } => {
+                    (sprite, renderer.draw_tinted(sprite, rect, palette.apply(color)))
                 },
-                Sprite::Rect { rect, color } => {
-                    canvas.set_draw_color(color);
-                    canvas.draw_rect(rect);
-                    canvas.fill_rect(rect);
+                Sprite::Rect { color, .. } => {
+                    renderer.draw_rect(rect, palette.apply(color));
+                    continue;
                 },
+            };
+            if !drawn {
+                if missing_sprites.insert(sprite_name) {
+                    eprintln!("warning: sprite \"{}\" is not on the spritesheet", sprite_name);
+                }
+                render_placeholder(renderer, rect);
             }
         }
-        canvas.present();
+        self.bars.render(renderer);
+    }
+}
+
+/// Farthest (px, along either axis) an interpolated sprite can move in a tick and still be drawn
+/// in between, see `Scene::set_interpolate`
+pub const INTERPOLATION_MAX_DISTANCE: i32 = 32;
+
+/// Where a sprite that was at `previous` on the last tick and is at `current` now is drawn
+/// `alpha` of the way into the next tick
+fn interpolate(previous: Rect, current: Rect, alpha: f32) -> Rect {
+    let (dx, dy) = (current.x() - previous.x(), current.y() - previous.y());
+    let same_size = previous.size() == current.size();
+    if !same_size || dx.abs() > INTERPOLATION_MAX_DISTANCE || dy.abs() > INTERPOLATION_MAX_DISTANCE {
+        return current;
+    }
+    let between = |from: i32, by: i32| from + (by as f32 * alpha.clamp(0.0, 1.0)).round() as i32;
+    Rect::new(between(previous.x(), dx), between(previous.y(), dy), current.width(), current.height())
+}
+
+/// Draws a magenta and black checkerboard in `rect`, used in place of missing sprites so they
+/// are easy to spot
+fn render_placeholder(renderer: &mut dyn Renderer, rect: Rect) {
+    const CELLS: u32 = 4;
+    let (cell_width, cell_height) = ((rect.width() / CELLS).max(1), (rect.height() / CELLS).max(1));
+    for row in 0..CELLS {
+        for column in 0..CELLS {
+            renderer.draw_rect(
+                Rect::new(
+                    rect.x() + (column * cell_width) as i32,
+                    rect.y() + (row * cell_height) as i32,
+                    cell_width,
+                    cell_height,
+                ),
+                if (row + column) % 2 == 0 { Color::MAGENTA } else { Color::BLACK },
+            );
+        }
+    }
+}
+
+/// What is drawn behind a scene's sprites at the start of every frame
+pub enum ClearMode {
+    Color(Color),
+    Texture {
+        name: String, // key in the engine's backgrounds
+        src: Option<Rect>,
+        dst: Option<Rect>,
+    },
+    None, // draw over whatever was on the canvas, e.g. for scenes that only draw an overlay
+}
+
+/// Default height of the cinematic bars, in logical pixels
+const CINEMATIC_BAR_HEIGHT: u32 = 24;
+/// Default time (ms) the cinematic bars take to slide fully in or out
+const CINEMATIC_BAR_DURATION: u32 = 300;
+
+/// Black letterbox bars at the top and bottom of the screen used during cutscenes. They slide in
+/// and out over `duration` ms as the engine ticks the scene.
+pub struct CinematicBars {
+    height: u32,
+    duration: u32,
+    showing: bool,
+    elapsed: u32, // how far the bars have slid in, from 0 to `duration`
+}
+
+impl CinematicBars {
+    pub fn new(height: u32, duration: u32) -> Self {
+        Self { height, duration, showing: false, elapsed: 0 }
+    }
+
+    fn tick(&mut self, interval: u32) {
+        if self.showing {
+            self.elapsed = (self.elapsed + interval).min(self.duration);
+        } else {
+            self.elapsed = self.elapsed.saturating_sub(interval);
+        }
+    }
+
+    fn render(&self, renderer: &mut dyn Renderer) {
+        if self.elapsed == 0 {
+            return;
+        }
+        let height = self.height * self.elapsed / self.duration.max(1);
+        let viewport = renderer.viewport();
+        let camera = renderer.camera();
+        renderer.set_camera((0, 0)); // bars are fixed to the screen
+        renderer.draw_rect(Rect::new(0, 0, viewport.width(), height), Color::BLACK);
+        renderer.draw_rect(
+            Rect::new(0, viewport.height() as i32 - height as i32, viewport.width(), height),
+            Color::BLACK,
+        );
+        renderer.set_camera(camera);
     }
 }
 
 /// Is called when a specified event type occurs
-pub type EventCallbackFn = fn(scene: &mut Scene, event: &Event) -> SceneFnOutcome;
-/// Is called between every frame. `interval` is the time that has passed since function was last
-/// called. Useful for cutscenes or other scenes based on time passing instead of events.
-pub type SceneOnTickFn = fn(scene: &mut Scene, interval: u32) -> SceneFnOutcome;
+pub type EventCallbackFn = fn(scene: &mut Scene, ctx: &mut EngineContext, event: &Event) -> SceneFnOutcome;
+/// Is called when a game event the scene handles is sent, see `EngineContext::emit`
+pub type GameEventFn = fn(scene: &mut Scene, ctx: &mut EngineContext, name: Key, props: &Dict) -> SceneFnOutcome;
+/// Is called every tick. `interval` is the time the tick stands for: the fixed timestep in the
+/// engine's own loop (see `EngineInfo::with_timestep`), whatever the host passes to `tick` in
+/// tests, so animations should advance by it rather than by a fixed step per call, e.g. by
+/// `speed * interval.as_secs_f64()` for a speed per second. The real frame time the ticks were
+/// collected from is `EngineContext::frame_time`. Useful for cutscenes or other scenes based on
+/// time passing instead of events.
+pub type SceneOnTickFn = fn(scene: &mut Scene, ctx: &mut EngineContext, interval: Duration) -> SceneFnOutcome;
 /// Is called when a child scene quits, and the responsibility for managing the game returns to
 /// this scene.
-pub type SceneOnChildQuitFn = fn(scene: &mut Scene, props: Dict) -> SceneFnOutcome;
+pub type SceneOnChildQuitFn = fn(scene: &mut Scene, ctx: &mut EngineContext, props: Dict) -> SceneFnOutcome;
+/// Is called when the window gains (`focused` true) or loses focus, see `Scene::set_on_focus_changed`
+pub type SceneOnFocusChangedFn = fn(scene: &mut Scene, ctx: &mut EngineContext, focused: bool) -> SceneFnOutcome;
 
 /// Scenes are stored on a stack. Whenever a game switches from one scene to another, but requires
 /// the player to go back to the old scene eventually, and so doesn't want to discard all of the
@@ -281,11 +2263,17 @@ pub enum SceneFnOutcome {
 /// A sprite can either be rendered using a `Texture` or a `Color`. Both variants contain the rect
 /// value, or where on the screen will the sprite be rendered. The Texture variant includes a name
 /// of a sprite on the spritesheet, and the Rect variant includes a color that will be rendered on
-/// to the rect.
+/// to the rect. The Tinted variant is a sprite on the spritesheet multiplied by a color, e.g. a
+/// white glyph drawn as red text.
 pub enum Sprite {
     Texture {
         rect: Rect,
-        sprite: String, // location on spritesheet
+        sprite: SpriteId, // location on spritesheet
+    },
+    Tinted {
+        rect: Rect,
+        sprite: SpriteId,
+        color: Color,
     },
     Rect {
         rect: Rect,
@@ -293,4 +2281,33 @@ pub enum Sprite {
     },
 }
 
+impl Sprite {
+    pub fn rect(&self) -> Rect {
+        match *self {
+            Self::Texture { rect, .. } | Self::Tinted { rect, .. } | Self::Rect { rect, .. } => rect,
+        }
+    }
+}
 
+/// A key press or release as SDL would send it, for input that doesn't come from the keyboard
+pub(crate) fn key_event(key: Keycode, down: bool) -> Event {
+    if down {
+        Event::KeyDown {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(key),
+            scancode: None,
+            keymod: Mod::NOMOD,
+            repeat: false,
+        }
+    } else {
+        Event::KeyUp {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Some(key),
+            scancode: None,
+            keymod: Mod::NOMOD,
+            repeat: false,
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use crate::intern::Key;
+use crate::rng::Rng;
+use crate::stats::Nature;
+use crate::team::PokemonInstance;
+
+/// Chance in 100 of a pokemon with Pickup finding an item after a battle
+pub const PICKUP_CHANCE: u32 = 10;
+/// Chance in 100 of Synchronize giving a wild pokemon the lead's nature
+pub const SYNCHRONIZE_CHANCE: u32 = 50;
+/// Items Pickup finds, by the highest level that finds them, rarest last
+const PICKUP_ITEMS: [(u32, &[&str]); 4] = [
+    (20, &["potion", "antidote", "super_potion", "great_ball", "repel", "escape_rope"]),
+    (50, &["super_potion", "full_heal", "ultra_ball", "revive", "rare_candy", "hyper_potion"]),
+    (80, &["hyper_potion", "ultra_ball", "revive", "rare_candy", "full_restore", "pp_up"]),
+    (u32::MAX, &["full_restore", "max_revive", "pp_up", "max_elixir", "ether", "rare_candy"]),
+];
+
+/// Runs after every battle for a party member, e.g. Pickup. Returns the item it found, if any,
+/// which the hook has already given it to hold.
+pub type AfterBattleFn = fn(pokemon: &mut PokemonInstance, rng: &mut Rng) -> Option<Key>;
+/// Changes how many steps an egg takes to hatch, e.g. Flame Body halving them
+pub type EggStepsFn = fn(steps: u32) -> u32;
+/// Picks the nature of a wild pokemon from the party's lead, e.g. Synchronize. `None` leaves it
+/// random.
+pub type WildNatureFn = fn(lead: &PokemonInstance, rng: &mut Rng) -> Option<Nature>;
+
+/// What the abilities of the party do outside of battle, as hooks keyed on the ability. The
+/// subsystems that care ask it: the battle after it ends (`after_battle`), the daycare as the
+/// player walks (`egg_steps`), and the encounter code when it makes a wild pokemon
+/// (`wild_nature`). `Default` has Pickup, Flame Body, Magma Armor and Synchronize; games add or
+/// replace hooks with the `on_*` methods.
+#[derive(Clone)]
+pub struct FieldAbilities {
+    after_battle: HashMap<Key, AfterBattleFn>,
+    egg_steps: HashMap<Key, EggStepsFn>,
+    wild_nature: HashMap<Key, WildNatureFn>,
+}
+
+impl Default for FieldAbilities {
+    fn default() -> Self {
+        let mut abilities = Self::new();
+        abilities.on_after_battle("pickup", pickup);
+        abilities.on_egg_steps("flame_body", halve_egg_steps);
+        abilities.on_egg_steps("magma_armor", halve_egg_steps);
+        abilities.on_wild_nature("synchronize", synchronize);
+        abilities
+    }
+}
+
+impl FieldAbilities {
+    /// No hooks at all
+    pub fn new() -> Self {
+        Self { after_battle: HashMap::new(), egg_steps: HashMap::new(), wild_nature: HashMap::new() }
+    }
+
+    pub fn on_after_battle(&mut self, ability: &str, hook: AfterBattleFn) {
+        self.after_battle.insert(Key::new(ability), hook);
+    }
+
+    pub fn on_egg_steps(&mut self, ability: &str, hook: EggStepsFn) {
+        self.egg_steps.insert(Key::new(ability), hook);
+    }
+
+    pub fn on_wild_nature(&mut self, ability: &str, hook: WildNatureFn) {
+        self.wild_nature.insert(Key::new(ability), hook);
+    }
+
+    /// Runs the after-battle hooks of every party member. Returns the items found, by party
+    /// index.
+    pub fn after_battle(&self, party: &mut [PokemonInstance], rng: &mut Rng) -> Vec<(usize, Key)> {
+        let mut found = Vec::new();
+        for (index, pokemon) in party.iter_mut().enumerate() {
+            if let Some(hook) = self.after_battle.get(&pokemon.ability) {
+                if let Some(item) = hook(pokemon, rng) {
+                    found.push((index, item));
+                }
+            }
+        }
+        found
+    }
+
+    /// Steps an egg takes to hatch from its species' `steps`, changed by the first party member
+    /// with an egg steps hook. They don't stack, so two Flame Body pokemon halve it only once.
+    pub fn egg_steps(&self, party: &[PokemonInstance], steps: u32) -> u32 {
+        match party.iter().find_map(|pokemon| self.egg_steps.get(&pokemon.ability)) {
+            Some(hook) => hook(steps),
+            None => steps,
+        }
+    }
+
+    /// The nature a wild pokemon gets from the lead of the party, `None` to roll it as usual.
+    /// Only the lead counts, even if it's fainted, like in the games.
+    pub fn wild_nature(&self, party: &[PokemonInstance], rng: &mut Rng) -> Option<Nature> {
+        let lead = party.first()?;
+        let hook = self.wild_nature.get(&lead.ability)?;
+        hook(lead, rng)
+    }
+}
+
+/// Finds an item now and then if the pokemon isn't holding one, better ones at higher levels
+fn pickup(pokemon: &mut PokemonInstance, rng: &mut Rng) -> Option<Key> {
+    if pokemon.item.is_some() || !rng.percent(PICKUP_CHANCE) {
+        return None;
+    }
+    let (_, items) = PICKUP_ITEMS.iter().find(|(max_level, _)| pokemon.level <= *max_level)?;
+    // earlier items are more common: each is picked half as often as the one before
+    let roll = rng.next_u64() % ((1 << items.len()) - 1);
+    let index = (0..items.len()).find(|i| roll < (1 << items.len()) - (1 << (items.len() - i - 1))).unwrap_or(0);
+    let item = Key::new(items[index]);
+    pokemon.item = Some(item);
+    Some(item)
+}
+
+fn halve_egg_steps(steps: u32) -> u32 {
+    (steps / 2).max(1)
+}
+
+fn synchronize(lead: &PokemonInstance, rng: &mut Rng) -> Option<Nature> {
+    if rng.percent(SYNCHRONIZE_CHANCE) {
+        Some(lead.nature)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,92 @@
+use sdl2::rect::Rect;
+use crate::engine::Sprite;
+use crate::input::{ Action, Binding, InputMap };
+use crate::intern::{ Key, SpriteId };
+use crate::text::TextSettings;
+
+/// What the player is playing with, for showing the right button prompts
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum InputDevice {
+    Keyboard,
+    Xbox,
+    PlayStation,
+    Switch,
+}
+
+impl InputDevice {
+    /// Name in glyph sprite names, e.g. `glyph_xbox_a`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Keyboard => "key",
+            Self::Xbox => "xbox",
+            Self::PlayStation => "playstation",
+            Self::Switch => "switch",
+        }
+    }
+
+    /// Guesses a controller's layout from the name SDL gives it. Controllers that aren't
+    /// recognized use the Xbox layout, which SDL's button names follow.
+    pub fn from_controller_name(name: &str) -> Self {
+        let name = name.to_lowercase();
+        if ["playstation", "ps3", "ps4", "ps5", "dualshock", "dualsense"].iter().any(|part| name.contains(part)) {
+            Self::PlayStation
+        } else if ["nintendo", "switch", "joy-con", "pro controller"].iter().any(|part| name.contains(part)) {
+            Self::Switch
+        } else {
+            Self::Xbox
+        }
+    }
+
+    pub fn is_controller(&self) -> bool {
+        *self != Self::Keyboard
+    }
+}
+
+/// The sprite of the button or key that triggers `action` on `device`, from the bindings in
+/// `map`, or `None` if nothing on the device is bound to it. Glyphs are sprites on the
+/// spritesheet named `glyph_key_<key name>` for keys (lower case, spaces as underscores, e.g.
+/// `glyph_key_return`) and `glyph_<device>_<SDL button name>` for buttons (e.g.
+/// `glyph_playstation_a` for the cross button). SDL names buttons by where they are, like on an
+/// Xbox controller, so `glyph_switch_a` is the bottom button, labelled B on a Switch.
+pub fn glyph(map: &InputMap, device: InputDevice, action: Action) -> Option<SpriteId> {
+    map.slots(action).iter().flatten().find_map(|binding| match (binding, device) {
+        (Binding::Key(key), InputDevice::Keyboard) => {
+            Some(Key::new(&format!("glyph_key_{}", key.name().to_lowercase().replace(' ', "_"))))
+        },
+        (Binding::Button(button), device) if device.is_controller() => {
+            Some(Key::new(&format!("glyph_{}_{}", device.name(), button.string())))
+        },
+        _ => None,
+    })
+}
+
+/// Draws a row of button prompts like `(A) Choose  (B) Back`, each the glyph of an action (see
+/// `glyph`) followed by a label, starting at `pos`. Returns the sprites and the width of the row.
+/// Actions without a glyph on the device show their name in brackets instead.
+pub fn prompts(
+    map: &InputMap,
+    device: InputDevice,
+    prompts: &[(Action, &str)],
+    pos: (i32, i32),
+    settings: &TextSettings,
+) -> (Vec<Sprite>, u32) {
+    let size = settings.line_height();
+    let mut sprites = Vec::new();
+    let mut x = pos.0;
+    for (action, label) in prompts {
+        match glyph(map, device, *action) {
+            Some(sprite) => {
+                sprites.push(Sprite::Texture { rect: Rect::new(x, pos.1, size, size), sprite });
+                x += size as i32 + settings.char_width() as i32 / 2;
+            },
+            None => {
+                let name = format!("[{}]", action.name().to_uppercase());
+                sprites.extend(settings.sprites(&name, (x, pos.1)));
+                x += (name.chars().count() as u32 * settings.char_width()) as i32 + settings.char_width() as i32 / 2;
+            },
+        }
+        sprites.extend(settings.sprites(label, (x, pos.1)));
+        x += ((label.chars().count() as u32 + 2) * settings.char_width()) as i32;
+    }
+    (sprites, (x - pos.0).max(0) as u32)
+}
@@ -0,0 +1,86 @@
+use std::env;
+use std::path::Path;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::surface::Surface;
+use crate::images;
+
+/// Pixels read back from a canvas, 4 bytes per pixel in RGBA order
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Loads a PNG (or any format SDL_image reads, only BMPs without the `image` feature) and
+    /// converts it to RGBA
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let surface = images::load_surface(path)?.convert_format(PixelFormatEnum::RGBA32)?;
+        let (width, height, pitch) = (surface.width(), surface.height(), surface.pitch() as usize);
+        let data = surface.without_lock().ok_or("surface needs locking")?;
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        for row in 0..height as usize {
+            pixels.extend_from_slice(&data[row * pitch..row * pitch + width as usize * 4]);
+        }
+        Ok(Self { width, height, pixels })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let mut pixels = self.pixels.clone();
+        let surface = Surface::from_data(
+            &mut pixels,
+            self.width,
+            self.height,
+            self.width * 4,
+            PixelFormatEnum::RGBA32,
+        )?;
+        images::save_surface(&surface, path)
+    }
+}
+
+/// Compares a captured image to the golden image at `path`. Pixels match if no channel differs by
+/// more than `tolerance`. On a mismatch the captured image is written next to the golden image as
+/// `<name>.actual.png` so the two can be compared by eye.
+///
+/// If the golden image doesn't exist yet and the `UPDATE_GOLDEN` environment variable is set, the
+/// captured image is saved as the new golden image instead. Setting `UPDATE_GOLDEN` also replaces
+/// golden images that don't match, for when a change to the rendering is intended.
+pub fn compare_golden(actual: &Image, path: &str, tolerance: u8) -> Result<(), String> {
+    let update = env::var_os("UPDATE_GOLDEN").is_some();
+    if !Path::new(path).exists() {
+        return if update {
+            actual.save(path)
+        } else {
+            Err(format!("{}: golden image missing, run with UPDATE_GOLDEN=1 to create it", path))
+        };
+    }
+    let expected = Image::load(path)?;
+    let mismatch = if (expected.width, expected.height) != (actual.width, actual.height) {
+        Some(format!(
+            "size is {}x{}, expected {}x{}",
+            actual.width, actual.height, expected.width, expected.height,
+        ))
+    } else {
+        let different = expected.pixels
+            .chunks(4)
+            .zip(actual.pixels.chunks(4))
+            .filter(|(expected, actual)| {
+                expected.iter().zip(actual.iter()).any(|(e, a)| (*e as i16 - *a as i16).abs() > tolerance as i16)
+            })
+            .count();
+        if different > 0 {
+            Some(format!("{} pixel(s) differ by more than {}", different, tolerance))
+        } else {
+            None
+        }
+    };
+    match mismatch {
+        None => Ok(()),
+        Some(_) if update => actual.save(path),
+        Some(reason) => {
+            let actual_path = format!("{}.actual.png", path.trim_end_matches(".png"));
+            actual.save(&actual_path)?;
+            Err(format!("{}: {} (captured image saved to {})", path, reason, actual_path))
+        },
+    }
+}
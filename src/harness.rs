@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::rect::Rect;
+use crate::audio::UiSoundMap;
+use crate::dict::*;
+use crate::engine::*;
+use crate::golden::{ self, Image };
+use crate::intern::{ Key, SpriteId };
+
+/// Drives an `Engine` from tests instead of from a window: events are injected directly and time
+/// only passes when `tick` is called, so runs are repeatable. Works without a display or sound
+/// card, e.g. in CI: `new` runs a headless engine, and `with_window` one on SDL's dummy video
+/// driver for tests that compare frames with `assert_golden`.
+///
+/// A typical test pushes the scene under test, injects the player's input, ticks, and then checks
+/// the stack depth, the globals, or the props the game quit with.
+pub struct Harness<'a> {
+    engine: Engine<'a>,
+    quit_props: Option<Dict>, // set once the last scene quits
+}
+
+impl<'a> Harness<'a> {
+    /// Creates a headless engine (see `Engine::new_headless`) with `root` as its only scene.
+    /// Nothing is drawn, so `capture` fails; use `with_window` for that.
+    pub fn new(
+        info: EngineInfo<'a>,
+        handle_quit: HandleQuitFn,
+        globals: Dict,
+        index: HashMap<SpriteId, Rect>,
+        root: Scene,
+    ) -> Result<Self, String> {
+        let mut engine = Engine::new_headless(info, handle_quit, globals, index, UiSoundMap::new())?;
+        engine.push_scene(root);
+        Ok(Self { engine, quit_props: None })
+    }
+
+    /// Creates an engine drawing `spritesheet` to a window on SDL's dummy video driver, with
+    /// `root` as its only scene. Audio goes to the dummy driver too. Both drivers are picked with
+    /// hints that are only set while the engine starts, see `Engine::new_headless`.
+    pub fn with_window(
+        info: EngineInfo<'a>,
+        handle_quit: HandleQuitFn,
+        globals: Dict,
+        spritesheet: &str,
+        index: HashMap<SpriteId, Rect>,
+        root: Scene,
+    ) -> Result<Self, String> {
+        let builder = EngineBuilder::new()
+            .info(info)
+            .on_quit(handle_quit)
+            .globals(globals)
+            .spritesheet(spritesheet, index);
+        let mut engine = with_hint("SDL_VIDEODRIVER", "dummy", || {
+            with_hint("SDL_AUDIODRIVER", "dummy", || builder.build())
+        })
+        .or_else(|err| Err(format!("{}", err)))?;
+        engine.push_scene(root);
+        Ok(Self { engine, quit_props: None })
+    }
+
+    /// Passes an event to the engine as if it came from SDL and handles it right away. Does
+    /// nothing once the game quit.
+    pub fn event(&mut self, event: Event) -> &mut Self {
+        if self.quit_props.is_none() {
+            self.engine.push_event(event);
+            self.quit_props = self.engine.handle_pushed_events();
+        }
+        self
+    }
+
+    /// Presses and releases a key
+    pub fn key(&mut self, key: Keycode) -> &mut Self {
+        self.event(key_event(key, true)).event(key_event(key, false))
+    }
+
+    /// Runs `frames` ticks of `interval` ms each, rendering after every tick like the real loop.
+    /// Ticks are run directly, without the real loop's fixed timestep.
+    pub fn tick(&mut self, frames: u32, interval: u32) -> &mut Self {
+        for _ in 0..frames {
+            if self.quit_props.is_some() {
+                break;
+            }
+            self.quit_props = self.engine.tick(interval);
+            self.engine.render();
+        }
+        self
+    }
+
+    /// Draws the current frame and reads it back from the canvas before it's presented, since
+    /// what's on the canvas after presenting is undefined
+    pub fn capture(&mut self) -> Result<Image, String> {
+        self.engine.draw_frame();
+        self.engine.capture()
+    }
+
+    /// Renders the current frame and compares it to a golden image, see `golden::compare_golden`.
+    /// Panics with the reason if they differ or the frame can't be captured, e.g. on a harness
+    /// made with `new`.
+    pub fn assert_golden(&mut self, path: &str, tolerance: u8) {
+        let result = self
+            .capture()
+            .map_err(|err| format!("{}: can't capture the frame: {}", path, err))
+            .and_then(|image| golden::compare_golden(&image, path, tolerance));
+        if let Err(err) = result {
+            panic!("{}", err);
+        }
+    }
+
+    pub fn engine(&mut self) -> &mut Engine<'a> {
+        &mut self.engine
+    }
+
+    pub fn stack_len(&self) -> usize {
+        self.engine.stack_len()
+    }
+
+    pub fn global(&self, key: &str) -> Option<&DictValue> {
+        self.engine.global(Key::new(key))
+    }
+
+    /// The props the game quit with, if the last scene has quit
+    pub fn quit_props(&self) -> Option<&Dict> {
+        self.quit_props.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use sdl2::event::EventType;
+    use sdl2::pixels::Color;
+    use super::*;
+
+    fn continue_tick(_scene: &mut Scene, _ctx: &mut EngineContext, _interval: Duration) -> SceneFnOutcome {
+        SceneFnOutcome::Continue
+    }
+
+    fn pass_props_on(_scene: &mut Scene, _ctx: &mut EngineContext, props: Dict) -> SceneFnOutcome {
+        SceneFnOutcome::Quit(props)
+    }
+
+    /// Opens the menu when the player presses Return
+    fn open_menu(_scene: &mut Scene, _ctx: &mut EngineContext, event: &Event) -> SceneFnOutcome {
+        match event {
+            Event::KeyDown { keycode: Some(Keycode::Return), .. } => {
+                SceneFnOutcome::CreateChild { create_scene: menu, props: Dict::new() }
+            },
+            _ => SceneFnOutcome::Continue,
+        }
+    }
+
+    /// A menu that picks its first option as soon as it ticks
+    fn menu(_props: Dict) -> Scene {
+        let clear = ClearMode::Color(Color::BLACK);
+        Scene::new(clear, Dict::new(), Vec::new(), HashMap::new(), pick_first, pass_props_on)
+    }
+
+    fn pick_first(_scene: &mut Scene, _ctx: &mut EngineContext, _interval: Duration) -> SceneFnOutcome {
+        let mut props = Dict::new();
+        props.insert(Key::new("picked"), DictValue::U32(0));
+        SceneFnOutcome::Quit(props)
+    }
+
+    #[test]
+    fn key_opens_a_child_scene_that_quits_on_tick() {
+        let mut callbacks: HashMap<EventType, EventCallbackFn> = HashMap::new();
+        callbacks.insert(EventType::KeyDown, open_menu);
+        let clear = ClearMode::Color(Color::BLACK);
+        let root = Scene::new(clear, Dict::new(), Vec::new(), callbacks, continue_tick, pass_props_on);
+        let info = EngineInfo::new("harness", 16, (160, 144), (1.0, 1.0));
+        let mut harness = Harness::new(info, |_, props| props, Dict::new(), HashMap::new(), root).unwrap();
+        assert_eq!(harness.stack_len(), 1);
+
+        harness.key(Keycode::Return);
+        assert_eq!(harness.stack_len(), 2);
+        assert!(harness.quit_props().is_none());
+
+        // the menu quits, and the root passes its props on, which quits the game
+        harness.tick(1, 16);
+        let quit_props = harness.quit_props().expect("the game should have quit");
+        assert_eq!(dict_u32(quit_props, "picked"), Some(0));
+    }
+}
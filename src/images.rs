@@ -0,0 +1,80 @@
+use std::path::Path;
+use sdl2::render::{ Texture, TextureCreator };
+use sdl2::surface::Surface;
+#[cfg(feature = "image")]
+use sdl2::image::{ LoadSurface, LoadTexture, SaveSurface };
+#[cfg(not(feature = "image"))]
+use sdl2::{ pixels::{ Color, PixelFormatEnum }, rwops::RWops };
+
+/// Width and height of the solid block that stands in for an image when the engine is built
+/// without the `image` feature (SDL_image), so games build and run without native image
+/// libraries, e.g. in CI. BMP files are still read and written with SDL itself. The block is big
+/// enough to cover a spritesheet, so every sprite draws as a solid block and text, drawn from
+/// glyph sprites, as rows of them.
+pub const STUB_IMAGE_SIZE: u32 = 1024;
+
+/// Loads an image file as a texture
+#[cfg(feature = "image")]
+pub fn load_texture<'a, T>(texture_creator: &'a TextureCreator<T>, path: &str) -> Result<Texture<'a>, String> {
+    texture_creator.load_texture(path)
+}
+
+/// Loads a BMP file as a texture, or a stand-in for any other file
+#[cfg(not(feature = "image"))]
+pub fn load_texture<'a, T>(texture_creator: &'a TextureCreator<T>, path: &str) -> Result<Texture<'a>, String> {
+    let surface = match Surface::load_bmp(path) {
+        Ok(surface) => surface,
+        Err(_) => stub_surface(path.as_bytes())?,
+    };
+    texture_creator.create_texture_from_surface(&surface).or_else(|err| Err(format!("{}", err)))
+}
+
+/// Loads an image from the contents of a file as a texture
+#[cfg(feature = "image")]
+pub fn load_texture_bytes<'a, T>(texture_creator: &'a TextureCreator<T>, bytes: &[u8]) -> Result<Texture<'a>, String> {
+    texture_creator.load_texture_bytes(bytes)
+}
+
+/// Loads a BMP from the contents of a file as a texture, or a stand-in for any other image
+#[cfg(not(feature = "image"))]
+pub fn load_texture_bytes<'a, T>(texture_creator: &'a TextureCreator<T>, bytes: &[u8]) -> Result<Texture<'a>, String> {
+    let surface = match RWops::from_bytes(bytes).and_then(|mut rwops| Surface::load_bmp_rw(&mut rwops)) {
+        Ok(surface) => surface,
+        Err(_) => stub_surface(&bytes[..bytes.len().min(64)])?,
+    };
+    texture_creator.create_texture_from_surface(&surface).or_else(|err| Err(format!("{}", err)))
+}
+
+/// Loads an image file (a PNG, or any format SDL_image reads) as a surface
+#[cfg(feature = "image")]
+pub fn load_surface<P: AsRef<Path>>(path: P) -> Result<Surface<'static>, String> {
+    Surface::from_file(path)
+}
+
+/// Loads a BMP file as a surface
+#[cfg(not(feature = "image"))]
+pub fn load_surface<P: AsRef<Path>>(path: P) -> Result<Surface<'static>, String> {
+    Surface::load_bmp(path)
+}
+
+/// Saves a surface as a PNG
+#[cfg(feature = "image")]
+pub fn save_surface<P: AsRef<Path>>(surface: &Surface, path: P) -> Result<(), String> {
+    surface.save(path)
+}
+
+/// Saves a surface as a BMP, whatever the path's extension
+#[cfg(not(feature = "image"))]
+pub fn save_surface<P: AsRef<Path>>(surface: &Surface, path: P) -> Result<(), String> {
+    surface.save_bmp(path)
+}
+
+/// A surface of one color, picked from `seed` so different images stand out from each other
+#[cfg(not(feature = "image"))]
+fn stub_surface(seed: &[u8]) -> Result<Surface<'static>, String> {
+    let hash = seed.iter().fold(2_166_136_261u32, |hash, byte| (hash ^ *byte as u32).wrapping_mul(16_777_619));
+    let [r, g, b, _] = hash.to_le_bytes();
+    let mut surface = Surface::new(STUB_IMAGE_SIZE, STUB_IMAGE_SIZE, PixelFormatEnum::RGBA32)?;
+    surface.fill_rect(None, Color::RGB(r | 0x40, g | 0x40, b | 0x40))?;
+    Ok(surface)
+}
@@ -0,0 +1,628 @@
+use std::collections::{ HashMap, VecDeque };
+use std::fs;
+use sdl2::controller::{ Axis, Button };
+use sdl2::keyboard::Keycode;
+use serde_json::{ json, Map, Value };
+use crate::data::read_json;
+use crate::glyphs::InputDevice;
+
+/// Logical game inputs. Scenes should react to these instead of raw keys, so controls can be
+/// rebound without touching scene code.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    A,
+    B,
+    Start,
+    Select,
+}
+
+impl Action {
+    pub const ALL: [Action; 8] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::A,
+        Action::B,
+        Action::Start,
+        Action::Select,
+    ];
+
+    /// Name in data files and saves, e.g. `up` or `a`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Up => "up",
+            Self::Down => "down",
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::A => "a",
+            Self::B => "b",
+            Self::Start => "start",
+            Self::Select => "select",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|action| action.name() == name)
+    }
+
+    pub fn is_direction(&self) -> bool {
+        matches!(self, Self::Up | Self::Down | Self::Left | Self::Right)
+    }
+}
+
+/// Something the player presses to trigger an `Action`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Binding {
+    Key(Keycode),
+    Button(Button), // on a game controller
+}
+
+impl Binding {
+    /// Name in the input config file, e.g. `key:Z` or `button:a`
+    pub fn name(&self) -> String {
+        match self {
+            Self::Key(key) => format!("key:{}", key.name()),
+            Self::Button(button) => format!("button:{}", button.string()),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(key) = name.strip_prefix("key:") {
+            Keycode::from_name(key).map(Self::Key)
+        } else {
+            name.strip_prefix("button:").and_then(Button::from_string).map(Self::Button)
+        }
+    }
+}
+
+/// How many bindings each action can have, e.g. arrow keys and WASD
+pub const BINDINGS_PER_ACTION: usize = 2;
+
+/// Maps keys and controller buttons to the `Action` they trigger. Each action has a primary and
+/// a secondary binding slot.
+#[derive(Clone, PartialEq, Debug)]
+pub struct InputMap {
+    bindings: HashMap<Action, [Option<Binding>; BINDINGS_PER_ACTION]>,
+}
+
+impl InputMap {
+    /// Creates a map from keys. Each action gets its keys in its free slots, in no particular
+    /// order; keys past the last slot are dropped.
+    pub fn new(keys: HashMap<Keycode, Action>) -> Self {
+        let mut map = Self { bindings: HashMap::new() };
+        for (key, action) in keys {
+            if let Some(slot) = map.slots(action).iter().position(Option::is_none) {
+                map.set(action, slot, Some(Binding::Key(key)));
+            }
+        }
+        map
+    }
+
+    pub fn get(&self, key: Keycode) -> Option<Action> {
+        self.find(Binding::Key(key)).map(|(action, _)| action)
+    }
+
+    pub fn get_button(&self, button: Button) -> Option<Action> {
+        self.find(Binding::Button(button)).map(|(action, _)| action)
+    }
+
+    /// Binds a key to an action's primary slot
+    pub fn bind(&mut self, key: Keycode, action: Action) {
+        self.set(action, 0, Some(Binding::Key(key)));
+    }
+
+    /// The first key bound to an action, e.g. for pressing the action on the player's behalf
+    pub fn key_for(&self, action: Action) -> Option<Keycode> {
+        self.slots(action).iter().find_map(|slot| match slot {
+            Some(Binding::Key(key)) => Some(*key),
+            _ => None,
+        })
+    }
+
+    /// The bindings in an action's slots, primary first
+    pub fn slots(&self, action: Action) -> [Option<Binding>; BINDINGS_PER_ACTION] {
+        self.bindings.get(&action).copied().unwrap_or_default()
+    }
+
+    /// Puts a binding in one of an action's slots, or clears the slot with `None`. A binding can
+    /// only trigger one action, so if it was already used elsewhere it is taken from there, and
+    /// that slot is returned for the rebinding screen to tell the player about.
+    pub fn set(&mut self, action: Action, slot: usize, binding: Option<Binding>) -> Option<(Action, usize)> {
+        if slot >= BINDINGS_PER_ACTION {
+            return None;
+        }
+        let conflict = binding.and_then(|binding| self.find(binding)).filter(|found| *found != (action, slot));
+        if let Some((other, other_slot)) = conflict {
+            self.bindings.entry(other).or_default()[other_slot] = None;
+        }
+        self.bindings.entry(action).or_default()[slot] = binding;
+        conflict
+    }
+
+    /// Where a binding is used, if anywhere
+    pub fn find(&self, binding: Binding) -> Option<(Action, usize)> {
+        Action::ALL.iter().find_map(|action| {
+            let slot = self.slots(*action).iter().position(|slot| *slot == Some(binding))?;
+            Some((*action, slot))
+        })
+    }
+
+    /// Bindings used by more than one slot and actions with no bindings at all, e.g. in a
+    /// hand-edited config file. Each problem is described for the rebinding screen.
+    pub fn conflicts(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut seen: Vec<(Binding, Action)> = Vec::new();
+        for action in Action::ALL.iter() {
+            let slots = self.slots(*action);
+            if slots.iter().all(Option::is_none) {
+                problems.push(format!("{} has no binding", action.name()));
+            }
+            for binding in slots.iter().flatten() {
+                match seen.iter().find(|(other, _)| other == binding) {
+                    Some((_, other)) => problems.push(format!(
+                        "{} is bound to both {} and {}",
+                        binding.name(),
+                        other.name(),
+                        action.name(),
+                    )),
+                    None => seen.push((*binding, *action)),
+                }
+            }
+        }
+        problems
+    }
+
+    fn to_json(&self) -> Value {
+        let mut map = Map::new();
+        for action in Action::ALL.iter() {
+            let slots: Vec<Value> = self
+                .slots(*action)
+                .iter()
+                .map(|slot| slot.map_or(Value::Null, |binding| Value::String(binding.name())))
+                .collect();
+            map.insert(action.name().to_string(), Value::Array(slots));
+        }
+        Value::Object(map)
+    }
+
+    /// Reads a map from `to_json`. Unknown actions and bindings are skipped, and duplicate
+    /// bindings are kept so `conflicts` can point them out.
+    fn from_json(json: &Value) -> Self {
+        let mut bindings = HashMap::new();
+        for action in Action::ALL.iter() {
+            let mut slots = [None; BINDINGS_PER_ACTION];
+            if let Some(names) = json[action.name()].as_array() {
+                for (slot, name) in slots.iter_mut().zip(names) {
+                    *slot = name.as_str().and_then(Binding::from_name);
+                }
+            }
+            bindings.insert(*action, slots);
+        }
+        Self { bindings }
+    }
+
+    /// Default controller layout. Face buttons follow their position rather than their label,
+    /// so A is the bottom button like on most pads.
+    pub fn default_pad() -> Self {
+        let mut map = Self { bindings: HashMap::new() };
+        let buttons = [
+            (Action::Up, Button::DPadUp),
+            (Action::Down, Button::DPadDown),
+            (Action::Left, Button::DPadLeft),
+            (Action::Right, Button::DPadRight),
+            (Action::A, Button::A),
+            (Action::B, Button::B),
+            (Action::Start, Button::Start),
+            (Action::Select, Button::Back),
+        ];
+        for (action, button) in buttons.iter() {
+            map.set(*action, 0, Some(Binding::Button(*button)));
+        }
+        map
+    }
+}
+
+impl Default for InputMap {
+    /// The arrow keys, Z, X, Enter and Backspace, with the buttons of `default_pad` in the
+    /// secondary slots so a controller works without setting anything up
+    fn default() -> Self {
+        let mut keys = HashMap::new();
+        keys.insert(Keycode::Up, Action::Up);
+        keys.insert(Keycode::Down, Action::Down);
+        keys.insert(Keycode::Left, Action::Left);
+        keys.insert(Keycode::Right, Action::Right);
+        keys.insert(Keycode::Z, Action::A);
+        keys.insert(Keycode::X, Action::B);
+        keys.insert(Keycode::Return, Action::Start);
+        keys.insert(Keycode::Backspace, Action::Select);
+        let mut map = Self::new(keys);
+        let pad = Self::default_pad();
+        for action in Action::ALL.iter() {
+            map.set(*action, 1, pad.slots(*action)[0]);
+        }
+        map
+    }
+}
+
+/// Name of the file input profiles are kept in, next to the save slots, so every save uses the
+/// same controls
+pub const INPUT_CONFIG_FILE: &str = "input.json";
+
+/// Named sets of bindings the player can switch between, e.g. `keyboard`, `pad` and `custom`.
+/// The active profile's map is the one the engine uses, see `Input::use_profile`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct InputProfiles {
+    profiles: Vec<(String, InputMap)>, // in the order they are listed in the options menu
+    active: usize,
+}
+
+impl InputProfiles {
+    pub fn active(&self) -> &InputMap {
+        &self.profiles[self.active].1
+    }
+
+    pub fn active_name(&self) -> &str {
+        &self.profiles[self.active].0
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Makes a profile the active one. Returns false if there is no profile with that name.
+    pub fn select(&mut self, name: &str) -> bool {
+        match self.profiles.iter().position(|(profile, _)| profile == name) {
+            Some(index) => {
+                self.active = index;
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut InputMap> {
+        self.profiles.iter_mut().find(|(profile, _)| profile == name).map(|(_, map)| map)
+    }
+
+    /// Adds a profile, or replaces the one with the same name
+    pub fn insert(&mut self, name: &str, map: InputMap) {
+        match self.get_mut(name) {
+            Some(existing) => *existing = map,
+            None => self.profiles.push((name.to_string(), map)),
+        }
+    }
+
+    /// Writes the profiles to a config file, see `INPUT_CONFIG_FILE`
+    pub fn export(&self, path: &str) -> Result<(), String> {
+        let profiles: Vec<Value> = self
+            .profiles
+            .iter()
+            .map(|(name, map)| json!({ "name": name, "bindings": map.to_json() }))
+            .collect();
+        let json = json!({ "active": self.active_name(), "profiles": profiles });
+        fs::write(path, json.to_string()).or_else(|err| Err(format!("{}: {}", path, err)))
+    }
+
+    /// Reads profiles from a config file written by `export`. A file without a given default
+    /// profile gets it back, so `keyboard` and `pad` are always there to fall back on.
+    pub fn import(path: &str) -> Result<Self, String> {
+        let json = read_json(path).or_else(|err| Err(format!("{}: {}", path, err)))?;
+        let entries = json["profiles"].as_array().ok_or(format!("{}: \"profiles\" is missing", path))?;
+        let mut profiles = Self::default();
+        for entry in entries {
+            let name = entry["name"].as_str().ok_or(format!("{}: a profile has no name", path))?;
+            profiles.insert(name, InputMap::from_json(&entry["bindings"]));
+        }
+        if let Some(active) = json["active"].as_str() {
+            profiles.select(active);
+        }
+        Ok(profiles)
+    }
+}
+
+impl Default for InputProfiles {
+    fn default() -> Self {
+        Self {
+            profiles: vec![
+                (String::from("keyboard"), InputMap::default()),
+                (String::from("pad"), InputMap::default_pad()),
+                (String::from("custom"), InputMap::default()),
+            ],
+            active: 0,
+        }
+    }
+}
+
+/// Remembers actions for a short window after they were pressed, so a press that comes a few
+/// frames early (e.g. the next direction while the player is still finishing a step, or A while
+/// a menu is still sliding in) is used once the scene is ready instead of being dropped.
+pub struct InputBuffer {
+    window: u32, // ms an action stays buffered
+    presses: VecDeque<(Action, u32)>, // (action, ms since pressed), oldest first
+}
+
+impl InputBuffer {
+    pub fn new(window: u32) -> Self {
+        Self { window, presses: VecDeque::new() }
+    }
+
+    pub fn push(&mut self, action: Action) {
+        self.presses.push_back((action, 0));
+    }
+
+    /// Ages all buffered actions by `interval` ms and drops the ones older than the window
+    pub fn tick(&mut self, interval: u32) {
+        let window = self.window;
+        for press in self.presses.iter_mut() {
+            press.1 += interval;
+        }
+        self.presses.retain(|(_, age)| *age <= window);
+    }
+
+    /// Removes and returns the oldest buffered action that `accept` returns true for
+    pub fn take<F: Fn(Action) -> bool>(&mut self, accept: F) -> Option<Action> {
+        let index = self.presses.iter().position(|(action, _)| accept(*action))?;
+        self.presses.remove(index).map(|(action, _)| action)
+    }
+
+    /// Removes and returns the most recently buffered direction, dropping older directions since
+    /// the player has changed their mind about them
+    pub fn take_direction(&mut self) -> Option<Action> {
+        let index = self.presses.iter().rposition(|(action, _)| action.is_direction())?;
+        let direction = self.presses.remove(index).map(|(action, _)| action);
+        self.presses.retain(|(action, _)| !action.is_direction());
+        direction
+    }
+
+    pub fn clear(&mut self) {
+        self.presses.clear();
+    }
+}
+
+/// Snapshot of which actions are held down this frame, taken by the engine from the keyboard
+/// and controller state once per frame. Lets held-movement code ask "is Left held?" instead of
+/// tracking key down/up events itself.
+///
+/// Conflicting directions are resolved by `direction`: the most recently pressed held direction
+/// wins, so holding Right and then also pressing Up walks up, and releasing Up goes back to
+/// walking right. Directions that start being held in the same frame count as pressed in the
+/// order Up, Down, Left, Right, so the later one in that list wins.
+pub struct HeldActions {
+    held: Vec<Action>, // in the order they started being held
+    now_held: Vec<Action>, // reused every update so snapshots don't allocate
+}
+
+impl HeldActions {
+    pub fn new() -> Self {
+        Self { held: Vec::new(), now_held: Vec::new() }
+    }
+
+    /// Replaces the snapshot with the actions held this frame, keeping press order for actions
+    /// that were already held
+    pub fn update<I: IntoIterator<Item = Action>>(&mut self, now_held: I) {
+        self.now_held.clear();
+        self.now_held.extend(now_held);
+        self.now_held.sort_by_key(|action| *action as u8);
+        self.now_held.dedup();
+        let now_held = &self.now_held;
+        self.held.retain(|action| now_held.contains(action));
+        for action in now_held {
+            if !self.held.contains(action) {
+                self.held.push(*action);
+            }
+        }
+    }
+
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    /// The direction the player is holding, see the struct docs for how conflicts are resolved
+    pub fn direction(&self) -> Option<Action> {
+        self.held.iter().rev().find(|action| action.is_direction()).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Action> {
+        self.held.iter()
+    }
+}
+
+impl Default for HeldActions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Dead zone, sensitivity and thresholds of an `AnalogStick`. Values are fractions of a full
+/// tilt, from 0.0 to 1.0.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct StickSettings {
+    pub dead_zone: f32, // tilts smaller than this are ignored, so a worn stick doesn't drift
+    pub sensitivity: f32, // multiplies the tilt past the dead zone
+    pub run_threshold: f32, // tilts at least this far run instead of walking
+    pub hysteresis: f32, // how much further the other axis has to be tilted to change direction
+}
+
+impl Default for StickSettings {
+    fn default() -> Self {
+        Self { dead_zone: 0.2, sensitivity: 1.0, run_threshold: 0.8, hysteresis: 0.15 }
+    }
+}
+
+/// How far the player tilts the movement stick
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StickTilt {
+    Walk,
+    Run,
+}
+
+/// A controller's left stick, turned into grid directions for movement. Kept up to date by the
+/// engine from controller axis events.
+///
+/// A stick held near a diagonal would flip between two directions every time it drifts across
+/// the diagonal, so the current direction is kept until the other axis is tilted further by
+/// `StickSettings::hysteresis`.
+pub struct AnalogStick {
+    pub settings: StickSettings,
+    x: f32, // -1.0 (left) to 1.0 (right)
+    y: f32, // -1.0 (up) to 1.0 (down)
+    direction: Option<Action>,
+}
+
+impl AnalogStick {
+    pub fn new(settings: StickSettings) -> Self {
+        Self { settings, x: 0.0, y: 0.0, direction: None }
+    }
+
+    /// Updates one axis from a controller axis event. Only the left stick is used.
+    pub fn set_axis(&mut self, axis: Axis, value: i16) {
+        let value = (value as f32 / i16::MAX as f32).clamp(-1.0, 1.0);
+        match axis {
+            Axis::LeftX => self.x = value,
+            Axis::LeftY => self.y = value,
+            _ => return,
+        }
+        self.direction = self.resolve_direction();
+    }
+
+    /// How far the stick is tilted past the dead zone, from 0.0 to 1.0, scaled by the sensitivity
+    pub fn magnitude(&self) -> f32 {
+        let tilt = (self.x * self.x + self.y * self.y).sqrt().min(1.0);
+        if tilt <= self.settings.dead_zone {
+            return 0.0;
+        }
+        ((tilt - self.settings.dead_zone) / (1.0 - self.settings.dead_zone) * self.settings.sensitivity).min(1.0)
+    }
+
+    /// The grid direction the stick points in, if it's tilted past the dead zone
+    pub fn direction(&self) -> Option<Action> {
+        self.direction
+    }
+
+    /// Whether the player is walking or running with the stick, if they're moving at all
+    pub fn tilt(&self) -> Option<StickTilt> {
+        self.direction?;
+        if self.magnitude() >= self.settings.run_threshold {
+            Some(StickTilt::Run)
+        } else {
+            Some(StickTilt::Walk)
+        }
+    }
+
+    fn resolve_direction(&self) -> Option<Action> {
+        if self.magnitude() <= 0.0 {
+            return None;
+        }
+        let horizontal = if self.x < 0.0 { Action::Left } else { Action::Right };
+        let vertical = if self.y < 0.0 { Action::Up } else { Action::Down };
+        let (x, y) = (self.x.abs(), self.y.abs());
+        match self.direction {
+            Some(Action::Left) | Some(Action::Right) if y <= x + self.settings.hysteresis => Some(horizontal),
+            Some(Action::Up) | Some(Action::Down) if x <= y + self.settings.hysteresis => Some(vertical),
+            _ if x >= y => Some(horizontal),
+            _ => Some(vertical),
+        }
+    }
+}
+
+/// Input state the engine keeps up to date for scenes
+pub struct Input {
+    pub map: InputMap,
+    pub buffer: InputBuffer,
+    pub held: HeldActions,
+    pub stick: AnalogStick,
+    pub device: InputDevice, // what the player last pressed something on, for button prompts
+}
+
+impl Input {
+    pub fn new(map: InputMap, buffer_window: u32) -> Self {
+        Self {
+            map,
+            buffer: InputBuffer::new(buffer_window),
+            held: HeldActions::new(),
+            stick: AnalogStick::new(StickSettings::default()),
+            device: InputDevice::Keyboard,
+        }
+    }
+
+    /// The direction the player wants to move in: a held direction if there is one, otherwise
+    /// the stick's
+    pub fn direction(&self) -> Option<Action> {
+        self.held.direction().or_else(|| self.stick.direction())
+    }
+
+    /// Switches to the bindings of the active input profile, e.g. after the player changes
+    /// profiles or rebinds an action in the options menu
+    pub fn use_profile(&mut self, profiles: &InputProfiles) {
+        self.map = profiles.active().clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffered_presses_expire_after_the_window() {
+        let mut buffer = InputBuffer::new(100);
+        buffer.push(Action::A);
+        buffer.tick(60);
+        buffer.push(Action::B);
+        buffer.tick(60); // A was pressed 120 ms ago, B 60 ms ago
+        assert_eq!(buffer.take(|_| true), Some(Action::B));
+        assert_eq!(buffer.take(|_| true), None);
+    }
+
+    #[test]
+    fn take_leaves_actions_the_scene_isnt_ready_for() {
+        let mut buffer = InputBuffer::new(100);
+        buffer.push(Action::Down);
+        buffer.push(Action::A);
+        buffer.push(Action::B);
+        assert_eq!(buffer.take(|action| !action.is_direction()), Some(Action::A));
+        assert_eq!(buffer.take_direction(), Some(Action::Down));
+        assert_eq!(buffer.take(|_| true), Some(Action::B));
+    }
+
+    #[test]
+    fn latest_buffered_direction_replaces_older_ones() {
+        let mut buffer = InputBuffer::new(100);
+        buffer.push(Action::Left);
+        buffer.push(Action::A);
+        buffer.push(Action::Up);
+        assert_eq!(buffer.take_direction(), Some(Action::Up));
+        assert_eq!(buffer.take_direction(), None);
+        assert_eq!(buffer.take(|_| true), Some(Action::A));
+    }
+
+    #[test]
+    fn default_map_has_keys_and_pad_buttons() {
+        let map = InputMap::default();
+        assert_eq!(map.get(Keycode::Z), Some(Action::A));
+        assert_eq!(map.get_button(Button::A), Some(Action::A));
+        assert_eq!(map.get_button(Button::DPadUp), Some(Action::Up));
+        let start = [Some(Binding::Key(Keycode::Return)), Some(Binding::Button(Button::Start))];
+        assert_eq!(map.slots(Action::Start), start);
+        assert!(map.conflicts().is_empty());
+    }
+
+    #[test]
+    fn latest_held_direction_wins() {
+        let mut held = HeldActions::new();
+        held.update(vec![Action::Right]);
+        held.update(vec![Action::Right, Action::Up, Action::A]);
+        assert_eq!(held.direction(), Some(Action::Up));
+        assert!(held.is_held(Action::A));
+        held.update(vec![Action::Right]);
+        assert_eq!(held.direction(), Some(Action::Right));
+        assert!(!held.is_held(Action::A));
+        // pressed in the same frame, the later one in Up, Down, Left, Right wins
+        held.update(vec![Action::Left, Action::Down]);
+        assert_eq!(held.direction(), Some(Action::Left));
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::{ HashMap, HashSet };
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use crate::dict::*;
+use crate::input::Action;
+use crate::intern::Key;
+use crate::renderer::Renderer;
+use crate::text::TextSettings;
+
+/// How long (ms) a value stays highlighted after it changes
+pub const CHANGE_HIGHLIGHT: u32 = 1000;
+
+const STATE_ROOT: &str = "state";
+const GLOBALS_ROOT: &str = "globals";
+
+/// A line of the inspector's tree
+struct Row {
+    path: String, // the keys from the root, joined with '/', e.g. "state/player/x"
+    depth: usize,
+    label: String,
+    value: Option<String>, // None for dicts, which can be expanded instead
+}
+
+/// Overlay for looking into the top scene's state and the globals while the game runs, as a tree
+/// of collapsible dicts. Numbers and bools can be edited in place, and values that changed in
+/// the last second are highlighted. See `Engine::set_inspector`.
+///
+/// Up and Down move the selection; Right expands a dict or increments a number, Left collapses a
+/// dict or decrements a number, and A toggles a dict or a bool.
+pub struct Inspector {
+    expanded: HashSet<String>, // paths of expanded dicts
+    selected: usize, // index of the selected row
+    last: HashMap<String, String>, // path -> summary of every value when last ticked
+    changed: HashMap<String, u32>, // path -> ms left highlighted
+}
+
+impl Inspector {
+    pub fn new() -> Self {
+        let mut expanded = HashSet::new();
+        expanded.insert(String::from(STATE_ROOT));
+        Self { expanded, selected: 0, last: HashMap::new(), changed: HashMap::new() }
+    }
+
+    /// Notices values that changed since the last tick and fades out older highlights
+    pub(crate) fn tick(&mut self, interval: u32, state: Option<&Dict>, globals: &Dict) {
+        let mut values = HashMap::new();
+        if let Some(state) = state {
+            flatten(state, STATE_ROOT, &mut values);
+        }
+        flatten(globals, GLOBALS_ROOT, &mut values);
+        self.changed.retain(|_, left| {
+            *left = left.saturating_sub(interval);
+            *left > 0
+        });
+        if !self.last.is_empty() {
+            for (path, value) in &values {
+                if self.last.get(path) != Some(value) {
+                    self.changed.insert(path.clone(), CHANGE_HIGHLIGHT);
+                }
+            }
+        }
+        self.last = values;
+    }
+
+    /// Moves the selection or edits the selected value. `state` is the top scene's state.
+    pub(crate) fn handle(&mut self, action: Action, state: Option<&mut Dict>, globals: &mut Dict) {
+        let rows = self.rows(state.as_deref(), globals);
+        if rows.is_empty() {
+            return;
+        }
+        self.selected = self.selected.min(rows.len() - 1);
+        let row = &rows[self.selected];
+        let mut parts = row.path.split('/');
+        let root = match parts.next() {
+            Some(STATE_ROOT) => state,
+            _ => Some(globals),
+        };
+        let keys: Vec<&str> = parts.collect();
+        let value = match root {
+            Some(root) if !keys.is_empty() => value_mut(root, &keys),
+            _ => None,
+        };
+        match (action, row.value.is_none()) {
+            (Action::Up, _) => self.selected = self.selected.saturating_sub(1),
+            (Action::Down, _) => self.selected = (self.selected + 1).min(rows.len() - 1),
+            (Action::Right, true) => {
+                self.expanded.insert(row.path.clone());
+            },
+            (Action::Left, true) => {
+                self.expanded.remove(&row.path);
+            },
+            // A on an expanded row collapses it, or expands it otherwise
+            (Action::A, true) if !self.expanded.remove(&row.path) => {
+                self.expanded.insert(row.path.clone());
+            },
+            (Action::Right, false) => value.into_iter().for_each(|value| step(value, 1)),
+            (Action::Left, false) => value.into_iter().for_each(|value| step(value, -1)),
+            (Action::A, false) => {
+                if let Some(DictValue::Bool(b)) = value {
+                    *b = !*b;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Draws the tree over the right half of the screen, scrolled so the selection is visible
+    pub(crate) fn render(&self, renderer: &mut dyn Renderer, text: &TextSettings, state: Option<&Dict>, globals: &Dict) {
+        let camera = renderer.camera();
+        renderer.set_camera((0, 0)); // the inspector is fixed to the screen
+        let viewport = renderer.viewport();
+        let line = text.line_height().max(1) as i32;
+        let x = viewport.width() as i32 / 2;
+        let width = viewport.width() - x as u32;
+        renderer.draw_rect(Rect::new(x, 0, width, viewport.height()), Color::RGB(0, 0, 0));
+        let rows = self.rows(state, globals);
+        let visible = (viewport.height() as i32 / line).max(1) as usize;
+        let scroll = (self.selected + 1).saturating_sub(visible);
+        for (i, row) in rows.iter().enumerate().skip(scroll).take(visible) {
+            let y = line * (i - scroll) as i32;
+            if i == self.selected {
+                renderer.draw_rect(Rect::new(x, y, width, line as u32), Color::RGB(48, 48, 80));
+            } else if self.changed.contains_key(&row.path) {
+                renderer.draw_rect(Rect::new(x, y, width, line as u32), Color::RGB(96, 64, 0));
+            }
+            let label = match &row.value {
+                Some(value) => format!("{}{}: {}", "  ".repeat(row.depth), row.label, value),
+                None if self.expanded.contains(&row.path) => format!("{}- {}", "  ".repeat(row.depth), row.label),
+                None => format!("{}+ {}", "  ".repeat(row.depth), row.label),
+            };
+            renderer.draw_text(&label, (x + 2, y), text);
+        }
+        renderer.set_camera(camera);
+    }
+
+    /// The visible lines of the tree, with only the expanded dicts' entries
+    fn rows(&self, state: Option<&Dict>, globals: &Dict) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for (root, dict) in [(STATE_ROOT, state), (GLOBALS_ROOT, Some(globals))].iter() {
+            rows.push(Row { path: root.to_string(), depth: 0, label: root.to_string(), value: None });
+            if let (Some(dict), true) = (dict, self.expanded.contains(*root)) {
+                self.dict_rows(dict, root, 1, &mut rows);
+            }
+        }
+        rows
+    }
+
+    fn dict_rows(&self, dict: &Dict, path: &str, depth: usize, rows: &mut Vec<Row>) {
+        let mut keys: Vec<&Key> = dict.keys().collect();
+        keys.sort_by_key(|key| key.name());
+        for key in keys {
+            let path = format!("{}/{}", path, key);
+            match &dict[key] {
+                DictValue::Dict(nested) => {
+                    let expanded = self.expanded.contains(&path);
+                    rows.push(Row { path: path.clone(), depth, label: key.to_string(), value: None });
+                    if expanded {
+                        self.dict_rows(nested, &path, depth + 1, rows);
+                    }
+                },
+                value => rows.push(Row { path, depth, label: key.to_string(), value: Some(value.summary()) }),
+            }
+        }
+    }
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Collects the summary of every value in a dict, nested ones included, by path
+fn flatten(dict: &Dict, path: &str, values: &mut HashMap<String, String>) {
+    for (key, value) in dict {
+        let path = format!("{}/{}", path, key);
+        match value {
+            DictValue::Dict(nested) => flatten(nested, &path, values),
+            value => {
+                values.insert(path, value.summary());
+            },
+        }
+    }
+}
+
+fn value_mut<'d>(dict: &'d mut Dict, keys: &[&str]) -> Option<&'d mut DictValue> {
+    let (last, parents) = keys.split_last()?;
+    let mut dict = dict;
+    for key in parents {
+        match dict.get_mut(&Key::new(key)) {
+            Some(DictValue::Dict(nested)) => dict = nested,
+            _ => return None,
+        }
+    }
+    dict.get_mut(&Key::new(last))
+}
+
+/// Adds `by` to a number, staying within its type's range. Other values are left alone.
+fn step(value: &mut DictValue, by: i8) {
+    match value {
+        DictValue::U8(n) => *n = if by > 0 { n.saturating_add(1) } else { n.saturating_sub(1) },
+        DictValue::I8(n) => *n = n.saturating_add(by),
+        DictValue::U16(n) => *n = if by > 0 { n.saturating_add(1) } else { n.saturating_sub(1) },
+        DictValue::I16(n) => *n = n.saturating_add(by.into()),
+        DictValue::U32(n) => *n = if by > 0 { n.saturating_add(1) } else { n.saturating_sub(1) },
+        DictValue::I32(n) => *n = n.saturating_add(by.into()),
+        DictValue::U64(n) => *n = if by > 0 { n.saturating_add(1) } else { n.saturating_sub(1) },
+        DictValue::I64(n) => *n = n.saturating_add(by.into()),
+        DictValue::U128(n) => *n = if by > 0 { n.saturating_add(1) } else { n.saturating_sub(1) },
+        DictValue::I128(n) => *n = n.saturating_add(by.into()),
+        DictValue::F32(n) => *n += f32::from(by),
+        DictValue::F64(n) => *n += f64::from(by),
+        _ => {},
+    }
+}
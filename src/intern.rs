@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{ Mutex, OnceLock };
+
+/// An interned string, used for sprite names and `Dict` keys. Comparing, hashing and copying a
+/// `Key` is as cheap as for a `u32`, so they can be used freely in the render loop. The name can
+/// still be looked up with `Key::name`, e.g. for debug output.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Key(u32);
+
+/// Alias used where a `Key` names a sprite on the spritesheet
+pub type SpriteId = Key;
+
+struct Interner {
+    ids: HashMap<&'static str, u32>,
+    names: Vec<&'static str>,
+}
+
+/// Interned names are never freed. There is a bounded number of them (sprite names and keys
+/// from data files), so this is the same as keeping them in a table for the whole game.
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| Mutex::new(Interner { ids: HashMap::new(), names: Vec::new() }))
+}
+
+impl Key {
+    /// Returns the key for `name`, interning it the first time it's seen
+    pub fn new(name: &str) -> Self {
+        let mut interner = interner().lock().unwrap();
+        if let Some(id) = interner.ids.get(name) {
+            return Key(*id);
+        }
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let id = interner.names.len() as u32;
+        interner.names.push(name);
+        interner.ids.insert(name, id);
+        Key(id)
+    }
+
+    pub fn name(&self) -> &'static str {
+        interner().lock().unwrap().names[self.0 as usize]
+    }
+}
+
+impl From<&str> for Key {
+    fn from(name: &str) -> Self {
+        Key::new(name)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Key({:?})", self.name())
+    }
+}
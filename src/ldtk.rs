@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use sdl2::rect::Rect;
+use serde_json::Value;
+use crate::data::read_json;
+use crate::mapfile::{ self, Connection, Edge, MapData, MapEntity, Warp };
+
+/// Entities with this identifier (in any case) become warps instead of `MapEntity`s
+const WARP_ENTITY: &str = "warp";
+/// The IntGrid layer with this identifier (in any case) is the map's elevation, not collision
+const ELEVATION_LAYER: &str = "elevation";
+
+/// Imports every level of an LDtk project as a map, named after the level's identifier:
+///
+/// - Tile and auto-layer tiles become tile layers, with tiles named `<tileset identifier>_<tile
+///   id>` like Tiled's, bottom layer first
+/// - IntGrid layers become the map's collision, their values written as they are, except a
+///   layer named `Elevation` (in any case), which becomes its elevation (see `elevation`)
+/// - Entities become `MapEntity`s of their identifier in lower case, named after their `name`
+///   field with every other field as a property, except `Warp` entities, which become warps
+///   taking the `map`, `x` and `y` fields
+/// - Levels that touch in the world become connections, so maps laid out side by side in the
+///   editor join up in the game
+///
+/// Levels saved in separate files are read from next to the project.
+pub fn import_ldtk(path: &str) -> Result<Vec<(String, MapData)>, String> {
+    let project = read_json(path).or_else(|err| Err(format!("{}: {}", path, err)))?;
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+    let tilesets: HashMap<u64, String> = project
+        .pointer("/defs/tilesets")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|tileset| Some((tileset.get("uid")?.as_u64()?, tileset.get("identifier")?.as_str()?.to_string())))
+        .collect();
+    let mut levels = Vec::new();
+    for level in project.get("levels").and_then(Value::as_array).into_iter().flatten() {
+        let level = match level.get("externalRelPath").and_then(Value::as_str) {
+            Some(external) => {
+                let external = dir.join(external);
+                read_json(&external.to_string_lossy()).or_else(|err| Err(format!("{}: {}", external.display(), err)))?
+            },
+            None => level.clone(),
+        };
+        levels.push(level);
+    }
+    let names: HashMap<&str, &str> = levels
+        .iter()
+        .filter_map(|level| Some((level.get("iid")?.as_str()?, level.get("identifier")?.as_str()?)))
+        .collect();
+    let positions: HashMap<&str, (i64, i64)> = levels
+        .iter()
+        .filter_map(|level| {
+            let pos = (level.get("worldX")?.as_i64()?, level.get("worldY")?.as_i64()?);
+            Some((level.get("iid")?.as_str()?, pos))
+        })
+        .collect();
+    let grid = project.get("defaultGridSize").and_then(Value::as_u64).unwrap_or(16).max(1);
+    let mut maps = Vec::new();
+    for level in &levels {
+        let name = level.get("identifier").and_then(Value::as_str).ok_or("a level has no identifier")?;
+        let map = import_level(level, grid, &tilesets, &names, &positions);
+        maps.push((name.to_string(), map.or_else(|err| Err(format!("{}: {}", name, err)))?));
+    }
+    Ok(maps)
+}
+
+/// Compiles every level of an LDtk project into `<dir>/<level identifier>.map`, see `MapFile`
+pub fn compile_ldtk<P: AsRef<Path>>(src: &str, dir: P) -> Result<(), String> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir).or_else(|err| Err(format!("{}: {}", dir.display(), err)))?;
+    for (name, map) in import_ldtk(src)? {
+        let path = dir.join(format!("{}.map", name));
+        mapfile::write_map(&map, &path)?;
+    }
+    Ok(())
+}
+
+fn import_level(
+    level: &Value,
+    grid: u64,
+    tilesets: &HashMap<u64, String>,
+    names: &HashMap<&str, &str>,
+    positions: &HashMap<&str, (i64, i64)>,
+) -> Result<MapData, String> {
+    let number = |name: &str| level.get(name).and_then(Value::as_u64).ok_or(format!("\"{}\" is missing", name));
+    let mut map = MapData {
+        width: (number("pxWid")? / grid) as u32,
+        height: (number("pxHei")? / grid) as u32,
+        tile_size: grid as u32,
+        ..MapData::default()
+    };
+    let tiles = (map.width * map.height) as usize;
+    let layers = level.get("layerInstances").and_then(Value::as_array).ok_or("the level has no layers")?;
+    // LDtk lists the top layer first
+    for layer in layers.iter().rev() {
+        let layer_grid = layer.get("__gridSize").and_then(Value::as_u64).unwrap_or(grid).max(1);
+        if layer_grid != grid {
+            let name = layer.get("__identifier").and_then(Value::as_str).unwrap_or("");
+            return Err(format!("layer \"{}\" has a grid of {}px instead of {}px", name, layer_grid, grid));
+        }
+        if let Some(values) = layer.get("intGridCsv").and_then(Value::as_array).filter(|values| !values.is_empty()) {
+            let name = layer.get("__identifier").and_then(Value::as_str).unwrap_or("");
+            let grid = if name.eq_ignore_ascii_case(ELEVATION_LAYER) { &mut map.elevation } else { &mut map.collision };
+            grid.resize(tiles, 0);
+            for (i, value) in values.iter().enumerate().take(tiles) {
+                let value = value.as_u64().unwrap_or(0).min(u8::MAX as u64) as u8;
+                if value > 0 {
+                    grid[i] = value;
+                }
+            }
+        }
+        let tileset = layer
+            .get("__tilesetDefUid")
+            .and_then(Value::as_u64)
+            .and_then(|uid| tilesets.get(&uid).cloned())
+            .or_else(|| {
+                let path = layer.get("__tilesetRelPath")?.as_str()?;
+                Some(Path::new(path).file_stem()?.to_string_lossy().to_string())
+            });
+        let placed: Vec<&Value> = ["gridTiles", "autoLayerTiles"]
+            .iter()
+            .filter_map(|key| layer.get(*key).and_then(Value::as_array))
+            .flatten()
+            .collect();
+        if let (Some(tileset), false) = (tileset, placed.is_empty()) {
+            let mut layer_tiles = vec![None; tiles];
+            for tile in placed {
+                let (x, y) = match pixel_pos(tile) {
+                    Some((x, y)) if x >= 0 && y >= 0 && (x as u64 / grid) < map.width as u64 => {
+                        (x as u64 / grid, y as u64 / grid)
+                    },
+                    _ => continue,
+                };
+                let index = (y * map.width as u64 + x) as usize;
+                if let (Some(id), true) = (tile.get("t").and_then(Value::as_u64), index < tiles) {
+                    layer_tiles[index] = Some(format!("{}_{}", tileset, id)); // later tiles cover earlier ones
+                }
+            }
+            map.add_layer(&layer_tiles)?;
+        }
+        for entity in layer.get("entityInstances").and_then(Value::as_array).into_iter().flatten() {
+            import_entity(entity, &mut map)?;
+        }
+    }
+    let iid = level.get("iid").and_then(Value::as_str).unwrap_or("");
+    let pos = positions.get(iid).copied().unwrap_or((0, 0));
+    for neighbour in level.get("__neighbours").and_then(Value::as_array).into_iter().flatten() {
+        let other = neighbour.get("levelIid").and_then(Value::as_str).unwrap_or("");
+        let edge = match neighbour.get("dir").and_then(Value::as_str) {
+            Some("n") => Edge::North,
+            Some("s") => Edge::South,
+            Some("e") => Edge::East,
+            Some("w") => Edge::West,
+            _ => continue, // corners and levels above or below
+        };
+        let (name, other_pos) = match (names.get(other), positions.get(other)) {
+            (Some(name), Some(other_pos)) => (name, other_pos),
+            _ => continue,
+        };
+        let offset = match edge {
+            Edge::North | Edge::South => other_pos.0 - pos.0,
+            Edge::East | Edge::West => other_pos.1 - pos.1,
+        } / grid as i64;
+        map.connections.push(Connection { edge, map: name.to_string(), offset: offset as i32 });
+    }
+    Ok(map)
+}
+
+/// The `px` position of a tile or entity
+fn pixel_pos(value: &Value) -> Option<(i64, i64)> {
+    let pos = value.get("px")?.as_array()?;
+    Some((pos.first()?.as_i64()?, pos.get(1)?.as_i64()?))
+}
+
+fn import_entity(entity: &Value, map: &mut MapData) -> Result<(), String> {
+    let kind = entity.get("__identifier").and_then(Value::as_str).unwrap_or("").to_lowercase();
+    let pos = match pixel_pos(entity) {
+        Some((x, y)) => (x as i32, y as i32),
+        None => return Err(format!("a {} has no position", kind)),
+    };
+    let mut fields: Vec<(String, Value)> = entity
+        .get("fieldInstances")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|field| Some((field.get("__identifier")?.as_str()?.to_string(), field.get("__value")?.clone())))
+        .collect();
+    let mut take = |name: &str| fields.iter().position(|(field, _)| field == name).map(|i| fields.remove(i).1);
+    if kind == WARP_ENTITY {
+        let target = take("map");
+        let target = target.as_ref().and_then(Value::as_str).ok_or("a warp has no map")?;
+        let tile = |value: Option<Value>| value.and_then(|value| value.as_u64()).unwrap_or(0) as u32;
+        let to = (tile(take("x")), tile(take("y")));
+        let size = |name: &str| entity.get(name).and_then(Value::as_u64).unwrap_or(map.tile_size as u64) as u32;
+        let rect = Rect::new(pos.0, pos.1, size("width"), size("height"));
+        map.warps.push(Warp { rect, map: target.to_string(), to });
+        return Ok(());
+    }
+    let name = match take("name") {
+        Some(Value::String(name)) => name,
+        _ => entity.get("iid").and_then(Value::as_str).unwrap_or("").to_string(),
+    };
+    let properties = fields
+        .into_iter()
+        .map(|(field, value)| match value {
+            Value::String(value) => (field, value),
+            value => (field, value.to_string()),
+        })
+        .collect();
+    map.entities.push(MapEntity { kind, name, pos, properties });
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use std::cell::RefCell;
+use std::collections::{ BTreeMap, BTreeSet };
+use crate::intern::Key;
+
+/// Where a key tracked by `KeyLeaks` was written
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum KeyOrigin {
+    Global,
+    Props,
+}
+
+impl KeyOrigin {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Global => "global",
+            Self::Props => "props key",
+        }
+    }
+}
+
+/// Debug mode that catches typo'd key names, which the `Dict` system otherwise lets fail
+/// silently: a global or props key that's written but never read again, and a global that's
+/// requested but was never written. See `Engine::set_leak_detection`.
+///
+/// Reads are noticed where the engine hands values out: a global counts as read when a scene
+/// requests it with `_REQUESTS` or the game gets it with `Engine::global`, and a props key when
+/// the parent it's passed to declared it with `Scene::set_child_quit_props`. Props passed to
+/// parents that declare nothing and to new scenes aren't tracked, since the engine can't see
+/// which of them the scene reads.
+#[derive(Default)]
+pub struct KeyLeaks {
+    unread: RefCell<BTreeMap<Key, KeyOrigin>>, // written and not read since, read through `&self`
+    missing: BTreeSet<Key>, // globals requested but never written
+}
+
+impl KeyLeaks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn written(&mut self, key: Key, origin: KeyOrigin) {
+        self.unread.get_mut().insert(key, origin);
+    }
+
+    pub(crate) fn read(&self, key: Key) {
+        self.unread.borrow_mut().remove(&key);
+    }
+
+    pub(crate) fn requested_missing(&mut self, key: Key) {
+        self.missing.insert(key);
+    }
+
+    /// Every key written but never read and every global requested but missing, one per line,
+    /// sorted by name. Empty if nothing was found.
+    pub fn report(&self) -> String {
+        let mut lines: Vec<String> = self
+            .unread
+            .borrow()
+            .iter()
+            .map(|(key, origin)| format!("{} \"{}\" is written but never read", origin.name(), key))
+            .chain(self.missing.iter().map(|key| format!("global \"{}\" is requested but never written", key)))
+            .collect();
+        lines.sort();
+        lines.join("\n")
+    }
+}
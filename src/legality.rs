@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::intern::Key;
+use crate::species::Species;
+use crate::team::{ import_team, PokemonInstance };
+
+/// Something about a pokemon that couldn't have happened in the game
+#[derive(Clone, PartialEq, Debug)]
+pub enum Violation {
+    UnknownSpecies(Key),
+    UnknownForm(Key),
+    UnlearnableMove(Key),
+    DuplicateMove(Key),
+    ImpossibleAbility(Key),
+    LevelTooLow { level: u32, min_level: u32 }, // e.g. evolved below the level it evolves at
+    Limit(String), // level, move count, IV or EV out of range, see `PokemonInstance::check_limits`
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownSpecies(species) => write!(f, "there is no species {}", species),
+            Self::UnknownForm(form) => write!(f, "the species has no form {}", form),
+            Self::UnlearnableMove(m) => write!(f, "can't learn {}", m),
+            Self::DuplicateMove(m) => write!(f, "knows {} more than once", m),
+            Self::ImpossibleAbility(ability) => write!(f, "can't have the ability {}", ability),
+            Self::LevelTooLow { level, min_level } => {
+                write!(f, "is level {}, but can't be below level {}", level, min_level)
+            },
+            Self::Limit(problem) => write!(f, "{}", problem),
+        }
+    }
+}
+
+/// Checks a pokemon against the species data. Moves and abilities are only checked if the
+/// species data lists them.
+pub fn check(pokemon: &PokemonInstance, species: &HashMap<Key, Species>) -> Vec<Violation> {
+    let mut violations: Vec<Violation> = pokemon.check_limits().into_iter().map(Violation::Limit).collect();
+    let entry = match species.get(&pokemon.species) {
+        Some(entry) => entry,
+        None => {
+            violations.push(Violation::UnknownSpecies(pokemon.species));
+            return violations;
+        },
+    };
+    if entry.form(pokemon.form).is_none() {
+        violations.push(Violation::UnknownForm(pokemon.form));
+    }
+    for (i, m) in pokemon.moves.iter().enumerate() {
+        if pokemon.moves[..i].contains(m) {
+            violations.push(Violation::DuplicateMove(*m));
+        } else if !entry.moves.is_empty() && !entry.moves.contains(m) {
+            violations.push(Violation::UnlearnableMove(*m));
+        }
+    }
+    if !entry.abilities.is_empty() && !entry.abilities.contains(&pokemon.ability) {
+        violations.push(Violation::ImpossibleAbility(pokemon.ability));
+    }
+    if pokemon.level < entry.min_level {
+        violations.push(Violation::LevelTooLow { level: pokemon.level, min_level: entry.min_level });
+    }
+    violations
+}
+
+/// Every violation in a team, by the pokemon's position
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct LegalityReport {
+    pub violations: Vec<(usize, Violation)>,
+}
+
+impl LegalityReport {
+    /// Checks every pokemon of a team, e.g. one imported from a team code or offered in a trade
+    pub fn check_team(team: &[PokemonInstance], species: &HashMap<Key, Species>) -> Self {
+        let mut violations = Vec::new();
+        for (i, pokemon) in team.iter().enumerate() {
+            violations.extend(check(pokemon, species).into_iter().map(|violation| (i, violation)));
+        }
+        Self { violations }
+    }
+
+    pub fn is_legal(&self) -> bool {
+        self.violations.is_empty()
+    }
+
+    /// One line per violation, e.g. "#2 pikachu: can't learn fly", for showing to the player
+    pub fn lines(&self, team: &[PokemonInstance]) -> Vec<String> {
+        self.violations
+            .iter()
+            .map(|(i, violation)| match team.get(*i) {
+                Some(pokemon) => format!("#{} {}: {}", i + 1, pokemon.species, violation),
+                None => format!("#{}: {}", i + 1, violation),
+            })
+            .collect()
+    }
+}
+
+/// Reads a team code like `import_team`, then checks the team against the species data. Returns
+/// one line per problem, for showing to the player.
+pub fn import_legal_team(code: &str, species: &HashMap<Key, Species>) -> Result<Vec<PokemonInstance>, Vec<String>> {
+    let team = import_team(code)?;
+    let report = LegalityReport::check_team(&team, species);
+    if report.is_legal() {
+        Ok(team)
+    } else {
+        Err(report.lines(&team))
+    }
+}
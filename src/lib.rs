@@ -0,0 +1,75 @@
+// errors are turned into strings with `.or_else(|err| Err(format!(..)))` throughout
+#![allow(clippy::bind_instead_of_map)]
+
+pub mod stack;
+pub mod dict;
+pub mod engine;
+pub mod pokemon;
+pub mod audio;
+pub mod palette;
+pub mod text;
+pub mod markup;
+pub mod input;
+pub mod textures;
+pub mod tilemap;
+pub mod data;
+pub mod intern;
+pub mod harness;
+pub mod golden;
+pub mod renderer;
+pub mod save;
+pub mod preload;
+pub mod encounter;
+pub mod rng;
+pub mod capture;
+pub mod stats;
+pub mod species;
+pub mod chat;
+pub mod playstats;
+pub mod trainercard;
+pub mod ribbons;
+pub mod minigame;
+pub mod team;
+pub mod legality;
+pub mod rules;
+pub mod battle;
+pub mod seasons;
+pub mod captions;
+pub mod toasts;
+pub mod dialog;
+pub mod macros;
+pub mod diagnostics;
+pub mod inspector;
+pub mod leaks;
+pub mod statemachine;
+pub mod sequence;
+pub mod atlas;
+pub mod crash;
+pub mod watchdog;
+pub mod compress;
+pub mod mapfile;
+pub mod ldtk;
+pub mod autotile;
+pub mod elevation;
+pub mod warps;
+pub mod schedule;
+pub mod counters;
+pub mod ambience;
+pub mod images;
+pub mod glyphs;
+pub mod dex;
+pub mod roaming;
+pub mod turnorder;
+pub mod results;
+pub mod weather;
+pub mod bag;
+pub mod repel;
+pub mod fieldabilities;
+pub mod windows;
+pub mod quests;
+#[cfg(not(feature = "mixer"))]
+mod silent;
+#[cfg(debug_assertions)]
+pub mod debugwindow;
+#[cfg(target_os = "emscripten")]
+pub mod web;
@@ -0,0 +1,204 @@
+use std::fs;
+use std::path::Path;
+use serde_json::{ json, Value };
+use crate::data::read_json;
+use crate::input::Action;
+
+/// Folder input macros are saved to, relative to the working directory
+pub const MACRO_DIR: &str = "macros";
+
+/// One press or release in an input macro
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MacroStep {
+    pub time: u32, // ms since the recording started
+    pub action: Action,
+    pub down: bool,
+}
+
+/// A recorded sequence of actions with their timings, for replaying a long flow (e.g. "leave town,
+/// beat the first trainer, enter the cave") over and over while testing. Dev tool only.
+#[derive(Clone, PartialEq, Debug)]
+pub struct InputMacro {
+    pub name: String,
+    pub steps: Vec<MacroStep>,
+}
+
+impl InputMacro {
+    /// Saves the macro as `<dir>/<name>.json`
+    pub fn save(&self, dir: &str) -> Result<(), String> {
+        let steps: Vec<Value> =
+            self.steps.iter().map(|step| json!([step.time, step.action.name(), step.down])).collect();
+        let json = json!({ "name": self.name, "steps": steps });
+        let path = Path::new(dir).join(format!("{}.json", self.name));
+        fs::create_dir_all(dir)
+            .and_then(|_| fs::write(&path, json.to_string()))
+            .or_else(|err| Err(format!("{}: {}", path.display(), err)))
+    }
+
+    /// Loads a macro saved with `save`
+    pub fn load(dir: &str, name: &str) -> Result<Self, String> {
+        let path = Path::new(dir).join(format!("{}.json", name));
+        let path = path.to_string_lossy();
+        let json = read_json(&path).or_else(|err| Err(format!("{}: {}", path, err)))?;
+        let steps = json["steps"]
+            .as_array()
+            .ok_or(format!("{}: \"steps\" is missing", path))?
+            .iter()
+            .map(|step| {
+                Some(MacroStep {
+                    time: step[0].as_u64()? as u32,
+                    action: Action::from_name(step[1].as_str()?)?,
+                    down: step[2].as_bool()?,
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(format!("{}: a step is damaged", path))?;
+        Ok(Self { name: name.to_string(), steps })
+    }
+}
+
+enum MacroState {
+    Idle,
+    Recording(InputMacro),
+    Playing { input_macro: InputMacro, next: usize },
+}
+
+/// Records and replays input macros, see `Engine::record_macro` and `Engine::play_macro`. Time is
+/// counted in engine ticks rather than wall time, so a replay lines up with the game the same way
+/// the recording did.
+pub struct Macros {
+    state: MacroState,
+    elapsed: u32, // ms since recording or playback started
+    held: Vec<Action>, // actions the macro being played is holding down
+}
+
+impl Macros {
+    pub fn new() -> Self {
+        Self { state: MacroState::Idle, elapsed: 0, held: Vec::new() }
+    }
+
+    /// Starts recording a new macro, dropping one being recorded or played
+    pub fn record(&mut self, name: &str) {
+        self.state = MacroState::Recording(InputMacro { name: name.to_string(), steps: Vec::new() });
+        self.elapsed = 0;
+        self.held.clear();
+    }
+
+    /// Starts replaying a macro, dropping one being recorded or played
+    pub fn play(&mut self, input_macro: InputMacro) {
+        self.state = MacroState::Playing { input_macro, next: 0 };
+        self.elapsed = 0;
+        self.held.clear();
+    }
+
+    /// Stops recording or playing. Returns the macro if one was being recorded.
+    pub fn stop(&mut self) -> Option<InputMacro> {
+        self.held.clear();
+        match std::mem::replace(&mut self.state, MacroState::Idle) {
+            MacroState::Recording(input_macro) => Some(input_macro),
+            _ => None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        matches!(self.state, MacroState::Recording(_))
+    }
+
+    pub fn is_playing(&self) -> bool {
+        matches!(self.state, MacroState::Playing { .. })
+    }
+
+    /// Actions the macro being played is holding down, added to what the player holds
+    pub fn held(&self) -> &[Action] {
+        &self.held
+    }
+
+    /// Records a press or release of the player's while recording
+    pub(crate) fn observe(&mut self, action: Action, down: bool) {
+        if let MacroState::Recording(input_macro) = &mut self.state {
+            input_macro.steps.push(MacroStep { time: self.elapsed, action, down });
+        }
+    }
+
+    /// Advances time. While playing, returns the steps that are due, to be passed to the engine as
+    /// if the player pressed them; playback stops after the last one.
+    pub(crate) fn tick(&mut self, interval: u32) -> Vec<MacroStep> {
+        self.elapsed += interval;
+        let elapsed = self.elapsed;
+        let (input_macro, next) = match &mut self.state {
+            MacroState::Playing { input_macro, next } => (input_macro, next),
+            _ => return Vec::new(),
+        };
+        let due: Vec<MacroStep> =
+            input_macro.steps[*next..].iter().take_while(|step| step.time <= elapsed).copied().collect();
+        *next += due.len();
+        for step in &due {
+            if step.down && !self.held.contains(&step.action) {
+                self.held.push(step.action);
+            } else if !step.down {
+                self.held.retain(|action| *action != step.action);
+            }
+        }
+        if *next >= input_macro.steps.len() {
+            self.state = MacroState::Idle;
+            self.held.clear();
+        }
+        due
+    }
+}
+
+impl Default for Macros {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playback_repeats_the_recording_tick_for_tick() {
+        let mut macros = Macros::new();
+        macros.record("route_1");
+        macros.tick(16);
+        macros.observe(Action::Up, true);
+        macros.tick(48);
+        macros.observe(Action::Up, false);
+        macros.observe(Action::A, true);
+        let input_macro = macros.stop().unwrap();
+        assert!(!macros.is_recording());
+        assert_eq!(input_macro.steps, vec![
+            MacroStep { time: 16, action: Action::Up, down: true },
+            MacroStep { time: 64, action: Action::Up, down: false },
+            MacroStep { time: 64, action: Action::A, down: true },
+        ]);
+
+        macros.play(input_macro);
+        assert!(macros.tick(10).is_empty());
+        assert_eq!(macros.tick(6).len(), 1);
+        assert_eq!(macros.held(), [Action::Up]);
+        assert!(macros.tick(32).is_empty());
+        assert_eq!(macros.held(), [Action::Up]); // still walking
+        assert_eq!(macros.tick(16).len(), 2);
+        // that was the last step, so playback is over and lets go of everything
+        assert!(!macros.is_playing());
+        assert!(macros.held().is_empty());
+    }
+
+    #[test]
+    fn saved_macros_load_the_same() {
+        let dir = std::env::temp_dir().join(format!("pokemon-macros-{}", std::process::id()));
+        let dir = dir.to_string_lossy();
+        let input_macro = InputMacro {
+            name: String::from("cave"),
+            steps: vec![
+                MacroStep { time: 0, action: Action::Start, down: true },
+                MacroStep { time: 33, action: Action::Start, down: false },
+            ],
+        };
+        input_macro.save(&dir).unwrap();
+        assert_eq!(InputMacro::load(&dir, "cave").unwrap(), input_macro);
+        fs::remove_dir_all(&*dir).unwrap();
+    }
+}
@@ -1,8 +1,3 @@
-pub mod stack;
-pub mod dict;
-pub mod engine;
-pub mod pokemon;
-
 fn main() {
     println!("Hello World")
 }
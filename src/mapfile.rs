@@ -0,0 +1,532 @@
+use std::convert::TryFrom;
+use std::fs::{ self, File };
+use std::ops::Deref;
+use std::path::Path;
+use memmap2::Mmap;
+use sdl2::rect::Rect;
+use serde_json::Value;
+use crate::autotile::TerrainRules;
+use crate::compress;
+use crate::data::read_json;
+use crate::elevation::ELEVATION_TRANSITION;
+use crate::intern::Key;
+use crate::tilemap::{ TileLayer, Tilemap };
+
+/// Start of every compiled map file
+pub const MAP_MAGIC: &[u8] = b"PKMNMAP\0";
+/// Version of the compiled map format, after the magic. Maps of other versions must be compiled
+/// again.
+pub const MAP_FORMAT_VERSION: u16 = 3;
+
+/// Bits of a Tiled tile id that flag flipped tiles, which the engine doesn't support
+const TILED_FLIP_FLAGS: u32 = 0xe000_0000;
+
+/// An area of a map that the game reacts to by name, e.g. tall grass or a sign, in pixels
+#[derive(Clone, PartialEq, Debug)]
+pub struct MapZone {
+    pub name: String,
+    pub rect: Rect,
+}
+
+/// An area of a map that takes the player to a tile of another map, e.g. a door
+#[derive(Clone, PartialEq, Debug)]
+pub struct Warp {
+    pub rect: Rect, // in pixels
+    pub map: String,
+    pub to: (u32, u32), // tile on the other map
+}
+
+/// Something placed on a map that the game spawns, e.g. an NPC or an item ball
+#[derive(Clone, PartialEq, Debug)]
+pub struct MapEntity {
+    pub kind: String, // e.g. "npc" or "item"
+    pub name: String,
+    pub pos: (i32, i32), // in pixels
+    pub properties: Vec<(String, String)>, // whatever else the editor gives the entity
+}
+
+/// Which edge of a map another map is joined to
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Edge {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl Edge {
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::North => 0,
+            Self::South => 1,
+            Self::East => 2,
+            Self::West => 3,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Option<Self> {
+        Some(match byte {
+            0 => Self::North,
+            1 => Self::South,
+            2 => Self::East,
+            3 => Self::West,
+            _ => return None,
+        })
+    }
+}
+
+/// A map joined to an edge of another, so walking off that edge continues on it. `offset` is
+/// where the other map starts along the edge, in tiles: a town east of a route with an offset of
+/// -4 starts 4 tiles above the route's top.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Connection {
+    pub edge: Edge,
+    pub map: String,
+    pub offset: i32,
+}
+
+/// A map ready to be compiled into the binary map format with `to_bytes`, e.g. one imported from
+/// Tiled with `from_tiled`. Games load compiled maps with `MapFile`.
+///
+/// The format is little endian: `MAP_MAGIC`, the version (u16), the width and height in tiles
+/// and the tile size in pixels (u32 each), then the tileset, layers, zones and warps, each as a
+/// count (u32) followed by the entries. Tiles are sprite names (u16 length, then UTF-8), and a
+/// layer is width * height u16s, row by row, where 0 is no tile and n is the nth tile of the
+/// tileset. Zones are a rect (i32 x, i32 y, u32 width, u32 height) and a name, and warps a rect,
+/// a map name and the tile to go to (u32 x, u32 y). Then come width * height collision bytes,
+/// 0 where the player can walk, width * height elevation bytes (see `elevation`), the entities
+/// as a kind, a name, a position (i32 x, i32 y) and a count (u32) of name and value properties,
+/// and the connections as an edge (u8, north, south, east, west), a map name and an offset (i32).
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MapData {
+    pub width: u32, // in tiles
+    pub height: u32,
+    pub tile_size: u32, // in pixels
+    pub tileset: Vec<String>,
+    pub layers: Vec<Vec<u16>>,
+    pub zones: Vec<MapZone>,
+    pub warps: Vec<Warp>,
+    pub collision: Vec<u8>, // one per tile, row by row, 0 where the player can walk; empty for none
+    pub elevation: Vec<u8>, // one per tile, row by row, see `elevation`; empty for all transitions
+    pub entities: Vec<MapEntity>,
+    pub connections: Vec<Connection>,
+}
+
+impl MapData {
+    /// Imports a map exported from Tiled as JSON. Tiles are named `<tileset name>_<tile id>` and
+    /// tile layers must be stored as arrays (Tiled's CSV setting). Objects of class `warp` become
+    /// warps, taking the `map`, `x` and `y` custom properties, and every other object a zone
+    /// named after the object.
+    pub fn from_tiled(json: &Value) -> Result<Self, String> {
+        let number = |value: &Value, name: &str| {
+            value.get(name).and_then(Value::as_u64).ok_or(format!("\"{}\" is missing", name))
+        };
+        let mut map = Self {
+            width: number(json, "width")? as u32,
+            height: number(json, "height")? as u32,
+            tile_size: number(json, "tilewidth")? as u32,
+            ..Self::default()
+        };
+        let mut tilesets: Vec<(u32, String)> = Vec::new(); // (first id, name)
+        for tileset in json.get("tilesets").and_then(Value::as_array).into_iter().flatten() {
+            let name = match (tileset.get("name"), tileset.get("source")) {
+                (Some(Value::String(name)), _) => name.clone(),
+                (_, Some(Value::String(source))) => Path::new(source)
+                    .file_stem()
+                    .map_or(source.clone(), |stem| stem.to_string_lossy().to_string()),
+                _ => return Err(String::from("a tileset has no name")),
+            };
+            tilesets.push((number(tileset, "firstgid")? as u32, name));
+        }
+        tilesets.sort();
+        for layer in json.get("layers").and_then(Value::as_array).into_iter().flatten() {
+            match layer.get("type").and_then(Value::as_str) {
+                Some("tilelayer") => {
+                    let data = layer
+                        .get("data")
+                        .and_then(Value::as_array)
+                        .ok_or("tile layers have to be stored as arrays")?;
+                    let mut tiles = Vec::with_capacity(data.len());
+                    for id in data {
+                        let id = id.as_u64().ok_or("a tile id is not a number")? as u32 & !TILED_FLIP_FLAGS;
+                        let tile = match tilesets.iter().rev().find(|(first, _)| *first <= id) {
+                            Some((first, name)) if id > 0 => Some(format!("{}_{}", name, id - first)),
+                            _ => None,
+                        };
+                        tiles.push(tile);
+                    }
+                    map.add_layer(&tiles)?;
+                },
+                Some("objectgroup") => {
+                    for object in layer.get("objects").and_then(Value::as_array).into_iter().flatten() {
+                        map.add_tiled_object(object)?;
+                    }
+                },
+                _ => {},
+            }
+        }
+        Ok(map)
+    }
+
+    fn add_tiled_object(&mut self, object: &Value) -> Result<(), String> {
+        let float = |name: &str| object.get(name).and_then(Value::as_f64).unwrap_or(0.0);
+        let rect = Rect::new(float("x") as i32, float("y") as i32, float("width") as u32, float("height") as u32);
+        let property = |name: &str| {
+            object
+                .get("properties")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .find(|property| property.get("name").and_then(Value::as_str) == Some(name))
+                .and_then(|property| property.get("value"))
+        };
+        let class = object.get("class").or_else(|| object.get("type")).and_then(Value::as_str);
+        let name = object.get("name").and_then(Value::as_str).unwrap_or("").to_string();
+        if class == Some("warp") {
+            let map = property("map").and_then(Value::as_str).ok_or(format!("warp \"{}\" has no map", name))?;
+            let tile = |name: &str| property(name).and_then(Value::as_u64).unwrap_or(0) as u32;
+            self.warps.push(Warp { rect, map: map.to_string(), to: (tile("x"), tile("y")) });
+        } else {
+            self.zones.push(MapZone { name, rect });
+        }
+        Ok(())
+    }
+
+    /// Adds a layer of terrain ids (`None` for no terrain), row by row, resolved into tiles with
+    /// the tileset's terrain rules
+    pub fn add_terrain_layer(&mut self, terrain: &[Option<String>], rules: &TerrainRules) -> Result<(), String> {
+        let terrain: Vec<Option<Key>> = terrain.iter().map(|name| name.as_deref().map(Key::new)).collect();
+        let tiles: Vec<Option<String>> = rules
+            .resolve(&terrain, self.width, self.height)
+            .into_iter()
+            .map(|tile| tile.map(|tile| tile.name().to_string()))
+            .collect();
+        self.add_layer(&tiles)
+    }
+
+    /// Adds a layer of tile names, row by row, adding new names to the tileset
+    pub fn add_layer(&mut self, tiles: &[Option<String>]) -> Result<(), String> {
+        if tiles.len() != (self.width * self.height) as usize {
+            return Err(format!("a layer has {} tiles instead of {}", tiles.len(), self.width * self.height));
+        }
+        let mut layer = Vec::with_capacity(tiles.len());
+        for tile in tiles {
+            let index = match tile {
+                None => 0,
+                Some(tile) => match self.tileset.iter().position(|name| name == tile) {
+                    Some(index) => index + 1,
+                    None => {
+                        self.tileset.push(tile.clone());
+                        self.tileset.len()
+                    },
+                },
+            };
+            layer.push(u16::try_from(index).or_else(|_| Err("a map can use at most 65535 different tiles"))?);
+        }
+        self.layers.push(layer);
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = MAP_MAGIC.to_vec();
+        data.extend(&MAP_FORMAT_VERSION.to_le_bytes());
+        for value in [self.width, self.height, self.tile_size, self.tileset.len() as u32] {
+            data.extend(&value.to_le_bytes());
+        }
+        for tile in &self.tileset {
+            write_string(&mut data, tile);
+        }
+        data.extend(&(self.layers.len() as u32).to_le_bytes());
+        for layer in &self.layers {
+            for tile in layer {
+                data.extend(&tile.to_le_bytes());
+            }
+        }
+        data.extend(&(self.zones.len() as u32).to_le_bytes());
+        for zone in &self.zones {
+            write_rect(&mut data, zone.rect);
+            write_string(&mut data, &zone.name);
+        }
+        data.extend(&(self.warps.len() as u32).to_le_bytes());
+        for warp in &self.warps {
+            write_rect(&mut data, warp.rect);
+            write_string(&mut data, &warp.map);
+            data.extend(&warp.to.0.to_le_bytes());
+            data.extend(&warp.to.1.to_le_bytes());
+        }
+        let tiles = (self.width * self.height) as usize;
+        data.extend(self.collision.iter().copied().chain(std::iter::repeat(0)).take(tiles));
+        data.extend(self.elevation.iter().copied().chain(std::iter::repeat(0)).take(tiles));
+        data.extend(&(self.entities.len() as u32).to_le_bytes());
+        for entity in &self.entities {
+            write_string(&mut data, &entity.kind);
+            write_string(&mut data, &entity.name);
+            data.extend(&entity.pos.0.to_le_bytes());
+            data.extend(&entity.pos.1.to_le_bytes());
+            data.extend(&(entity.properties.len() as u32).to_le_bytes());
+            for (name, value) in &entity.properties {
+                write_string(&mut data, name);
+                write_string(&mut data, value);
+            }
+        }
+        data.extend(&(self.connections.len() as u32).to_le_bytes());
+        for connection in &self.connections {
+            data.push(connection.edge.to_byte());
+            write_string(&mut data, &connection.map);
+            data.extend(&connection.offset.to_le_bytes());
+        }
+        data
+    }
+}
+
+fn write_string(data: &mut Vec<u8>, string: &str) {
+    let bytes = &string.as_bytes()[..string.len().min(u16::MAX as usize)];
+    data.extend(&(bytes.len() as u16).to_le_bytes());
+    data.extend(bytes);
+}
+
+fn write_rect(data: &mut Vec<u8>, rect: Rect) {
+    data.extend(&rect.x().to_le_bytes());
+    data.extend(&rect.y().to_le_bytes());
+    data.extend(&rect.width().to_le_bytes());
+    data.extend(&rect.height().to_le_bytes());
+}
+
+/// Compiles a map exported from Tiled as JSON into the binary map format, e.g. from the game's
+/// build script for every map that changed
+pub fn compile_tiled<P: AsRef<Path>>(src: &str, dst: P) -> Result<(), String> {
+    let map = MapData::from_tiled(&read_json(src)?).or_else(|err| Err(format!("{}: {}", src, err)))?;
+    write_map(&map, dst.as_ref())
+}
+
+/// Writes a map in the binary format to a temporary file next to `path` and renames it over
+/// `path`, so a build that's stopped halfway never leaves a truncated map for the game to load
+pub fn write_map(map: &MapData, path: &Path) -> Result<(), String> {
+    let tmp_path = path.with_extension("tmp");
+    let result = fs::write(&tmp_path, map.to_bytes()).and_then(|_| fs::rename(&tmp_path, path));
+    if let Err(err) = result {
+        let _ = fs::remove_file(&tmp_path);
+        return Err(format!("{}: {}", path.display(), err));
+    }
+    Ok(())
+}
+
+/// Reads the binary map format field by field
+struct Reader<'d> {
+    data: &'d [u8],
+    pos: usize,
+}
+
+impl<'d> Reader<'d> {
+    fn bytes(&mut self, len: usize) -> Result<&'d [u8], String> {
+        let bytes = self.data.get(self.pos..self.pos + len).ok_or("map file is truncated")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let mut bytes = [0; 2];
+        bytes.copy_from_slice(self.bytes(2)?);
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u16()? as usize;
+        String::from_utf8(self.bytes(len)?.to_vec()).or_else(|_| Err(String::from("a name is not UTF-8")))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(self.u32()? as i32)
+    }
+
+    fn rect(&mut self) -> Result<Rect, String> {
+        Ok(Rect::new(self.i32()?, self.i32()?, self.u32()?, self.u32()?))
+    }
+}
+
+/// What a `MapFile` reads from: the file mapped into memory, or bytes read the usual way where
+/// mapping isn't possible (compressed maps, or the web)
+enum MapBytes {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Deref for MapBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Mapped(mmap) => mmap,
+            Self::Owned(bytes) => bytes,
+        }
+    }
+}
+
+/// A compiled map (see `MapData`) opened for playing. The file is mapped into memory instead of
+/// read and parsed, so even a huge route opens in about the time it takes to read its tileset,
+/// zones and warps; tiles are only read when they're asked for.
+pub struct MapFile {
+    data: MapBytes,
+    width: u32,
+    height: u32,
+    tile_size: u32,
+    tileset: Vec<Key>,
+    layers: Vec<usize>, // offset of each layer in the data
+    zones: Vec<MapZone>,
+    warps: Vec<Warp>,
+    collision: usize, // offset of the collision bytes in the data
+    elevation: usize, // offset of the elevation bytes in the data
+    entities: Vec<MapEntity>,
+    connections: Vec<Connection>,
+}
+
+impl MapFile {
+    /// Maps a compiled map file into memory. Compressed files (see `compress`) are read and
+    /// decompressed instead.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = File::open(path).or_else(|err| Err(format!("{}: {}", path.display(), err)))?;
+        // Safety: maps are only ever replaced by the build step, never changed while the game
+        // runs, so the mapped memory doesn't change under it
+        let mmap = unsafe { Mmap::map(&file) }.or_else(|err| Err(format!("{}: {}", path.display(), err)))?;
+        let data = if compress::is_compressed(&mmap) {
+            MapBytes::Owned(compress::decompress(mmap.to_vec())?)
+        } else {
+            MapBytes::Mapped(mmap)
+        };
+        Self::parse(data).or_else(|err| Err(format!("{}: {}", path.display(), err)))
+    }
+
+    /// Reads a compiled map from bytes, e.g. ones fetched on the web
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, String> {
+        Self::parse(MapBytes::Owned(compress::decompress(data)?))
+    }
+
+    fn parse(data: MapBytes) -> Result<Self, String> {
+        let mut reader = Reader { data: &data, pos: 0 };
+        if reader.bytes(MAP_MAGIC.len()).ok() != Some(MAP_MAGIC) {
+            return Err(String::from("not a compiled map"));
+        }
+        let version = reader.u16()?;
+        if version != MAP_FORMAT_VERSION {
+            return Err(format!("map format {} is not {}, compile the map again", version, MAP_FORMAT_VERSION));
+        }
+        let (width, height, tile_size) = (reader.u32()?, reader.u32()?, reader.u32()?);
+        let tileset = (0..reader.u32()?).map(|_| Ok(Key::new(&reader.string()?))).collect::<Result<_, String>>()?;
+        let layer_len = width as usize * height as usize * 2;
+        let mut layers = Vec::new();
+        for _ in 0..reader.u32()? {
+            layers.push(reader.pos);
+            reader.bytes(layer_len)?;
+        }
+        let mut zones = Vec::new();
+        for _ in 0..reader.u32()? {
+            zones.push(MapZone { rect: reader.rect()?, name: reader.string()? });
+        }
+        let mut warps = Vec::new();
+        for _ in 0..reader.u32()? {
+            warps.push(Warp { rect: reader.rect()?, map: reader.string()?, to: (reader.u32()?, reader.u32()?) });
+        }
+        let collision = reader.pos;
+        reader.bytes(width as usize * height as usize)?;
+        let elevation = reader.pos;
+        reader.bytes(width as usize * height as usize)?;
+        let mut entities = Vec::new();
+        for _ in 0..reader.u32()? {
+            let (kind, name, pos) = (reader.string()?, reader.string()?, (reader.i32()?, reader.i32()?));
+            let properties = (0..reader.u32()?)
+                .map(|_| Ok((reader.string()?, reader.string()?)))
+                .collect::<Result<_, String>>()?;
+            entities.push(MapEntity { kind, name, pos, properties });
+        }
+        let mut connections = Vec::new();
+        for _ in 0..reader.u32()? {
+            let edge = Edge::from_byte(reader.bytes(1)?[0]).ok_or("a connection's edge is unknown")?;
+            connections.push(Connection { edge, map: reader.string()?, offset: reader.i32()? });
+        }
+        Ok(Self {
+            data,
+            width,
+            height,
+            tile_size,
+            tileset,
+            layers,
+            zones,
+            warps,
+            collision,
+            elevation,
+            entities,
+            connections,
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    pub fn zones(&self) -> &[MapZone] {
+        &self.zones
+    }
+
+    pub fn warps(&self) -> &[Warp] {
+        &self.warps
+    }
+
+    pub fn entities(&self) -> &[MapEntity] {
+        &self.entities
+    }
+
+    pub fn connections(&self) -> &[Connection] {
+        &self.connections
+    }
+
+    /// The collision value of a tile, 0 where the player can walk. Outside the map is blocked.
+    pub fn collision(&self, x: u32, y: u32) -> u8 {
+        if x >= self.width || y >= self.height {
+            return u8::MAX;
+        }
+        self.data[self.collision + (y * self.width + x) as usize]
+    }
+
+    /// The elevation of a tile, see `elevation::step`. Outside the map is a transition, since
+    /// it's blocked by collision anyway.
+    pub fn elevation(&self, x: u32, y: u32) -> u8 {
+        if x >= self.width || y >= self.height {
+            return ELEVATION_TRANSITION;
+        }
+        self.data[self.elevation + (y * self.width + x) as usize]
+    }
+
+    /// The tile at a position, read straight from the file
+    pub fn tile(&self, layer: usize, x: u32, y: u32) -> Option<Key> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let offset = self.layers.get(layer)? + (y * self.width + x) as usize * 2;
+        let index = u16::from_le_bytes([self.data[offset], self.data[offset + 1]]) as usize;
+        self.tileset.get(index.checked_sub(1)?).copied()
+    }
+
+    /// Builds a `Tilemap` of the whole map, for drawing it
+    pub fn to_tilemap(&self) -> Tilemap {
+        let layers: Vec<TileLayer> = (0..self.layers.len())
+            .map(|layer| {
+                (0..self.height)
+                    .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+                    .map(|(x, y)| self.tile(layer, x, y))
+                    .collect()
+            })
+            .collect();
+        Tilemap::new(self.width, self.height, self.tile_size, layers)
+    }
+}
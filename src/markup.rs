@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use crate::dict::*;
+use crate::engine::Sprite;
+use crate::input::Action;
+use crate::intern::{ Key, SpriteId };
+use crate::text::TextSettings;
+
+/// Time (ms) each character of dialog takes to appear, until a `{speed:..}` code changes it
+pub const TEXT_SPEED: u32 = 30;
+
+/// One thing in a line of rich text
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Glyph {
+    Char(char, Option<Color>), // in the color of its span, the font's own outside of one
+    Item(SpriteId), // an item's icon, `item_<item>` on the spritesheet
+    Button(Action), // the button the player presses for an action, see `glyphs::glyph`
+    Pause(u32), // ms to wait before showing the rest
+    Speed(u32), // ms each character takes to appear from here on, 0 for all at once
+}
+
+impl Glyph {
+    /// Width in pixels. Icons are square, and pauses and speed changes take no room.
+    fn width(&self, settings: &TextSettings) -> u32 {
+        match self {
+            Self::Char(..) => settings.char_width(),
+            Self::Item(_) | Self::Button(_) => settings.line_height(),
+            Self::Pause(_) | Self::Speed(_) => 0,
+        }
+    }
+}
+
+pub type RichLine = Vec<Glyph>;
+
+fn width(glyphs: &[Glyph], settings: &TextSettings) -> u32 {
+    glyphs.iter().map(|glyph| glyph.width(settings)).sum()
+}
+
+/// Dialog text with markup, parsed once when the dialog is loaded. `{color:red}` starts a span
+/// of colored text that `{/color}` ends, with the names in `color_from_name` or `#rrggbb`.
+/// `{item:potion}` shows an item's icon and `{button:a}` the player's button for an action.
+/// `{pause:500}` waits that many ms before showing the rest and `{speed:60}` changes how many ms
+/// each character takes to appear. Upper case codes like `{PLAYER}`, `{RIVAL}` or `{POKEMON}`
+/// are replaced by the variable of the same name in lower case, and left as they are when it's
+/// missing so a writer can tell. `{{` is a brace.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct RichText {
+    glyphs: Vec<Glyph>,
+}
+
+impl RichText {
+    /// Parses text with markup, replacing variables with the strings in `vars`, e.g.
+    /// `{ "player": "Red" }`. Fails on codes it doesn't know and `{/color}` outside of a span.
+    pub fn parse(text: &str, vars: &Dict) -> Result<Self, String> {
+        let mut glyphs = Vec::new();
+        let mut colors: Vec<Color> = Vec::new(); // open spans, innermost last
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '{' {
+                glyphs.push(Glyph::Char(c, colors.last().copied()));
+                continue;
+            }
+            if chars.peek() == Some(&'{') {
+                chars.next();
+                glyphs.push(Glyph::Char('{', colors.last().copied()));
+                continue;
+            }
+            let code = read_code(&mut chars)?;
+            let (name, arg) = match code.split_once(':') {
+                Some((name, arg)) => (name, Some(arg)),
+                None => (code.as_str(), None),
+            };
+            match (name, arg) {
+                ("color", Some(color)) => {
+                    colors.push(color_from_name(color).ok_or_else(|| format!("unknown color \"{}\"", color))?);
+                },
+                ("/color", None) => {
+                    if colors.pop().is_none() {
+                        return Err(String::from("\"{/color}\" without a \"{color:..}\" before it"));
+                    }
+                },
+                ("item", Some(item)) => glyphs.push(Glyph::Item(Key::new(&format!("item_{}", item)))),
+                ("button", Some(action)) => match Action::from_name(action) {
+                    Some(action) => glyphs.push(Glyph::Button(action)),
+                    None => return Err(format!("unknown button \"{}\"", action)),
+                },
+                ("pause", Some(ms)) => glyphs.push(Glyph::Pause(parse_ms(ms)?)),
+                ("speed", Some(ms)) => glyphs.push(Glyph::Speed(parse_ms(ms)?)),
+                (var, None) if !var.is_empty() && var.chars().all(|c| c.is_ascii_uppercase() || c == '_') => {
+                    let value = match vars.get(&Key::new(&var.to_lowercase())) {
+                        Some(DictValue::String(value)) => value.clone(),
+                        _ => format!("{{{}}}", var),
+                    };
+                    glyphs.extend(value.chars().map(|c| Glyph::Char(c, colors.last().copied())));
+                },
+                _ => return Err(format!("unknown code \"{{{}}}\"", code)),
+            }
+        }
+        Ok(Self { glyphs })
+    }
+
+    /// Text shown as it is, without looking for markup, e.g. in place of text that didn't parse
+    pub fn literal(text: &str) -> Self {
+        Self { glyphs: text.chars().map(|c| Glyph::Char(c, None)).collect() }
+    }
+
+    /// The characters of the text without the markup
+    pub fn plain(&self) -> String {
+        self.glyphs
+            .iter()
+            .filter_map(|glyph| match glyph {
+                Glyph::Char(c, _) => Some(*c),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Splits the text into lines that fit in `width` pixels like `TextSettings::wrap`, breaking
+    /// between words where possible
+    pub fn wrap(&self, settings: &TextSettings, width: u32) -> Vec<RichLine> {
+        let space = settings.char_width();
+        let mut lines = Vec::new();
+        for paragraph in self.glyphs.split(|glyph| matches!(glyph, Glyph::Char('\n', _))) {
+            let mut line = Vec::new();
+            let mut line_width = 0;
+            let words = paragraph.split(|glyph| matches!(glyph, Glyph::Char(c, _) if c.is_whitespace()));
+            for word in words.filter(|word| !word.is_empty()) {
+                let mut word = word.to_vec();
+                if !line.is_empty() && line_width + space + self::width(&word, settings) > width {
+                    lines.push(std::mem::take(&mut line));
+                    line_width = 0;
+                }
+                // words longer than a whole line are broken up
+                while word.len() > 1 && self::width(&word, settings) > width {
+                    let fits = (1..word.len()).rev().find(|end| self::width(&word[..*end], settings) <= width);
+                    let rest = word.split_off(fits.unwrap_or(1));
+                    lines.push(word);
+                    word = rest;
+                }
+                if !line.is_empty() {
+                    line.push(Glyph::Char(' ', None));
+                    line_width += space;
+                }
+                line_width += self::width(&word, settings);
+                line.extend(word);
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Wraps the text to the width of `rect` and splits it into pages of as many lines as fit in
+    /// its height
+    pub fn paginate(&self, settings: &TextSettings, rect: Rect) -> Vec<Vec<RichLine>> {
+        let lines_per_page = (rect.height() / settings.line_height()).max(1) as usize;
+        self.wrap(settings, rect.width()).chunks(lines_per_page).map(|page| page.to_vec()).collect()
+    }
+}
+
+/// Reads a code up to its closing brace, the opening one already read
+fn read_code(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut code = String::new();
+    loop {
+        match chars.next() {
+            Some('}') => return Ok(code),
+            Some(c) => code.push(c),
+            None => return Err(format!("\"{{{}\" is never closed", code)),
+        }
+    }
+}
+
+fn parse_ms(ms: &str) -> Result<u32, String> {
+    ms.parse().or_else(|_| Err(format!("\"{}\" is not a number of ms", ms)))
+}
+
+/// Colors spans of text can have, by name: `red`, `blue`, `green`, `yellow`, `gray`, `white` and
+/// `black`, or any as `#rrggbb`
+pub fn color_from_name(name: &str) -> Option<Color> {
+    match name {
+        "red" => Some(Color::RGB(224, 56, 56)),
+        "blue" => Some(Color::RGB(48, 96, 232)),
+        "green" => Some(Color::RGB(40, 160, 64)),
+        "yellow" => Some(Color::RGB(232, 184, 32)),
+        "gray" => Some(Color::RGB(128, 128, 136)),
+        "white" => Some(Color::WHITE),
+        "black" => Some(Color::BLACK),
+        _ if name.len() == 7 && name.starts_with('#') => {
+            let channel = |i: usize| u8::from_str_radix(name.get(i..i + 2)?, 16).ok();
+            Some(Color::RGB(channel(1)?, channel(3)?, channel(5)?))
+        },
+        _ => None,
+    }
+}
+
+/// Builds the sprites for the first `shown` glyphs of a line starting at `pos`. `buttons` has
+/// the glyph sprites of the player's buttons by action (see `glyphs::glyph`); actions without
+/// one show their initial instead.
+pub fn line_sprites(
+    line: &[Glyph],
+    shown: usize,
+    pos: (i32, i32),
+    settings: &TextSettings,
+    buttons: &HashMap<Action, SpriteId>,
+) -> Vec<Sprite> {
+    let (char_width, height) = (settings.char_width(), settings.line_height());
+    let mut sprites = Vec::new();
+    let mut x = pos.0;
+    for glyph in line.iter().take(shown) {
+        match *glyph {
+            Glyph::Char(c, _) if c.is_whitespace() => {},
+            Glyph::Char(c, color) => {
+                let rect = Rect::new(x, pos.1, char_width, height);
+                let sprite = Key::new(&format!("font_{}_{}", settings.family.name(), c as u32));
+                sprites.push(match color {
+                    Some(color) => Sprite::Tinted { rect, sprite, color },
+                    None => Sprite::Texture { rect, sprite },
+                });
+            },
+            Glyph::Item(sprite) => sprites.push(Sprite::Texture { rect: Rect::new(x, pos.1, height, height), sprite }),
+            Glyph::Button(action) => match buttons.get(&action) {
+                Some(sprite) => {
+                    sprites.push(Sprite::Texture { rect: Rect::new(x, pos.1, height, height), sprite: *sprite });
+                },
+                None => {
+                    let initial = action.name()[..1].to_uppercase();
+                    sprites.extend(settings.sprites(&initial, (x + (height - char_width) as i32 / 2, pos.1)));
+                },
+            },
+            Glyph::Pause(_) | Glyph::Speed(_) => {},
+        }
+        x += glyph.width(settings) as i32;
+    }
+    sprites
+}
+
+/// When (ms into the page) each glyph of a page has appeared, in order. Every page starts at
+/// `TEXT_SPEED`, and spaces appear along with the character before them.
+fn reveal_times(page: &[RichLine]) -> Vec<u32> {
+    let mut speed = TEXT_SPEED;
+    let mut time = 0;
+    page.iter()
+        .flatten()
+        .map(|glyph| {
+            match glyph {
+                Glyph::Char(c, _) if c.is_whitespace() => {},
+                Glyph::Pause(ms) => time += ms,
+                Glyph::Speed(ms) => speed = *ms,
+                _ => time += speed,
+            }
+            time
+        })
+        .collect()
+}
+
+/// How many glyphs of a page, counted across its lines, have appeared `elapsed` ms after it was
+/// first shown
+pub fn revealed(page: &[RichLine], elapsed: u32) -> usize {
+    reveal_times(page).iter().take_while(|time| **time <= elapsed).count()
+}
+
+/// Time (ms) until all of a page has appeared
+pub fn reveal_time(page: &[RichLine]) -> u32 {
+    reveal_times(page).last().copied().unwrap_or(0)
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use sdl2::event::{ Event, EventType };
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use crate::dict::*;
+use crate::engine::*;
+use crate::input::Action;
+use crate::intern::Key;
+use crate::text::TextSettings;
+
+/// Where a minigame is in its flow
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Phase {
+    Countdown,
+    Playing,
+    Results,
+}
+
+impl Phase {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Countdown => "countdown",
+            Self::Playing => "playing",
+            Self::Results => "results",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [Self::Countdown, Self::Playing, Self::Results].iter().copied().find(|phase| phase.name() == name)
+    }
+}
+
+/// Points for pressing the next action of the pattern
+pub const MINIGAME_HIT_POINTS: u32 = 10;
+/// Points lost for pressing anything else
+pub const MINIGAME_MISS_POINTS: u32 = 5;
+
+/// The flow and scoring shared by timed minigames (contests, Pokéathlon events, bike races): a
+/// countdown, then `duration` ms of playing where the player presses the actions of `pattern`
+/// over and over in order, then a results screen. A pattern of one action is a button masher.
+///
+/// Can be driven from a game's own scene through `tick` and `press`, or used as-is through
+/// `create_minigame`. Stored in a scene's state with `to_dict` between callbacks.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Minigame {
+    pub countdown: u32, // ms
+    pub duration: u32, // ms
+    pub pattern: Vec<Action>,
+    pub thresholds: Vec<u32>, // scores needed for each reward tier, lowest first
+    phase: Phase,
+    elapsed: u32, // ms into the current phase
+    next: usize, // index into the pattern
+    score: u32,
+}
+
+impl Minigame {
+    pub fn new(countdown: u32, duration: u32, pattern: Vec<Action>, thresholds: Vec<u32>) -> Self {
+        Self { countdown, duration, pattern, thresholds, phase: Phase::Countdown, elapsed: 0, next: 0, score: 0 }
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    /// The action the player has to press next
+    pub fn next_action(&self) -> Option<Action> {
+        self.pattern.get(self.next).copied()
+    }
+
+    /// Time left in the current phase (ms), 0 on the results screen
+    pub fn time_left(&self) -> u32 {
+        match self.phase {
+            Phase::Countdown => self.countdown.saturating_sub(self.elapsed),
+            Phase::Playing => self.duration.saturating_sub(self.elapsed),
+            Phase::Results => 0,
+        }
+    }
+
+    /// Highest reward tier the score reached, 0 if none
+    pub fn reward_tier(&self) -> u32 {
+        self.thresholds.iter().filter(|threshold| self.score >= **threshold).count() as u32
+    }
+
+    /// Advances time, moving on to the next phase when the current one runs out
+    pub fn tick(&mut self, interval: u32) {
+        self.elapsed += interval;
+        match self.phase {
+            Phase::Countdown if self.elapsed >= self.countdown => {
+                self.elapsed -= self.countdown;
+                self.phase = Phase::Playing;
+            },
+            Phase::Playing if self.elapsed >= self.duration => {
+                self.elapsed = 0;
+                self.phase = Phase::Results;
+            },
+            _ => {},
+        }
+    }
+
+    /// Scores a press while playing. Returns true if it was the right action.
+    pub fn press(&mut self, action: Action) -> bool {
+        if self.phase != Phase::Playing || self.pattern.is_empty() {
+            return false;
+        }
+        if self.next_action() == Some(action) {
+            self.score += MINIGAME_HIT_POINTS;
+            self.next = (self.next + 1) % self.pattern.len();
+            true
+        } else {
+            self.score = self.score.saturating_sub(MINIGAME_MISS_POINTS);
+            false
+        }
+    }
+
+    /// Props for the parent scene once the minigame is over: `score` and `tier`
+    pub fn results(&self) -> Dict {
+        let mut props = HashMap::new();
+        props.insert(Key::new("score"), DictValue::U32(self.score));
+        props.insert(Key::new("tier"), DictValue::U32(self.reward_tier()));
+        props
+    }
+
+    pub fn to_dict(&self) -> Dict {
+        let numbers = |values: &[u32]| DictValue::Array(values.iter().map(|value| DictValue::U32(*value)).collect());
+        let mut dict = HashMap::new();
+        dict.insert(Key::new("countdown"), DictValue::U32(self.countdown));
+        dict.insert(Key::new("duration"), DictValue::U32(self.duration));
+        dict.insert(
+            Key::new("pattern"),
+            DictValue::Array(self.pattern.iter().map(|action| DictValue::String(action.name().to_string())).collect()),
+        );
+        dict.insert(Key::new("thresholds"), numbers(&self.thresholds));
+        dict.insert(Key::new("phase"), DictValue::String(self.phase.name().to_string()));
+        dict.insert(Key::new("elapsed"), DictValue::U32(self.elapsed));
+        dict.insert(Key::new("next"), DictValue::U32(self.next as u32));
+        dict.insert(Key::new("score"), DictValue::U32(self.score));
+        dict
+    }
+
+    /// Reads a minigame from `to_dict`, or from the props of `create_minigame`, which only need
+    /// the settings. Numbers may also be `I64`, as they are in JSON data.
+    pub fn from_dict(dict: &Dict) -> Self {
+        let get = |key: &str, default: u32| dict_u32(dict, key).unwrap_or(default);
+        let array = |key: &str| match dict.get(&Key::new(key)) {
+            Some(DictValue::Array(values)) => values.clone(),
+            _ => Vec::new(),
+        };
+        let mut minigame = Self::new(
+            get("countdown", 3_000),
+            get("duration", 10_000),
+            array("pattern")
+                .iter()
+                .filter_map(|action| if let DictValue::String(action) = action { Action::from_name(action) } else { None })
+                .collect(),
+            array("thresholds").iter().filter_map(DictValue::as_u32).collect(),
+        );
+        if let Some(DictValue::String(phase)) = dict.get(&Key::new("phase")) {
+            minigame.phase = Phase::from_name(phase).unwrap_or(Phase::Countdown);
+        }
+        minigame.elapsed = get("elapsed", 0);
+        minigame.next = get("next", 0) as usize % minigame.pattern.len().max(1);
+        minigame.score = get("score", 0);
+        minigame
+    }
+}
+
+/// Creates a ready-made minigame scene from its settings in the props (see `Minigame::from_dict`:
+/// `countdown`, `duration`, `pattern` as action names, `thresholds`), and optionally `text`, the
+/// player's `TextSettings` as a dict. Shows the countdown, the time left, the score and the next
+/// action to press, then the results. Pressing A on the results quits with `Minigame::results`
+/// as props, so the parent can hand out the reward.
+pub fn create_minigame(props: Dict) -> Scene {
+    let mut state = Minigame::from_dict(&props).to_dict();
+    if let Some(text) = props.get(&Key::new("text")) {
+        state.insert(Key::new("text"), text.clone());
+    }
+    let mut event_callbacks: HashMap<EventType, EventCallbackFn> = HashMap::new();
+    event_callbacks.insert(EventType::KeyDown, minigame_key_down);
+    let mut scene = Scene::new(
+        ClearMode::Color(Color::BLACK),
+        state,
+        Vec::new(),
+        event_callbacks,
+        minigame_on_tick,
+        |_, _, _| SceneFnOutcome::Continue,
+    );
+    layout_minigame(&mut scene);
+    scene
+}
+
+fn minigame_on_tick(scene: &mut Scene, _ctx: &mut EngineContext, interval: Duration) -> SceneFnOutcome {
+    let mut minigame = Minigame::from_dict(scene.state());
+    minigame.tick(interval.as_millis() as u32);
+    scene.state().extend(minigame.to_dict());
+    layout_minigame(scene);
+    SceneFnOutcome::Continue
+}
+
+fn minigame_key_down(scene: &mut Scene, ctx: &mut EngineContext, event: &Event) -> SceneFnOutcome {
+    let action = match *event {
+        Event::KeyDown { keycode: Some(key), repeat: false, .. } => ctx.input.map.get(key),
+        _ => None,
+    };
+    let action = match action {
+        Some(action) => action,
+        None => return SceneFnOutcome::Continue,
+    };
+    let mut minigame = Minigame::from_dict(scene.state());
+    if minigame.phase() == Phase::Results {
+        return match action {
+            Action::A => SceneFnOutcome::Quit(minigame.results()),
+            _ => SceneFnOutcome::Continue,
+        };
+    }
+    minigame.press(action);
+    scene.state().extend(minigame.to_dict());
+    layout_minigame(scene);
+    SceneFnOutcome::Continue
+}
+
+fn layout_minigame(scene: &mut Scene) {
+    let minigame = Minigame::from_dict(scene.state());
+    let text = match scene.state().get(&Key::new("text")) {
+        Some(DictValue::Dict(text)) => TextSettings::from_dict(text),
+        _ => TextSettings::default(),
+    };
+    let line = text.line_height() as i32;
+    let mut sprites = Vec::new();
+    match minigame.phase() {
+        Phase::Countdown => {
+            let seconds = minigame.time_left().div_ceil(1000);
+            sprites.extend(text.sprites(&seconds.to_string(), (156, 112)));
+        },
+        Phase::Playing => {
+            sprites.extend(text.sprites(&format!("Time {}", minigame.time_left().div_ceil(1000)), (8, 8)));
+            sprites.extend(text.sprites(&format!("Score {}", minigame.score()), (8, 8 + line)));
+            if let Some(next) = minigame.next_action() {
+                sprites.push(Sprite::Rect { rect: Rect::new(136, 96, 48, 48), color: Color::RGB(64, 64, 128) });
+                sprites.push(Sprite::Texture {
+                    rect: Rect::new(144, 104, 32, 32),
+                    sprite: Key::new(&format!("button_{}", next.name())),
+                });
+            }
+        },
+        Phase::Results => {
+            sprites.extend(text.sprites(&format!("Score {}", minigame.score()), (8, 8)));
+            sprites.extend(text.sprites(&format!("Rank {}", minigame.reward_tier()), (8, 8 + line)));
+        },
+    }
+    *scene.sprites() = sprites;
+}
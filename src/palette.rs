@@ -0,0 +1,105 @@
+use sdl2::pixels::Color;
+
+/// Accessibility palettes that UI colors are passed through before being drawn. The color-blind
+/// palettes move the color information a player can't see into channels they can, instead of
+/// simulating what they see.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Palette {
+    Default,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+    HighContrast,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 5] = [
+        Palette::Default,
+        Palette::Protanopia,
+        Palette::Deuteranopia,
+        Palette::Tritanopia,
+        Palette::HighContrast,
+    ];
+
+    /// Name shown in the options menu
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Default => "Default",
+            Self::Protanopia => "Protanopia",
+            Self::Deuteranopia => "Deuteranopia",
+            Self::Tritanopia => "Tritanopia",
+            Self::HighContrast => "High Contrast",
+        }
+    }
+
+    /// Maps a UI color to the color it should be drawn with in this palette
+    pub fn apply(&self, color: Color) -> Color {
+        let matrix = match self {
+            Self::Default => return color,
+            Self::HighContrast => {
+                let snap = |channel: u8| if channel < 128 { 0 } else { 255 };
+                return Color::RGBA(snap(color.r), snap(color.g), snap(color.b), color.a);
+            },
+            Self::Protanopia => [
+                [1.0, 0.0, 0.0],
+                [0.7, 0.3, 0.0],
+                [0.7, 0.0, 1.0],
+            ],
+            Self::Deuteranopia => [
+                [1.0, 0.5, 0.0],
+                [0.0, 0.3, 0.0],
+                [0.0, 0.7, 1.0],
+            ],
+            Self::Tritanopia => [
+                [1.0, 0.0, 0.7],
+                [0.0, 1.0, 0.7],
+                [0.0, 0.0, 0.3],
+            ],
+        };
+        let input = [color.r as f32, color.g as f32, color.b as f32];
+        let channel = |row: [f32; 3]| {
+            (row[0] * input[0] + row[1] * input[1] + row[2] * input[2]).min(255.0) as u8
+        };
+        Color::RGBA(channel(matrix[0]), channel(matrix[1]), channel(matrix[2]), color.a)
+    }
+
+    /// Color modulation for background textures. High contrast dims backgrounds so UI drawn on
+    /// top of them stands out.
+    pub fn background_mod(&self) -> Color {
+        match self {
+            Self::HighContrast => Color::RGB(96, 96, 96),
+            _ => Color::WHITE,
+        }
+    }
+}
+
+/// How effective a move is against its target. Shown in battle with both a color and an icon, so
+/// it can still be read when the colors can't be told apart.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Effectiveness {
+    Immune,
+    NotVeryEffective,
+    Effective,
+    SuperEffective,
+}
+
+impl Effectiveness {
+    pub fn color(&self, palette: Palette) -> Color {
+        palette.apply(match self {
+            Self::Immune => Color::RGB(128, 128, 128),
+            Self::NotVeryEffective => Color::RGB(200, 40, 40),
+            Self::Effective => Color::WHITE,
+            Self::SuperEffective => Color::RGB(40, 200, 40),
+        })
+    }
+
+    /// Name of the icon on the spritesheet
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Self::Immune => "icon_immune",
+            Self::NotVeryEffective => "icon_not_very_effective",
+            Self::Effective => "icon_effective",
+            Self::SuperEffective => "icon_super_effective",
+        }
+    }
+}
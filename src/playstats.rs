@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use crate::dict::*;
+use crate::intern::Key;
+
+/// Name of the global the stats are saved under, so they are saved with the rest of the globals
+pub const PLAY_STATS_GLOBAL: &str = "playstats";
+
+/// Counters kept per save, for the trainer card and for NPCs that comment on them
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum PlayStat {
+    Steps,
+    BattlesWon,
+    Caught,
+    PlayTime, // ms
+    LoginStreak, // days in a row the game was played on
+}
+
+impl PlayStat {
+    pub const ALL: [PlayStat; 5] = [
+        PlayStat::Steps,
+        PlayStat::BattlesWon,
+        PlayStat::Caught,
+        PlayStat::PlayTime,
+        PlayStat::LoginStreak,
+    ];
+
+    /// Name in saves and dialog conditions
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Steps => "steps",
+            Self::BattlesWon => "battles_won",
+            Self::Caught => "caught",
+            Self::PlayTime => "play_time",
+            Self::LoginStreak => "login_streak",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|stat| stat.name() == name)
+    }
+
+    /// Label on the trainer card
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Steps => "Steps",
+            Self::BattlesWon => "Battles won",
+            Self::Caught => "Caught",
+            Self::PlayTime => "Play time",
+            Self::LoginStreak => "Days in a row",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct PlayStats {
+    values: HashMap<PlayStat, u64>,
+    last_login: Option<u64>, // day number, see `login`
+}
+
+impl PlayStats {
+    pub fn get(&self, stat: PlayStat) -> u64 {
+        self.values.get(&stat).copied().unwrap_or(0)
+    }
+
+    pub fn add(&mut self, stat: PlayStat, amount: u64) {
+        *self.values.entry(stat).or_insert(0) += amount;
+    }
+
+    /// Counts a day the game was played on. `day` is any day number that goes up by one each
+    /// day, e.g. days since the Unix epoch in the player's time zone. Returns the login streak if
+    /// this is the first login of the day, so the game can hand out that day's bonus; returns
+    /// `None` for later logins on the same day.
+    pub fn login(&mut self, day: u64) -> Option<u64> {
+        let streak = match self.last_login {
+            Some(last) if last >= day => return None,
+            Some(last) if last + 1 == day => self.get(PlayStat::LoginStreak) + 1,
+            _ => 1,
+        };
+        self.last_login = Some(day);
+        self.values.insert(PlayStat::LoginStreak, streak);
+        Some(streak)
+    }
+
+    /// Checks a dialog condition like `"steps >= 10000"`: a stat name, one of `<`, `<=`, `==`,
+    /// `!=`, `>=` or `>`, and a number. Conditions that can't be read are false.
+    pub fn check(&self, condition: &str) -> bool {
+        let mut parts = condition.split_whitespace();
+        let (stat, op, value) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(stat), Some(op), Some(value), None) => (stat, op, value),
+            _ => return false,
+        };
+        let (stat, value) = match (PlayStat::from_name(stat), value.parse::<u64>()) {
+            (Some(stat), Ok(value)) => (self.get(stat), value),
+            _ => return false,
+        };
+        match op {
+            "<" => stat < value,
+            "<=" => stat <= value,
+            "==" => stat == value,
+            "!=" => stat != value,
+            ">=" => stat >= value,
+            ">" => stat > value,
+            _ => false,
+        }
+    }
+
+    pub fn to_dict(&self) -> Dict {
+        let mut dict = HashMap::new();
+        for stat in PlayStat::ALL.iter() {
+            dict.insert(Key::new(stat.name()), DictValue::U64(self.get(*stat)));
+        }
+        if let Some(day) = self.last_login {
+            dict.insert(Key::new("last_login"), DictValue::U64(day));
+        }
+        dict
+    }
+
+    /// Reads stats saved with `to_dict`. Numbers may also come back from JSON as `I64`.
+    pub fn from_dict(dict: &Dict) -> Self {
+        let number = |key: &str| dict.get(&Key::new(key)).and_then(DictValue::as_u64);
+        let mut stats = Self::default();
+        for stat in PlayStat::ALL.iter() {
+            if let Some(value) = number(stat.name()) {
+                stats.values.insert(*stat, value);
+            }
+        }
+        stats.last_login = number("last_login");
+        stats
+    }
+}
+
+/// Formats play time as hours and minutes
+pub fn format_play_time(ms: u64) -> String {
+    let minutes = ms / 60_000;
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+use std::fs;
+use std::sync::mpsc::{ self, Receiver, Sender };
+use serde_json::Value;
+use crate::compress;
+
+/// Files a scene needs, as (name, path) pairs. Backgrounds and sounds end up in the engine's
+/// backgrounds and `Audio` under their names, and data files in the globals, where scenes can
+/// request them through props like any other global.
+#[derive(Clone, Default, Debug)]
+pub struct SceneAssets {
+    pub backgrounds: Vec<(String, String)>,
+    pub data: Vec<(String, String)>, // JSON, which may be compressed (see `compress`), e.g. map data
+    pub sounds: Vec<(String, String)>,
+}
+
+/// A file read by a preload, ready to be turned into a texture, a global or a sound
+pub(crate) enum Loaded {
+    Background(String, Vec<u8>),
+    Data(String, Value),
+    Sound(String, Vec<u8>),
+}
+
+/// What a file of `SceneAssets` is loaded as
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum Kind {
+    Background,
+    Data,
+    Sound,
+}
+
+/// Reads and parses files on a background thread and hands them to the engine, which finishes
+/// loading them on the main thread, since textures and sounds can only be created there
+pub(crate) struct Preloader {
+    sender: Sender<(String, Result<Loaded, String>)>, // with the path read
+    receiver: Receiver<(String, Result<Loaded, String>)>,
+    requested: Vec<SceneAssets>, // since the engine last started reading, see `start`
+    reading: HashSet<String>, // paths being read
+}
+
+impl Preloader {
+    pub(crate) fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { sender, receiver, requested: Vec::new(), reading: HashSet::new() }
+    }
+
+    /// Queues the files of `assets` to be read once the engine calls `start`
+    pub(crate) fn request(&mut self, assets: &SceneAssets) {
+        self.requested.push(assets.clone());
+    }
+
+    /// Starts reading the requested files that aren't being read already and that the engine
+    /// doesn't still hold, which `loaded` tells from their kind and name. Files whose asset was
+    /// evicted or taken out of the globals since they were last read are read again.
+    pub(crate) fn start<F: Fn(Kind, &str) -> bool>(&mut self, loaded: F) {
+        let mut files = Vec::new();
+        for assets in self.requested.drain(..) {
+            let SceneAssets { backgrounds, data, sounds } = assets;
+            for (kind, list) in [(Kind::Background, backgrounds), (Kind::Data, data), (Kind::Sound, sounds)] {
+                for (name, path) in list {
+                    if !loaded(kind, &name) && self.reading.insert(path.clone()) {
+                        files.push((kind, name, path));
+                    }
+                }
+            }
+        }
+        if files.is_empty() {
+            return;
+        }
+        let sender = self.sender.clone();
+        spawn(move || {
+            for (kind, name, path) in files {
+                let loaded = fs::read(&path)
+                    .or_else(|err| Err(format!("{}", err)))
+                    .and_then(|bytes| match kind {
+                        Kind::Background => Ok(Loaded::Background(name, bytes)),
+                        Kind::Data => compress::decompress(bytes).and_then(|bytes| {
+                            serde_json::from_slice(&bytes)
+                                .map(|json| Loaded::Data(name, json))
+                                .or_else(|err| Err(format!("{}", err)))
+                        }),
+                        Kind::Sound => Ok(Loaded::Sound(name, bytes)),
+                    })
+                    .or_else(|err| Err(format!("{}: {}", path, err)));
+                if sender.send((path, loaded)).is_err() {
+                    break; // the engine is gone
+                }
+            }
+        });
+    }
+
+    /// Files that finished reading since the last call
+    pub(crate) fn finished(&mut self) -> Vec<Result<Loaded, String>> {
+        let mut finished = Vec::new();
+        for (path, loaded) in self.receiver.try_iter() {
+            self.reading.remove(&path);
+            finished.push(loaded);
+        }
+        finished
+    }
+}
+
+#[cfg(not(target_os = "emscripten"))]
+fn spawn<F: FnOnce() + Send + 'static>(f: F) {
+    rayon::spawn(f);
+}
+
+/// The browser build has no threads, so files are read right away instead
+#[cfg(target_os = "emscripten")]
+fn spawn<F: FnOnce() + Send + 'static>(f: F) {
+    f();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compressed_data_is_decompressed() {
+        let path = std::env::temp_dir().join(format!("preload-{}.json.zst", std::process::id()));
+        fs::write(&path, compress::compress(br#"{"width": 20}"#).unwrap()).unwrap();
+        let path = path.to_string_lossy().into_owned();
+        let mut preloader = Preloader::new();
+        preloader.request(&SceneAssets { data: vec![("map".to_string(), path.clone())], ..SceneAssets::default() });
+        preloader.start(|_, _| false);
+        let (_, loaded) = preloader.receiver.recv().unwrap();
+        fs::remove_file(&path).unwrap();
+        match loaded {
+            Ok(Loaded::Data(name, json)) => {
+                assert_eq!(name, "map");
+                assert_eq!(json, serde_json::json!({ "width": 20 }));
+            },
+            _ => panic!("the map wasn't loaded"),
+        }
+    }
+
+    #[test]
+    fn files_are_read_again_once_no_longer_loaded() {
+        let path = std::env::temp_dir().join(format!("preload-{}.json", std::process::id()));
+        fs::write(&path, "{}").unwrap();
+        let path = path.to_string_lossy().into_owned();
+        let assets = SceneAssets { data: vec![("map".to_string(), path.clone())], ..SceneAssets::default() };
+        let mut preloader = Preloader::new();
+        preloader.request(&assets);
+        preloader.request(&assets);
+        preloader.start(|_, _| false);
+        // the second request is skipped while the file is being read
+        assert_eq!(finish(&mut preloader), 1);
+
+        preloader.request(&assets);
+        preloader.start(|kind, name| kind == Kind::Data && name == "map");
+        assert!(preloader.reading.is_empty());
+
+        preloader.request(&assets);
+        preloader.start(|_, _| false); // e.g. a scene took the global
+        assert_eq!(finish(&mut preloader), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// Waits for the files being read and returns how many were read
+    fn finish(preloader: &mut Preloader) -> usize {
+        let mut count = 0;
+        while !preloader.reading.is_empty() {
+            count += preloader.finished().len();
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        count
+    }
+}
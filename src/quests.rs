@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use sdl2::event::{ Event, EventType };
+use sdl2::pixels::Color;
+use sdl2::rect::{ Point, Rect };
+use crate::dict::*;
+use crate::engine::*;
+use crate::input::Action;
+use crate::intern::Key;
+use crate::text::TextSettings;
+
+/// Name of the global with the event flags, a dict of flag name to `true`
+pub const FLAGS_GLOBAL: &str = "flags";
+/// Name of the global with the quests' progress, a dict of quest id to the stage it's at. A
+/// quest at the stage after its last is complete.
+pub const QUESTS_GLOBAL: &str = "quests";
+/// Where the region map is drawn in the quest log, and its sprite
+const REGION_MAP: (i32, i32, u32, u32) = (168, 40, 144, 120);
+const REGION_MAP_SPRITE: &str = "region_map";
+const QUESTS_SHOWN: usize = 8;
+
+pub fn is_flag_set(globals: &Dict, flag: Key) -> bool {
+    match globals.get(&Key::new(FLAGS_GLOBAL)) {
+        Some(DictValue::Dict(flags)) => matches!(flags.get(&flag), Some(DictValue::Bool(true))),
+        _ => false,
+    }
+}
+
+pub fn set_flag(globals: &mut Dict, flag: Key) {
+    let flags = globals.entry(Key::new(FLAGS_GLOBAL)).or_insert_with(|| DictValue::Dict(HashMap::new()));
+    if !matches!(flags, DictValue::Dict(_)) {
+        *flags = DictValue::Dict(HashMap::new());
+    }
+    if let DictValue::Dict(flags) = flags {
+        flags.insert(flag, DictValue::Bool(true));
+    }
+}
+
+/// One step of a quest: what the player is told to do, and the flags that are set once they've
+/// done it
+#[derive(Clone, PartialEq, Debug)]
+pub struct QuestStage {
+    pub objective: String,
+    pub complete_when: Vec<Key>, // every one of these flags
+    pub marker: Option<Point>, // where to go, in region map pixels
+}
+
+/// A quest as defined in data, e.g. `{ "name": "Lost Delivery", "start": "met_clerk", "stages":
+/// [{ "objective": "Find the parcel", "flags": ["got_parcel"], "marker": [40, 32] }], "event":
+/// "quest_reward", "props": { "item": "rare_candy" } }`. Without `start` the quest is active from
+/// the start of the game. `event`, if any, is sent with `props` once it's complete (see
+/// `EngineContext::emit`), for the scene that handles it to give out the reward.
+#[derive(Clone)]
+pub struct Quest {
+    pub id: Key,
+    pub name: String,
+    pub start: Option<Key>,
+    pub stages: Vec<QuestStage>,
+    pub event: Option<Key>,
+    pub props: Dict,
+}
+
+/// Where the player is with a quest
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QuestStatus {
+    NotStarted,
+    Active(usize), // the stage
+    Completed,
+}
+
+impl Quest {
+    pub fn from_dict(id: Key, dict: &Dict) -> Result<Self, String> {
+        let error = |message: &str| format!("quest {}: {}", id, message);
+        let string = |value: Option<&DictValue>| match value {
+            Some(DictValue::String(value)) => Some(value.clone()),
+            _ => None,
+        };
+        let stages = match dict.get(&Key::new("stages")) {
+            Some(DictValue::Array(stages)) if !stages.is_empty() => stages
+                .iter()
+                .map(|stage| match stage {
+                    DictValue::Dict(stage) => Ok(QuestStage {
+                        objective: string(stage.get(&Key::new("objective"))).unwrap_or_default(),
+                        complete_when: match stage.get(&Key::new("flags")) {
+                            Some(DictValue::Array(flags)) => flags
+                                .iter()
+                                .filter_map(|flag| string(Some(flag)))
+                                .map(|flag| Key::new(&flag))
+                                .collect(),
+                            _ => Vec::new(),
+                        },
+                        marker: match stage.get(&Key::new("marker")) {
+                            Some(DictValue::Array(marker)) => match (number(marker.first()), number(marker.get(1))) {
+                                (Some(x), Some(y)) => Some(Point::new(x, y)),
+                                _ => None,
+                            },
+                            _ => None,
+                        },
+                    }),
+                    _ => Err(error("a stage is not a dict")),
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err(error("\"stages\" is missing")),
+        };
+        if stages.iter().any(|stage| stage.complete_when.is_empty()) {
+            return Err(error("every stage needs \"flags\" to complete it"));
+        }
+        Ok(Self {
+            id,
+            name: string(dict.get(&Key::new("name"))).unwrap_or_else(|| id.name().to_string()),
+            start: string(dict.get(&Key::new("start"))).map(|start| Key::new(&start)),
+            stages,
+            event: string(dict.get(&Key::new("event"))).map(|event| Key::new(&event)),
+            props: match dict.get(&Key::new("props")) {
+                Some(DictValue::Dict(props)) => props.clone(),
+                _ => Dict::new(),
+            },
+        })
+    }
+
+    /// Where the player is with the quest, from the quests' progress (the `QUESTS_GLOBAL`)
+    pub fn status(&self, progress: &Dict) -> QuestStatus {
+        match progress.get(&self.id).and_then(|stage| number(Some(stage))) {
+            Some(stage) if stage as usize >= self.stages.len() => QuestStatus::Completed,
+            Some(stage) => QuestStatus::Active(stage.max(0) as usize),
+            None => QuestStatus::NotStarted,
+        }
+    }
+}
+
+fn number(value: Option<&DictValue>) -> Option<i32> {
+    match value? {
+        DictValue::I64(n) => Some(*n as i32),
+        DictValue::U64(n) => Some(*n as i32),
+        DictValue::U32(n) => Some(*n as i32),
+        DictValue::I32(n) => Some(*n),
+        _ => None,
+    }
+}
+
+/// Every quest of the game, defined in data. The game calls `update` whenever it sets flags,
+/// which starts quests, moves them through their stages and completes them.
+#[derive(Clone, Default)]
+pub struct Quests {
+    quests: Vec<Quest>, // by id
+}
+
+impl Quests {
+    /// Reads the quests from a dict of quest id to quest (see `Quest::from_dict`)
+    pub fn from_dict(dict: &Dict) -> Result<Self, String> {
+        let mut quests = Vec::new();
+        for (id, quest) in dict {
+            match quest {
+                DictValue::Dict(quest) => quests.push(Quest::from_dict(*id, quest)?),
+                _ => return Err(format!("quest {}: not a dict", id)),
+            }
+        }
+        quests.sort_by(|a, b| a.id.name().cmp(b.id.name()));
+        Ok(Self { quests })
+    }
+
+    pub fn quests(&self) -> &[Quest] {
+        &self.quests
+    }
+
+    /// Starts the quests whose start flag is set and moves every active quest past the stages
+    /// whose flags are all set, so setting a later stage's flags early skips ahead. Sends the
+    /// reward event of every quest completed. Returns the ids of the quests that changed.
+    pub fn update(&self, globals: &mut Dict, ctx: &mut EngineContext) -> Vec<Key> {
+        let progress = match globals.get(&Key::new(QUESTS_GLOBAL)) {
+            Some(DictValue::Dict(progress)) => progress.clone(),
+            _ => Dict::new(),
+        };
+        let mut changed = Vec::new();
+        let mut updated = progress.clone();
+        for quest in &self.quests {
+            let mut stage = match quest.status(&progress) {
+                QuestStatus::Completed => continue,
+                QuestStatus::Active(stage) => stage,
+                QuestStatus::NotStarted if quest.start.is_none_or(|flag| is_flag_set(globals, flag)) => 0,
+                QuestStatus::NotStarted => continue,
+            };
+            let before = quest.status(&progress);
+            while stage < quest.stages.len()
+                && quest.stages[stage].complete_when.iter().all(|flag| is_flag_set(globals, *flag))
+            {
+                stage += 1;
+            }
+            if before == QuestStatus::Active(stage) {
+                continue;
+            }
+            updated.insert(quest.id, DictValue::U32(stage as u32));
+            changed.push(quest.id);
+            if let (true, Some(event)) = (stage == quest.stages.len(), quest.event) {
+                let mut props = quest.props.clone();
+                props.insert(Key::new("quest"), DictValue::String(quest.id.name().to_string()));
+                ctx.emit(event.name(), props);
+            }
+        }
+        globals.insert(Key::new(QUESTS_GLOBAL), DictValue::Dict(updated));
+        changed
+    }
+}
+
+/// Creates the quest log scene. Expects `quests`, the quest definitions (see
+/// `Quests::from_dict`), and `progress`, the `QUESTS_GLOBAL`, and optionally `text`, the
+/// player's `TextSettings` as a dict.
+///
+/// Lists the active quests, or the completed ones after Left or Right. Up and Down select a
+/// quest, whose current objective is shown under the list, with its marker highlighted on the
+/// region map (the `region_map` sprite) when the stage has one. Completed quests list their
+/// stages' objectives instead. B quits.
+pub fn create_quest_log(props: Dict) -> Scene {
+    let mut state = HashMap::new();
+    for key in ["quests", "progress", "text"] {
+        if let Some(value) = props.get(&Key::new(key)) {
+            state.insert(Key::new(key), value.clone());
+        }
+    }
+    state.insert(Key::new("completed"), DictValue::Bool(false));
+    state.insert(Key::new("selected"), DictValue::U32(0));
+    let mut event_callbacks: HashMap<EventType, EventCallbackFn> = HashMap::new();
+    event_callbacks.insert(EventType::KeyDown, quest_log_key_down);
+    let mut scene = Scene::new(
+        ClearMode::Color(Color::RGB(56, 48, 40)),
+        state,
+        Vec::new(),
+        event_callbacks,
+        |_, _, _| SceneFnOutcome::Continue,
+        |_, _, _| SceneFnOutcome::Continue,
+    );
+    scene.set_menu_sounds(true);
+    layout_quest_log(&mut scene);
+    scene
+}
+
+/// The quests the log lists with their status: the active ones, or the completed ones
+fn listed(state: &Dict) -> (bool, Vec<(Quest, QuestStatus)>) {
+    let completed = matches!(state.get(&Key::new("completed")), Some(DictValue::Bool(true)));
+    let quests = match state.get(&Key::new("quests")) {
+        Some(DictValue::Dict(quests)) => Quests::from_dict(quests).unwrap_or_default(),
+        _ => Quests::default(),
+    };
+    let progress = match state.get(&Key::new("progress")) {
+        Some(DictValue::Dict(progress)) => progress.clone(),
+        _ => Dict::new(),
+    };
+    let listed = quests
+        .quests()
+        .iter()
+        .map(|quest| (quest.clone(), quest.status(&progress)))
+        .filter(|(_, status)| match status {
+            QuestStatus::Completed => completed,
+            QuestStatus::Active(_) => !completed,
+            QuestStatus::NotStarted => false,
+        })
+        .collect();
+    (completed, listed)
+}
+
+fn quest_log_key_down(scene: &mut Scene, ctx: &mut EngineContext, event: &Event) -> SceneFnOutcome {
+    let action = match *event {
+        Event::KeyDown { keycode: Some(key), .. } => ctx.input.map.get(key),
+        _ => None,
+    };
+    let (completed, quests) = listed(scene.state());
+    let state = scene.state();
+    let selected = number(state.get(&Key::new("selected"))).unwrap_or(0).max(0) as usize;
+    match action {
+        Some(Action::Up) => {
+            state.insert(Key::new("selected"), DictValue::U32(selected.saturating_sub(1) as u32));
+        },
+        Some(Action::Down) => {
+            let last = quests.len().saturating_sub(1);
+            state.insert(Key::new("selected"), DictValue::U32((selected + 1).min(last) as u32));
+        },
+        Some(Action::Left) | Some(Action::Right) => {
+            state.insert(Key::new("completed"), DictValue::Bool(!completed));
+            state.insert(Key::new("selected"), DictValue::U32(0));
+        },
+        Some(Action::B) => return SceneFnOutcome::Quit(HashMap::new()),
+        _ => return SceneFnOutcome::Continue,
+    }
+    layout_quest_log(scene);
+    SceneFnOutcome::Continue
+}
+
+fn layout_quest_log(scene: &mut Scene) {
+    let (completed, quests) = listed(scene.state());
+    let state = scene.state();
+    let text = match state.get(&Key::new("text")) {
+        Some(DictValue::Dict(text)) => TextSettings::from_dict(text),
+        _ => TextSettings::default(),
+    };
+    let line = text.line_height() as i32;
+    let selected = number(state.get(&Key::new("selected"))).unwrap_or(0).max(0) as usize;
+    let highlight = Color::RGB(248, 200, 96);
+
+    let mut sprites = Vec::new();
+    sprites.extend(text.sprites(if completed { "< Completed >" } else { "< Active >" }, (16, 12)));
+    let first = selected.saturating_sub(QUESTS_SHOWN - 1);
+    for (i, (quest, _)) in quests.iter().enumerate().skip(first).take(QUESTS_SHOWN) {
+        let y = 16 + line + 8 + (i - first) as i32 * (line + 4);
+        if i == selected {
+            sprites.push(Sprite::Rect { rect: Rect::new(12, y - 2, 148, line as u32 + 4), color: highlight });
+        }
+        sprites.extend(text.sprites(&quest.name, (16, y)));
+    }
+    if quests.is_empty() {
+        sprites.extend(text.sprites("No quests.", (16, 16 + line + 8)));
+    }
+
+    let map = Rect::new(REGION_MAP.0, REGION_MAP.1, REGION_MAP.2, REGION_MAP.3);
+    sprites.push(Sprite::Texture { rect: map, sprite: Key::new(REGION_MAP_SPRITE) });
+    let mut y = 172;
+    match quests.get(selected) {
+        Some((quest, QuestStatus::Active(stage))) => {
+            let stage = &quest.stages[(*stage).min(quest.stages.len() - 1)];
+            sprites.extend(text.sprites(&stage.objective, (16, y)));
+            if let Some(marker) = stage.marker {
+                let center = Point::new(map.x() + marker.x(), map.y() + marker.y());
+                sprites.push(Sprite::Rect { rect: Rect::from_center(center, 8, 8), color: Color::RGB(232, 56, 56) });
+                sprites.push(Sprite::Rect { rect: Rect::from_center(center, 4, 4), color: highlight });
+            }
+        },
+        Some((quest, _)) => {
+            for stage in &quest.stages {
+                sprites.extend(text.sprites(&stage.objective, (16, y)));
+                y += line;
+            }
+        },
+        None => {},
+    }
+    *scene.sprites() = sprites;
+}
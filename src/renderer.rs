@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{
+    BlendMode,
+    Canvas,
+    RenderTarget,
+};
+use crate::engine::{ Sprite, SpriteSheet };
+use crate::intern::SpriteId;
+use crate::text::TextSettings;
+use crate::textures::TextureCache;
+use crate::tilemap::{ self, Tilemap, TilemapRenderer };
+
+/// Everything scenes need to draw a frame. Scenes only draw through this trait, so they don't
+/// depend on SDL's canvas and another backend (e.g. wgpu, or a software one for tests) can be
+/// swapped in. Sprite and rect positions are in world coordinates and are moved by the camera.
+pub trait Renderer {
+    fn clear(&mut self, color: Color);
+
+    /// Draws a background texture, ignoring the camera. Returns false if it doesn't exist.
+    fn draw_background(&mut self, name: &str, src: Option<Rect>, dst: Option<Rect>) -> bool;
+
+    /// Draws a sprite from the spritesheet. Returns false if it isn't on the spritesheet.
+    fn draw_sprite(&mut self, sprite: SpriteId, dst: Rect) -> bool;
+
+    /// Draws a sprite multiplied by a color, e.g. a white glyph as colored text. Renderers that
+    /// can't tint draw it as it is. Returns false if it isn't on the spritesheet.
+    fn draw_tinted(&mut self, sprite: SpriteId, dst: Rect, _color: Color) -> bool {
+        self.draw_sprite(sprite, dst)
+    }
+
+    /// Fills a rect with a color, blended over what is under it if the color is translucent
+    fn draw_rect(&mut self, rect: Rect, color: Color);
+
+    /// Draws the part of a tilemap the camera sees. Returns the tiles that aren't on the
+    /// spritesheet. Renderers draw tile by tile unless they have a faster way.
+    fn draw_tilemap(&mut self, map: &Tilemap) -> Vec<SpriteId> {
+        tilemap::draw_tiles(self, map)
+    }
+
+    /// Draws a line of text with its glyph sprites
+    fn draw_text(&mut self, text: &str, pos: (i32, i32), settings: &TextSettings) {
+        for glyph in settings.sprites(text, pos) {
+            if let Sprite::Texture { rect, sprite } = glyph {
+                self.draw_sprite(sprite, rect);
+            }
+        }
+    }
+
+    /// Sets the top left of the visible part of the world
+    fn set_camera(&mut self, pos: (i32, i32));
+
+    fn camera(&self) -> (i32, i32);
+
+    /// The area being drawn to, in screen coordinates
+    fn viewport(&self) -> Rect;
+
+    fn present(&mut self);
+}
+
+/// `Renderer` backed by an SDL canvas, either a window or a software surface
+pub struct SdlRenderer<'r, 'a, T: RenderTarget> {
+    canvas: &'r mut Canvas<T>,
+    spritesheet: &'r mut SpriteSheet<'a>, // mutable for tinting
+    backgrounds: &'r TextureCache<'a>,
+    tilemaps: Option<&'r mut TilemapRenderer<'a>>, // see `with_tilemaps`
+    camera: (i32, i32),
+}
+
+impl<'r, 'a, T: RenderTarget> SdlRenderer<'r, 'a, T> {
+    pub fn new(
+        canvas: &'r mut Canvas<T>,
+        spritesheet: &'r mut SpriteSheet<'a>,
+        backgrounds: &'r TextureCache<'a>,
+    ) -> Self {
+        Self { canvas, spritesheet, backgrounds, tilemaps: None, camera: (0, 0) }
+    }
+
+    /// Draws tilemaps from the chunks cached by `tilemaps` instead of tile by tile. Its textures
+    /// have to be from the canvas's texture creator.
+    pub fn with_tilemaps(mut self, tilemaps: &'r mut TilemapRenderer<'a>) -> Self {
+        self.tilemaps = Some(tilemaps);
+        self
+    }
+
+    fn to_screen(&self, rect: Rect) -> Rect {
+        Rect::new(rect.x() - self.camera.0, rect.y() - self.camera.1, rect.width(), rect.height())
+    }
+}
+
+impl<'r, 'a, T: RenderTarget> Renderer for SdlRenderer<'r, 'a, T> {
+    fn clear(&mut self, color: Color) {
+        self.canvas.set_draw_color(color);
+        self.canvas.clear();
+    }
+
+    fn draw_background(&mut self, name: &str, src: Option<Rect>, dst: Option<Rect>) -> bool {
+        match self.backgrounds.get(name) {
+            Some(background) => {
+                let _ = self.canvas.copy(background, src, dst);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn draw_sprite(&mut self, sprite: SpriteId, dst: Rect) -> bool {
+        match self.spritesheet.get(sprite) {
+            Some(src) => {
+                let dst = self.to_screen(dst);
+                let _ = self.canvas.copy(&self.spritesheet.texture, *src, dst);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn draw_tinted(&mut self, sprite: SpriteId, dst: Rect, color: Color) -> bool {
+        let src = match self.spritesheet.get(sprite) {
+            Some(src) => *src,
+            None => return false,
+        };
+        let dst = self.to_screen(dst);
+        let texture = &mut self.spritesheet.texture;
+        texture.set_color_mod(color.r, color.g, color.b);
+        let _ = self.canvas.copy(texture, src, dst);
+        texture.set_color_mod(255, 255, 255);
+        true
+    }
+
+    fn draw_tilemap(&mut self, map: &Tilemap) -> Vec<SpriteId> {
+        let viewport = self.canvas.viewport();
+        let camera = Rect::new(self.camera.0, self.camera.1, viewport.width(), viewport.height());
+        match &mut self.tilemaps {
+            // a chunk that fails to draw is skipped, like a failed copy of a sprite
+            Some(tilemaps) => tilemaps.render(self.canvas, map, self.spritesheet, camera).unwrap_or_default(),
+            None => tilemap::draw_tiles(self, map),
+        }
+    }
+
+    fn draw_rect(&mut self, rect: Rect, color: Color) {
+        let rect = self.to_screen(rect);
+        // translucent rects are blended over what's under them, e.g. to fade a sprite
+        self.canvas.set_blend_mode(if color.a < 255 { BlendMode::Blend } else { BlendMode::None });
+        self.canvas.set_draw_color(color);
+        let _ = self.canvas.fill_rect(rect);
+    }
+
+    fn set_camera(&mut self, pos: (i32, i32)) {
+        self.camera = pos;
+    }
+
+    fn camera(&self) -> (i32, i32) {
+        self.camera
+    }
+
+    fn viewport(&self) -> Rect {
+        self.canvas.viewport()
+    }
+
+    fn present(&mut self) {
+        self.canvas.present();
+    }
+}
+
+/// `Renderer` that draws nothing, for a headless engine (see `Engine::new_headless`). It still
+/// knows which sprites are on the spritesheet, so scenes using missing ones are caught without a
+/// window.
+pub struct NullRenderer<'r> {
+    index: &'r HashMap<SpriteId, Rect>,
+    viewport: Rect,
+    camera: (i32, i32),
+}
+
+impl<'r> NullRenderer<'r> {
+    pub fn new(index: &'r HashMap<SpriteId, Rect>, size: (u32, u32)) -> Self {
+        Self { index, viewport: Rect::new(0, 0, size.0, size.1), camera: (0, 0) }
+    }
+}
+
+impl<'r> Renderer for NullRenderer<'r> {
+    fn clear(&mut self, _color: Color) {}
+
+    fn draw_background(&mut self, _name: &str, _src: Option<Rect>, _dst: Option<Rect>) -> bool {
+        false
+    }
+
+    fn draw_sprite(&mut self, sprite: SpriteId, _dst: Rect) -> bool {
+        self.index.contains_key(&sprite)
+    }
+
+    fn draw_rect(&mut self, _rect: Rect, _color: Color) {}
+
+    fn set_camera(&mut self, pos: (i32, i32)) {
+        self.camera = pos;
+    }
+
+    fn camera(&self) -> (i32, i32) {
+        self.camera
+    }
+
+    fn viewport(&self) -> Rect {
+        self.viewport
+    }
+
+    fn present(&mut self) {}
+}
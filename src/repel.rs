@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+use sdl2::event::{ Event, EventType };
+use crate::bag;
+use crate::dialog::DialogBox;
+use crate::dict::*;
+use crate::encounter::{ Encounter, EncounterModel, EncounterSlot, Step };
+use crate::engine::*;
+use crate::input::Action;
+use crate::intern::Key;
+use crate::text::TextSettings;
+
+/// Name of the global with the steps left on the active repel, missing while none is
+pub const REPEL_STEPS_GLOBAL: &str = "repel_steps";
+/// Name of the global with the item of the active repel, which the player is offered another of
+/// when it wears off
+pub const REPEL_ITEM_GLOBAL: &str = "repel_item";
+
+/// Steps left on the active repel, 0 if there is none
+pub fn steps_left(globals: &Dict) -> u32 {
+    dict_u32(globals, REPEL_STEPS_GLOBAL).unwrap_or(0)
+}
+
+pub fn is_active(globals: &Dict) -> bool {
+    steps_left(globals) > 0
+}
+
+/// Starts a repel that lasts `steps`. Like in the games, a repel can't be used while another is
+/// still active, so this returns false and changes nothing then.
+pub fn start(globals: &mut Dict, item: Key, steps: u32) -> bool {
+    if is_active(globals) || steps == 0 {
+        return false;
+    }
+    globals.insert(Key::new(REPEL_STEPS_GLOBAL), DictValue::U32(steps));
+    globals.insert(Key::new(REPEL_ITEM_GLOBAL), DictValue::String(item.name().to_string()));
+    true
+}
+
+/// Takes a repel out of the bag and starts it, see `start`. Returns false, leaving the bag as it
+/// was, if the player has none, one is still active or `steps` is 0.
+pub fn use_from_bag(globals: &mut Dict, item: Key, steps: u32) -> bool {
+    if is_active(globals) || steps == 0 || !bag::take(globals, item) {
+        return false;
+    }
+    start(globals, item, steps)
+}
+
+/// Counts down a step the player took. Returns the repel's item on the step it wears off, for the
+/// overworld to show the prompt (see `create_repel_prompt`).
+pub fn step(globals: &mut Dict) -> Option<Key> {
+    let steps = steps_left(globals);
+    if steps == 0 {
+        return None;
+    }
+    if steps > 1 {
+        globals.insert(Key::new(REPEL_STEPS_GLOBAL), DictValue::U32(steps - 1));
+        return None;
+    }
+    globals.remove(&Key::new(REPEL_STEPS_GLOBAL));
+    match globals.get(&Key::new(REPEL_ITEM_GLOBAL)) {
+        Some(DictValue::String(item)) => Some(Key::new(item)),
+        _ => Some(Key::new("repel")),
+    }
+}
+
+/// Keeps wild pokemon of a lower level than the party's lead from appearing while a repel is
+/// active, on top of another model. Everything else, including the encounter chance, is the
+/// inner model's, so the rolls of steps stay the same with or without a repel. The overworld
+/// calls `update` before each step.
+pub struct Repelled<M: EncounterModel> {
+    pub inner: M,
+    lead_level: u32,
+    active: bool,
+}
+
+impl<M: EncounterModel> Repelled<M> {
+    pub fn new(inner: M) -> Self {
+        Self { inner, lead_level: 0, active: false }
+    }
+
+    /// Catches up with the repel globals and the level of the first pokemon in the party that
+    /// isn't fainted
+    pub fn update(&mut self, globals: &Dict, lead_level: u32) {
+        self.active = is_active(globals);
+        self.lead_level = lead_level;
+    }
+}
+
+impl<M: EncounterModel> EncounterModel for Repelled<M> {
+    fn chance(&mut self, step: &Step) -> f64 {
+        self.inner.chance(step)
+    }
+
+    fn weight(&mut self, step: &Step, slot: &EncounterSlot) -> u32 {
+        self.inner.weight(step, slot)
+    }
+
+    fn shiny_odds(&mut self, species: Key) -> u32 {
+        self.inner.shiny_odds(species)
+    }
+
+    fn allows(&mut self, encounter: &Encounter) -> bool {
+        (!self.active || encounter.level >= self.lead_level) && self.inner.allows(encounter)
+    }
+
+    fn encountered(&mut self, encounter: &Encounter) {
+        self.inner.encountered(encounter)
+    }
+}
+
+/// Creates the prompt shown when a repel wears off. Expects `item`, the repel that wore off, and
+/// optionally `name`, its name to show, `count`, how many more of it are in the bag, and `text`,
+/// the player's `TextSettings` as a dict.
+///
+/// Says the repel wore off, and if there are more, asks whether to use another. A on Yes quits
+/// with `item` for the overworld to pass to `use_from_bag`; No, B or A with none left quits with
+/// nothing.
+pub fn create_repel_prompt(props: Dict) -> Scene {
+    let mut state = HashMap::new();
+    for key in ["item", "name", "count", "text"] {
+        if let Some(value) = props.get(&Key::new(key)) {
+            state.insert(Key::new(key), value.clone());
+        }
+    }
+    state.insert(Key::new("selected"), DictValue::U32(0));
+    let mut event_callbacks: HashMap<EventType, EventCallbackFn> = HashMap::new();
+    event_callbacks.insert(EventType::KeyDown, repel_prompt_key_down);
+    let mut scene = Scene::new(
+        ClearMode::None,
+        state,
+        Vec::new(),
+        event_callbacks,
+        |_, _, _| SceneFnOutcome::Continue,
+        |_, _, _| SceneFnOutcome::Continue,
+    );
+    scene.set_menu_sounds(true);
+    layout_repel_prompt(&mut scene);
+    scene
+}
+
+/// Whether the prompt offers another repel
+fn has_more(state: &Dict) -> bool {
+    match state.get(&Key::new("count")) {
+        Some(DictValue::U32(count)) => *count > 0,
+        Some(DictValue::I64(count)) => *count > 0,
+        Some(DictValue::U64(count)) => *count > 0,
+        _ => false,
+    }
+}
+
+fn repel_prompt_key_down(scene: &mut Scene, ctx: &mut EngineContext, event: &Event) -> SceneFnOutcome {
+    let action = match *event {
+        Event::KeyDown { keycode: Some(key), repeat: false, .. } => ctx.input.map.get(key),
+        _ => None,
+    };
+    let state = scene.state();
+    let selected = match state.get(&Key::new("selected")) {
+        Some(DictValue::U32(selected)) => *selected,
+        _ => 0,
+    };
+    match action {
+        Some(Action::Up) | Some(Action::Down) if has_more(state) => {
+            state.insert(Key::new("selected"), DictValue::U32(1 - selected.min(1)));
+        },
+        Some(Action::A) if has_more(state) && selected == 0 => {
+            let mut props = HashMap::new();
+            if let Some(item) = state.get(&Key::new("item")) {
+                props.insert(Key::new("item"), item.clone());
+            }
+            return SceneFnOutcome::Quit(props);
+        },
+        Some(Action::A) | Some(Action::B) => return SceneFnOutcome::Quit(HashMap::new()),
+        _ => {},
+    }
+    layout_repel_prompt(scene);
+    SceneFnOutcome::Continue
+}
+
+fn layout_repel_prompt(scene: &mut Scene) {
+    let state = scene.state();
+    let text = match state.get(&Key::new("text")) {
+        Some(DictValue::Dict(text)) => TextSettings::from_dict(text),
+        _ => TextSettings::default(),
+    };
+    let name = match (state.get(&Key::new("name")), state.get(&Key::new("item"))) {
+        (Some(DictValue::String(name)), _) | (None, Some(DictValue::String(name))) => name.clone(),
+        _ => "Repel".to_string(),
+    };
+    let selected = match state.get(&Key::new("selected")) {
+        Some(DictValue::U32(selected)) => *selected as usize,
+        _ => 0,
+    };
+    let dialog = DialogBox::default();
+    let (message, options) = if has_more(state) {
+        (format!("{}'s effect wore off... Use another?", name), vec!["Yes".to_string(), "No".to_string()])
+    } else {
+        (format!("{}'s effect wore off...", name), Vec::new())
+    };
+    let page = dialog.pages(&message, &text).into_iter().next().unwrap_or_default();
+    *scene.sprites() = dialog.sprites(&page, &options, selected, &text);
+}
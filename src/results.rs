@@ -0,0 +1,551 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use sdl2::event::{ Event, EventType };
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use crate::dict::*;
+use crate::engine::*;
+use crate::input::Action;
+use crate::intern::Key;
+use crate::stats::{ Stat, Stats };
+use crate::team::MAX_LEVEL;
+use crate::text::TextSettings;
+
+/// ms the EXP bar takes to fill a whole level
+pub const EXP_FILL_TIME: u32 = 1000;
+const BAR_RECT: (i32, u32, u32) = (120, 160, 6); // x, width, height
+const ROW_HEIGHT: i32 = 28;
+
+/// How much experience a species needs for each level
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GrowthRate {
+    Fast,
+    MediumFast,
+    MediumSlow,
+    Slow,
+}
+
+impl GrowthRate {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Fast => "fast",
+            Self::MediumFast => "medium_fast",
+            Self::MediumSlow => "medium_slow",
+            Self::Slow => "slow",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fast" => Some(Self::Fast),
+            "medium_fast" => Some(Self::MediumFast),
+            "medium_slow" => Some(Self::MediumSlow),
+            "slow" => Some(Self::Slow),
+            _ => None,
+        }
+    }
+
+    /// Total experience a pokemon has at the start of `level`
+    pub fn exp_for_level(&self, level: u32) -> u32 {
+        if level <= 1 {
+            return 0;
+        }
+        let n = level.min(MAX_LEVEL) as i64;
+        let exp = match self {
+            Self::Fast => 4 * n * n * n / 5,
+            Self::MediumFast => n * n * n,
+            Self::MediumSlow => 6 * n * n * n / 5 - 15 * n * n + 100 * n - 140,
+            Self::Slow => 5 * n * n * n / 4,
+        };
+        exp.max(0) as u32
+    }
+
+    /// The level a pokemon with `exp` total experience is
+    pub fn level_for(&self, exp: u32) -> u32 {
+        (1..MAX_LEVEL).find(|level| self.exp_for_level(level + 1) > exp).unwrap_or(MAX_LEVEL)
+    }
+}
+
+/// Experience for defeating a pokemon of `level` whose species gives `base_exp`, half as much
+/// again from a trainer's pokemon
+pub fn exp_yield(base_exp: u32, level: u32, trainer: bool) -> u32 {
+    let exp = base_exp * level / 7;
+    if trainer {
+        exp * 3 / 2
+    } else {
+        exp
+    }
+}
+
+/// Splits the experience for one defeated pokemon between the party members (by index) that
+/// fought it and those holding an Exp. Share: half to each group, shared evenly within it, or
+/// everything to the fighters if nobody holds one. A member in both groups gets both shares.
+/// Returns the experience of every member that gets some, in party order.
+pub fn split_exp(total: u32, participants: &[usize], exp_share: &[usize]) -> Vec<(usize, u32)> {
+    let mut gains: HashMap<usize, u32> = HashMap::new();
+    let mut share = |members: &[usize], exp: u32| {
+        for member in members {
+            *gains.entry(*member).or_insert(0) += exp / members.len() as u32;
+        }
+    };
+    if exp_share.is_empty() {
+        if !participants.is_empty() {
+            share(participants, total);
+        }
+    } else if participants.is_empty() {
+        share(exp_share, total);
+    } else {
+        share(participants, total / 2);
+        share(exp_share, total - total / 2);
+    }
+    let mut gains: Vec<(usize, u32)> = gains.into_iter().filter(|(_, exp)| *exp > 0).collect();
+    gains.sort_unstable();
+    gains
+}
+
+/// Who gets experience besides the party members that fought
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ExpShare {
+    Off,
+    Held(Vec<usize>), // the party members holding an Exp. Share split half of it, see `split_exp`
+    All(usize), // Exp. All: every member of a party this big gets half as much as the fighters
+}
+
+/// What one party member gets for an opponent being defeated, see `Participation::credit`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Credit {
+    pub member: usize,
+    pub exp: u32,
+    pub evs: Stats, // the defeated pokemon's whole effort yield, for fighters and sharers alike
+    pub fought: bool, // also gains friendship
+}
+
+/// Which party members fought which opponents during a battle, so experience, effort values and
+/// friendship go to the right pokemon. The battle calls `faced` whenever a party member and an
+/// opponent are out at the same time, and `fainted` when a party member faints, since fainted
+/// pokemon get nothing.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct Participation {
+    fought: Vec<(usize, Vec<usize>)>, // opponent index, party members that faced it in order
+    fainted: Vec<usize>,
+}
+
+impl Participation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn faced(&mut self, opponent: usize, member: usize) {
+        let index = match self.fought.iter().position(|(fought, _)| *fought == opponent) {
+            Some(index) => index,
+            None => {
+                self.fought.push((opponent, Vec::new()));
+                self.fought.len() - 1
+            },
+        };
+        let members = &mut self.fought[index].1;
+        if !members.contains(&member) {
+            members.push(member);
+        }
+    }
+
+    /// Marks a party member as fainted. It stays fainted for the rest of the battle here even if
+    /// it's revived, like in the main games.
+    pub fn fainted(&mut self, member: usize) {
+        if !self.fainted.contains(&member) {
+            self.fainted.push(member);
+        }
+    }
+
+    /// The party members that faced an opponent and haven't fainted
+    pub fn participants(&self, opponent: usize) -> Vec<usize> {
+        self.fought
+            .iter()
+            .find(|(fought, _)| *fought == opponent)
+            .map_or(Vec::new(), |(_, members)| {
+                members.iter().copied().filter(|member| !self.fainted.contains(member)).collect()
+            })
+    }
+
+    /// Every party member that fought at all, e.g. for friendship after the battle
+    pub fn members(&self) -> Vec<usize> {
+        let mut members: Vec<usize> = self.fought.iter().flat_map(|(_, members)| members.iter().copied()).collect();
+        members.sort_unstable();
+        members.dedup();
+        members
+    }
+
+    /// What each party member gets for defeating `opponent`, which gives `exp` experience (see
+    /// `exp_yield`) and `evs` effort values. Sharers that fainted get nothing either.
+    pub fn credit(&self, opponent: usize, exp: u32, evs: Stats, share: &ExpShare) -> Vec<Credit> {
+        let participants = self.participants(opponent);
+        let alive = |members: Vec<usize>| -> Vec<usize> {
+            members.into_iter().filter(|member| !self.fainted.contains(member)).collect()
+        };
+        let gains = match share {
+            ExpShare::Off => split_exp(exp, &participants, &[]),
+            ExpShare::Held(holders) => split_exp(exp, &participants, &alive(holders.clone())),
+            ExpShare::All(party_size) => alive((0..*party_size).collect())
+                .into_iter()
+                .map(|member| (member, if participants.contains(&member) { exp } else { exp / 2 }))
+                .collect(),
+        };
+        gains
+            .into_iter()
+            .map(|(member, exp)| Credit { member, exp, evs, fought: participants.contains(&member) })
+            .collect()
+    }
+
+    fn to_dict_value(&self) -> DictValue {
+        let numbers = |members: &[usize]| DictValue::Array(members.iter().map(|m| DictValue::U32(*m as u32)).collect());
+        let mut dict = HashMap::new();
+        let fought = self
+            .fought
+            .iter()
+            .map(|(opponent, members)| DictValue::Array(vec![DictValue::U32(*opponent as u32), numbers(members)]))
+            .collect();
+        dict.insert(Key::new("fought"), DictValue::Array(fought));
+        dict.insert(Key::new("fainted"), numbers(&self.fainted));
+        DictValue::Dict(dict)
+    }
+
+    fn from_dict_value(value: &DictValue) -> Self {
+        let numbers = |value: &DictValue| -> Vec<usize> {
+            match value {
+                DictValue::Array(values) => values.iter().filter_map(DictValue::as_u32).map(|n| n as usize).collect(),
+                _ => Vec::new(),
+            }
+        };
+        let dict = match value {
+            DictValue::Dict(dict) => dict,
+            _ => return Self::default(),
+        };
+        let fought = match dict.get(&Key::new("fought")) {
+            Some(DictValue::Array(fought)) => fought
+                .iter()
+                .filter_map(|entry| match entry {
+                    DictValue::Array(entry) if entry.len() == 2 => {
+                        Some((entry[0].as_u32()? as usize, numbers(&entry[1])))
+                    },
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let fainted = dict.get(&Key::new("fainted")).map_or(Vec::new(), numbers);
+        Self { fought, fainted }
+    }
+}
+
+/// The experience one party member got from a battle
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ExpGain {
+    pub member: usize, // index in the party
+    pub species: Key,
+    pub growth: GrowthRate,
+    pub from: u32, // total experience before the battle
+    pub to: u32, // and after
+}
+
+impl ExpGain {
+    pub fn from_level(&self) -> u32 {
+        self.growth.level_for(self.from)
+    }
+
+    pub fn to_level(&self) -> u32 {
+        self.growth.level_for(self.to)
+    }
+
+    fn to_dict_value(self) -> DictValue {
+        let mut dict = HashMap::new();
+        dict.insert(Key::new("member"), DictValue::U32(self.member as u32));
+        dict.insert(Key::new("species"), DictValue::String(self.species.name().to_string()));
+        dict.insert(Key::new("growth"), DictValue::String(self.growth.name().to_string()));
+        dict.insert(Key::new("from"), DictValue::U32(self.from));
+        dict.insert(Key::new("to"), DictValue::U32(self.to));
+        DictValue::Dict(dict)
+    }
+
+    fn from_dict(dict: &Dict) -> Option<Self> {
+        let string = |key: &str| match dict.get(&Key::new(key)) {
+            Some(DictValue::String(value)) => Some(value.as_str()),
+            _ => None,
+        };
+        Some(Self {
+            member: dict_u32(dict, "member")? as usize,
+            species: Key::new(string("species")?),
+            growth: GrowthRate::from_name(string("growth")?)?,
+            from: dict_u32(dict, "from")?,
+            to: dict_u32(dict, "to")?,
+        })
+    }
+}
+
+/// What the player got out of a won battle, shown by the results scene (see
+/// `create_battle_results`) and handed back to the scene that started the battle in its quit
+/// props. `add_credit` builds it up from each opponent's `Participation::credit`.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct BattleResults {
+    pub gains: Vec<ExpGain>,
+    pub money: Option<u32>, // prize money, only from trainer battles
+    pub evs: Vec<(usize, Stats)>, // effort values each member gained, only shown in debug builds
+    pub participation: Participation, // who fought whom, e.g. for friendship
+}
+
+impl BattleResults {
+    /// Adds what the party got for one defeated opponent. `party` gives the species, growth rate
+    /// and current total experience of a member, counting experience already added.
+    pub fn add_credit<F: Fn(usize) -> Option<(Key, GrowthRate, u32)>>(&mut self, credits: &[Credit], party: F) {
+        for credit in credits {
+            match self.gains.iter_mut().find(|gain| gain.member == credit.member) {
+                Some(gain) => gain.to = gain.to.saturating_add(credit.exp),
+                None => {
+                    if let Some((species, growth, exp)) = party(credit.member) {
+                        let to = exp.saturating_add(credit.exp);
+                        self.gains.push(ExpGain { member: credit.member, species, growth, from: exp, to });
+                    }
+                },
+            }
+            match self.evs.iter_mut().find(|(member, _)| *member == credit.member) {
+                Some((_, evs)) => {
+                    for stat in Stat::ALL.iter() {
+                        evs.set(*stat, evs.get(*stat) + credit.evs.get(*stat));
+                    }
+                },
+                None => self.evs.push((credit.member, credit.evs)),
+            }
+        }
+        self.gains.sort_by_key(|gain| gain.member);
+    }
+
+    pub fn to_dict(&self) -> Dict {
+        let mut dict = HashMap::new();
+        dict.insert(Key::new("gains"), DictValue::Array(self.gains.iter().map(|gain| gain.to_dict_value()).collect()));
+        if let Some(money) = self.money {
+            dict.insert(Key::new("money"), DictValue::U32(money));
+        }
+        let evs = self
+            .evs
+            .iter()
+            .map(|(member, evs)| {
+                let mut values = vec![DictValue::U32(*member as u32)];
+                values.extend(Stat::ALL.iter().map(|stat| DictValue::U32(evs.get(*stat))));
+                DictValue::Array(values)
+            })
+            .collect();
+        dict.insert(Key::new("evs"), DictValue::Array(evs));
+        dict.insert(Key::new("participation"), self.participation.to_dict_value());
+        dict
+    }
+
+    /// Reads results written with `to_dict`, skipping entries that don't read
+    pub fn from_dict(dict: &Dict) -> Self {
+        let array = |key: &str| match dict.get(&Key::new(key)) {
+            Some(DictValue::Array(values)) => values.as_slice(),
+            _ => &[],
+        };
+        let gains = array("gains")
+            .iter()
+            .filter_map(|gain| match gain {
+                DictValue::Dict(gain) => ExpGain::from_dict(gain),
+                _ => None,
+            })
+            .collect();
+        let evs = array("evs")
+            .iter()
+            .filter_map(|evs| {
+                let values = match evs {
+                    DictValue::Array(values) if values.len() == 1 + Stat::ALL.len() => values,
+                    _ => return None,
+                };
+                let mut numbers = values.iter().map(DictValue::as_u32);
+                let member = numbers.next()?? as usize;
+                let mut stat = || numbers.next().flatten();
+                Some((member, Stats {
+                    hp: stat()?,
+                    attack: stat()?,
+                    defense: stat()?,
+                    sp_attack: stat()?,
+                    sp_defense: stat()?,
+                    speed: stat()?,
+                }))
+            })
+            .collect();
+        let participation = dict
+            .get(&Key::new("participation"))
+            .map_or_else(Participation::default, Participation::from_dict_value);
+        Self { gains, money: dict_u32(dict, "money"), evs, participation }
+    }
+}
+
+/// Creates the results scene shown after a won battle. Expects `results`, a `BattleResults` as a
+/// dict, and optionally `text`, the player's `TextSettings` as a dict.
+///
+/// The EXP bars fill one party member at a time, stopping at every level up until the player
+/// presses A. A while a bar is filling skips to where it would stop. Once every bar is full the
+/// prize money is shown, and A quits with the `results` for the parent to apply to the party.
+pub fn create_battle_results(props: Dict) -> Scene {
+    let mut state = HashMap::new();
+    let results = match props.get(&Key::new("results")) {
+        Some(DictValue::Dict(results)) => BattleResults::from_dict(results),
+        _ => BattleResults::default(),
+    };
+    if let Some(gain) = results.gains.first() {
+        state.insert(Key::new("shown"), DictValue::U32(gain.from));
+    }
+    state.insert(Key::new("row"), DictValue::U32(0));
+    state.insert(Key::new("results"), DictValue::Dict(results.to_dict()));
+    if let Some(text) = props.get(&Key::new("text")) {
+        state.insert(Key::new("text"), text.clone());
+    }
+    let mut event_callbacks: HashMap<EventType, EventCallbackFn> = HashMap::new();
+    event_callbacks.insert(EventType::KeyDown, battle_results_key_down);
+    let mut scene = Scene::new(
+        ClearMode::None,
+        state,
+        Vec::new(),
+        event_callbacks,
+        battle_results_tick,
+        |_, _, _| SceneFnOutcome::Continue,
+    );
+    layout_battle_results(&mut scene);
+    scene
+}
+
+/// Where the results scene is: the row being filled, the experience its bar shows, and the level
+/// it stopped at, if any
+fn progress(state: &Dict) -> (BattleResults, usize, u32, Option<u32>) {
+    let results = match state.get(&Key::new("results")) {
+        Some(DictValue::Dict(results)) => BattleResults::from_dict(results),
+        _ => BattleResults::default(),
+    };
+    let row = dict_u32(state, "row").unwrap_or(0) as usize;
+    let shown = dict_u32(state, "shown").unwrap_or(0);
+    (results, row, shown, dict_u32(state, "level_up"))
+}
+
+/// Where the bar of `gain` stops next from `shown`: the next level, or the experience gained
+fn next_stop(gain: &ExpGain, shown: u32) -> u32 {
+    let level = gain.growth.level_for(shown);
+    if level >= MAX_LEVEL {
+        return gain.to;
+    }
+    gain.growth.exp_for_level(level + 1).min(gain.to)
+}
+
+/// Moves the bar of the current row to `shown`, stopping for a level up or going on to the next
+/// row once it's full
+fn advance_bar(scene: &mut Scene, results: &BattleResults, row: usize, shown: u32) {
+    let gain = &results.gains[row];
+    let state = scene.state();
+    if shown >= gain.to {
+        state.insert(Key::new("row"), DictValue::U32(row as u32 + 1));
+        let next = results.gains.get(row + 1).map_or(0, |gain| gain.from);
+        state.insert(Key::new("shown"), DictValue::U32(next));
+    } else {
+        state.insert(Key::new("shown"), DictValue::U32(shown));
+    }
+    let level = gain.growth.level_for(shown.min(gain.to));
+    if shown > gain.from && level > gain.growth.level_for(shown - 1) {
+        state.insert(Key::new("level_up"), DictValue::U32(level));
+        state.insert(Key::new("level_up_row"), DictValue::U32(row as u32));
+    }
+}
+
+fn battle_results_tick(scene: &mut Scene, _ctx: &mut EngineContext, interval: Duration) -> SceneFnOutcome {
+    let (results, row, shown, level_up) = progress(scene.state());
+    let gain = match results.gains.get(row) {
+        Some(gain) if level_up.is_none() => gain,
+        _ => return SceneFnOutcome::Continue,
+    };
+    let level = gain.growth.level_for(shown);
+    let span = gain.growth.exp_for_level(level + 1).saturating_sub(gain.growth.exp_for_level(level)).max(1);
+    let step = ((span as u64 * interval.as_millis() as u64 / EXP_FILL_TIME as u64) as u32).max(1);
+    advance_bar(scene, &results, row, (shown + step).min(next_stop(gain, shown)));
+    layout_battle_results(scene);
+    SceneFnOutcome::Continue
+}
+
+fn battle_results_key_down(scene: &mut Scene, ctx: &mut EngineContext, event: &Event) -> SceneFnOutcome {
+    let action = match *event {
+        Event::KeyDown { keycode: Some(key), repeat: false, .. } => ctx.input.map.get(key),
+        _ => None,
+    };
+    if action != Some(Action::A) {
+        return SceneFnOutcome::Continue;
+    }
+    let (results, row, shown, level_up) = progress(scene.state());
+    if level_up.is_some() {
+        scene.state().remove(&Key::new("level_up"));
+    } else if let Some(gain) = results.gains.get(row) {
+        advance_bar(scene, &results, row, next_stop(gain, shown));
+    } else {
+        let mut props = HashMap::new();
+        props.insert(Key::new("results"), DictValue::Dict(results.to_dict()));
+        return SceneFnOutcome::Quit(props);
+    }
+    layout_battle_results(scene);
+    SceneFnOutcome::Continue
+}
+
+fn layout_battle_results(scene: &mut Scene) {
+    let state = scene.state();
+    let text = match state.get(&Key::new("text")) {
+        Some(DictValue::Dict(text)) => TextSettings::from_dict(text),
+        _ => TextSettings::default(),
+    };
+    let (results, row, shown, level_up) = progress(state);
+    let line = text.line_height() as i32;
+
+    let mut sprites = vec![Sprite::Rect { rect: Rect::new(8, 8, 304, 224), color: Color::RGB(40, 56, 96) }];
+    sprites.extend(text.sprites("Results", (16, 16)));
+    for (i, gain) in results.gains.iter().enumerate() {
+        // rows before the current one are full, rows after it haven't started
+        let exp = match i.cmp(&row) {
+            std::cmp::Ordering::Less => gain.to,
+            std::cmp::Ordering::Equal => shown,
+            std::cmp::Ordering::Greater => gain.from,
+        };
+        let level = gain.growth.level_for(exp);
+        let (start, end) = (gain.growth.exp_for_level(level), gain.growth.exp_for_level(level + 1));
+        let y = 16 + line + 4 + i as i32 * ROW_HEIGHT;
+        sprites.extend(text.sprites(gain.species.name(), (16, y)));
+        sprites.extend(text.sprites(&format!("Lv{}", level), (72, y)));
+        sprites.extend(text.sprites(&format!("+{}", gain.to - gain.from), (BAR_RECT.0 + BAR_RECT.1 as i32 + 8, y)));
+        let bar_y = y + line + 2;
+        let bar = Rect::new(BAR_RECT.0, bar_y, BAR_RECT.1, BAR_RECT.2);
+        sprites.push(Sprite::Rect { rect: bar, color: Color::RGB(64, 64, 64) });
+        let fill = if level >= MAX_LEVEL {
+            0
+        } else {
+            (BAR_RECT.1 as u64 * (exp - start) as u64 / (end - start).max(1) as u64) as u32
+        };
+        if fill > 0 {
+            let rect = Rect::new(bar.x(), bar.y(), fill, bar.height());
+            sprites.push(Sprite::Rect { rect, color: Color::RGB(64, 160, 232) });
+        }
+    }
+    let message_y = 232 - 8 - line * 2;
+    let level_up_gain = dict_u32(state, "level_up_row").and_then(|row| results.gains.get(row as usize));
+    if let (Some(level), Some(gain)) = (level_up, level_up_gain) {
+        sprites.extend(text.sprites(&format!("{} grew to Lv{}!", gain.species, level), (16, message_y)));
+    } else if row >= results.gains.len() {
+        if let Some(money) = results.money {
+            sprites.extend(text.sprites(&format!("Got ${} for winning!", money), (16, message_y)));
+        }
+    }
+    if cfg!(debug_assertions) {
+        for (i, (member, evs)) in results.evs.iter().enumerate() {
+            let gained: Vec<String> = Stat::ALL
+                .iter()
+                .filter(|stat| evs.get(**stat) > 0)
+                .map(|stat| format!("+{} {}", evs.get(*stat), stat.name()))
+                .collect();
+            if !gained.is_empty() {
+                let y = message_y - line * (results.evs.len() - i) as i32;
+                sprites.extend(text.sprites(&format!("#{} EVs {}", member + 1, gained.join(" ")), (16, y)));
+            }
+        }
+    }
+    *scene.sprites() = sprites;
+}
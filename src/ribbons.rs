@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use sdl2::rect::Rect;
+use crate::dict::*;
+use crate::engine::Sprite;
+use crate::intern::Key;
+use crate::text::TextSettings;
+
+/// An award shown on a pokemon's ribbons page
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Ribbon {
+    Champion, // entered the Hall of Fame
+    TowerStreak(u32), // won this many battles in a row at the battle tower
+    Event(Key), // handed out at an event, by name
+}
+
+impl Ribbon {
+    /// Name in saves, e.g. `champion`, `tower_50` or `event_<name>`
+    pub fn id(&self) -> String {
+        match self {
+            Self::Champion => String::from("champion"),
+            Self::TowerStreak(streak) => format!("tower_{}", streak),
+            Self::Event(name) => format!("event_{}", name),
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        if id == "champion" {
+            Some(Self::Champion)
+        } else if let Some(streak) = id.strip_prefix("tower_") {
+            streak.parse().ok().map(Self::TowerStreak)
+        } else {
+            id.strip_prefix("event_").map(|name| Self::Event(Key::new(name)))
+        }
+    }
+
+    /// Text on the ribbons page
+    pub fn label(&self) -> String {
+        match self {
+            Self::Champion => String::from("Champion Ribbon"),
+            Self::TowerStreak(streak) => format!("Tower Ribbon ({} wins)", streak),
+            Self::Event(name) => format!("{} Ribbon", name),
+        }
+    }
+
+    /// Sprite of the ribbon's icon, `ribbon_<id>`
+    pub fn sprite(&self) -> Key {
+        Key::new(&format!("ribbon_{}", self.id()))
+    }
+}
+
+/// The ribbons a pokemon has, in the order it got them. Kept in the pokemon's dict under
+/// `ribbons`, see `to_dict_value`.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct Ribbons(Vec<Ribbon>);
+
+impl Ribbons {
+    /// Awards a ribbon. Returns false if the pokemon already has it.
+    pub fn award(&mut self, ribbon: Ribbon) -> bool {
+        if self.0.contains(&ribbon) {
+            return false;
+        }
+        self.0.push(ribbon);
+        true
+    }
+
+    pub fn has(&self, ribbon: Ribbon) -> bool {
+        self.0.contains(&ribbon)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Ribbon> {
+        self.0.iter()
+    }
+
+    pub fn to_dict_value(&self) -> DictValue {
+        DictValue::Array(self.0.iter().map(|ribbon| DictValue::String(ribbon.id())).collect())
+    }
+
+    /// Reads ribbons saved with `to_dict_value`, skipping ids this version doesn't know
+    pub fn from_dict_value(value: &DictValue) -> Self {
+        match value {
+            DictValue::Array(ids) => Self(
+                ids.iter()
+                    .filter_map(|id| if let DictValue::String(id) = id { Ribbon::from_id(id) } else { None })
+                    .collect(),
+            ),
+            _ => Self::default(),
+        }
+    }
+
+    /// Builds the ribbons page of the summary screen: one row per ribbon with its icon and name,
+    /// inside `rect`. Ribbons that don't fit are left out.
+    pub fn page_sprites(&self, rect: Rect, text: &TextSettings) -> Vec<Sprite> {
+        let row = text.line_height().max(16) + 4;
+        let mut sprites = Vec::new();
+        for (i, ribbon) in self.0.iter().enumerate().take((rect.height() / row) as usize) {
+            let y = rect.y() + (i as u32 * row) as i32;
+            sprites.push(Sprite::Texture { rect: Rect::new(rect.x(), y, 16, 16), sprite: ribbon.sprite() });
+            sprites.extend(text.sprites(&ribbon.label(), (rect.x() + 20, y)));
+        }
+        sprites
+    }
+}
+
+/// Condition stats for contests. Nothing uses them yet; they are kept on pokemon so a contest
+/// minigame can be added later without changing saves. Each stat goes up to `CONTEST_STAT_MAX`.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct ContestStats {
+    pub cool: u8,
+    pub beauty: u8,
+    pub cute: u8,
+    pub smart: u8,
+    pub tough: u8,
+    pub sheen: u8, // how much the pokemon has been fed, limits further feeding
+}
+
+pub const CONTEST_STAT_MAX: u8 = 255;
+
+impl ContestStats {
+    const NAMES: [&'static str; 6] = ["cool", "beauty", "cute", "smart", "tough", "sheen"];
+
+    fn fields(&mut self) -> [&mut u8; 6] {
+        [&mut self.cool, &mut self.beauty, &mut self.cute, &mut self.smart, &mut self.tough, &mut self.sheen]
+    }
+
+    /// Kept in the pokemon's dict under `contest`
+    pub fn to_dict(&self) -> Dict {
+        let mut copy = *self;
+        let mut dict = HashMap::new();
+        for (name, value) in Self::NAMES.iter().zip(copy.fields()) {
+            dict.insert(Key::new(name), DictValue::U8(*value));
+        }
+        dict
+    }
+
+    pub fn from_dict(dict: &Dict) -> Self {
+        let mut stats = Self::default();
+        for (name, field) in Self::NAMES.iter().zip(stats.fields()) {
+            *field = match dict.get(&Key::new(name)) {
+                Some(DictValue::U8(value)) => *value,
+                Some(DictValue::I64(value)) => (*value).clamp(0, CONTEST_STAT_MAX as i64) as u8,
+                _ => 0,
+            };
+        }
+        stats
+    }
+}
@@ -0,0 +1,32 @@
+/// Small xorshift* generator, good enough for gameplay rolls. Seeded, so the same seed always
+/// gives the same rolls, e.g. for replays, tests and resuming a battle (see `state`).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.max(1)) // xorshift gets stuck at 0
+    }
+
+    /// Where the generator is, to save and later carry on from with `Rng::new`
+    pub fn state(&self) -> u64 {
+        self.0
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// True with a chance of `percent` in 100
+    pub fn percent(&mut self, percent: u32) -> bool {
+        self.next_u64() % 100 < percent as u64
+    }
+}
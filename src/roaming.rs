@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use sdl2::rect::Rect;
+use crate::dict::*;
+use crate::elevation;
+use crate::encounter::{ Encounter, EncounterModel, EncounterSlot, Encounters };
+use crate::engine::Sprite;
+use crate::input::Action;
+use crate::intern::{ Key, SpriteId };
+use crate::mapfile::MapFile;
+use crate::rng::Rng;
+
+/// ms a roaming pokemon takes to walk one tile
+pub const ROAM_STEP_TIME: u32 = 400;
+/// ms between the frames of a roaming pokemon's walk
+pub const ROAM_FRAME_TIME: u32 = 200;
+/// ms a roaming pokemon stays before it leaves and makes room for another
+pub const ROAM_LIFETIME: u32 = 30_000;
+/// Roaming pokemon don't appear this close to the player, in tiles, so they don't pop up on top
+/// of them
+const SPAWN_DISTANCE: u32 = 3;
+
+/// How wild encounters happen on a map, set per map in its data with `encounter_mode`
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum EncounterMode {
+    #[default]
+    Random, // a roll on every step in the grass, see `Encounters::step`
+    Visible {
+        max: usize, // roaming pokemon on the map at once
+        spawn_time: u32, // ms between new ones appearing
+    },
+}
+
+impl EncounterMode {
+    /// Reads the mode from a map's data, e.g. `{ "encounter_mode": "visible", "max_roaming": 4,
+    /// "spawn_time": 3000 }`. Maps without `encounter_mode` use random encounters.
+    pub fn from_dict(dict: &Dict) -> Self {
+        let number = |key: &str| dict_u32(dict, key);
+        match dict.get(&Key::new("encounter_mode")) {
+            Some(DictValue::String(mode)) if mode == "visible" => Self::Visible {
+                max: number("max_roaming").unwrap_or(4) as usize,
+                spawn_time: number("spawn_time").unwrap_or(3000),
+            },
+            _ => Self::Random,
+        }
+    }
+}
+
+/// A wild pokemon walking around in the grass, which the player battles by touching it
+#[derive(Clone, PartialEq, Debug)]
+pub struct Roamer {
+    pub encounter: Encounter,
+    pub tile: (u32, u32), // where it's walking to, or standing
+    pub from: (u32, u32), // where it's walking from
+    pub facing: Action, // one of the directions
+    area: Key, // the grass zone it stays in
+    level: u8, // elevation level, see `elevation::step`
+    walked: u32, // ms into the current step, `ROAM_STEP_TIME` once it's there
+    wait: u32, // ms left before it takes another step
+    age: u32, // ms since it appeared
+}
+
+impl Roamer {
+    /// Where it is in pixels, part of the way between two tiles while walking
+    pub fn pos(&self, tile_size: u32) -> (i32, i32) {
+        let progress = |from: u32, to: u32| {
+            let (from, to) = ((from * tile_size) as i32, (to * tile_size) as i32);
+            from + (to - from) * self.walked as i32 / ROAM_STEP_TIME as i32
+        };
+        (progress(self.from.0, self.tile.0), progress(self.from.1, self.tile.1))
+    }
+
+    pub fn is_walking(&self) -> bool {
+        self.walked < ROAM_STEP_TIME
+    }
+
+    /// The sprite it's drawn with: `roam_<species>_<direction>_<frame>`, e.g. `roam_pidgey_down_1`,
+    /// with the two frames alternating while it walks and frame 0 while it stands
+    pub fn sprite(&self) -> SpriteId {
+        let frame = if self.is_walking() { 1 + self.walked / ROAM_FRAME_TIME % 2 } else { 0 };
+        Key::new(&format!("roam_{}_{}_{}", self.encounter.species, self.facing.name(), frame))
+    }
+}
+
+/// The visible wild pokemon of a map in `EncounterMode::Visible`: they appear in the map's grass
+/// zones (those with encounter slots), wander around them, and start a battle when they touch the
+/// player, instead of steps rolling for encounters. Their species and levels come from the same
+/// slots and `Encounters` as random encounters, and they walk with the same collision and
+/// elevation rules as the player and NPCs (see `elevation::walk`).
+pub struct Roamers {
+    max: usize,
+    spawn_time: u32,
+    roamers: Vec<Roamer>,
+    rng: Rng,
+    until_spawn: u32, // ms
+}
+
+impl Roamers {
+    /// Roamers for a map, `None` if it uses random encounters
+    pub fn new(mode: EncounterMode, seed: u64) -> Option<Self> {
+        match mode {
+            EncounterMode::Random => None,
+            EncounterMode::Visible { max, spawn_time } => {
+                Some(Self { max, spawn_time, roamers: Vec::new(), rng: Rng::new(seed), until_spawn: 0 })
+            },
+        }
+    }
+
+    pub fn roamers(&self) -> &[Roamer] {
+        &self.roamers
+    }
+
+    /// Spawns, moves and retires the roaming pokemon by `interval` ms. `slots` are the encounter
+    /// slots of each grass zone, by zone name. Returns the encounter of the pokemon that touched
+    /// the player on `player` (a tile) this tick, if any, which is taken off the map.
+    pub fn update<M: EncounterModel>(
+        &mut self,
+        map: &MapFile,
+        slots: &HashMap<Key, Vec<EncounterSlot>>,
+        encounters: &mut Encounters<M>,
+        player: (u32, u32),
+        interval: u32,
+    ) -> Option<Encounter> {
+        self.until_spawn = self.until_spawn.saturating_sub(interval);
+        if self.until_spawn == 0 && self.roamers.len() < self.max {
+            self.spawn(map, slots, encounters, player);
+            self.until_spawn = self.spawn_time;
+        }
+        self.roamers.retain(|roamer| roamer.age < ROAM_LIFETIME || roamer.is_walking());
+        for index in 0..self.roamers.len() {
+            self.wander(index, map, player, interval);
+        }
+        self.touch(player)
+    }
+
+    /// Takes the pokemon on or walking onto the player's tile off the map and returns its
+    /// encounter. `update` calls it, but the scene can also call it right after the player steps.
+    pub fn touch(&mut self, player: (u32, u32)) -> Option<Encounter> {
+        let index = self.roamers.iter().position(|roamer| roamer.tile == player || roamer.from == player)?;
+        Some(self.roamers.remove(index).encounter)
+    }
+
+    /// Removes every roaming pokemon, e.g. when a repel is used or the player leaves the map
+    pub fn clear(&mut self) {
+        self.roamers.clear();
+    }
+
+    /// Sprites of the roaming pokemon, in map pixels
+    pub fn sprites(&self, tile_size: u32) -> Vec<Sprite> {
+        self.roamers
+            .iter()
+            .map(|roamer| {
+                let (x, y) = roamer.pos(tile_size);
+                Sprite::Texture { rect: Rect::new(x, y, tile_size, tile_size), sprite: roamer.sprite() }
+            })
+            .collect()
+    }
+
+    fn is_free(&self, tile: (u32, u32), player: (u32, u32)) -> bool {
+        tile != player && self.roamers.iter().all(|roamer| roamer.tile != tile && roamer.from != tile)
+    }
+
+    fn spawn<M: EncounterModel>(
+        &mut self,
+        map: &MapFile,
+        slots: &HashMap<Key, Vec<EncounterSlot>>,
+        encounters: &mut Encounters<M>,
+        player: (u32, u32),
+    ) {
+        let tile_size = map.tile_size().max(1);
+        let zones: Vec<_> = map
+            .zones()
+            .iter()
+            .filter_map(|zone| Some((zone, slots.get(&Key::new(&zone.name)).filter(|slots| !slots.is_empty())?)))
+            .collect();
+        if zones.is_empty() {
+            return;
+        }
+        let (zone, zone_slots) = zones[(self.rng.next_u64() % zones.len() as u64) as usize];
+        let (x, y) = (zone.rect.x().max(0) as u32 / tile_size, zone.rect.y().max(0) as u32 / tile_size);
+        let (width, height) = ((zone.rect.width() / tile_size).max(1), (zone.rect.height() / tile_size).max(1));
+        let tile = (
+            x + (self.rng.next_u64() % width as u64) as u32,
+            y + (self.rng.next_u64() % height as u64) as u32,
+        );
+        let distance = tile.0.abs_diff(player.0) + tile.1.abs_diff(player.1);
+        if map.collision(tile.0, tile.1) != 0 || distance < SPAWN_DISTANCE || !self.is_free(tile, player) {
+            return; // tried again after the next `spawn_time`
+        }
+        let area = Key::new(&zone.name);
+        if let Some(encounter) = encounters.pick(area, zone_slots) {
+            self.roamers.push(Roamer {
+                encounter,
+                tile,
+                from: tile,
+                facing: Action::Down,
+                area,
+                level: map.elevation(tile.0, tile.1),
+                walked: ROAM_STEP_TIME,
+                wait: 0,
+                age: 0,
+            });
+        }
+    }
+
+    /// Walks a roamer on, or starts its next step once it has waited long enough. It only walks
+    /// within its own zone, and may walk into the player, which is how most battles start.
+    fn wander(&mut self, index: usize, map: &MapFile, player: (u32, u32), interval: u32) {
+        let roamer = &mut self.roamers[index];
+        roamer.age = roamer.age.saturating_add(interval);
+        if roamer.is_walking() {
+            roamer.walked = (roamer.walked + interval).min(ROAM_STEP_TIME);
+            if !roamer.is_walking() {
+                roamer.from = roamer.tile;
+                roamer.wait = 500 + (self.rng.next_u64() % 1500) as u32;
+            }
+            return;
+        }
+        roamer.wait = roamer.wait.saturating_sub(interval);
+        if roamer.wait > 0 {
+            return;
+        }
+        let direction = [Action::Up, Action::Down, Action::Left, Action::Right][(self.rng.next_u64() % 4) as usize];
+        let (x, y) = (roamer.tile.0 as i64, roamer.tile.1 as i64);
+        let (to_x, to_y) = match direction {
+            Action::Up => (x, y - 1),
+            Action::Down => (x, y + 1),
+            Action::Left => (x - 1, y),
+            _ => (x + 1, y),
+        };
+        roamer.facing = direction;
+        roamer.wait = 500;
+        if to_x < 0 || to_y < 0 {
+            return;
+        }
+        let to = (to_x as u32, to_y as u32);
+        let tile_size = map.tile_size().max(1) as i32;
+        let in_zone = map.zones().iter().any(|zone| {
+            Key::new(&zone.name) == roamer.area
+                && zone.rect.contains_point((to.0 as i32 * tile_size, to.1 as i32 * tile_size))
+        });
+        let level = elevation::walk(map, roamer.level, to);
+        let occupied = self
+            .roamers
+            .iter()
+            .enumerate()
+            .any(|(other, roamer)| other != index && (roamer.tile == to || roamer.from == to));
+        let roamer = &mut self.roamers[index];
+        if let (true, Some(level), false) = (in_zone || to == player, level, occupied) {
+            roamer.from = roamer.tile;
+            roamer.tile = to;
+            roamer.level = level;
+            roamer.walked = 0;
+        }
+    }
+}
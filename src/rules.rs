@@ -0,0 +1,163 @@
+use std::collections::{ HashMap, HashSet };
+use crate::battle::TurnTimer;
+use crate::dict::*;
+use crate::intern::Key;
+use crate::team::{ PokemonInstance, TEAM_SIZE };
+
+/// The rules of a battle format, e.g. those of one battle facility. The team selection screen
+/// checks teams with `check_team`, and the battle asks the rule set about the rest (the sleep
+/// clause and the turn timer). Both sides of a network match have to use the same rules, see
+/// `fingerprint`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RuleSet {
+    pub name: Key,
+    pub team_size: usize, // pokemon brought into battle
+    pub level_cap: Option<u32>,
+    pub scale_levels: bool, // scale pokemon over the level cap down instead of rejecting them
+    pub species_clause: bool, // no two pokemon of the same species
+    pub item_clause: bool, // no two pokemon holding the same item
+    pub sleep_clause: bool, // only one of the opponent's pokemon can be put to sleep at a time
+    pub banned: HashSet<Key>, // species, moves, abilities or items that can't be used
+    pub turn_timer: Option<u32>, // ms each side has to choose per turn
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self {
+            name: Key::new("default"),
+            team_size: TEAM_SIZE,
+            level_cap: None,
+            scale_levels: false,
+            species_clause: false,
+            item_clause: false,
+            sleep_clause: false,
+            banned: HashSet::new(),
+            turn_timer: None,
+        }
+    }
+}
+
+impl RuleSet {
+    /// Checks a team chosen for battle. Returns every rule it breaks.
+    pub fn check_team(&self, team: &[PokemonInstance]) -> Vec<String> {
+        let mut problems = Vec::new();
+        if team.is_empty() || team.len() > self.team_size {
+            problems.push(format!("{} allows 1 to {} pokemon, the team has {}", self.name, self.team_size, team.len()));
+        }
+        let mut species = HashSet::new();
+        let mut items = HashSet::new();
+        for (i, pokemon) in team.iter().enumerate() {
+            let mut problem = |problem: String| problems.push(format!("#{} {}: {}", i + 1, pokemon.species, problem));
+            if let Some(cap) = self.level_cap {
+                if pokemon.level > cap && !self.scale_levels {
+                    problem(format!("is over the level cap of {}", cap));
+                }
+            }
+            if self.species_clause && !species.insert(pokemon.species) {
+                problem(String::from("is already in the team (species clause)"));
+            }
+            if let Some(item) = pokemon.item {
+                if self.item_clause && !items.insert(item) {
+                    problem(format!("{} is already held by another pokemon (item clause)", item));
+                }
+            }
+            let used = [Some(pokemon.species), Some(pokemon.ability), pokemon.item];
+            for banned in used.iter().flatten().chain(pokemon.moves.iter()).filter(|key| self.banned.contains(key)) {
+                problem(format!("{} is banned", banned));
+            }
+        }
+        problems
+    }
+
+    /// The level a pokemon battles at under these rules
+    pub fn battle_level(&self, level: u32) -> u32 {
+        match self.level_cap {
+            Some(cap) if self.scale_levels => level.min(cap),
+            _ => level,
+        }
+    }
+
+    /// Whether a move may put one of the opponent's pokemon to sleep, given how many of them are
+    /// already asleep because of the player
+    pub fn allows_sleep(&self, asleep: usize) -> bool {
+        !self.sleep_clause || asleep == 0
+    }
+
+    /// The battle's turn timer, if these rules have one
+    pub fn timer(&self) -> Option<TurnTimer> {
+        self.turn_timer.map(TurnTimer::new)
+    }
+
+    pub fn to_dict(&self) -> Dict {
+        let mut banned: Vec<&str> = self.banned.iter().map(|key| key.name()).collect();
+        banned.sort(); // so the dict, and the fingerprint, don't depend on hash order
+        let mut dict = HashMap::new();
+        dict.insert(Key::new("name"), DictValue::String(self.name.name().to_string()));
+        dict.insert(Key::new("team_size"), DictValue::U32(self.team_size as u32));
+        if let Some(cap) = self.level_cap {
+            dict.insert(Key::new("level_cap"), DictValue::U32(cap));
+        }
+        dict.insert(Key::new("scale_levels"), DictValue::Bool(self.scale_levels));
+        dict.insert(Key::new("species_clause"), DictValue::Bool(self.species_clause));
+        dict.insert(Key::new("item_clause"), DictValue::Bool(self.item_clause));
+        dict.insert(Key::new("sleep_clause"), DictValue::Bool(self.sleep_clause));
+        dict.insert(
+            Key::new("banned"),
+            DictValue::Array(banned.into_iter().map(|name| DictValue::String(name.to_string())).collect()),
+        );
+        if let Some(timer) = self.turn_timer {
+            dict.insert(Key::new("turn_timer"), DictValue::U32(timer));
+        }
+        dict
+    }
+
+    /// Reads a rule set from `to_dict` or from the game data. Anything left out keeps its default,
+    /// so data only has to list the rules a format changes.
+    pub fn from_dict(dict: &Dict) -> Self {
+        let number = |key: &str| dict_u32(dict, key);
+        let flag = |key: &str| matches!(dict.get(&Key::new(key)), Some(DictValue::Bool(true)));
+        let default = Self::default();
+        Self {
+            name: match dict.get(&Key::new("name")) {
+                Some(DictValue::String(name)) => Key::new(name),
+                _ => default.name,
+            },
+            team_size: number("team_size").map_or(default.team_size, |size| size as usize),
+            level_cap: number("level_cap"),
+            scale_levels: flag("scale_levels"),
+            species_clause: flag("species_clause"),
+            item_clause: flag("item_clause"),
+            sleep_clause: flag("sleep_clause"),
+            banned: match dict.get(&Key::new("banned")) {
+                Some(DictValue::Array(names)) => names
+                    .iter()
+                    .filter_map(|name| if let DictValue::String(name) = name { Some(Key::new(name)) } else { None })
+                    .collect(),
+                _ => HashSet::new(),
+            },
+            turn_timer: number("turn_timer"),
+        }
+    }
+
+    /// Reads the rules of a battle facility from the game data: `facilities.<facility>.rules`.
+    /// Facilities without rules use the default rule set.
+    pub fn for_facility(data: &Dict, facility: &str) -> Self {
+        let rules = match data.get(&Key::new("facilities")) {
+            Some(DictValue::Dict(facilities)) => match facilities.get(&Key::new(facility)) {
+                Some(DictValue::Dict(facility)) => facility.get(&Key::new("rules")),
+                _ => None,
+            },
+            _ => None,
+        };
+        match rules {
+            Some(DictValue::Dict(rules)) => Self::from_dict(rules),
+            _ => Self::default(),
+        }
+    }
+
+    /// A checksum of the rules. Both sides of a network match send theirs and only start the
+    /// battle if they are the same.
+    pub fn fingerprint(&self) -> u32 {
+        crc32fast::hash(crate::data::dict_to_json(&self.to_dict()).to_string().as_bytes())
+    }
+}
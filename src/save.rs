@@ -0,0 +1,692 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{ self, File };
+use std::io::{ Read, Write };
+use std::path::{ Path, PathBuf };
+use sdl2::event::{ Event, EventType };
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use serde_json::{ json, Value };
+use crate::battle::BattleSnapshot;
+use crate::compress;
+use crate::data::{ self, dict_to_json };
+use crate::dict::*;
+use crate::engine::*;
+use crate::input::Action;
+use crate::intern::{ Key, SpriteId };
+use crate::playstats::format_play_time;
+use crate::text::TextSettings;
+
+/// What the slot selection scene shows about a save without loading the whole thing
+#[derive(Clone, PartialEq, Debug)]
+pub struct SlotInfo {
+    pub name: String, // the player's name
+    pub play_time: u64, // ms
+    pub badges: u32,
+    pub party: Vec<SpriteId>, // icon sprites of the party, in order
+}
+
+impl SlotInfo {
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "play_time": self.play_time,
+            "badges": self.badges,
+            "party": self.party.iter().map(|sprite| sprite.name()).collect::<Vec<_>>(),
+        })
+    }
+
+    fn from_json(json: &Value) -> Result<Self, String> {
+        let field = |name: &str| json.get(name).ok_or(format!("slot info is missing \"{}\"", name));
+        Ok(Self {
+            name: field("name")?.as_str().ok_or("slot name is not a string")?.to_string(),
+            play_time: field("play_time")?.as_u64().ok_or("play time is not a number")?,
+            badges: field("badges")?.as_u64().ok_or("badges is not a number")? as u32,
+            party: field("party")?
+                .as_array()
+                .ok_or("party is not an array")?
+                .iter()
+                .filter_map(|sprite| sprite.as_str().map(Key::new))
+                .collect(),
+        })
+    }
+}
+
+/// Brings the globals of a save up to date by one version, e.g. by adding a global the game
+/// started using or renaming a bag pocket
+pub type MigrationFn = fn(globals: &mut Dict) -> Result<(), String>;
+
+/// A fixed number of save slots stored as JSON files in one directory, `slot<n>.json`. Each file
+/// holds the save format version, the slot's `SlotInfo` and the game's globals.
+///
+/// The current save format version is the number of migrations registered with
+/// `with_migrations`. Loading an older save runs the migrations from its version onwards in
+/// order, so a save from any earlier release keeps loading. Saves from a newer version than the
+/// game can't be loaded.
+///
+/// Saves are written to a temporary file which then replaces the old one, so a crash halfway
+/// through saving leaves the previous save intact. The save being replaced is kept as
+/// `slot<n>.json.bak`. Every file starts with a line holding the CRC-32 of the rest of it, and a
+/// slot that is missing or fails its checksum is restored from the backup when it is read. Games
+/// should check `take_restored` after loading to tell the player their last save was lost.
+///
+/// A slot can also hold a `BattleSnapshot` of a battle in progress, `slot<n>.battle.json`, written
+/// the same way. Saving or emptying the slot removes it, since a save after the battle is newer.
+///
+/// With `with_compression`, the files are written compressed with zstd (see `compress`). Saves
+/// are read the same way either way, so turning it on or off doesn't break existing slots.
+pub struct Profiles {
+    dir: PathBuf,
+    slots: u32,
+    migrations: Vec<MigrationFn>, // the nth migrates saves from version n to n + 1
+    version: u32,
+    compressed: bool,
+    restored: RefCell<Vec<u32>>, // slots restored from their backup since `take_restored`
+}
+
+impl Profiles {
+    pub fn new<P: AsRef<Path>>(dir: P, slots: u32) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            slots,
+            migrations: Vec::new(),
+            version: 0,
+            compressed: false,
+            restored: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Sets the chain of migrations. Only ever add to the end of it: the position of a migration
+    /// is the version of the saves it upgrades.
+    pub fn with_migrations(mut self, migrations: Vec<MigrationFn>) -> Self {
+        self.version = migrations.len() as u32;
+        self.migrations = migrations;
+        self
+    }
+
+    /// Writes saves compressed from now on
+    pub fn with_compression(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    /// Only for listing slots without the migrations at hand, never for loading
+    fn with_version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// The version new saves are written with
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn slots(&self) -> u32 {
+        self.slots
+    }
+
+    /// The info of every slot in order, `None` for empty slots. Slots that can't be read are
+    /// reported as errors instead of looking empty, so they don't get overwritten by accident.
+    pub fn list(&self) -> Vec<Result<Option<SlotInfo>, String>> {
+        (0..self.slots).map(|slot| self.info(slot)).collect()
+    }
+
+    pub fn info(&self, slot: u32) -> Result<Option<SlotInfo>, String> {
+        match self.read(slot)? {
+            Some(json) => Ok(Some(SlotInfo::from_json(&json["info"]).or_else(|err| Err(self.error(slot, err)))?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Loads the globals saved in a slot, or `None` if the slot is empty. Older saves are migrated
+    /// to the current version; the file itself is only updated the next time the slot is saved.
+    pub fn load(&self, slot: u32) -> Result<Option<Dict>, String> {
+        let mut json = match self.read(slot)? {
+            Some(json) => json,
+            None => return Ok(None),
+        };
+        let version = json["version"].as_u64().unwrap_or(0) as u32; // saves from before versioning are version 0
+        let mut globals = data::json_to_dict(json["globals"].take()).or_else(|err| Err(self.error(slot, err)))?;
+        for (from, migration) in self.migrations.iter().enumerate().skip(version as usize) {
+            migration(&mut globals).or_else(|err| {
+                Err(self.error(slot, format!("migrating from version {} failed: {}", from, err)))
+            })?;
+        }
+        Ok(Some(globals))
+    }
+
+    /// Saves the globals to a slot with the current version, replacing whatever was in it
+    pub fn save(&self, slot: u32, info: &SlotInfo, globals: &Dict) -> Result<(), String> {
+        let json = json!({
+            "version": self.version(),
+            "info": info.to_json(),
+            "globals": dict_to_json(globals),
+        });
+        self.write(slot, &json.to_string())?;
+        self.clear_battle(slot)
+    }
+
+    /// Saves a snapshot of the battle in progress next to a slot, replacing the last one
+    pub fn save_battle(&self, slot: u32, snapshot: &BattleSnapshot) -> Result<(), String> {
+        self.check(slot)?;
+        let json = json!({ "version": self.version(), "battle": dict_to_json(&snapshot.to_dict()) });
+        self.write_file(slot, &self.battle_path(slot), &json.to_string(), None)
+    }
+
+    /// The snapshot of a battle that was in progress when the game last stopped, if any. A
+    /// damaged snapshot is an error rather than `None`, so the game can tell the player.
+    pub fn battle(&self, slot: u32) -> Result<Option<BattleSnapshot>, String> {
+        self.check(slot)?;
+        let path = self.battle_path(slot);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let snapshot = read_checked(&path)
+            .and_then(|mut json| data::json_to_dict(json["battle"].take()))
+            .and_then(|battle| BattleSnapshot::from_dict(&battle));
+        snapshot.map(Some).or_else(|err| Err(format!("{}: {}", path.display(), err)))
+    }
+
+    /// Removes the battle snapshot of a slot, e.g. once the battle is over
+    pub fn clear_battle(&self, slot: u32) -> Result<(), String> {
+        self.check(slot)?;
+        match fs::remove_file(self.battle_path(slot)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(self.error(slot, err)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Copies a slot over another one. Copying an empty slot empties the target. A battle
+    /// snapshot the target had is dropped, since it belonged to the save it replaced.
+    pub fn copy(&self, from: u32, to: u32) -> Result<(), String> {
+        self.check(to)?;
+        match self.read(from)? {
+            Some(json) => {
+                self.write(to, &json.to_string())?;
+                self.clear_battle(to)
+            },
+            None => self.delete(to),
+        }
+    }
+
+    /// Empties a slot, along with its backup so it isn't restored
+    pub fn delete(&self, slot: u32) -> Result<(), String> {
+        self.check(slot)?;
+        for path in [self.path(slot), self.backup_path(slot), self.battle_path(slot)] {
+            match fs::remove_file(path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(self.error(slot, err)),
+                _ => {},
+            }
+        }
+        Ok(())
+    }
+
+    /// The slots that were restored from their backup since this was last called
+    pub fn take_restored(&self) -> Vec<u32> {
+        self.restored.replace(Vec::new())
+    }
+
+    /// Packs a slot into a single file that can be moved to another machine and brought back in
+    /// with `import`. See `EXPORT_MAGIC` for the layout.
+    pub fn export(&self, slot: u32) -> Result<Vec<u8>, String> {
+        let json = self.read(slot)?.ok_or(format!("save slot {} is empty", slot))?;
+        let text = json.to_string();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(text.as_bytes()).or_else(|err| Err(format!("{}", err)))?;
+        let compressed = encoder.finish().or_else(|err| Err(format!("{}", err)))?;
+        let mut data = Vec::with_capacity(EXPORT_MAGIC.len() + 4 + compressed.len());
+        data.extend_from_slice(EXPORT_MAGIC);
+        data.extend_from_slice(&crc32fast::hash(text.as_bytes()).to_le_bytes());
+        data.extend(compressed);
+        Ok(data)
+    }
+
+    /// Checks an exported save and writes it to a slot. Nothing is written unless the checksum
+    /// matches and the save could be loaded by this game. The slot's battle snapshot is dropped
+    /// along with the save it belonged to.
+    pub fn import(&self, slot: u32, data: &[u8]) -> Result<(), String> {
+        self.check(slot)?;
+        let header = EXPORT_MAGIC.len() + 4;
+        if data.len() < header || &data[..EXPORT_MAGIC.len()] != EXPORT_MAGIC {
+            return Err(String::from("not an exported save"));
+        }
+        let mut checksum = [0; 4];
+        checksum.copy_from_slice(&data[EXPORT_MAGIC.len()..header]);
+        let mut text = String::new();
+        ZlibDecoder::new(&data[header..])
+            .read_to_string(&mut text)
+            .or_else(|err| Err(format!("exported save is damaged: {}", err)))?;
+        if crc32fast::hash(text.as_bytes()) != u32::from_le_bytes(checksum) {
+            return Err(String::from("exported save is damaged: checksum doesn't match"));
+        }
+        let json: Value = serde_json::from_str(&text).or_else(|err| Err(format!("exported save is damaged: {}", err)))?;
+        let version = json["version"].as_u64().unwrap_or(0);
+        if version > self.version() as u64 {
+            return Err(format!(
+                "exported save is from a newer version of the game (save format {}, this game reads up to {})",
+                version,
+                self.version(),
+            ));
+        }
+        SlotInfo::from_json(&json["info"])?;
+        if !json["globals"].is_object() {
+            return Err(String::from("exported save has no globals"));
+        }
+        self.write(slot, &text)?;
+        self.clear_battle(slot)
+    }
+
+    /// Exports a slot and hands it to a sync backend
+    pub fn upload(&self, slot: u32, backend: &mut dyn SyncBackend) -> Result<(), String> {
+        backend.upload(slot, &self.export(slot)?)
+    }
+
+    /// Fetches a slot from a sync backend and imports it. Returns false if the backend has
+    /// nothing for the slot, in which case the local slot is left alone.
+    pub fn download(&self, slot: u32, backend: &mut dyn SyncBackend) -> Result<bool, String> {
+        match backend.download(slot)? {
+            Some(data) => self.import(slot, &data).map(|_| true),
+            None => Ok(false),
+        }
+    }
+
+    fn path(&self, slot: u32) -> PathBuf {
+        self.dir.join(format!("slot{}.json", slot))
+    }
+
+    fn backup_path(&self, slot: u32) -> PathBuf {
+        self.path(slot).with_extension("json.bak")
+    }
+
+    fn battle_path(&self, slot: u32) -> PathBuf {
+        self.dir.join(format!("slot{}.battle.json", slot))
+    }
+
+    fn check(&self, slot: u32) -> Result<(), String> {
+        if slot >= self.slots {
+            return Err(format!("there is no save slot {}, only {}", slot, self.slots));
+        }
+        Ok(())
+    }
+
+    fn error<E: std::fmt::Display>(&self, slot: u32, err: E) -> String {
+        format!("{}: {}", self.path(slot).display(), err)
+    }
+
+    fn read(&self, slot: u32) -> Result<Option<Value>, String> {
+        self.check(slot)?;
+        let (path, backup_path) = (self.path(slot), self.backup_path(slot));
+        if !path.exists() && !backup_path.exists() {
+            return Ok(None);
+        }
+        let json = match read_checked(&path) {
+            Ok(json) => json,
+            Err(err) => {
+                // a crash between the two renames in `write` leaves only the backup
+                let backup = read_checked(&backup_path).or_else(|_| Err(self.error(slot, &err)))?;
+                fs::copy(&backup_path, &path).or_else(|err| Err(self.error(slot, err)))?;
+                eprintln!("warning: {}: {}, restored the backup", path.display(), err);
+                self.restored.borrow_mut().push(slot);
+                backup
+            },
+        };
+        let version = json["version"].as_u64().unwrap_or(0);
+        if version > self.version() as u64 {
+            return Err(self.error(slot, format!(
+                "save is from a newer version of the game (save format {}, this game reads up to {})",
+                version,
+                self.version(),
+            )));
+        }
+        Ok(Some(json))
+    }
+
+    /// Writes the whole file next to the slot first, then moves the old save to the backup and
+    /// the new file into its place
+    fn write(&self, slot: u32, text: &str) -> Result<(), String> {
+        self.check(slot)?;
+        self.write_file(slot, &self.path(slot), text, Some(&self.backup_path(slot)))
+    }
+
+    /// Writes a file with its checksum through a temporary file, moving the old file to `backup`
+    /// if there is one
+    fn write_file(&self, slot: u32, path: &Path, text: &str, backup: Option<&Path>) -> Result<(), String> {
+        fs::create_dir_all(&self.dir).or_else(|err| Err(self.error(slot, err)))?;
+        let tmp_path = path.with_extension("json.tmp");
+        let data = format!("{:08x}\n{}", crc32fast::hash(text.as_bytes()), text).into_bytes();
+        let data = if self.compressed {
+            compress::compress(&data).or_else(|err| Err(self.error(slot, err)))?
+        } else {
+            data
+        };
+        let result = File::create(&tmp_path)
+            .and_then(|mut file| {
+                file.write_all(&data)?;
+                file.sync_all()
+            })
+            .and_then(|_| match backup {
+                Some(backup) if path.exists() => fs::rename(path, backup),
+                _ => Ok(()),
+            })
+            .and_then(|_| fs::rename(&tmp_path, path));
+        if let Err(err) = result {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(self.error(slot, err));
+        }
+        Ok(())
+    }
+}
+
+/// Reads a save file, compressed or not, and checks it against the checksum on its first line.
+/// Files written before saves had checksums start straight with the JSON and aren't checked.
+fn read_checked(path: &Path) -> Result<Value, String> {
+    let text = String::from_utf8(compress::read(path)?).or_else(|err| Err(format!("{}", err)))?;
+    let json = match text.split_once('\n') {
+        Some((checksum, json)) if !checksum.starts_with('{') => {
+            let checksum = u32::from_str_radix(checksum.trim(), 16).or_else(|_| Err("checksum is unreadable"))?;
+            if crc32fast::hash(json.as_bytes()) != checksum {
+                return Err(String::from("checksum doesn't match, the save is damaged"));
+            }
+            json
+        },
+        _ => &text,
+    };
+    serde_json::from_str(json).or_else(|err| Err(format!("{}", err)))
+}
+
+/// Start of every exported save. It is followed by the CRC-32 of the save's JSON (4 bytes, little
+/// endian) and then the JSON compressed with zlib.
+pub const EXPORT_MAGIC: &[u8] = b"PKMNSAVE";
+
+/// Where exported saves are kept to move progress between machines, e.g. a cloud storage service
+/// the host game talks to. The data is opaque to backends and already checked on import, so they
+/// only need to store and return it.
+pub trait SyncBackend {
+    fn upload(&mut self, slot: u32, data: &[u8]) -> Result<(), String>;
+    /// The last data uploaded for the slot, or `None` if there is none
+    fn download(&mut self, slot: u32) -> Result<Option<Vec<u8>>, String>;
+}
+
+/// Keeps exported saves as files in a directory, e.g. one a file sync client watches, or a USB
+/// stick
+pub struct DirSync {
+    dir: PathBuf,
+}
+
+impl DirSync {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Self {
+        Self { dir: dir.as_ref().to_path_buf() }
+    }
+
+    fn path(&self, slot: u32) -> PathBuf {
+        self.dir.join(format!("slot{}.pksave", slot))
+    }
+}
+
+impl SyncBackend for DirSync {
+    fn upload(&mut self, slot: u32, data: &[u8]) -> Result<(), String> {
+        fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(self.path(slot), data))
+            .or_else(|err| Err(format!("{}: {}", self.path(slot).display(), err)))
+    }
+
+    fn download(&mut self, slot: u32) -> Result<Option<Vec<u8>>, String> {
+        match fs::read(self.path(slot)) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(format!("{}: {}", self.path(slot).display(), err)),
+        }
+    }
+}
+
+/// Height of a slot's row in the slot selection scene
+const SLOT_ROW_HEIGHT: u32 = 40;
+/// Size of the party icons in a slot's row
+const SLOT_ICON_SIZE: u32 = 16;
+
+/// Creates the title screen's slot selection scene. Expects the props `dir` (string), `slots`
+/// (u32) and `version` (u32) of the game's `Profiles`, and optionally `text`, the player's
+/// `TextSettings` as a dict.
+///
+/// Up and down move between slots. A picks a slot that isn't damaged or too new, and the scene
+/// quits with the props `slot` (u32) and `empty` (bool). The game then loads the slot with its
+/// own `Profiles`, so its migrations run. B quits with `slot` set to null. Start marks the slot
+/// to copy from and a later A copies it to the selected slot; Select pressed twice on the same
+/// slot deletes it.
+pub fn create_slot_select(props: Dict) -> Scene {
+    let mut state = HashMap::new();
+    for key in ["dir", "slots", "version", "text"] {
+        if let Some(value) = props.get(&Key::new(key)) {
+            state.insert(Key::new(key), value.clone());
+        }
+    }
+    state.insert(Key::new("cursor"), DictValue::U32(0));
+    let mut event_callbacks: HashMap<EventType, EventCallbackFn> = HashMap::new();
+    event_callbacks.insert(EventType::KeyDown, slot_select_key_down);
+    let mut scene = Scene::new(
+        ClearMode::Color(Color::BLACK),
+        state,
+        Vec::new(),
+        event_callbacks,
+        |_, _, _| SceneFnOutcome::Continue,
+        |_, _, _| SceneFnOutcome::Continue,
+    );
+    scene.set_menu_sounds(true);
+    let text = match scene.state().get(&Key::new("text")) {
+        Some(DictValue::Dict(text)) => TextSettings::from_dict(text),
+        _ => TextSettings::default(),
+    };
+    layout_slot_select(&mut scene, &text);
+    scene
+}
+
+/// The scene only lists and copies slots, so it doesn't need the game's migrations, only the
+/// version to tell saves that are too new apart
+fn slot_select_profiles(state: &Dict) -> Profiles {
+    let dir = match state.get(&Key::new("dir")) {
+        Some(DictValue::String(dir)) => dir.clone(),
+        _ => String::from("saves"),
+    };
+    let slots = dict_u32(state, "slots").unwrap_or(3);
+    Profiles::new(dir, slots).with_version(dict_u32(state, "version").unwrap_or(0))
+}
+
+fn slot_select_key_down(scene: &mut Scene, ctx: &mut EngineContext, event: &Event) -> SceneFnOutcome {
+    let action = match *event {
+        Event::KeyDown { keycode: Some(key), .. } => ctx.input.map.get(key),
+        _ => None,
+    };
+    let profiles = slot_select_profiles(scene.state());
+    let cursor = dict_u32(scene.state(), "cursor").unwrap_or(0);
+    let copy_from = dict_u32(scene.state(), "copy_from");
+    let delete = dict_u32(scene.state(), "delete");
+    let state = scene.state();
+    match action {
+        Some(Action::Up) => {
+            state.insert(Key::new("cursor"), DictValue::U32(cursor.saturating_sub(1)));
+        },
+        Some(Action::Down) => {
+            state.insert(Key::new("cursor"), DictValue::U32((cursor + 1).min(profiles.slots().saturating_sub(1))));
+        },
+        Some(Action::A) => match copy_from {
+            Some(from) => {
+                state.remove(&Key::new("copy_from"));
+                if let Err(err) = profiles.copy(from, cursor) {
+                    eprintln!("warning: couldn't copy save: {}", err);
+                }
+            },
+            None => match profiles.info(cursor) {
+                Ok(info) => {
+                    let mut props = HashMap::new();
+                    props.insert(Key::new("slot"), DictValue::U32(cursor));
+                    props.insert(Key::new("empty"), DictValue::Bool(info.is_none()));
+                    return SceneFnOutcome::Quit(props);
+                },
+                Err(err) => eprintln!("warning: can't load save: {}", err),
+            },
+        },
+        Some(Action::B) => {
+            if copy_from.is_some() || delete.is_some() {
+                state.remove(&Key::new("copy_from"));
+                state.remove(&Key::new("delete"));
+            } else {
+                let mut props = HashMap::new();
+                props.insert(Key::new("slot"), DictValue::Null);
+                return SceneFnOutcome::Quit(props);
+            }
+        },
+        Some(Action::Start) => {
+            state.insert(Key::new("copy_from"), DictValue::U32(cursor));
+        },
+        Some(Action::Select) => {
+            if delete == Some(cursor) {
+                state.remove(&Key::new("delete"));
+                if let Err(err) = profiles.delete(cursor) {
+                    eprintln!("warning: couldn't delete save: {}", err);
+                }
+            } else {
+                state.insert(Key::new("delete"), DictValue::U32(cursor));
+            }
+        },
+        _ => return SceneFnOutcome::Continue,
+    }
+    if !matches!(action, Some(Action::Select)) {
+        scene.state().remove(&Key::new("delete")); // anything else cancels a pending delete
+    }
+    layout_slot_select(scene, &ctx.text);
+    SceneFnOutcome::Continue
+}
+
+/// Rebuilds the slot rows from what is currently on disk
+fn layout_slot_select(scene: &mut Scene, text: &TextSettings) {
+    let profiles = slot_select_profiles(scene.state());
+    let cursor = dict_u32(scene.state(), "cursor").unwrap_or(0);
+    let copy_from = dict_u32(scene.state(), "copy_from");
+    let delete = dict_u32(scene.state(), "delete");
+    let list = profiles.list();
+    let restored = match scene.state().entry(Key::new("restored")).or_insert(DictValue::Array(Vec::new())) {
+        DictValue::Array(restored) => {
+            restored.extend(profiles.take_restored().into_iter().map(DictValue::U32));
+            restored.iter().filter_map(|slot| if let DictValue::U32(slot) = slot { Some(*slot) } else { None }).collect()
+        },
+        _ => Vec::new(),
+    };
+    let sprites = scene.sprites();
+    sprites.clear();
+    for (slot, info) in list.into_iter().enumerate() {
+        let slot = slot as u32;
+        let y = (slot * SLOT_ROW_HEIGHT) as i32;
+        let color = if delete == Some(slot) {
+            Color::RGB(128, 32, 32)
+        } else if slot == cursor {
+            Color::RGB(64, 64, 128)
+        } else if copy_from == Some(slot) {
+            Color::RGB(32, 96, 32)
+        } else {
+            Color::RGB(32, 32, 32)
+        };
+        sprites.push(Sprite::Rect { rect: Rect::new(0, y, 320, SLOT_ROW_HEIGHT - 2), color });
+        let line = match &info {
+            Ok(Some(info)) => format!("{} {} {} badges", info.name, format_play_time(info.play_time), info.badges),
+            Ok(None) => String::from("New game"),
+            Err(_) => String::from("Can't read save"), // damaged, or from a newer version
+        };
+        let line = if restored.contains(&slot) { format!("{} (restored from backup)", line) } else { line };
+        sprites.extend(text.sprites(&line, (4, y + 2)));
+        if let Ok(Some(info)) = info {
+            for (i, icon) in info.party.iter().enumerate() {
+                sprites.push(Sprite::Texture {
+                    rect: Rect::new(
+                        4 + (i as u32 * SLOT_ICON_SIZE) as i32,
+                        y + (SLOT_ROW_HEIGHT - SLOT_ICON_SIZE - 4) as i32,
+                        SLOT_ICON_SIZE,
+                        SLOT_ICON_SIZE,
+                    ),
+                    sprite: *icon,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of the test's own for its saves
+    fn save_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pokemon-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn info() -> SlotInfo {
+        SlotInfo { name: String::from("RED"), play_time: 0, badges: 0, party: Vec::new() }
+    }
+
+    fn string_global(globals: &Dict, key: &str) -> Option<String> {
+        match globals.get(&Key::new(key)) {
+            Some(DictValue::String(value)) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Version 1 renamed "rival" to "rival_name"
+    fn rename_rival(globals: &mut Dict) -> Result<(), String> {
+        let rival = globals.remove(&Key::new("rival")).ok_or("there is no rival")?;
+        globals.insert(Key::new("rival_name"), rival);
+        Ok(())
+    }
+
+    /// Version 2 added the running shoes
+    fn add_running_shoes(globals: &mut Dict) -> Result<(), String> {
+        globals.insert(Key::new("running_shoes"), DictValue::Bool(false));
+        Ok(())
+    }
+
+    #[test]
+    fn older_saves_are_migrated_on_load() {
+        let dir = save_dir("migrate");
+        let mut globals = Dict::new();
+        globals.insert(Key::new("rival"), DictValue::String(String::from("BLUE")));
+        Profiles::new(&dir, 1).save(0, &info(), &globals).unwrap();
+
+        let profiles = Profiles::new(&dir, 1).with_migrations(vec![rename_rival, add_running_shoes]);
+        let globals = profiles.load(0).unwrap().unwrap();
+        assert_eq!(string_global(&globals, "rival_name").as_deref(), Some("BLUE"));
+        assert!(!globals.contains_key(&Key::new("rival")));
+        assert!(matches!(globals.get(&Key::new("running_shoes")), Some(DictValue::Bool(false))));
+
+        // once saved at version 2, it's too new for a game that only knows version 1
+        profiles.save(0, &info(), &globals).unwrap();
+        assert!(Profiles::new(&dir, 1).with_migrations(vec![rename_rival]).load(0).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn damaged_save_is_restored_from_the_backup() {
+        let dir = save_dir("backup");
+        let profiles = Profiles::new(&dir, 1);
+        let mut globals = Dict::new();
+        globals.insert(Key::new("map"), DictValue::String(String::from("pallet_town")));
+        profiles.save(0, &info(), &globals).unwrap();
+        globals.insert(Key::new("map"), DictValue::String(String::from("viridian_city")));
+        profiles.save(0, &info(), &globals).unwrap();
+
+        // a flipped bit makes the save fail its checksum, so the one before it is loaded instead
+        let path = dir.join("slot0.json");
+        let damaged = fs::read_to_string(&path).unwrap().replace("viridian", "viridiam");
+        fs::write(&path, damaged).unwrap();
+        let globals = profiles.load(0).unwrap().unwrap();
+        assert_eq!(string_global(&globals, "map").as_deref(), Some("pallet_town"));
+        assert_eq!(profiles.take_restored(), vec![0]);
+
+        // the backup was copied over the damaged save
+        assert!(profiles.load(0).unwrap().is_some());
+        assert!(profiles.take_restored().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use crate::data::json_to_dict_value;
+use crate::dict::*;
+use crate::intern::Key;
+use crate::mapfile::MapEntity;
+
+/// Minutes in a day of the world clock
+pub const DAY_MINUTES: u32 = 24 * 60;
+/// Name of the `MapEntity` property an NPC's schedule is read from, as JSON
+pub const SCHEDULE_PROPERTY: &str = "schedule";
+
+/// How the game decides what time it is
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WorldClock {
+    RealTime(i32), // follows the real time, with this offset from UTC in minutes
+    PlayTime(u64), // a day lasts this many seconds of play
+}
+
+impl WorldClock {
+    /// The minute of the day, given the seconds since the Unix epoch and the save's play time in
+    /// seconds
+    pub fn minute(&self, now: u64, play_time: u64) -> u32 {
+        match self {
+            Self::RealTime(offset) => {
+                let minutes = (now / 60) as i64 + *offset as i64;
+                minutes.rem_euclid(DAY_MINUTES as i64) as u32
+            },
+            Self::PlayTime(length) => {
+                let length = (*length).max(1);
+                (play_time % length * DAY_MINUTES as u64 / length) as u32
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TimeOfDay {
+    Morning, // 4:00 to 10:00
+    Day, // 10:00 to 18:00
+    Evening, // 18:00 to 20:00
+    Night, // 20:00 to 4:00
+}
+
+impl TimeOfDay {
+    pub fn from_minute(minute: u32) -> Self {
+        match minute % DAY_MINUTES / 60 {
+            4..=9 => Self::Morning,
+            10..=17 => Self::Day,
+            18..=19 => Self::Evening,
+            _ => Self::Night,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Morning => "morning",
+            Self::Day => "day",
+            Self::Evening => "evening",
+            Self::Night => "night",
+        }
+    }
+}
+
+/// Reads a time of day as `"HH:MM"` or as minutes since midnight
+fn parse_minute(value: &DictValue) -> Option<u32> {
+    let minute = match value {
+        DictValue::String(time) => {
+            let (hours, minutes) = time.split_once(':')?;
+            hours.trim().parse::<u32>().ok()? * 60 + minutes.trim().parse::<u32>().ok()?
+        },
+        value => value.as_u32()?,
+    };
+    Some(minute.min(DAY_MINUTES))
+}
+
+/// Where an NPC is and what it does during part of the day
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScheduleEntry {
+    pub from: u32, // minute of the day it starts, see `WorldClock::minute`
+    pub to: u32, // and ends, not included; before `from` for entries that go past midnight
+    pub pos: (u32, u32), // tile
+    pub behavior: Key, // e.g. "shopkeeper", "wander" or "sleep", up to the game
+}
+
+impl ScheduleEntry {
+    pub fn contains(&self, minute: u32) -> bool {
+        if self.from <= self.to {
+            self.from <= minute && minute < self.to
+        } else {
+            minute >= self.from || minute < self.to
+        }
+    }
+}
+
+/// Where an NPC is over the day. Outside every entry the NPC isn't on the map at all, so a
+/// shopkeeper whose schedule ends at 20:00 is gone at night and a trainer with only a morning
+/// entry only shows up in the morning. Read from an array of entries:
+///
+/// `[{ "from": "08:00", "to": "20:00", "pos": [4, 2], "behavior": "shopkeeper" }, ...]`
+///
+/// When entries overlap, the first one wins.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Schedule {
+    pub entries: Vec<ScheduleEntry>,
+}
+
+impl Schedule {
+    /// Reads a schedule. Entries with a missing or broken field are skipped.
+    pub fn from_dict_value(value: &DictValue) -> Self {
+        let entries = match value {
+            DictValue::Array(entries) => entries,
+            _ => return Self::default(),
+        };
+        let entries = entries
+            .iter()
+            .filter_map(|entry| {
+                let entry = match entry {
+                    DictValue::Dict(entry) => entry,
+                    _ => return None,
+                };
+                let from = parse_minute(entry.get(&Key::new("from"))?)?;
+                let to = parse_minute(entry.get(&Key::new("to"))?)?;
+                let pos = match entry.get(&Key::new("pos"))? {
+                    DictValue::Array(xy) if xy.len() == 2 => (xy[0].as_u32()?, xy[1].as_u32()?),
+                    _ => return None,
+                };
+                let behavior = match entry.get(&Key::new("behavior")) {
+                    Some(DictValue::String(behavior)) => Key::new(behavior),
+                    _ => Key::new("idle"),
+                };
+                Some(ScheduleEntry { from, to, pos, behavior })
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Reads the schedule of an NPC placed in a map editor, from its `schedule` property. NPCs
+    /// without one have no schedule.
+    pub fn from_entity(entity: &MapEntity) -> Result<Option<Self>, String> {
+        let json = match entity.properties.iter().find(|(name, _)| name == SCHEDULE_PROPERTY) {
+            Some((_, json)) => json,
+            None => return Ok(None),
+        };
+        let value = serde_json::from_str(json)
+            .or_else(|err| Err(format!("schedule of \"{}\": {}", entity.name, err)))?;
+        Ok(Some(Self::from_dict_value(&json_to_dict_value(value))))
+    }
+
+    /// Where the NPC is at `minute`, `None` if it isn't on the map
+    pub fn at(&self, minute: u32) -> Option<&ScheduleEntry> {
+        self.entries.iter().find(|entry| entry.contains(minute))
+    }
+}
+
+/// The NPCs of a map that have schedules, with where each one was last put. Evaluate it when the
+/// map loads and whenever the clock moves on; it only reports the NPCs that have to move, appear
+/// or disappear.
+#[derive(Clone, Debug, Default)]
+pub struct Schedules {
+    schedules: HashMap<String, Schedule>, // by NPC name
+    current: HashMap<String, Option<ScheduleEntry>>, // what each NPC was last told to do
+}
+
+impl Schedules {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The schedules of every entity of a map that has one
+    pub fn from_entities(entities: &[MapEntity]) -> Result<Self, String> {
+        let mut schedules = Self::new();
+        for entity in entities {
+            if let Some(schedule) = Schedule::from_entity(entity)? {
+                schedules.insert(&entity.name, schedule);
+            }
+        }
+        Ok(schedules)
+    }
+
+    pub fn insert(&mut self, npc: &str, schedule: Schedule) {
+        self.schedules.insert(npc.to_string(), schedule);
+        self.current.remove(npc);
+    }
+
+    pub fn get(&self, npc: &str) -> Option<&Schedule> {
+        self.schedules.get(npc)
+    }
+
+    /// Whether an NPC is on the map at `minute`. NPCs without a schedule always are.
+    pub fn is_present(&self, npc: &str, minute: u32) -> bool {
+        self.schedules.get(npc).is_none_or(|schedule| schedule.at(minute).is_some())
+    }
+
+    /// Evaluates every schedule at `minute`, returning the NPCs whose entry changed since the
+    /// last call, with the new entry (`None` to remove the NPC). The first call after loading
+    /// returns every NPC with a schedule.
+    pub fn update(&mut self, minute: u32) -> Vec<(String, Option<ScheduleEntry>)> {
+        let mut changes = Vec::new();
+        for (npc, schedule) in &self.schedules {
+            let entry = schedule.at(minute).cloned();
+            if self.current.get(npc) != Some(&entry) {
+                self.current.insert(npc.clone(), entry.clone());
+                changes.push((npc.clone(), entry));
+            }
+        }
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+        changes
+    }
+}
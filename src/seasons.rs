@@ -0,0 +1,185 @@
+use crate::dict::*;
+use crate::encounter::EncounterSlot;
+use crate::intern::{ Key, SpriteId };
+use crate::tilemap::Tilemap;
+
+/// Name of the global the current season is saved in, as the season's name
+pub const SEASON_GLOBAL: &str = "season";
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    pub const ALL: [Season; 4] = [Season::Spring, Season::Summer, Season::Autumn, Season::Winter];
+
+    /// Name in data files and saves, also the suffix of seasonal tiles and encounter areas
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Spring => "spring",
+            Self::Summer => "summer",
+            Self::Autumn => "autumn",
+            Self::Winter => "winter",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|season| season.name() == name)
+    }
+
+    pub fn next(&self) -> Self {
+        Self::ALL[(*self as usize + 1) % Self::ALL.len()]
+    }
+
+    /// The season on a calendar day, counted in days since the Unix epoch like
+    /// `PlayStats::login`. Spring is March to May, and so on.
+    pub fn from_day(day: u64) -> Self {
+        // month of the civil date, from Howard Hinnant's days_from_civil algorithms
+        let day = day as i64 + 719_468;
+        let day_of_era = day.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153; // 0 is March
+        Self::ALL[(month / 3) as usize % Self::ALL.len()]
+    }
+
+    /// The season after `play_time` seconds of play, when each season lasts `length` seconds
+    pub fn from_play_time(play_time: u64, length: u64) -> Self {
+        Self::ALL[(play_time / length.max(1) % Self::ALL.len() as u64) as usize]
+    }
+
+    /// Reads the season saved in the globals, spring if there is none
+    pub fn from_globals(globals: &Dict) -> Self {
+        match globals.get(&Key::new(SEASON_GLOBAL)) {
+            Some(DictValue::String(name)) => Self::from_name(name).unwrap_or(Self::Spring),
+            _ => Self::Spring,
+        }
+    }
+
+    pub fn to_dict_value(&self) -> DictValue {
+        DictValue::String(self.name().to_string())
+    }
+}
+
+/// How the game decides what season it is
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SeasonClock {
+    Calendar, // follows the real date
+    PlayTime(u64), // changes every this many seconds of play
+}
+
+impl SeasonClock {
+    /// The current season, given today's day number (see `Season::from_day`) and the save's play
+    /// time in seconds
+    pub fn season(&self, day: u64, play_time: u64) -> Season {
+        match self {
+            Self::Calendar => Season::from_day(day),
+            Self::PlayTime(length) => Season::from_play_time(play_time, *length),
+        }
+    }
+}
+
+/// The tile shown in `season` in place of `tile`: tiles with seasonal variants are named
+/// `<tile>_<season>` on the spritesheet, e.g. `tree_winter`. `tile` may already be another
+/// season's variant. Tiles without a variant for the season stay the base tile.
+pub fn seasonal_tile(tile: SpriteId, season: Season, has_sprite: impl Fn(SpriteId) -> bool) -> SpriteId {
+    let name = tile.name();
+    let base = Season::ALL
+        .iter()
+        .find_map(|season| name.strip_suffix(&format!("_{}", season.name())))
+        .unwrap_or(name);
+    let variant = Key::new(&format!("{}_{}", base, season.name()));
+    if has_sprite(variant) {
+        variant
+    } else {
+        Key::new(base)
+    }
+}
+
+/// Swaps every tile of a map for its variant in `season`, see `seasonal_tile`. Only the chunks
+/// whose tiles changed get redrawn.
+pub fn apply_season(tilemap: &mut Tilemap, season: Season, has_sprite: impl Fn(SpriteId) -> bool) {
+    let (width, height) = tilemap.size();
+    for layer in 0..tilemap.layers() {
+        for y in 0..height {
+            for x in 0..width {
+                if let Some(tile) = tilemap.get(layer, x, y) {
+                    let variant = seasonal_tile(tile, season, &has_sprite);
+                    if variant != tile {
+                        tilemap.set(layer, x, y, Some(variant));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The wild pokemon of an area in `season`. The encounters data can give an area a table per
+/// season as `<area>_<season>`; areas without one use the table of `<area>` all year.
+pub fn seasonal_encounters(encounters: &Dict, area: Key, season: Season) -> Vec<EncounterSlot> {
+    encounters
+        .get(&Key::new(&format!("{}_{}", area, season.name())))
+        .or_else(|| encounters.get(&area))
+        .map(EncounterSlot::from_dict_value)
+        .unwrap_or_default()
+}
+
+/// Tiles that can only be walked on in some seasons, e.g. a frozen lake in winter or a path
+/// overgrown in summer
+#[derive(Clone, PartialEq, Debug)]
+pub struct SeasonalPath {
+    pub tiles: Vec<(u32, u32)>,
+    pub open: Vec<Season>,
+}
+
+impl SeasonalPath {
+    /// Reads a map's `seasonal_paths`, an array of dicts with `tiles` (an array of `[x, y]`) and
+    /// `open` (an array of season names). Entries with a missing field are skipped.
+    pub fn from_dict_value(value: &DictValue) -> Vec<Self> {
+        let entries = match value {
+            DictValue::Array(entries) => entries,
+            _ => return Vec::new(),
+        };
+        entries
+            .iter()
+            .filter_map(|entry| {
+                let entry = match entry {
+                    DictValue::Dict(entry) => entry,
+                    _ => return None,
+                };
+                let tiles = match entry.get(&Key::new("tiles"))? {
+                    DictValue::Array(tiles) => tiles
+                        .iter()
+                        .filter_map(|tile| match tile {
+                            DictValue::Array(xy) if xy.len() == 2 => Some((xy[0].as_u32()?, xy[1].as_u32()?)),
+                            _ => None,
+                        })
+                        .collect(),
+                    _ => return None,
+                };
+                let open = match entry.get(&Key::new("open"))? {
+                    DictValue::Array(seasons) => seasons
+                        .iter()
+                        .filter_map(|season| if let DictValue::String(season) = season { Season::from_name(season) } else { None })
+                        .collect(),
+                    _ => return None,
+                };
+                Some(Self { tiles, open })
+            })
+            .collect()
+    }
+
+    pub fn is_open(&self, season: Season) -> bool {
+        self.open.contains(&season)
+    }
+}
+
+/// Whether the player may walk onto a tile in `season`. Tiles that aren't part of any seasonal
+/// path are left to the map's own collision.
+pub fn is_passable(paths: &[SeasonalPath], tile: (u32, u32), season: Season) -> bool {
+    paths.iter().filter(|path| path.tiles.contains(&tile)).all(|path| path.is_open(season))
+}
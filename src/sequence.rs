@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use crate::dict::*;
+use crate::engine::SceneFnOutcome;
+use crate::input::Action;
+use crate::intern::Key;
+
+/// Shows a line of text, e.g. by opening the scene's text box
+pub type SayFn<C> = fn(ctx: &mut C, text: &str);
+/// Does something right away, e.g. turns an NPC to face the player
+pub type DoFn<C> = fn(ctx: &mut C);
+/// Keeps doing something every tick until it returns true, e.g. walks an NPC until it has gone 3
+/// tiles. Gets the ms passed this tick and the ms since the step started.
+pub type UntilFn<C> = fn(ctx: &mut C, interval: u32, elapsed: u32) -> bool;
+/// Ends the sequence with an outcome for the engine, e.g. starting a battle
+pub type FinishFn<C> = fn(ctx: &mut C) -> SceneFnOutcome;
+
+enum Step<C> {
+    Say(String),
+    WaitFor(Action),
+    Wait(u32), // ms
+    Do(DoFn<C>),
+    Until(UntilFn<C>),
+    Finish(FinishFn<C>),
+}
+
+/// A scripted sequence written as straight-line steps instead of a hand-rolled switch on frame
+/// counters, e.g. `.say("Hey!").wait_for(Action::A).until(walk_npc).wait(500).finish(battle)`.
+/// Steps that take no time run back to back; the sequence stops at the first one that has to
+/// wait and picks up there on a later tick.
+///
+/// Like `StateMachine`, the steps are built the same way on every callback and only how far the
+/// sequence got is kept in the scene's state, with `save` and `load`.
+pub struct Sequence<C> {
+    say: SayFn<C>,
+    steps: Vec<Step<C>>,
+    next: usize, // index of the step being run
+    elapsed: u32, // ms since that step started
+}
+
+impl<C> Sequence<C> {
+    /// Creates an empty sequence. `say` is how the scene shows text for `say` steps.
+    pub fn new(say: SayFn<C>) -> Self {
+        Self { say, steps: Vec::new(), next: 0, elapsed: 0 }
+    }
+
+    pub fn say(mut self, text: &str) -> Self {
+        self.steps.push(Step::Say(text.to_string()));
+        self
+    }
+
+    /// Waits until the scene passes the action to `press`
+    pub fn wait_for(mut self, action: Action) -> Self {
+        self.steps.push(Step::WaitFor(action));
+        self
+    }
+
+    /// Waits `ms` ms
+    pub fn wait(mut self, ms: u32) -> Self {
+        self.steps.push(Step::Wait(ms));
+        self
+    }
+
+    pub fn then(mut self, step: DoFn<C>) -> Self {
+        self.steps.push(Step::Do(step));
+        self
+    }
+
+    pub fn until(mut self, step: UntilFn<C>) -> Self {
+        self.steps.push(Step::Until(step));
+        self
+    }
+
+    /// Ends the sequence with the outcome `finish` returns. Steps after it never run.
+    pub fn finish(mut self, finish: FinishFn<C>) -> Self {
+        self.steps.push(Step::Finish(finish));
+        self
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.next >= self.steps.len()
+    }
+
+    /// Runs the sequence for a tick. Returns the outcome of a `finish` step if one was reached,
+    /// `Continue` otherwise.
+    pub fn tick(&mut self, ctx: &mut C, interval: u32) -> SceneFnOutcome {
+        self.elapsed += interval;
+        let mut interval = interval;
+        while let Some(step) = self.steps.get(self.next) {
+            let done = match step {
+                Step::Say(text) => {
+                    (self.say)(ctx, text);
+                    true
+                },
+                Step::WaitFor(_) => false,
+                Step::Wait(ms) => self.elapsed >= *ms,
+                Step::Do(step) => {
+                    step(ctx);
+                    true
+                },
+                Step::Until(step) => step(ctx, interval, self.elapsed),
+                Step::Finish(finish) => {
+                    self.next = self.steps.len();
+                    return finish(ctx);
+                },
+            };
+            if !done {
+                break;
+            }
+            self.next += 1;
+            self.elapsed = 0;
+            interval = 0; // the time this tick has been used up by the step that just finished
+        }
+        SceneFnOutcome::Continue
+    }
+
+    /// Passes a press to the sequence. Returns true if the sequence was waiting for it; it moves
+    /// on at the next `tick`.
+    pub fn press(&mut self, action: Action) -> bool {
+        match self.steps.get(self.next) {
+            Some(Step::WaitFor(waiting)) if *waiting == action => {
+                self.next += 1;
+                self.elapsed = 0;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Keeps how far the sequence got in a scene's state under `key`
+    pub fn save(&self, dict: &mut Dict, key: &str) {
+        let mut saved = HashMap::new();
+        saved.insert(Key::new("next"), DictValue::U32(self.next as u32));
+        saved.insert(Key::new("elapsed"), DictValue::U32(self.elapsed));
+        dict.insert(Key::new(key), DictValue::Dict(saved));
+    }
+
+    /// Picks up where the sequence saved with `save` left off. Starts from the beginning if
+    /// nothing was saved.
+    pub fn load(mut self, dict: &Dict, key: &str) -> Self {
+        if let Some(DictValue::Dict(saved)) = dict.get(&Key::new(key)) {
+            if let Some(DictValue::U32(next)) = saved.get(&Key::new("next")) {
+                self.next = (*next as usize).min(self.steps.len());
+            }
+            if let Some(DictValue::U32(elapsed)) = saved.get(&Key::new("elapsed")) {
+                self.elapsed = *elapsed;
+            }
+        }
+        self
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::{ HashMap, HashSet };
+use crate::dict::*;
+use crate::intern::{ Key, SpriteId };
+use crate::stats::Stats;
+
+/// What makes a pokemon change into a form after it was caught
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FormTrigger {
+    Item(Key), // while holding the item, e.g. a plate or an orb
+    Battle(Key), // while a battle condition holds, e.g. weather or low HP; checked by the battle
+}
+
+/// One form of a species, e.g. a regional variant. Forms share a dex number but can differ in
+/// types, stats and sprite.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Form {
+    pub name: Key, // "default" for the base form
+    pub types: Vec<Key>,
+    pub base_stats: Stats,
+    pub sprite: SpriteId,
+    pub trigger: Option<FormTrigger>, // `None` for forms a pokemon is born or caught in
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Species {
+    pub name: Key,
+    pub dex: u32,
+    pub forms: Vec<Form>, // the base form is first
+    pub moves: HashSet<Key>, // every move it can learn in any way, empty if the data doesn't say
+    pub abilities: Vec<Key>, // empty if the data doesn't say
+    pub min_level: u32, // lowest level it can be met at, e.g. the level it evolves at
+}
+
+impl Species {
+    /// Reads a species from the species data. The species' own `types`, `base_stats` and
+    /// `sprite` make up the base form, and each entry of the optional `forms` array overrides
+    /// whichever of those it sets. A form's `trigger` is a dict with either `item` or `battle`.
+    /// The optional `moves` and `abilities` arrays and `min_level` are used to check legality.
+    pub fn from_dict(name: Key, dict: &Dict) -> Result<Self, String> {
+        let error = |err: &str| format!("species {}: {}", name, err);
+        let dex = match dict.get(&Key::new("dex")) {
+            Some(DictValue::I64(dex)) if *dex > 0 => *dex as u32,
+            Some(DictValue::U64(dex)) => *dex as u32,
+            _ => return Err(error("dex number is missing")),
+        };
+        let base = Form {
+            name: Key::new("default"),
+            types: read_types(dict).ok_or_else(|| error("types are missing"))?,
+            base_stats: read_stats(dict).ok_or_else(|| error("base stats are missing"))?,
+            sprite: read_string(dict, "sprite").ok_or_else(|| error("sprite is missing"))?,
+            trigger: None,
+        };
+        let mut forms = vec![base.clone()];
+        if let Some(DictValue::Array(entries)) = dict.get(&Key::new("forms")) {
+            for entry in entries {
+                let entry = match entry {
+                    DictValue::Dict(entry) => entry,
+                    _ => return Err(error("forms have to be dicts")),
+                };
+                forms.push(Form {
+                    name: read_string(entry, "name").ok_or_else(|| error("a form has no name"))?,
+                    types: read_types(entry).unwrap_or_else(|| base.types.clone()),
+                    base_stats: read_stats(entry).unwrap_or(base.base_stats),
+                    sprite: read_string(entry, "sprite").unwrap_or(base.sprite),
+                    trigger: match entry.get(&Key::new("trigger")) {
+                        Some(DictValue::Dict(trigger)) => read_string(trigger, "item")
+                            .map(FormTrigger::Item)
+                            .or_else(|| read_string(trigger, "battle").map(FormTrigger::Battle)),
+                        _ => None,
+                    },
+                });
+            }
+        }
+        let names = |key: &str| match dict.get(&Key::new(key)) {
+            Some(DictValue::Array(names)) => names
+                .iter()
+                .filter_map(|name| if let DictValue::String(name) = name { Some(Key::new(name)) } else { None })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let min_level = match dict.get(&Key::new("min_level")) {
+            Some(DictValue::I64(level)) if *level > 0 => *level as u32,
+            Some(DictValue::U64(level)) => *level as u32,
+            _ => 1,
+        };
+        Ok(Self {
+            name,
+            dex,
+            forms,
+            moves: names("moves").into_iter().collect(),
+            abilities: names("abilities"),
+            min_level,
+        })
+    }
+
+    /// Reads every species in the species data, a dict of species by name. Returns the errors of
+    /// every species that couldn't be read.
+    pub fn load_all(data: &Dict) -> Result<HashMap<Key, Species>, Vec<String>> {
+        let mut species = HashMap::new();
+        let mut errors = Vec::new();
+        for (name, value) in data {
+            match value {
+                DictValue::Dict(dict) => match Self::from_dict(*name, dict) {
+                    Ok(entry) => {
+                        species.insert(*name, entry);
+                    },
+                    Err(err) => errors.push(err),
+                },
+                _ => errors.push(format!("species {}: has to be a dict", name)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(species)
+        } else {
+            errors.sort(); // the data is a hash map, so keep errors in a stable order
+            Err(errors)
+        }
+    }
+
+    pub fn form(&self, name: Key) -> Option<&Form> {
+        self.forms.iter().find(|form| form.name == name)
+    }
+
+    /// The form a pokemon holding `item` changes into, if any
+    pub fn form_for_item(&self, item: Key) -> Option<&Form> {
+        self.forms.iter().find(|form| form.trigger == Some(FormTrigger::Item(item)))
+    }
+
+    /// The form a pokemon changes into while a battle condition holds, if any
+    pub fn form_for_battle(&self, condition: Key) -> Option<&Form> {
+        self.forms.iter().find(|form| form.trigger == Some(FormTrigger::Battle(condition)))
+    }
+}
+
+/// The forms of one species the player has seen, for the Pokédex page
+#[derive(Clone, Default, Debug)]
+pub struct DexEntry {
+    registered: Vec<Key>, // in the order they were seen
+    seen: HashSet<Key>,
+}
+
+impl DexEntry {
+    pub fn register(&mut self, form: Key) {
+        if self.seen.insert(form) {
+            self.registered.push(form);
+        }
+    }
+
+    pub fn registered(&self) -> &[Key] {
+        &self.registered
+    }
+
+    /// The form shown after `current` when the player cycles through forms on the page, wrapping
+    /// around to the first
+    pub fn next_form(&self, current: Key) -> Option<Key> {
+        let index = self.registered.iter().position(|form| *form == current).map_or(0, |index| index + 1);
+        self.registered.get(index % self.registered.len().max(1)).copied()
+    }
+}
+
+fn read_string(dict: &Dict, key: &str) -> Option<Key> {
+    match dict.get(&Key::new(key)) {
+        Some(DictValue::String(value)) => Some(Key::new(value)),
+        _ => None,
+    }
+}
+
+fn read_types(dict: &Dict) -> Option<Vec<Key>> {
+    match dict.get(&Key::new("types")) {
+        Some(DictValue::Array(types)) => Some(
+            types
+                .iter()
+                .filter_map(|t| if let DictValue::String(t) = t { Some(Key::new(t)) } else { None })
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+fn read_stats(dict: &Dict) -> Option<Stats> {
+    let stats = match dict.get(&Key::new("base_stats")) {
+        Some(DictValue::Dict(stats)) => stats,
+        _ => return None,
+    };
+    let stat = |key: &str| dict_u32(stats, key);
+    Some(Stats {
+        hp: stat("hp")?,
+        attack: stat("attack")?,
+        defense: stat("defense")?,
+        sp_attack: stat("sp_attack")?,
+        sp_defense: stat("sp_defense")?,
+        speed: stat("speed")?,
+    })
+}
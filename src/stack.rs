@@ -29,11 +29,27 @@ impl<T> Stack<T> {
         self.stack.last_mut()
     }
 
-    pub fn empty(&self) -> bool {
-        self.stack.len() == 0
+    /// Iterates from the bottom of the stack to the top
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.stack.iter()
+    }
+
+    /// Iterates from the bottom of the stack to the top
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, T> {
+        self.stack.iter_mut()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
     }
 
     pub fn len(&self) -> usize {
         self.stack.len()
     }
 }
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
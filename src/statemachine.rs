@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use crate::dict::*;
+use crate::intern::Key;
+
+/// A state of a `StateMachine`, usually a fieldless enum. States can be nested by giving them a
+/// parent, e.g. `ChoosingMove` inside `PlayerTurn`: hooks of the parent run while any of its
+/// children is the current state.
+pub trait MachineState: Copy + PartialEq + Sized {
+    /// Name the state is saved under in a scene's state, see `StateMachine::save`
+    fn name(&self) -> &'static str;
+
+    fn from_name(name: &str) -> Option<Self>;
+
+    fn parent(&self) -> Option<Self> {
+        None
+    }
+}
+
+/// Called with the state being entered or exited
+pub type StateHookFn<S, C> = fn(state: S, ctx: &mut C);
+/// Called every tick with the state it was registered for and the ms passed. Returns the state
+/// to change to, if any.
+pub type StateUpdateFn<S, C> = fn(state: S, ctx: &mut C, interval: u32) -> Option<S>;
+/// Decides whether a transition may happen. Registered for a pair of states; `None` matches any.
+pub type TransitionGuardFn<S, C> = fn(from: S, to: S, ctx: &C) -> bool;
+/// A guard with the states it was registered for, `(from, to, guard)`
+type Guard<S, C> = (Option<S>, Option<S>, TransitionGuardFn<S, C>);
+
+struct Hooks<S, C> {
+    enter: Option<StateHookFn<S, C>>,
+    exit: Option<StateHookFn<S, C>>,
+    update: Option<StateUpdateFn<S, C>>,
+}
+
+/// Tracks the sub-state of a complex scene (e.g. choosing an action, then a move, then
+/// animating, then resolving in a battle) and runs enter, exit and update hooks as it changes,
+/// instead of the scene keeping magic numbers in its `Dict` and matching on them everywhere.
+///
+/// `C` is whatever the hooks work on, usually the `Scene`. The machine itself is built the same
+/// way on every callback (hooks are plain functions), and only the current state is kept between
+/// callbacks with `save` and `load`.
+pub struct StateMachine<S: MachineState, C> {
+    current: S,
+    elapsed: u32, // ms in the current state
+    hooks: Vec<(S, Hooks<S, C>)>,
+    guards: Vec<Guard<S, C>>,
+}
+
+impl<S: MachineState, C> StateMachine<S, C> {
+    /// Creates a machine in `initial`. The initial state's enter hooks don't run, see `start`.
+    pub fn new(initial: S) -> Self {
+        Self { current: initial, elapsed: 0, hooks: Vec::new(), guards: Vec::new() }
+    }
+
+    pub fn on_enter(mut self, state: S, hook: StateHookFn<S, C>) -> Self {
+        self.hooks_mut(state).enter = Some(hook);
+        self
+    }
+
+    pub fn on_exit(mut self, state: S, hook: StateHookFn<S, C>) -> Self {
+        self.hooks_mut(state).exit = Some(hook);
+        self
+    }
+
+    pub fn on_update(mut self, state: S, update: StateUpdateFn<S, C>) -> Self {
+        self.hooks_mut(state).update = Some(update);
+        self
+    }
+
+    /// Adds a guard for transitions from `from` to `to` (`None` matches any state). Every
+    /// matching guard has to allow a transition for it to happen.
+    pub fn guard(mut self, from: Option<S>, to: Option<S>, guard: TransitionGuardFn<S, C>) -> Self {
+        self.guards.push((from, to, guard));
+        self
+    }
+
+    pub fn current(&self) -> S {
+        self.current
+    }
+
+    /// Time (ms) spent in the current state
+    pub fn elapsed(&self) -> u32 {
+        self.elapsed
+    }
+
+    /// Whether the current state is `state` or one of its children
+    pub fn is_in(&self, state: S) -> bool {
+        self.path(self.current).contains(&state)
+    }
+
+    /// Runs the enter hooks of the current state and its parents, outermost first. For when the
+    /// scene is created.
+    pub fn start(&mut self, ctx: &mut C) {
+        for state in self.path(self.current).into_iter().rev() {
+            if let Some(enter) = self.hooks(state).and_then(|hooks| hooks.enter) {
+                enter(state, ctx);
+            }
+        }
+    }
+
+    /// Changes to `to` if the guards allow it. Exits the current state and its parents up to the
+    /// parent shared with `to`, innermost first, then enters down to `to`. Returns whether the
+    /// transition happened.
+    pub fn transition(&mut self, to: S, ctx: &mut C) -> bool {
+        let from = self.current;
+        let matches = |state: &Option<S>, other: S| state.is_none_or(|state| state == other);
+        let allowed = self
+            .guards
+            .iter()
+            .filter(|(guard_from, guard_to, _)| matches(guard_from, from) && matches(guard_to, to))
+            .all(|(_, _, guard)| guard(from, to, ctx));
+        if !allowed {
+            return false;
+        }
+        let exiting = self.path(from);
+        let entering = self.path(to);
+        for state in exiting.iter().filter(|state| !entering.contains(state) || from == to) {
+            if let Some(exit) = self.hooks(*state).and_then(|hooks| hooks.exit) {
+                exit(*state, ctx);
+            }
+        }
+        self.current = to;
+        self.elapsed = 0;
+        for state in entering.iter().rev().filter(|state| !exiting.contains(state) || from == to) {
+            if let Some(enter) = self.hooks(*state).and_then(|hooks| hooks.enter) {
+                enter(*state, ctx);
+            }
+        }
+        true
+    }
+
+    /// Runs the update hooks of the current state, then of its parents, until one asks for a
+    /// transition, which is then made
+    pub fn update(&mut self, ctx: &mut C, interval: u32) {
+        self.elapsed += interval;
+        for state in self.path(self.current) {
+            let update = self.hooks(state).and_then(|hooks| hooks.update);
+            let next = update.and_then(|update| update(state, ctx, interval));
+            if let Some(next) = next {
+                self.transition(next, ctx);
+                return;
+            }
+        }
+    }
+
+    /// Keeps the current state in a scene's state under `key`, as the state's name and the time
+    /// spent in it
+    pub fn save(&self, dict: &mut Dict, key: &str) {
+        let mut saved = HashMap::new();
+        saved.insert(Key::new("state"), DictValue::String(self.current.name().to_string()));
+        saved.insert(Key::new("elapsed"), DictValue::U32(self.elapsed));
+        dict.insert(Key::new(key), DictValue::Dict(saved));
+    }
+
+    /// Restores the state saved with `save`, without running any hooks. Keeps the current state
+    /// if nothing was saved.
+    pub fn load(mut self, dict: &Dict, key: &str) -> Self {
+        if let Some(DictValue::Dict(saved)) = dict.get(&Key::new(key)) {
+            if let Some(DictValue::String(name)) = saved.get(&Key::new("state")) {
+                if let Some(state) = S::from_name(name) {
+                    self.current = state;
+                }
+            }
+            if let Some(DictValue::U32(elapsed)) = saved.get(&Key::new("elapsed")) {
+                self.elapsed = *elapsed;
+            }
+        }
+        self
+    }
+
+    /// The state and its parents, innermost first
+    fn path(&self, state: S) -> Vec<S> {
+        let mut path = vec![state];
+        while let Some(parent) = path.last().and_then(MachineState::parent) {
+            if path.contains(&parent) {
+                break; // a state that is its own ancestor would loop forever
+            }
+            path.push(parent);
+        }
+        path
+    }
+
+    fn hooks(&self, state: S) -> Option<&Hooks<S, C>> {
+        self.hooks.iter().find(|(hooked, _)| *hooked == state).map(|(_, hooks)| hooks)
+    }
+
+    fn hooks_mut(&mut self, state: S) -> &mut Hooks<S, C> {
+        let index = match self.hooks.iter().position(|(hooked, _)| *hooked == state) {
+            Some(index) => index,
+            None => {
+                self.hooks.push((state, Hooks { enter: None, exit: None, update: None }));
+                self.hooks.len() - 1
+            },
+        };
+        &mut self.hooks[index].1
+    }
+}
@@ -0,0 +1,210 @@
+use sdl2::pixels::Color;
+use crate::palette::Palette;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Stat {
+    Hp,
+    Attack,
+    Defense,
+    SpAttack,
+    SpDefense,
+    Speed,
+}
+
+impl Stat {
+    pub const ALL: [Stat; 6] = [
+        Stat::Hp,
+        Stat::Attack,
+        Stat::Defense,
+        Stat::SpAttack,
+        Stat::SpDefense,
+        Stat::Speed,
+    ];
+
+    /// Label shown on the summary screen
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Hp => "HP",
+            Self::Attack => "Attack",
+            Self::Defense => "Defense",
+            Self::SpAttack => "Sp. Atk",
+            Self::SpDefense => "Sp. Def",
+            Self::Speed => "Speed",
+        }
+    }
+}
+
+/// A value for each stat, e.g. base stats, IVs or EVs
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Stats {
+    pub hp: u32,
+    pub attack: u32,
+    pub defense: u32,
+    pub sp_attack: u32,
+    pub sp_defense: u32,
+    pub speed: u32,
+}
+
+impl Stats {
+    pub fn get(&self, stat: Stat) -> u32 {
+        match stat {
+            Stat::Hp => self.hp,
+            Stat::Attack => self.attack,
+            Stat::Defense => self.defense,
+            Stat::SpAttack => self.sp_attack,
+            Stat::SpDefense => self.sp_defense,
+            Stat::Speed => self.speed,
+        }
+    }
+
+    pub fn set(&mut self, stat: Stat, value: u32) {
+        match stat {
+            Stat::Hp => self.hp = value,
+            Stat::Attack => self.attack = value,
+            Stat::Defense => self.defense = value,
+            Stat::SpAttack => self.sp_attack = value,
+            Stat::SpDefense => self.sp_defense = value,
+            Stat::Speed => self.speed = value,
+        }
+    }
+
+    /// Calculates every stat of a pokemon from its species' base stats
+    pub fn calculate(base: &Stats, ivs: &Stats, evs: &Stats, level: u32, nature: Nature) -> Stats {
+        let stat = |stat| calculate_stat(stat, base.get(stat), ivs.get(stat), evs.get(stat), level, nature);
+        Stats {
+            hp: stat(Stat::Hp),
+            attack: stat(Stat::Attack),
+            defense: stat(Stat::Defense),
+            sp_attack: stat(Stat::SpAttack),
+            sp_defense: stat(Stat::SpDefense),
+            speed: stat(Stat::Speed),
+        }
+    }
+}
+
+/// The usual stat formula, with the nature's 10% boost or drop applied to everything but HP
+pub fn calculate_stat(stat: Stat, base: u32, iv: u32, ev: u32, level: u32, nature: Nature) -> u32 {
+    let scaled = (2 * base + iv + ev / 4) * level / 100;
+    match stat {
+        Stat::Hp => scaled + level + 10,
+        _ => (scaled + 5) * nature.modifier(stat) / 100,
+    }
+}
+
+/// Stats natures can raise or lower, in the order that makes up the nature table: the nature
+/// with index `i` raises `NATURE_STATS[i / 5]` and lowers `NATURE_STATS[i % 5]`
+const NATURE_STATS: [Stat; 5] = [Stat::Attack, Stat::Defense, Stat::Speed, Stat::SpAttack, Stat::SpDefense];
+
+const NATURE_NAMES: [&str; 25] = [
+    "Hardy", "Lonely", "Brave", "Adamant", "Naughty",
+    "Bold", "Docile", "Relaxed", "Impish", "Lax",
+    "Timid", "Hasty", "Serious", "Jolly", "Naive",
+    "Modest", "Mild", "Quiet", "Bashful", "Rash",
+    "Calm", "Gentle", "Sassy", "Careful", "Quirky",
+];
+
+/// One of the 25 natures. Each raises one stat by 10% and lowers another by 10%; the five that
+/// would raise and lower the same stat are neutral.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Nature(u8);
+
+impl Nature {
+    /// The nature with the given index in the usual order (Hardy is 0, Quirky is 24). Wraps
+    /// around, so a random number can be passed in directly.
+    pub fn new(index: u32) -> Self {
+        Self((index % 25) as u8)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        NATURE_NAMES.iter().position(|nature| nature.eq_ignore_ascii_case(name)).map(|index| Self(index as u8))
+    }
+
+    pub fn all() -> impl Iterator<Item = Nature> {
+        (0..25).map(Self)
+    }
+
+    pub fn index(&self) -> u32 {
+        self.0 as u32
+    }
+
+    pub fn name(&self) -> &'static str {
+        NATURE_NAMES[self.0 as usize]
+    }
+
+    /// The stat this nature raises, `None` for neutral natures
+    pub fn raised(&self) -> Option<Stat> {
+        self.stats().map(|(raised, _)| raised)
+    }
+
+    /// The stat this nature lowers, `None` for neutral natures
+    pub fn lowered(&self) -> Option<Stat> {
+        self.stats().map(|(_, lowered)| lowered)
+    }
+
+    /// Percentage a stat is multiplied by: 110, 100 or 90
+    pub fn modifier(&self, stat: Stat) -> u32 {
+        match self.stats() {
+            Some((raised, _)) if raised == stat => 110,
+            Some((_, lowered)) if lowered == stat => 90,
+            _ => 100,
+        }
+    }
+
+    /// Color of a stat's label on the summary screen: red if the nature raises it, blue if it
+    /// lowers it, white otherwise
+    pub fn label_color(&self, stat: Stat, palette: Palette) -> Color {
+        palette.apply(match self.modifier(stat) {
+            110 => Color::RGB(240, 80, 64),
+            90 => Color::RGB(64, 128, 240),
+            _ => Color::WHITE,
+        })
+    }
+
+    /// The nature of an egg. A parent holding an Everstone always passes its nature on,
+    /// otherwise the nature comes from `roll`, any random number.
+    pub fn inherited(everstone_parent: Option<Nature>, roll: u32) -> Self {
+        everstone_parent.unwrap_or(Self::new(roll))
+    }
+
+    fn stats(&self) -> Option<(Stat, Stat)> {
+        let (raised, lowered) = (NATURE_STATS[self.0 as usize / 5], NATURE_STATS[self.0 as usize % 5]);
+        if raised == lowered {
+            None
+        } else {
+            Some((raised, lowered))
+        }
+    }
+}
+
+/// Order stats are checked in when looking for the highest IV for a characteristic
+const CHARACTERISTIC_STATS: [Stat; 6] = [
+    Stat::Hp,
+    Stat::Attack,
+    Stat::Defense,
+    Stat::Speed,
+    Stat::SpAttack,
+    Stat::SpDefense,
+];
+
+/// The summary screen line that hints at a pokemon's highest IV. Ties are broken by starting the
+/// search at the stat picked by `personality`, and which of the five lines of that stat is shown
+/// depends on the IV itself.
+pub fn characteristic(ivs: &Stats, personality: u32) -> &'static str {
+    let start = (personality % 6) as usize;
+    let mut best = CHARACTERISTIC_STATS[start];
+    for i in 1..6 {
+        let stat = CHARACTERISTIC_STATS[(start + i) % 6];
+        if ivs.get(stat) > ivs.get(best) {
+            best = stat;
+        }
+    }
+    let lines = match best {
+        Stat::Hp => ["Loves to eat", "Takes plenty of siestas", "Nods off a lot", "Scatters things often", "Likes to relax"],
+        Stat::Attack => ["Proud of its power", "Likes to thrash about", "A little quick tempered", "Likes to fight", "Quick tempered"],
+        Stat::Defense => ["Sturdy body", "Capable of taking hits", "Highly persistent", "Good endurance", "Good perseverance"],
+        Stat::SpAttack => ["Highly curious", "Mischievous", "Thoroughly cunning", "Often lost in thought", "Very finicky"],
+        Stat::SpDefense => ["Strong willed", "Somewhat vain", "Strongly defiant", "Hates to lose", "Somewhat stubborn"],
+        Stat::Speed => ["Likes to run", "Alert to sounds", "Impetuous and silly", "Somewhat of a clown", "Quick to flee"],
+    };
+    lines[(ivs.get(best) % 5) as usize]
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::io::{ Read, Write };
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use serde_json::{ json, Value };
+use crate::dict::*;
+use crate::intern::Key;
+use crate::stats::{ Nature, Stat, Stats };
+
+/// Most pokemon in a team
+pub const TEAM_SIZE: usize = 6;
+/// Most moves a pokemon knows
+pub const MAX_MOVES: usize = 4;
+pub const MAX_LEVEL: u32 = 100;
+pub const MAX_IV: u32 = 31;
+pub const MAX_EV: u32 = 252;
+pub const MAX_EV_TOTAL: u32 = 510;
+
+/// One caught pokemon: everything that makes it different from others of its species
+#[derive(Clone, PartialEq, Debug)]
+pub struct PokemonInstance {
+    pub species: Key,
+    pub form: Key, // "default" for the base form
+    pub level: u32,
+    pub nature: Nature,
+    pub ability: Key,
+    pub item: Option<Key>,
+    pub moves: Vec<Key>,
+    pub ivs: Stats,
+    pub evs: Stats,
+    pub shiny: bool,
+    pub boss: bool, // a special encounter, e.g. a legendary or a totem, that enters with an aura
+}
+
+impl PokemonInstance {
+    /// Checks the things any pokemon has to get right regardless of species: level, number of
+    /// moves, and IV and EV limits. Returns every problem found.
+    pub fn check_limits(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.level == 0 || self.level > MAX_LEVEL {
+            problems.push(format!("level {} is not between 1 and {}", self.level, MAX_LEVEL));
+        }
+        if self.moves.is_empty() || self.moves.len() > MAX_MOVES {
+            problems.push(format!("knows {} moves, has to know 1 to {}", self.moves.len(), MAX_MOVES));
+        }
+        let mut ev_total = 0;
+        for stat in Stat::ALL.iter() {
+            if self.ivs.get(*stat) > MAX_IV {
+                problems.push(format!("{} IV {} is over {}", stat.name(), self.ivs.get(*stat), MAX_IV));
+            }
+            if self.evs.get(*stat) > MAX_EV {
+                problems.push(format!("{} EVs {} are over {}", stat.name(), self.evs.get(*stat), MAX_EV));
+            }
+            ev_total += self.evs.get(*stat);
+        }
+        if ev_total > MAX_EV_TOTAL {
+            problems.push(format!("EVs add up to {}, over {}", ev_total, MAX_EV_TOTAL));
+        }
+        problems
+    }
+
+    fn to_json(&self) -> Value {
+        let stats = |stats: &Stats| Stat::ALL.iter().map(|stat| stats.get(*stat)).collect::<Vec<_>>();
+        json!({
+            "s": self.species.name(),
+            "f": self.form.name(),
+            "l": self.level,
+            "n": self.nature.index(),
+            "a": self.ability.name(),
+            "i": self.item.map(|item| item.name()),
+            "m": self.moves.iter().map(|m| m.name()).collect::<Vec<_>>(),
+            "iv": stats(&self.ivs),
+            "ev": stats(&self.evs),
+            "sh": self.shiny,
+            "b": self.boss,
+        })
+    }
+
+    fn from_json(json: &Value) -> Result<Self, String> {
+        let string = |key: &str| json[key].as_str().map(Key::new).ok_or(format!("\"{}\" is missing", key));
+        let number = |key: &str| json[key].as_u64().map(|n| n as u32).ok_or(format!("\"{}\" is missing", key));
+        let stats = |key: &str| -> Result<Stats, String> {
+            let values: Vec<u32> = json[key]
+                .as_array()
+                .ok_or(format!("\"{}\" is missing", key))?
+                .iter()
+                .map(|value| value.as_u64().map(|n| n as u32).ok_or(format!("\"{}\" has to be numbers", key)))
+                .collect::<Result<_, _>>()?;
+            if values.len() != Stat::ALL.len() {
+                return Err(format!("\"{}\" needs {} numbers", key, Stat::ALL.len()));
+            }
+            Ok(Stats {
+                hp: values[0],
+                attack: values[1],
+                defense: values[2],
+                sp_attack: values[3],
+                sp_defense: values[4],
+                speed: values[5],
+            })
+        };
+        Ok(Self {
+            species: string("s")?,
+            form: string("f")?,
+            level: number("l")?,
+            nature: Nature::new(number("n")?),
+            ability: string("a")?,
+            item: json["i"].as_str().map(Key::new),
+            moves: json["m"]
+                .as_array()
+                .ok_or("\"m\" is missing")?
+                .iter()
+                .filter_map(|m| m.as_str().map(Key::new))
+                .collect(),
+            ivs: stats("iv")?,
+            evs: stats("ev")?,
+            shiny: json["sh"].as_bool().unwrap_or(false),
+            boss: json["b"].as_bool().unwrap_or(false),
+        })
+    }
+
+    /// Keeps the pokemon in a `Dict`, e.g. in the player's party global
+    pub fn to_dict(&self) -> Dict {
+        match crate::data::json_to_dict_value(self.to_json()) {
+            DictValue::Dict(dict) => dict,
+            _ => HashMap::new(),
+        }
+    }
+
+    pub fn from_dict(dict: &Dict) -> Result<Self, String> {
+        Self::from_json(&crate::data::dict_to_json(dict))
+    }
+}
+
+/// Version of the team code format, the first character of every code
+const TEAM_CODE_VERSION: char = '1';
+/// Crockford's base32 alphabet. Codes only use these characters so they fit QR codes'
+/// alphanumeric mode and can be typed in without mixing up 0/O or 1/I/L.
+const BASE32: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Encodes a team as a short code of letters and digits that can be shared as text or a QR code
+/// and turned back into the team with `import_team`
+pub fn export_team(team: &[PokemonInstance]) -> String {
+    let json = Value::Array(team.iter().map(PokemonInstance::to_json).collect()).to_string();
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    let _ = encoder.write_all(json.as_bytes()); // writing to a Vec can't fail
+    let mut data = crc32fast::hash(json.as_bytes()).to_be_bytes().to_vec();
+    data.extend(encoder.finish().unwrap_or_default());
+    let mut code = String::new();
+    code.push(TEAM_CODE_VERSION);
+    code.push_str(&base32_encode(&data));
+    code
+}
+
+/// Reads a team code from `export_team`. Spaces and dashes are ignored and lowercase is
+/// accepted, for codes typed in by hand. The team is checked against `check_limits`, and every
+/// problem found is returned. See `legality::import_legal_team` to also check it against the
+/// species data.
+pub fn import_team(code: &str) -> Result<Vec<PokemonInstance>, Vec<String>> {
+    let code: String = code.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+    let code = code.to_ascii_uppercase();
+    let rest = match code.strip_prefix(TEAM_CODE_VERSION) {
+        Some(rest) => rest,
+        None => return Err(vec![String::from("not a team code, or from a newer version of the game")]),
+    };
+    let data = base32_decode(rest).ok_or_else(|| vec![String::from("team code has invalid characters")])?;
+    if data.len() < 4 {
+        return Err(vec![String::from("team code is too short")]);
+    }
+    let mut json = String::new();
+    ZlibDecoder::new(&data[4..])
+        .read_to_string(&mut json)
+        .or_else(|_| Err(vec![String::from("team code is damaged")]))?;
+    if crc32fast::hash(json.as_bytes()).to_be_bytes() != data[..4] {
+        return Err(vec![String::from("team code is damaged, check it was copied correctly")]);
+    }
+    let json: Value = serde_json::from_str(&json).or_else(|_| Err(vec![String::from("team code is damaged")]))?;
+    let members = json.as_array().ok_or_else(|| vec![String::from("team code is damaged")])?;
+    if members.is_empty() || members.len() > TEAM_SIZE {
+        return Err(vec![format!("team has {} pokemon, has to have 1 to {}", members.len(), TEAM_SIZE)]);
+    }
+    let mut team = Vec::new();
+    let mut problems = Vec::new();
+    for (i, member) in members.iter().enumerate() {
+        match PokemonInstance::from_json(member) {
+            Ok(pokemon) => {
+                problems.extend(pokemon.check_limits().into_iter().map(|problem| format!("#{}: {}", i + 1, problem)));
+                team.push(pokemon);
+            },
+            Err(err) => problems.push(format!("#{}: {}", i + 1, err)),
+        }
+    }
+    if problems.is_empty() {
+        Ok(team)
+    } else {
+        Err(problems)
+    }
+}
+
+fn base32_encode(data: &[u8]) -> String {
+    let mut code = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            code.push(BASE32[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        code.push(BASE32[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    code
+}
+
+fn base32_decode(code: &str) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in code.bytes() {
+        let c = match c {
+            b'O' => b'0',
+            b'I' | b'L' => b'1',
+            c => c,
+        };
+        let value = BASE32.iter().position(|b| *b == c)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((buffer >> bits) as u8);
+        }
+    }
+    Some(data)
+}
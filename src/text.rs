@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use sdl2::rect::Rect;
+use crate::dict::*;
+use crate::engine::Sprite;
+use crate::intern::Key;
+
+/// Fonts are drawn from glyph sprites on the spritesheet, named `font_<family>_<char code>`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FontFamily {
+    Default,
+    Dyslexic, // wider glyphs with weighted bottoms, easier to read for dyslexic players
+}
+
+impl FontFamily {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Dyslexic => "dyslexic",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::Default),
+            "dyslexic" => Some(Self::Dyslexic),
+            _ => None,
+        }
+    }
+
+    /// Size of one glyph before the size multiplier is applied
+    fn glyph_size(&self) -> (u32, u32) {
+        match self {
+            Self::Default => (6, 12),
+            Self::Dyslexic => (8, 14),
+        }
+    }
+}
+
+/// Player-selectable text options. Dialog boxes and menus lay out their text through these
+/// every time they are built, so changing them reflows all text from then on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TextSettings {
+    pub family: FontFamily,
+    pub size: u32, // multiplier, 1 is the normal size
+}
+
+impl TextSettings {
+    pub fn new(family: FontFamily, size: u32) -> Self {
+        Self { family, size: size.max(1) }
+    }
+
+    pub fn char_width(&self) -> u32 {
+        self.family.glyph_size().0 * self.size
+    }
+
+    pub fn line_height(&self) -> u32 {
+        self.family.glyph_size().1 * self.size
+    }
+
+    /// Splits text into lines that fit in `width` pixels, breaking between words where possible.
+    /// Words longer than a whole line are broken up.
+    pub fn wrap(&self, text: &str, width: u32) -> Vec<String> {
+        let max_chars = (width / self.char_width()).max(1) as usize;
+        let mut lines = Vec::new();
+        for paragraph in text.split('\n') {
+            let mut line = String::new();
+            for word in paragraph.split_whitespace() {
+                let mut word: Vec<char> = word.chars().collect();
+                let line_len = line.chars().count();
+                if line_len > 0 && line_len + 1 + word.len() > max_chars {
+                    lines.push(line);
+                    line = String::new();
+                }
+                while word.len() > max_chars {
+                    let rest = word.split_off(max_chars);
+                    lines.push(word.into_iter().collect());
+                    word = rest;
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.extend(word);
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// Wraps text to the width of `rect` and splits it into pages of as many lines as fit in
+    /// its height
+    pub fn paginate(&self, text: &str, rect: Rect) -> Vec<Vec<String>> {
+        let lines_per_page = (rect.height() / self.line_height()).max(1) as usize;
+        self.wrap(text, rect.width())
+            .chunks(lines_per_page)
+            .map(|page| page.to_vec())
+            .collect()
+    }
+
+    /// Builds the glyph sprites for a line of text starting at `pos`
+    pub fn sprites(&self, line: &str, pos: (i32, i32)) -> Vec<Sprite> {
+        let (width, height) = (self.char_width(), self.line_height());
+        line.chars()
+            .enumerate()
+            .filter(|(_, c)| !c.is_whitespace())
+            .map(|(i, c)| Sprite::Texture {
+                rect: Rect::new(pos.0 + (i as u32 * width) as i32, pos.1, width, height),
+                sprite: Key::new(&format!("font_{}_{}", self.family.name(), c as u32)),
+            })
+            .collect()
+    }
+
+    /// Converts the settings to a `Dict` so they can be stored with the save
+    pub fn to_dict(&self) -> Dict {
+        let mut dict = HashMap::new();
+        dict.insert(Key::new("family"), DictValue::String(self.family.name().to_string()));
+        dict.insert(Key::new("size"), DictValue::U32(self.size));
+        dict
+    }
+
+    /// Reads settings saved with `to_dict`, falling back to the defaults for anything missing
+    pub fn from_dict(dict: &Dict) -> Self {
+        let mut settings = Self::default();
+        if let Some(DictValue::String(family)) = dict.get(&Key::new("family")) {
+            settings.family = FontFamily::from_name(family).unwrap_or(settings.family);
+        }
+        if let Some(DictValue::U32(size)) = dict.get(&Key::new("size")) {
+            settings.size = (*size).max(1);
+        }
+        settings
+    }
+}
+
+impl Default for TextSettings {
+    fn default() -> Self {
+        Self::new(FontFamily::Default, 1)
+    }
+}
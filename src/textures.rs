@@ -0,0 +1,187 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use sdl2::render::{
+    Texture,
+    TextureCreator,
+};
+use sdl2::video::WindowContext;
+use crate::images;
+
+struct CachedTexture<'a> {
+    texture: Texture<'a>,
+    bytes: usize,
+    pinned: bool, // pinned textures are never evicted
+    last_used: Cell<u64>,
+}
+
+/// Numbers about the texture cache, for the debug overlay
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TextureStats {
+    pub count: usize,
+    pub pinned: usize,
+    pub bytes: usize,
+    pub budget: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// Holds named textures (backgrounds, battle sprites, tilesets) within a budget of approximate
+/// video memory. When adding a texture would go over the budget, the least recently used
+/// textures that aren't pinned are dropped until it fits. Dropped textures have to be loaded
+/// again by whoever needs them next, so atlases that are always needed should be pinned.
+pub struct TextureCache<'a> {
+    textures: HashMap<String, CachedTexture<'a>>,
+    budget: usize, // bytes
+    used: usize,
+    clock: Cell<u64>,
+    hits: Cell<u64>,
+    misses: Cell<u64>,
+    evictions: u64,
+}
+
+impl<'a> TextureCache<'a> {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            textures: HashMap::new(),
+            budget,
+            used: 0,
+            clock: Cell::new(0),
+            hits: Cell::new(0),
+            misses: Cell::new(0),
+            evictions: 0,
+        }
+    }
+
+    /// Adds a texture, evicting least recently used textures if needed to stay within budget.
+    /// Pinned textures are always added, even if they alone go over budget.
+    pub fn insert(&mut self, name: &str, texture: Texture<'a>, pinned: bool) {
+        let query = texture.query();
+        let bytes = query.format.byte_size_of_pixels((query.width * query.height) as usize);
+        self.remove(name);
+        self.evict(bytes);
+        self.used += bytes;
+        self.textures.insert(name.to_string(), CachedTexture {
+            texture,
+            bytes,
+            pinned,
+            last_used: Cell::new(self.tick()),
+        });
+    }
+
+    /// Loads a texture from a file and adds it to the cache
+    pub fn load<T>(
+        &mut self,
+        texture_creator: &'a TextureCreator<T>,
+        name: &str,
+        path: &str,
+        pinned: bool,
+    ) -> Result<(), String> {
+        self.insert(name, images::load_texture(texture_creator, path)?, pinned);
+        Ok(())
+    }
+
+    /// Retrieves a texture and marks it as recently used
+    pub fn get(&self, name: &str) -> Option<&Texture<'a>> {
+        match self.textures.get(name) {
+            Some(cached) => {
+                self.hits.set(self.hits.get() + 1);
+                cached.last_used.set(self.tick());
+                Some(&cached.texture)
+            },
+            None => {
+                self.misses.set(self.misses.get() + 1);
+                None
+            },
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.textures.contains_key(name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Texture<'a>> {
+        let cached = self.textures.remove(name)?;
+        self.used -= cached.bytes;
+        Some(cached.texture)
+    }
+
+    pub fn set_pinned(&mut self, name: &str, pinned: bool) {
+        if let Some(cached) = self.textures.get_mut(name) {
+            cached.pinned = pinned;
+        }
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict(0);
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Texture<'a>> {
+        self.textures.values_mut().map(|cached| &mut cached.texture)
+    }
+
+    pub fn stats(&self) -> TextureStats {
+        TextureStats {
+            count: self.textures.len(),
+            pinned: self.textures.values().filter(|cached| cached.pinned).count(),
+            bytes: self.used,
+            budget: self.budget,
+            hits: self.hits.get(),
+            misses: self.misses.get(),
+            evictions: self.evictions,
+        }
+    }
+
+    /// Drops least recently used unpinned textures until `extra` more bytes fit in the budget, or
+    /// until only pinned textures are left
+    fn evict(&mut self, extra: usize) {
+        while self.used + extra > self.budget {
+            let oldest = self.textures
+                .iter()
+                .filter(|(_, cached)| !cached.pinned)
+                .min_by_key(|(_, cached)| cached.last_used.get())
+                .map(|(name, _)| name.clone());
+            match oldest {
+                Some(name) => {
+                    self.remove(&name);
+                    self.evictions += 1;
+                },
+                None => break,
+            }
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.set(self.clock.get() + 1);
+        self.clock.get()
+    }
+}
+
+/// Owns a window's texture creator while textures borrow it. Textures can't sit in the same
+/// struct as the creator they borrow, so `leak` gives the borrow a lifetime of its own and this
+/// frees the creator again once it's dropped. Whatever holds one has to declare it after every
+/// field with textures made by it, since fields are dropped in the order they're declared.
+pub(crate) struct TextureCreatorOwner {
+    creator: *mut TextureCreator<WindowContext>,
+}
+
+impl TextureCreatorOwner {
+    /// Moves `creator` to the heap and returns the owner that frees it along with a borrow of it
+    /// for textures to be made with.
+    ///
+    /// # Safety
+    ///
+    /// Every texture made with the borrow has to be dropped before the owner.
+    pub(crate) unsafe fn leak<'a>(creator: TextureCreator<WindowContext>) -> (Self, &'a TextureCreator<WindowContext>) {
+        let creator = Box::into_raw(Box::new(creator));
+        (Self { creator }, &*creator)
+    }
+}
+
+impl Drop for TextureCreatorOwner {
+    fn drop(&mut self) {
+        // made by `Box::into_raw` in `leak`, and the textures borrowing it are gone by now
+        drop(unsafe { Box::from_raw(self.creator) });
+    }
+}
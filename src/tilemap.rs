@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+use std::sync::atomic::{ AtomicU64, Ordering };
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{
+    BlendMode,
+    Canvas,
+    RenderTarget,
+    Texture,
+    TextureCreator,
+};
+use sdl2::video::WindowContext;
+use crate::dict::*;
+use crate::engine::SpriteSheet;
+use crate::intern::{ Key, SpriteId };
+use crate::renderer::Renderer;
+
+/// Width and height of a chunk, in tiles
+pub const CHUNK_TILES: u32 = 16;
+
+/// One layer of tiles, stored row by row. Each tile is the name of a sprite on the spritesheet.
+pub type TileLayer = Vec<Option<SpriteId>>;
+
+/// A tile that cycles through frames, like water or flowers. Every tile of the same kind shows
+/// the same frame, so a pond shimmers as one.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TileAnimation {
+    pub frames: Vec<SpriteId>,
+    pub frame_time: u32, // ms per frame
+}
+
+impl TileAnimation {
+    /// The frame showing `time` ms after the map started animating
+    pub fn frame(&self, time: u64) -> Option<SpriteId> {
+        let index = time / self.frame_time.max(1) as u64 % self.frames.len().max(1) as u64;
+        self.frames.get(index as usize).copied()
+    }
+}
+
+/// Reads the animated tiles of a tileset's data, keyed by the tile placed on the map:
+///
+/// `{ "water": { "frames": ["water_0", "water_1", "water_2"], "frame_time": 250 } }`
+pub fn tile_animations(dict: &Dict) -> Result<HashMap<SpriteId, TileAnimation>, String> {
+    let mut animations = HashMap::new();
+    for (tile, animation) in dict {
+        let animation = match animation {
+            DictValue::Dict(animation) => animation,
+            _ => return Err(format!("animation of \"{}\" is not an object", tile)),
+        };
+        let frames: Vec<SpriteId> = match animation.get(&Key::new("frames")) {
+            Some(DictValue::Array(frames)) => frames
+                .iter()
+                .filter_map(|frame| match frame {
+                    DictValue::String(frame) => Some(Key::new(frame)),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        if frames.is_empty() {
+            return Err(format!("animation of \"{}\" has no frames", tile));
+        }
+        let frame_time = match animation.get(&Key::new("frame_time")) {
+            Some(DictValue::I64(time)) if *time > 0 => *time as u32,
+            Some(DictValue::U64(time)) if *time > 0 => *time as u32,
+            _ => return Err(format!("animation of \"{}\" has no frame_time", tile)),
+        };
+        animations.insert(*tile, TileAnimation { frames, frame_time });
+    }
+    Ok(animations)
+}
+
+/// Ids of `Tilemap`s, so a `TilemapRenderer` notices when it's given a different map
+static NEXT_TILEMAP_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A grid of tiles made of several layers drawn on top of each other (e.g. ground, then paths,
+/// then tree tops), with the animated tiles moving on as the scene drawing it ticks (see
+/// `Scene::set_tilemap`). Keeps track of which chunks changed so a `TilemapRenderer` knows what
+/// to redraw.
+pub struct Tilemap {
+    id: u64,
+    width: u32, // in tiles
+    height: u32,
+    tile_size: u32, // in pixels
+    layers: Vec<TileLayer>,
+    versions: HashMap<(u32, u32), u32>, // times each chunk changed
+    animations: HashMap<SpriteId, TileAnimation>,
+    time: u64, // ms the animations have run for
+}
+
+impl Tilemap {
+    pub fn new(width: u32, height: u32, tile_size: u32, layers: Vec<TileLayer>) -> Self {
+        Self {
+            id: NEXT_TILEMAP_ID.fetch_add(1, Ordering::Relaxed),
+            width,
+            height,
+            tile_size,
+            layers,
+            versions: HashMap::new(),
+            animations: HashMap::new(),
+            time: 0,
+        }
+    }
+
+    pub fn get(&self, layer: usize, x: u32, y: u32) -> Option<SpriteId> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        *self.layers.get(layer)?.get((y * self.width + x) as usize)?
+    }
+
+    /// Changes a tile and marks its chunk to be redrawn
+    pub fn set(&mut self, layer: usize, x: u32, y: u32, tile: Option<SpriteId>) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let index = (y * self.width + x) as usize;
+        if let Some(slot) = self.layers.get_mut(layer).and_then(|layer| layer.get_mut(index)) {
+            *slot = tile;
+            *self.versions.entry((x / CHUNK_TILES, y / CHUNK_TILES)).or_default() += 1;
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn tile_size(&self) -> u32 {
+        self.tile_size
+    }
+
+    pub fn layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Sets the animated tiles, see `tile_animations`
+    pub fn set_animations(&mut self, animations: HashMap<SpriteId, TileAnimation>) {
+        self.animations = animations;
+        // every chunk might show different tiles now, so it counts as a new map
+        self.id = NEXT_TILEMAP_ID.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Moves every tile animation forward. The engine does this every tick for a scene's map.
+    pub fn advance(&mut self, interval: u32) {
+        self.time += interval as u64;
+    }
+
+    fn chunk_pixels(&self) -> u32 {
+        CHUNK_TILES * self.tile_size
+    }
+
+    /// The chunks that overlap `area` (in map pixels)
+    fn chunks_in(&self, area: Rect) -> Vec<(u32, u32)> {
+        let size = self.chunk_pixels().max(1) as i32;
+        let columns = self.width.div_ceil(CHUNK_TILES);
+        let rows = self.height.div_ceil(CHUNK_TILES);
+        let first = ((area.x() / size).max(0) as u32, (area.y() / size).max(0) as u32);
+        let last = ((area.right() - 1) / size, (area.bottom() - 1) / size);
+        if last.0 < 0 || last.1 < 0 {
+            return Vec::new();
+        }
+        let last = ((last.0 as u32).min(columns.saturating_sub(1)), (last.1 as u32).min(rows.saturating_sub(1)));
+        (first.1..=last.1).flat_map(|y| (first.0..=last.0).map(move |x| (x, y))).collect()
+    }
+
+    /// The tiles of a chunk as they show now, with where they go in map pixels, layer by layer.
+    /// Also returns whether any of them are animated.
+    fn chunk_tiles(&self, chunk: (u32, u32)) -> (Vec<(SpriteId, Rect)>, bool) {
+        let mut tiles = Vec::new();
+        let mut animated = false;
+        for layer in 0..self.layers.len() {
+            for y in chunk.1 * CHUNK_TILES..(chunk.1 + 1) * CHUNK_TILES {
+                for x in chunk.0 * CHUNK_TILES..(chunk.0 + 1) * CHUNK_TILES {
+                    let mut tile = self.get(layer, x, y);
+                    if let Some(animation) = tile.and_then(|tile| self.animations.get(&tile)) {
+                        tile = animation.frame(self.time);
+                        animated = true;
+                    }
+                    if let Some(tile) = tile {
+                        let size = self.tile_size;
+                        tiles.push((tile, Rect::new((x * size) as i32, (y * size) as i32, size, size)));
+                    }
+                }
+            }
+        }
+        (tiles, animated)
+    }
+
+    /// Whether an animated tile shows a different frame now than at `time`
+    fn frames_changed_since(&self, time: u64) -> bool {
+        self.animations.values().any(|animation| animation.frame(time) != animation.frame(self.time))
+    }
+}
+
+/// Draws the tiles of `map` the renderer's camera sees one by one. Returns the tiles that aren't
+/// on the spritesheet. For renderers without a `TilemapRenderer`, see `Renderer::draw_tilemap`.
+pub fn draw_tiles<R: Renderer + ?Sized>(renderer: &mut R, map: &Tilemap) -> Vec<SpriteId> {
+    let (x, y) = renderer.camera();
+    let viewport = renderer.viewport();
+    let area = Rect::new(x, y, viewport.width(), viewport.height());
+    let mut missing = Vec::new();
+    for chunk in map.chunks_in(area) {
+        for (tile, rect) in map.chunk_tiles(chunk).0 {
+            if rect.has_intersection(area) && !renderer.draw_sprite(tile, rect) {
+                missing.push(tile);
+            }
+        }
+    }
+    missing
+}
+
+/// A chunk drawn to a texture, with what it was drawn from
+struct Chunk<'a> {
+    texture: Texture<'a>,
+    version: u32, // of the chunk in the map
+    animated_at: Option<u64>, // the map's animation time, if the chunk has animated tiles
+}
+
+/// Draws a `Tilemap` from pre-rendered chunk textures, so a screen of tiles takes a handful of
+/// copies instead of one per tile per layer. Chunks are drawn the first time they're visible and
+/// again only after a tile in them changes. Chunks with animated tiles are also redrawn whenever
+/// one of the animations moves to its next frame. The engine keeps one for its window and draws
+/// scenes' maps with it, see `Renderer::draw_tilemap`.
+pub struct TilemapRenderer<'a> {
+    texture_creator: &'a TextureCreator<WindowContext>,
+    map: Option<u64>, // id of the map the chunks are of
+    chunks: HashMap<(u32, u32), Chunk<'a>>,
+}
+
+impl<'a> TilemapRenderer<'a> {
+    pub fn new(texture_creator: &'a TextureCreator<WindowContext>) -> Self {
+        Self { texture_creator, map: None, chunks: HashMap::new() }
+    }
+
+    /// Draws the part of the map that is inside `camera` (in map pixels) to the whole canvas.
+    /// Returns the tiles of the chunks it drew that aren't on the spritesheet.
+    pub fn render<T: RenderTarget>(
+        &mut self,
+        canvas: &mut Canvas<T>,
+        map: &Tilemap,
+        spritesheet: &SpriteSheet,
+        camera: Rect,
+    ) -> Result<Vec<SpriteId>, String> {
+        if self.map != Some(map.id) {
+            self.invalidate();
+            self.map = Some(map.id);
+        }
+        let size = map.chunk_pixels();
+        let mut missing = Vec::new();
+        for chunk in map.chunks_in(camera) {
+            let version = map.versions.get(&chunk).copied().unwrap_or(0);
+            let stale = self.chunks.get(&chunk).is_none_or(|drawn| {
+                drawn.version != version || drawn.animated_at.is_some_and(|time| map.frames_changed_since(time))
+            });
+            if stale {
+                let (texture, animated) = self.draw_chunk(canvas, map, spritesheet, chunk, &mut missing)?;
+                let animated_at = if animated { Some(map.time) } else { None };
+                self.chunks.insert(chunk, Chunk { texture, version, animated_at });
+            }
+            let dst = Rect::new(
+                (chunk.0 * size) as i32 - camera.x(),
+                (chunk.1 * size) as i32 - camera.y(),
+                size,
+                size,
+            );
+            canvas.copy(&self.chunks[&chunk].texture, None, dst)?;
+        }
+        Ok(missing)
+    }
+
+    /// Forgets every chunk texture, e.g. after the spritesheet changed
+    pub fn invalidate(&mut self) {
+        self.chunks.clear();
+    }
+
+    /// Draws a chunk at the current frame of its animated tiles, adding the tiles that aren't on
+    /// the spritesheet to `missing`. Also returns whether it has animated tiles.
+    fn draw_chunk<T: RenderTarget>(
+        &self,
+        canvas: &mut Canvas<T>,
+        map: &Tilemap,
+        spritesheet: &SpriteSheet,
+        chunk: (u32, u32),
+        missing: &mut Vec<SpriteId>,
+    ) -> Result<(Texture<'a>, bool), String> {
+        let size = map.chunk_pixels();
+        let mut texture = self.texture_creator
+            .create_texture_target(None, size, size)
+            .or_else(|err| Err(format!("{}", err)))?;
+        texture.set_blend_mode(BlendMode::Blend);
+        let (tiles, animated) = map.chunk_tiles(chunk);
+        let origin = ((chunk.0 * size) as i32, (chunk.1 * size) as i32);
+        let mut result = Ok(());
+        canvas
+            .with_texture_canvas(&mut texture, |chunk_canvas| {
+                chunk_canvas.set_draw_color(Color::RGBA(0, 0, 0, 0));
+                chunk_canvas.clear();
+                for (tile, rect) in tiles {
+                    match spritesheet.get(tile) {
+                        Some(src_rect) => {
+                            let (x, y) = (rect.x() - origin.0, rect.y() - origin.1);
+                            let dst_rect = Rect::new(x, y, rect.width(), rect.height());
+                            if let Err(err) = chunk_canvas.copy(&spritesheet.texture, *src_rect, dst_rect) {
+                                result = Err(err);
+                            }
+                        },
+                        None => missing.push(tile),
+                    }
+                }
+            })
+            .or_else(|err| Err(format!("{}", err)))?;
+        result.map(|_| (texture, animated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+    use crate::engine::{ ClearMode, EngineContext, EngineInfo, Scene, SceneFnOutcome };
+    use crate::harness::Harness;
+
+    fn on_tick(_scene: &mut Scene, _ctx: &mut EngineContext, _interval: Duration) -> SceneFnOutcome {
+        SceneFnOutcome::Continue
+    }
+
+    fn on_child_quit(_scene: &mut Scene, _ctx: &mut EngineContext, _props: Dict) -> SceneFnOutcome {
+        SceneFnOutcome::Continue
+    }
+
+    #[test]
+    fn engine_draws_scene_tilemaps() {
+        let (grass, water, flower) = (SpriteId::new("grass"), SpriteId::new("water"), SpriteId::new("flower"));
+        let mut map = Tilemap::new(2, 1, 16, vec![vec![Some(grass), Some(water)]]);
+        let animation = TileAnimation { frames: vec![grass, flower], frame_time: 100 };
+        map.set_animations(vec![(grass, animation)].into_iter().collect());
+        let clear = ClearMode::Color(Color::BLACK);
+        let mut scene = Scene::new(clear, Dict::new(), Vec::new(), HashMap::new(), on_tick, on_child_quit);
+        scene.set_tilemap(Some(map));
+        let index = vec![(grass, Rect::new(0, 0, 16, 16))].into_iter().collect();
+        let info = EngineInfo::new("tilemap", 16, (160, 144), (1.0, 1.0));
+        let mut harness = Harness::new(info, |_, props| props, Dict::new(), index, scene).unwrap();
+
+        harness.tick(1, 16);
+        assert_eq!(harness.engine().missing_sprites_report(), "1 missing sprite(s)\n  water");
+        // the grass has moved on to its second frame, which isn't on the spritesheet either
+        harness.tick(1, 100);
+        assert_eq!(harness.engine().missing_sprites_report(), "2 missing sprite(s)\n  flower\n  water");
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::VecDeque;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use crate::renderer::Renderer;
+use crate::text::TextSettings;
+
+/// How long (ms) a toast stays on screen by default, sliding included
+pub const TOAST_DURATION: u32 = 2_500;
+/// How long (ms) a toast takes to slide in, and again to slide out
+pub const TOAST_SLIDE: u32 = 200;
+
+/// Short notices shown in the top right corner above every scene, e.g. "Autosaved" or "Item
+/// registered!". Callbacks show them through `EngineContext::toasts`. One is shown at a time and
+/// the rest wait their turn, each sliding in from the right edge and back out when its time is
+/// up. Toasts are only drawn, so they never take input from the scene under them.
+pub struct Toasts {
+    pub duration: u32, // ms, for toasts shown with `show`
+    queue: VecDeque<(String, u32)>, // (text, duration) waiting to be shown
+    current: Option<(String, u32, u32)>, // (text, duration, ms shown)
+}
+
+impl Toasts {
+    pub fn new() -> Self {
+        Self { duration: TOAST_DURATION, queue: VecDeque::new(), current: None }
+    }
+
+    /// Queues a toast for the default duration
+    pub fn show(&mut self, text: &str) {
+        self.show_for(text, self.duration);
+    }
+
+    /// Queues a toast for `duration` ms. Toasts shorter than the slides in and out are stretched
+    /// to fit them.
+    pub fn show_for(&mut self, text: &str, duration: u32) {
+        self.queue.push_back((text.to_string(), duration.max(TOAST_SLIDE * 2)));
+        if self.current.is_none() {
+            self.next();
+        }
+    }
+
+    /// Takes down the toast showing and drops the waiting ones, e.g. before the credits
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.current = None;
+    }
+
+    fn next(&mut self) {
+        self.current = self.queue.pop_front().map(|(text, duration)| (text, duration, 0));
+    }
+
+    pub fn tick(&mut self, interval: u32) {
+        if let Some((_, duration, shown)) = &mut self.current {
+            *shown += interval;
+            if *shown >= *duration {
+                self.next();
+            }
+        }
+    }
+
+    /// The toast showing right now
+    pub fn current(&self) -> Option<&str> {
+        self.current.as_ref().map(|(text, _, _)| text.as_str())
+    }
+
+    /// Toasts waiting behind the one showing
+    pub fn waiting(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Draws the toast showing in the top right corner of the screen over a dark box, pushed
+    /// right by how far it is through sliding in or out
+    pub fn render(&self, renderer: &mut dyn Renderer, text: &TextSettings) {
+        let (toast, duration, shown) = match &self.current {
+            Some((toast, duration, shown)) => (toast, *duration, *shown),
+            None => return,
+        };
+        let viewport = renderer.viewport();
+        let camera = renderer.camera();
+        renderer.set_camera((0, 0)); // toasts are fixed to the screen
+        let width = text.char_width() * toast.chars().count() as u32 + 8;
+        let height = text.line_height() + 8;
+        let slid = shown.min(duration - shown).min(TOAST_SLIDE); // ms into the slide, TOAST_SLIDE once it's in
+        let hidden = ((width + 4) as u64 * (TOAST_SLIDE - slid) as u64 / TOAST_SLIDE as u64) as i32;
+        let x = viewport.width() as i32 - 4 - width as i32 + hidden;
+        renderer.draw_rect(Rect::new(x, 4, width, height), Color::RGB(24, 24, 32));
+        renderer.draw_text(toast, (x + 4, 8), text);
+        renderer.set_camera(camera);
+    }
+}
+
+impl Default for Toasts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
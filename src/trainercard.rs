@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use sdl2::event::{ Event, EventType };
+use sdl2::mouse::MouseButton;
+use sdl2::pixels::Color;
+use sdl2::rect::{ Point, Rect };
+use crate::dict::*;
+use crate::engine::*;
+use crate::input::Action;
+use crate::intern::Key;
+use crate::playstats::{ format_play_time, PlayStat, PlayStats, PLAY_STATS_GLOBAL };
+use crate::text::TextSettings;
+
+/// Name of the global holding the player's `name`, `money`, `sprite`, `badges` (array of bools,
+/// one per badge) and `signature`
+pub const TRAINER_GLOBAL: &str = "trainer";
+
+/// Size of the signature bitmap in pixels. It is stored 1 bit per pixel, row by row, as an array
+/// of bytes in the trainer global.
+pub const SIGNATURE_SIZE: (u32, u32) = (64, 16);
+/// How many screen pixels each signature pixel takes up on the card
+const SIGNATURE_SCALE: u32 = 2;
+const SIGNATURE_POS: (i32, i32) = (16, 184);
+const BADGE_SIZE: u32 = 24;
+const BADGE_Y: i32 = 148;
+
+/// Creates the trainer card scene. Expects the trainer and play stats globals in the props'
+/// globals, i.e. the opening scene requests `TRAINER_GLOBAL` and `PLAY_STATS_GLOBAL`, and
+/// optionally `text`, the player's `TextSettings` as a dict.
+///
+/// Hovering a badge with the mouse makes it gleam. The player signs by drawing in the signature
+/// area with the mouse (touches are turned into mouse events by SDL), and Select clears it. A or
+/// B closes the card, quitting with the props `signature`, the bitmap as an array of bytes, for
+/// the parent to store back in the trainer global.
+pub fn create_trainer_card(props: Dict) -> Scene {
+    let globals = match props.get(&Key::new("globals")) {
+        Some(DictValue::Dict(globals)) => globals.clone(),
+        _ => HashMap::new(),
+    };
+    let mut state = HashMap::new();
+    for key in [TRAINER_GLOBAL, PLAY_STATS_GLOBAL] {
+        let value = globals.get(&Key::new(key)).cloned().unwrap_or(DictValue::Dict(HashMap::new()));
+        state.insert(Key::new(key), value);
+    }
+    if let Some(text) = props.get(&Key::new("text")) {
+        state.insert(Key::new("text"), text.clone());
+    }
+    let signature = match state.get(&Key::new(TRAINER_GLOBAL)) {
+        Some(DictValue::Dict(trainer)) => read_signature(trainer.get(&Key::new("signature"))),
+        _ => read_signature(None),
+    };
+    state.insert(Key::new("signature"), write_signature(&signature));
+    let mut event_callbacks: HashMap<EventType, EventCallbackFn> = HashMap::new();
+    event_callbacks.insert(EventType::KeyDown, trainer_card_key_down);
+    event_callbacks.insert(EventType::MouseMotion, trainer_card_mouse);
+    event_callbacks.insert(EventType::MouseButtonDown, trainer_card_mouse);
+    let mut scene = Scene::new(
+        ClearMode::None,
+        state,
+        Vec::new(),
+        event_callbacks,
+        |_, _, _| SceneFnOutcome::Continue,
+        |_, _, _| SceneFnOutcome::Continue,
+    );
+    layout_trainer_card(&mut scene);
+    scene
+}
+
+fn trainer_card_key_down(scene: &mut Scene, ctx: &mut EngineContext, event: &Event) -> SceneFnOutcome {
+    let action = match *event {
+        Event::KeyDown { keycode: Some(key), .. } => ctx.input.map.get(key),
+        _ => None,
+    };
+    match action {
+        Some(Action::A) | Some(Action::B) => {
+            let mut props = HashMap::new();
+            if let Some(signature) = scene.state().remove(&Key::new("signature")) {
+                props.insert(Key::new("signature"), signature);
+            }
+            SceneFnOutcome::Quit(props)
+        },
+        Some(Action::Select) => {
+            let blank = read_signature(None);
+            scene.state().insert(Key::new("signature"), write_signature(&blank));
+            layout_trainer_card(scene);
+            SceneFnOutcome::Continue
+        },
+        _ => SceneFnOutcome::Continue,
+    }
+}
+
+fn trainer_card_mouse(scene: &mut Scene, _ctx: &mut EngineContext, event: &Event) -> SceneFnOutcome {
+    let (pos, drawing) = match *event {
+        Event::MouseMotion { x, y, mousestate, .. } => (Point::new(x, y), mousestate.left()),
+        Event::MouseButtonDown { x, y, mouse_btn: MouseButton::Left, .. } => (Point::new(x, y), true),
+        _ => return SceneFnOutcome::Continue,
+    };
+    let hover = (0..badges(scene.state()).len()).find(|i| badge_rect(*i).contains_point(pos));
+    let old_hover = match scene.state().get(&Key::new("hover")) {
+        Some(DictValue::U32(hover)) => Some(*hover as usize),
+        _ => None,
+    };
+    let mut changed = old_hover != hover;
+    match hover {
+        Some(hover) => scene.state().insert(Key::new("hover"), DictValue::U32(hover as u32)),
+        None => scene.state().remove(&Key::new("hover")),
+    };
+    let area = Rect::new(
+        SIGNATURE_POS.0,
+        SIGNATURE_POS.1,
+        SIGNATURE_SIZE.0 * SIGNATURE_SCALE,
+        SIGNATURE_SIZE.1 * SIGNATURE_SCALE,
+    );
+    if drawing && area.contains_point(pos) {
+        let x = ((pos.x() - area.x()) as u32 / SIGNATURE_SCALE) as usize;
+        let y = ((pos.y() - area.y()) as u32 / SIGNATURE_SCALE) as usize;
+        let mut signature = read_signature(scene.state().get(&Key::new("signature")));
+        let bit = y * SIGNATURE_SIZE.0 as usize + x;
+        if signature[bit / 8] & (1 << (bit % 8)) == 0 {
+            signature[bit / 8] |= 1 << (bit % 8);
+            scene.state().insert(Key::new("signature"), write_signature(&signature));
+            changed = true;
+        }
+    }
+    if changed {
+        layout_trainer_card(scene);
+    }
+    SceneFnOutcome::Continue
+}
+
+fn badges(state: &Dict) -> Vec<bool> {
+    match state.get(&Key::new(TRAINER_GLOBAL)) {
+        Some(DictValue::Dict(trainer)) => match trainer.get(&Key::new("badges")) {
+            Some(DictValue::Array(badges)) => badges.iter().map(|badge| matches!(badge, DictValue::Bool(true))).collect(),
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+fn badge_rect(index: usize) -> Rect {
+    Rect::new(16 + (index as u32 * (BADGE_SIZE + 4)) as i32, BADGE_Y, BADGE_SIZE, BADGE_SIZE)
+}
+
+/// Reads a signature bitmap, giving a blank one if it's missing or the wrong size
+fn read_signature(value: Option<&DictValue>) -> Vec<u8> {
+    let len = (SIGNATURE_SIZE.0 * SIGNATURE_SIZE.1 / 8) as usize;
+    let bytes: Vec<u8> = match value {
+        Some(DictValue::Array(bytes)) => bytes
+            .iter()
+            .map(|byte| match byte {
+                DictValue::U8(byte) => *byte,
+                DictValue::I64(byte) => *byte as u8, // after a trip through JSON
+                _ => 0,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    if bytes.len() == len {
+        bytes
+    } else {
+        vec![0; len]
+    }
+}
+
+fn write_signature(signature: &[u8]) -> DictValue {
+    DictValue::Array(signature.iter().map(|byte| DictValue::U8(*byte)).collect())
+}
+
+fn layout_trainer_card(scene: &mut Scene) {
+    let state = scene.state();
+    let text = match state.get(&Key::new("text")) {
+        Some(DictValue::Dict(text)) => TextSettings::from_dict(text),
+        _ => TextSettings::default(),
+    };
+    let empty = HashMap::new();
+    let trainer = match state.get(&Key::new(TRAINER_GLOBAL)) {
+        Some(DictValue::Dict(trainer)) => trainer,
+        _ => &empty,
+    };
+    let stats = match state.get(&Key::new(PLAY_STATS_GLOBAL)) {
+        Some(DictValue::Dict(stats)) => PlayStats::from_dict(stats),
+        _ => PlayStats::default(),
+    };
+    let string = |key: &str| match trainer.get(&Key::new(key)) {
+        Some(DictValue::String(value)) => value.clone(),
+        _ => String::new(),
+    };
+    let money = match trainer.get(&Key::new("money")) {
+        Some(DictValue::U32(money)) => *money as i64,
+        Some(DictValue::I64(money)) => *money,
+        _ => 0,
+    };
+    let hover = match state.get(&Key::new("hover")) {
+        Some(DictValue::U32(hover)) => Some(*hover as usize),
+        _ => None,
+    };
+    let signature = read_signature(state.get(&Key::new("signature")));
+    let badges = badges(state);
+    let line = text.line_height() as i32;
+
+    let mut sprites = vec![Sprite::Rect { rect: Rect::new(8, 8, 304, 224), color: Color::RGB(200, 72, 56) }];
+    let player_sprite = string("sprite");
+    if !player_sprite.is_empty() {
+        sprites.push(Sprite::Texture { rect: Rect::new(16, 16, 48, 48), sprite: Key::new(&player_sprite) });
+    }
+    sprites.extend(text.sprites(&string("name"), (72, 16)));
+    sprites.extend(text.sprites(&format!("Money ${}", money), (72, 16 + line)));
+    sprites.extend(text.sprites(&format!("Time {}", format_play_time(stats.get(PlayStat::PlayTime))), (72, 16 + line * 2)));
+    for (i, stat) in PlayStat::ALL.iter().filter(|stat| **stat != PlayStat::PlayTime).enumerate() {
+        let value = stats.get(*stat).to_string();
+        let y = 72 + i as i32 * line;
+        sprites.extend(text.sprites(stat.label(), (16, y)));
+        sprites.extend(text.sprites(&value, (304 - (value.len() as u32 * text.char_width()) as i32, y)));
+    }
+    for (i, earned) in badges.iter().enumerate() {
+        let sprite = match (earned, hover == Some(i)) {
+            (false, _) => String::from("badge_empty"),
+            (true, false) => format!("badge_{}", i),
+            (true, true) => format!("badge_{}_gleam", i),
+        };
+        sprites.push(Sprite::Texture { rect: badge_rect(i), sprite: Key::new(&sprite) });
+    }
+    sprites.push(Sprite::Rect {
+        rect: Rect::new(
+            SIGNATURE_POS.0,
+            SIGNATURE_POS.1,
+            SIGNATURE_SIZE.0 * SIGNATURE_SCALE,
+            SIGNATURE_SIZE.1 * SIGNATURE_SCALE,
+        ),
+        color: Color::WHITE,
+    });
+    for bit in 0..(SIGNATURE_SIZE.0 * SIGNATURE_SIZE.1) as usize {
+        if signature[bit / 8] & (1 << (bit % 8)) != 0 {
+            let (x, y) = ((bit as u32 % SIGNATURE_SIZE.0), (bit as u32 / SIGNATURE_SIZE.0));
+            sprites.push(Sprite::Rect {
+                rect: Rect::new(
+                    SIGNATURE_POS.0 + (x * SIGNATURE_SCALE) as i32,
+                    SIGNATURE_POS.1 + (y * SIGNATURE_SCALE) as i32,
+                    SIGNATURE_SCALE,
+                    SIGNATURE_SCALE,
+                ),
+                color: Color::BLACK,
+            });
+        }
+    }
+    *scene.sprites() = sprites;
+}
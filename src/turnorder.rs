@@ -0,0 +1,199 @@
+use crate::intern::Key;
+use crate::rng::Rng;
+
+/// Priority of running from a battle, before anything else
+pub const RUN_PRIORITY: i8 = 8;
+/// Priority of switching out and using an item from the bag, before every move
+pub const SWITCH_PRIORITY: i8 = 7;
+/// Chance in 100 of Quick Claw letting its holder move first in its bracket
+pub const QUICK_CLAW_CHANCE: u32 = 20;
+/// Chance in 100 of Quick Draw letting its pokemon move first in its bracket
+pub const QUICK_DRAW_CHANCE: u32 = 30;
+
+/// The move a pokemon is about to use, as far as turn order cares
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MoveUse {
+    pub priority: i8, // the move's own priority, from -7 to 5
+    pub move_type: Key,
+    pub status: bool, // a status move, rather than a physical or special one
+    pub heals: bool, // restores HP, e.g. Recover or Drain Punch
+}
+
+/// What a battler chose to do this turn
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Choice {
+    Move(MoveUse),
+    Switch,
+    Item,
+    Run,
+}
+
+/// A battler about to act, with everything that decides when it acts
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Contender {
+    pub battler: usize, // whatever the battle tells its battlers apart with
+    pub choice: Choice,
+    pub speed: u32, // with stat stages, paralysis, Tailwind and so on already applied
+    pub ability: Option<Key>,
+    pub item: Option<Key>,
+    pub full_hp: bool,
+    pub low_hp: bool, // at or below a quarter of its HP, for Custap Berry
+}
+
+/// Where in its priority bracket a battler acts, from items and abilities like Quick Claw and
+/// Lagging Tail
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum InBracket {
+    First,
+    Normal,
+    Last,
+}
+
+impl Contender {
+    /// The priority bracket the battler acts in: that of its move, raised by Prankster (status
+    /// moves), Gale Wings (Flying moves at full HP) and Triage (healing moves), or
+    /// `SWITCH_PRIORITY` and `RUN_PRIORITY` for everything that isn't a move
+    pub fn priority(&self) -> i8 {
+        let used = match self.choice {
+            Choice::Move(used) => used,
+            Choice::Switch | Choice::Item => return SWITCH_PRIORITY,
+            Choice::Run => return RUN_PRIORITY,
+        };
+        let bonus = match self.ability.as_ref().map(Key::name) {
+            Some("prankster") if used.status => 1,
+            Some("gale_wings") if self.full_hp && used.move_type == Key::new("flying") => 1,
+            Some("triage") if used.heals => 3,
+            _ => 0,
+        };
+        used.priority.saturating_add(bonus)
+    }
+
+    /// Where in its bracket the battler acts. Quick Claw, Quick Draw and Custap Berry can put it
+    /// first, rolled with `rng`; Lagging Tail, Full Incense and Stall put it last. Only moves are
+    /// affected, since switching and running always go by speed.
+    pub fn in_bracket(&self, rng: &mut Rng) -> InBracket {
+        if !matches!(self.choice, Choice::Move(_)) {
+            return InBracket::Normal;
+        }
+        let item = self.item.as_ref().map(Key::name);
+        let ability = self.ability.as_ref().map(Key::name);
+        let first = match (item, ability) {
+            (Some("quick_claw"), _) if rng.percent(QUICK_CLAW_CHANCE) => true,
+            (Some("custap_berry"), _) if self.low_hp => true,
+            (_, Some("quick_draw")) => matches!(self.choice, Choice::Move(used) if !used.status)
+                && rng.percent(QUICK_DRAW_CHANCE),
+            _ => false,
+        };
+        if first {
+            InBracket::First
+        } else if matches!(item, Some("lagging_tail") | Some("full_incense")) || ability == Some("stall") {
+            InBracket::Last
+        } else {
+            InBracket::Normal
+        }
+    }
+}
+
+/// The order the battlers act in this turn, as their `battler` ids:
+///
+/// - Higher priority brackets go first (see `Contender::priority`)
+/// - Within a bracket, those who act first by item or ability go before the rest, and those who
+///   act last after them (see `Contender::in_bracket`)
+/// - Then the faster battler goes first, or the slower one while `trick_room` is up. Trick Room
+///   only flips speed; it doesn't touch priority or the effects above.
+/// - Speed ties are broken with `rng`, so every tied battler is as likely to go first
+///
+/// Everything random is rolled with `rng`, so a battle replayed from the same seed orders its
+/// turns the same way.
+pub fn order(contenders: &[Contender], trick_room: bool, rng: &mut Rng) -> Vec<usize> {
+    let mut keyed: Vec<(i8, InBracket, u32, u64, usize)> = contenders
+        .iter()
+        .map(|contender| {
+            let in_bracket = contender.in_bracket(rng);
+            (contender.priority(), in_bracket, contender.speed, rng.next_u64(), contender.battler)
+        })
+        .collect();
+    keyed.sort_by(|a, b| {
+        let speed = if trick_room { a.2.cmp(&b.2) } else { b.2.cmp(&a.2) };
+        b.0.cmp(&a.0)
+            .then(a.1.cmp(&b.1))
+            .then(speed)
+            .then(a.3.cmp(&b.3))
+    });
+    keyed.into_iter().map(|(_, _, _, _, battler)| battler).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contender(battler: usize, priority: i8, speed: u32) -> Contender {
+        let used = MoveUse { priority, move_type: Key::new("normal"), status: false, heals: false };
+        Contender {
+            battler,
+            choice: Choice::Move(used),
+            speed,
+            ability: None,
+            item: None,
+            full_hp: true,
+            low_hp: false,
+        }
+    }
+
+    fn holding(mut contender: Contender, item: &str) -> Contender {
+        contender.item = Some(Key::new(item));
+        contender
+    }
+
+    #[test]
+    fn higher_priority_beats_higher_speed() {
+        let contenders = [contender(0, 0, 300), contender(1, 1, 10)];
+        for seed in 0..20 {
+            assert_eq!(order(&contenders, false, &mut Rng::new(seed)), vec![1, 0]);
+        }
+    }
+
+    #[test]
+    fn bracket_items_only_reorder_within_their_bracket() {
+        let fast = contender(0, 0, 200);
+        let custap = Contender { low_hp: true, ..holding(contender(1, 0, 10), "custap_berry") };
+        let lagging = holding(contender(2, 0, 300), "lagging_tail");
+        let priority = contender(3, 1, 5);
+        for seed in 0..20 {
+            let order = order(&[fast, custap, lagging, priority], false, &mut Rng::new(seed));
+            assert_eq!(order, vec![3, 1, 0, 2]);
+        }
+        let quick_claw = holding(contender(1, 0, 10), "quick_claw");
+        let mut first = 0;
+        for seed in 0..200 {
+            let order = order(&[fast, quick_claw, priority], false, &mut Rng::new(seed));
+            assert_eq!(order[0], 3, "Quick Claw never beats a higher bracket");
+            if order[1] == 1 {
+                first += 1;
+            }
+        }
+        assert!(first > 0 && first < 200, "Quick Claw only sometimes goes first, went first {} times", first);
+    }
+
+    #[test]
+    fn trick_room_flips_speed_but_not_priority() {
+        let contenders = [contender(0, 0, 300), contender(1, 0, 10), contender(2, 1, 200), contender(3, 1, 50)];
+        for seed in 0..20 {
+            assert_eq!(order(&contenders, false, &mut Rng::new(seed)), vec![2, 3, 0, 1]);
+            assert_eq!(order(&contenders, true, &mut Rng::new(seed)), vec![3, 2, 1, 0]);
+        }
+    }
+
+    #[test]
+    fn speed_ties_are_the_same_for_a_seed() {
+        let contenders: Vec<Contender> = (0..6).map(|battler| contender(battler, 0, 100)).collect();
+        let mut orders = Vec::new();
+        for seed in 0..20 {
+            let first = order(&contenders, false, &mut Rng::new(seed));
+            assert_eq!(first, order(&contenders, false, &mut Rng::new(seed)));
+            orders.push(first);
+        }
+        orders.dedup();
+        assert!(orders.len() > 1, "ties should be broken differently by different seeds");
+    }
+}
@@ -0,0 +1,250 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use crate::engine::{ EngineContext, Sprite };
+use crate::input::Action;
+use crate::intern::SpriteId;
+use crate::mapfile::Warp;
+use crate::tilemap::TileAnimation;
+
+/// ms the player takes to walk one tile into or out of a door or up or down stairs
+pub const WARP_STEP_TIME: u32 = 240;
+/// ms the screen takes to fade to or from black
+pub const WARP_FADE_TIME: u32 = 200;
+
+/// How going through a warp looks
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WarpStyle {
+    Door, // the door opens, the player walks in and it closes behind them
+    StairsUp, // the player walks up the stairs as the screen fades
+    StairsDown,
+    Fade, // just a fade, e.g. for a warp tile or a cave mouth
+}
+
+impl WarpStyle {
+    /// Picks the style from the tile under the warp, by name: doors (`door`, `gym_door_0`, ...),
+    /// stairs up (`stairs_up...`) or down (`stairs_down...`), and fades for anything else
+    pub fn for_tile(tile: Option<SpriteId>) -> Self {
+        let name = tile.map_or("", |tile| tile.name());
+        if name.starts_with("stairs_up") {
+            Self::StairsUp
+        } else if name.starts_with("stairs_down") {
+            Self::StairsDown
+        } else if name.split('_').any(|part| part == "door") {
+            Self::Door
+        } else {
+            Self::Fade
+        }
+    }
+}
+
+/// Which part of a `WarpTransition` is playing
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Phase {
+    FadeIn,
+    Open,
+    Walk,
+    Close,
+    FadeOut,
+}
+
+/// The built-in animation of going through a warp: leaving a map through a door or stairs, and
+/// coming out of them on the other one. Holds the player's input while it plays, so they can't
+/// walk off mid-animation or queue up moves for after it, and locks scene switching (see
+/// `EngineContext::begin_transition`).
+///
+/// The scene ticks it, draws the door at `door_frame` and the player moved by `player_offset`,
+/// and adds `sprites` on top of everything. Leaving ends with the warp, for the scene to load the
+/// other map and `arrive` there.
+pub struct WarpTransition {
+    style: WarpStyle,
+    warp: Option<Warp>, // None when arriving
+    direction: Action, // the way the player walks
+    door: Option<TileAnimation>, // the door's frames, from closed to open
+    phases: Vec<(Phase, u32)>, // what's left to play and how long each part takes
+    elapsed: u32, // ms into the first phase
+}
+
+impl WarpTransition {
+    /// Starts leaving through `warp`, walking in `direction`. `door` is the door tile's animation
+    /// from the tileset (see `tile_animations`), played forwards to open it and backwards to
+    /// close it; doors without one just fade.
+    pub fn leave(
+        ctx: &mut EngineContext,
+        warp: Warp,
+        style: WarpStyle,
+        direction: Action,
+        door: Option<&TileAnimation>,
+    ) -> Self {
+        let mut transition = Self::new(ctx, Some(warp), style, direction, door);
+        let door_time = transition.door_time();
+        transition.phases = match transition.style {
+            WarpStyle::Door => vec![
+                (Phase::Open, door_time),
+                (Phase::Walk, WARP_STEP_TIME),
+                (Phase::Close, door_time),
+                (Phase::FadeOut, WARP_FADE_TIME),
+            ],
+            WarpStyle::StairsUp | WarpStyle::StairsDown => {
+                vec![(Phase::Walk, WARP_STEP_TIME), (Phase::FadeOut, WARP_FADE_TIME)]
+            },
+            WarpStyle::Fade => vec![(Phase::FadeOut, WARP_FADE_TIME)],
+        };
+        transition
+    }
+
+    /// Starts coming out of a door or stairs on the new map, walking in `direction`
+    pub fn arrive(ctx: &mut EngineContext, style: WarpStyle, direction: Action, door: Option<&TileAnimation>) -> Self {
+        let mut transition = Self::new(ctx, None, style, direction, door);
+        let door_time = transition.door_time();
+        transition.phases = match transition.style {
+            WarpStyle::Door => vec![
+                (Phase::FadeIn, WARP_FADE_TIME),
+                (Phase::Open, door_time),
+                (Phase::Walk, WARP_STEP_TIME),
+                (Phase::Close, door_time),
+            ],
+            WarpStyle::StairsUp | WarpStyle::StairsDown => {
+                vec![(Phase::FadeIn, WARP_FADE_TIME), (Phase::Walk, WARP_STEP_TIME)]
+            },
+            WarpStyle::Fade => vec![(Phase::FadeIn, WARP_FADE_TIME)],
+        };
+        transition
+    }
+
+    fn new(
+        ctx: &mut EngineContext,
+        warp: Option<Warp>,
+        style: WarpStyle,
+        direction: Action,
+        door: Option<&TileAnimation>,
+    ) -> Self {
+        ctx.begin_transition();
+        ctx.input.buffer.clear();
+        let style = if style == WarpStyle::Door && door.is_none() { WarpStyle::Fade } else { style };
+        Self { style, warp, direction, door: door.cloned(), phases: Vec::new(), elapsed: 0 }
+    }
+
+    fn door_time(&self) -> u32 {
+        self.door.as_ref().map_or(0, |door| door.frame_time * door.frames.len() as u32)
+    }
+
+    pub fn style(&self) -> WarpStyle {
+        self.style
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.phases.is_empty()
+    }
+
+    /// Advances the animation. Returns the warp once leaving is over, and unlocks input and scene
+    /// switching once the whole animation is.
+    pub fn tick(&mut self, ctx: &mut EngineContext, interval: u32) -> Option<Warp> {
+        if !self.is_playing() {
+            return None;
+        }
+        ctx.input.buffer.clear();
+        self.elapsed += interval;
+        while let Some((_, time)) = self.phases.first().copied() {
+            if self.elapsed < time {
+                break;
+            }
+            self.elapsed -= time;
+            self.phases.remove(0);
+        }
+        if self.is_playing() {
+            return None;
+        }
+        self.elapsed = 0;
+        ctx.end_transition();
+        self.warp.take()
+    }
+
+    /// The door frame to draw in place of the door tile, or `None` for the closed door
+    pub fn door_frame(&self) -> Option<SpriteId> {
+        let door = self.door.as_ref()?;
+        let (phase, _) = self.phases.first().copied()?;
+        let last = door.frames.len().saturating_sub(1) as u32;
+        let frame = (self.elapsed / door.frame_time.max(1)).min(last);
+        let frame = match phase {
+            Phase::Open => frame,
+            Phase::Walk => last,
+            Phase::Close => last - frame,
+            Phase::FadeIn | Phase::FadeOut => return None,
+        };
+        door.frames.get(frame as usize).copied()
+    }
+
+    /// How far the player has walked from their tile, in pixels. Arriving starts from inside the
+    /// door or stairs and walks out; stairs also move the player up or down as they climb.
+    pub fn player_offset(&self, tile_size: u32) -> (i32, i32) {
+        let progress = match self.phases.iter().position(|(phase, _)| *phase == Phase::Walk) {
+            Some(0) => self.phase_progress(),
+            Some(_) => 0.0,
+            None => 1.0, // the player stays where they walked to
+        };
+        // arriving walks from inside the warp back to the player's tile
+        let distance = if self.warp.is_some() { progress } else { progress - 1.0 };
+        let step = (distance * tile_size as f32) as i32;
+        let (dx, dy) = match self.direction {
+            Action::Up => (0, -step),
+            Action::Down => (0, step),
+            Action::Left => (-step, 0),
+            Action::Right => (step, 0),
+            _ => (0, 0),
+        };
+        let climb = match self.style {
+            WarpStyle::StairsUp => -step.abs() / 2,
+            WarpStyle::StairsDown => step.abs() / 2,
+            _ => 0,
+        };
+        (dx, dy + climb)
+    }
+
+    /// Whether the player is hidden, i.e. inside the door: after walking in, and before walking
+    /// out
+    pub fn player_hidden(&self) -> bool {
+        if self.style != WarpStyle::Door {
+            return false;
+        }
+        match self.phases.first() {
+            Some((Phase::Close, _)) | Some((Phase::FadeOut, _)) => self.warp.is_some(),
+            Some((Phase::FadeIn, _)) | Some((Phase::Open, _)) => self.warp.is_none(),
+            _ => false,
+        }
+    }
+
+    /// How dark the screen is, from 0 to 255
+    pub fn fade(&self) -> u8 {
+        let phase = match self.phases.first() {
+            Some((phase, _)) => *phase,
+            None => return 0,
+        };
+        let progress = self.phase_progress();
+        match phase {
+            Phase::FadeOut => (progress * 255.0) as u8,
+            Phase::FadeIn => ((1.0 - progress) * 255.0) as u8,
+            // stairs start fading while the player climbs them
+            Phase::Walk if self.style != WarpStyle::Door => {
+                let darkness = if self.warp.is_some() { progress } else { 1.0 - progress };
+                (darkness * 128.0) as u8
+            },
+            _ => 0,
+        }
+    }
+
+    /// How far into the current phase the animation is, from 0 to 1
+    fn phase_progress(&self) -> f32 {
+        match self.phases.first() {
+            Some((_, time)) => (self.elapsed as f32 / (*time).max(1) as f32).min(1.0),
+            None => 1.0,
+        }
+    }
+
+    /// The fade over the whole screen, to draw on top of everything else
+    pub fn sprites(&self) -> Vec<Sprite> {
+        match self.fade() {
+            0 => Vec::new(),
+            fade => vec![Sprite::Rect { rect: Rect::new(0, 0, 320, 240), color: Color::RGBA(0, 0, 0, fade) }],
+        }
+    }
+}
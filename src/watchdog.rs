@@ -0,0 +1,62 @@
+use std::cmp::Reverse;
+use std::time::Duration;
+
+/// Times the parts of every frame and, when a frame goes over budget, describes where the time
+/// went, so stutters that only happen now and then can be traced from the log afterwards. See
+/// `Engine::set_frame_budget`.
+pub struct FrameWatchdog {
+    budget: Duration,
+    spans: Vec<(String, Duration)>, // what ran this frame and how long it took, in order
+    evictions: u64, // texture evictions when the frame started
+    spikes: u64,
+}
+
+impl FrameWatchdog {
+    pub fn new(budget: Duration) -> Self {
+        Self { budget, spans: Vec::new(), evictions: 0, spikes: 0 }
+    }
+
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Frames that went over budget so far
+    pub fn spikes(&self) -> u64 {
+        self.spikes
+    }
+
+    pub(crate) fn begin(&mut self, evictions: u64) {
+        self.spans.clear();
+        self.evictions = evictions;
+    }
+
+    pub(crate) fn record(&mut self, label: String, took: Duration) {
+        self.spans.push((label, took));
+    }
+
+    /// Ends the frame. Returns the breakdown if it took longer than the budget, e.g.
+    /// `frame took 48ms (budget 20ms): load background town 40ms, tick #0 overworld 4ms, ...`,
+    /// slowest part first, with how many textures were evicted to make room
+    pub(crate) fn finish(&mut self, took: Duration, evictions: u64) -> Option<String> {
+        if took <= self.budget {
+            return None;
+        }
+        self.spikes += 1;
+        let mut spans: Vec<&(String, Duration)> = self.spans.iter().collect();
+        spans.sort_by_key(|span| Reverse(span.1));
+        let mut parts: Vec<String> = spans
+            .iter()
+            .map(|(label, took)| format!("{} {}ms", label, took.as_millis()))
+            .collect();
+        let evicted = evictions.saturating_sub(self.evictions);
+        if evicted > 0 {
+            parts.push(format!("{} texture(s) evicted", evicted));
+        }
+        Some(format!(
+            "frame took {}ms (budget {}ms): {}",
+            took.as_millis(),
+            self.budget.as_millis(),
+            parts.join(", "),
+        ))
+    }
+}
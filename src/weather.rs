@@ -0,0 +1,81 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use crate::engine::Sprite;
+
+/// Particles of rain, sand or hail on screen at once
+const WEATHER_PARTICLES: u32 = 48;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Weather {
+    #[default]
+    Clear,
+    Rain,
+    Sun,
+    Sandstorm,
+    Hail,
+}
+
+impl Weather {
+    pub const ALL: [Weather; 5] = [Weather::Clear, Weather::Rain, Weather::Sun, Weather::Sandstorm, Weather::Hail];
+
+    /// Name in data files and saves
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Clear => "clear",
+            Self::Rain => "rain",
+            Self::Sun => "sun",
+            Self::Sandstorm => "sandstorm",
+            Self::Hail => "hail",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|weather| weather.name() == name)
+    }
+}
+
+/// Draws weather over a scene: falling rain, a sandstorm blowing across, hail, or the warm tint of
+/// harsh sunlight. Made of rect sprites, so it needs nothing on the spritesheet. The scene ticks
+/// it and adds its sprites last, on top of everything else.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct WeatherOverlay {
+    pub weather: Weather,
+    time: u32, // ms
+}
+
+impl WeatherOverlay {
+    pub fn new(weather: Weather) -> Self {
+        Self { weather, time: 0 }
+    }
+
+    pub fn tick(&mut self, interval: u32) {
+        self.time = self.time.wrapping_add(interval);
+    }
+
+    /// The overlay's sprites, covering `rect`
+    pub fn sprites(&self, rect: Rect) -> Vec<Sprite> {
+        let (tint, particle, size, velocity) = match self.weather {
+            Weather::Clear => return Vec::new(),
+            Weather::Sun => return vec![Sprite::Rect { rect, color: Color::RGBA(255, 200, 64, 40) }],
+            Weather::Rain => (Color::RGBA(32, 48, 96, 48), Color::RGBA(160, 192, 255, 160), (1, 6), (-40, 320)),
+            Weather::Sandstorm => (Color::RGBA(160, 120, 64, 72), Color::RGBA(208, 176, 112, 192), (2, 1), (280, 40)),
+            Weather::Hail => (Color::RGBA(192, 208, 224, 40), Color::RGBA(240, 248, 255, 224), (2, 2), (-20, 160)),
+        };
+        let mut sprites = vec![Sprite::Rect { rect, color: tint }];
+        let (width, height) = (rect.width().max(1) as i64, rect.height().max(1) as i64);
+        for i in 0..WEATHER_PARTICLES {
+            // scatter the particles with a hash of their index, then move them by the time, in px
+            // per second, wrapping around the rect
+            let seed = (i as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 16;
+            let (x, y) = ((seed % width as u64) as i64, ((seed >> 20) % height as u64) as i64);
+            let time = self.time as i64;
+            let x = (x + velocity.0 * time / 1000).rem_euclid(width);
+            let y = (y + velocity.1 * time / 1000).rem_euclid(height);
+            sprites.push(Sprite::Rect {
+                rect: Rect::new(rect.x() + x as i32, rect.y() + y as i32, size.0, size.1),
+                color: particle,
+            });
+        }
+        sprites
+    }
+}
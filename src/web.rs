@@ -0,0 +1,26 @@
+use std::os::raw::{ c_int, c_void };
+
+extern "C" {
+    fn emscripten_set_main_loop_arg(
+        func: extern "C" fn(arg: *mut c_void),
+        arg: *mut c_void,
+        fps: c_int,
+        simulate_infinite_loop: c_int,
+    );
+    fn emscripten_cancel_main_loop();
+}
+
+/// Makes the browser call `frame` once per animation frame until it returns false. Browsers
+/// can't have a blocking loop on the main thread, so this replaces `loop { ...; sleep }`. Never
+/// returns, so `frame` has to own everything it uses.
+pub fn main_loop<F: FnMut() -> bool + 'static>(frame: F) -> ! {
+    extern "C" fn call<F: FnMut() -> bool>(arg: *mut c_void) {
+        let frame = unsafe { &mut *(arg as *mut F) };
+        if !frame() {
+            unsafe { emscripten_cancel_main_loop() };
+        }
+    }
+    let frame = Box::into_raw(Box::new(frame));
+    unsafe { emscripten_set_main_loop_arg(call::<F>, frame as *mut c_void, 0, 1) };
+    unreachable!("emscripten_set_main_loop_arg doesn't return when simulating an infinite loop")
+}
@@ -0,0 +1,152 @@
+use std::collections::{ HashMap, HashSet };
+use sdl2::event::Event;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{ TextureCreator, WindowCanvas };
+use sdl2::video::{ WindowBuilder, WindowContext };
+use crate::engine::{ EngineContext, Scene, SceneFnOutcome, SpriteSheet };
+use crate::images;
+use crate::intern::SpriteId;
+use crate::renderer::{ Renderer, SdlRenderer };
+use crate::stack::Stack;
+use crate::textures::{ TextureCache, TextureCreatorOwner };
+
+/// SDL's id of a window, see `Engine::create_window`
+pub type WindowId = u32;
+
+/// A window's canvas and its own copy of the spritesheet, since textures can't be shared between
+/// windows. Every window the engine opens (the game's, `GameWindow`s and the debug window) is
+/// made with `open`, which is the only place a texture creator is leaked (see
+/// `TextureCreatorOwner`).
+pub(crate) struct WindowVideo<'a> {
+    pub(crate) canvas: WindowCanvas,
+    // textures made with it outside of this struct have to be dropped before it, e.g. by
+    // declaring them before it
+    pub(crate) texture_creator: &'a TextureCreator<WindowContext>,
+    pub(crate) spritesheet: SpriteSheet<'a>,
+    _texture_creator_owner: TextureCreatorOwner, // last, so the spritesheet is dropped before it
+}
+
+impl<'a> WindowVideo<'a> {
+    /// Builds the window and its canvas, presenting in step with the display if `vsync`, and
+    /// loads the spritesheet for it
+    pub(crate) fn open(
+        window: WindowBuilder,
+        vsync: bool,
+        spritesheet: &str,
+        index: HashMap<SpriteId, Rect>,
+    ) -> Result<Self, String> {
+        let window = window.build().or_else(|err| Err(format!("{}", err)))?;
+        let mut canvas_builder = window.into_canvas();
+        if vsync {
+            canvas_builder = canvas_builder.present_vsync();
+        }
+        let canvas = canvas_builder.build().or_else(|err| Err(format!("{}", err)))?;
+        // the spritesheet is dropped before the owner, and anything else made with the texture
+        // creator has to be too, see `texture_creator`
+        let (owner, texture_creator) = unsafe { TextureCreatorOwner::leak(canvas.texture_creator()) };
+        Ok(Self {
+            canvas,
+            texture_creator,
+            spritesheet: SpriteSheet::new(images::load_texture(texture_creator, spritesheet)?, index),
+            _texture_creator_owner: owner,
+        })
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.canvas.window().id()
+    }
+}
+
+/// A window besides the game's own, e.g. a map editor next to the game, with its own stack of
+/// scenes. The engine passes it the events of its window, ticks its top scene along with the
+/// game's and draws it after the game's frame. Its scenes share the `EngineContext` with the
+/// game's, but their outcomes only change its own stack, and the last of them quitting closes
+/// the window rather than the game.
+pub struct GameWindow {
+    video: WindowVideo<'static>,
+    backgrounds: TextureCache<'static>, // always empty, only the game window has backgrounds
+    stack: Stack<Scene>,
+    clear_color: Color,
+    missing_sprites: HashSet<SpriteId>,
+}
+
+impl GameWindow {
+    pub(crate) fn new(video: WindowVideo<'static>, clear_color: Color) -> Self {
+        Self {
+            video,
+            backgrounds: TextureCache::new(0),
+            stack: Stack::new(),
+            clear_color,
+            missing_sprites: HashSet::new(),
+        }
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.video.id()
+    }
+
+    pub fn stack_len(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub(crate) fn push_scene(&mut self, scene: Scene) {
+        self.stack.push(scene);
+    }
+
+    /// Passes an event to the top scene. Returns false once the window should close: its last
+    /// scene quit or the window was closed.
+    pub(crate) fn handle_event(&mut self, ctx: &mut EngineContext, event: &Event) -> bool {
+        if let Event::Window { win_event: sdl2::event::WindowEvent::Close, .. } = event {
+            return false;
+        }
+        let outcome = match self.stack.peek_mut() {
+            Some(scene) => scene.handle_event(ctx, event),
+            None => return false,
+        };
+        match outcome {
+            Some(outcome) => self.apply(ctx, outcome),
+            None => true,
+        }
+    }
+
+    /// Ticks the top scene. Returns false once the window should close.
+    pub(crate) fn tick(&mut self, ctx: &mut EngineContext, interval: u32) -> bool {
+        let outcome = match self.stack.peek_mut() {
+            Some(scene) => scene.tick(ctx, interval),
+            None => return false,
+        };
+        self.apply(ctx, outcome)
+    }
+
+    /// Changes the stack like the engine does for its own, minus the transitions, snapshots and
+    /// debug checks
+    fn apply(&mut self, ctx: &mut EngineContext, outcome: SceneFnOutcome) -> bool {
+        match outcome {
+            SceneFnOutcome::Continue => {},
+            SceneFnOutcome::CreateChild { create_scene, props } => self.stack.push(create_scene(props)),
+            SceneFnOutcome::Replace { create_scene, props } => {
+                self.stack.replace(create_scene(props));
+            },
+            SceneFnOutcome::Quit(props) => {
+                self.stack.pop();
+                let outcome = match self.stack.peek_mut() {
+                    Some(parent) => parent.child_quit(ctx, props),
+                    None => return false,
+                };
+                return self.apply(ctx, outcome);
+            },
+        }
+        true
+    }
+
+    pub(crate) fn render(&mut self, ctx: &EngineContext, alpha: f32) {
+        let video = &mut self.video;
+        let mut renderer = SdlRenderer::new(&mut video.canvas, &mut video.spritesheet, &self.backgrounds);
+        renderer.clear(ctx.palette.apply(self.clear_color));
+        if let Some(scene) = self.stack.peek() {
+            scene.draw(&mut renderer, ctx.palette, &mut self.missing_sprites, alpha);
+        }
+        renderer.present();
+    }
+}